@@ -1,9 +1,13 @@
 use std::iter::{IntoIterator, Iterator};
 use std::string::ToString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 
-use scdb::Store;
+use scdb::{Store, StoreBuilder};
 
 const STORE_PATH: &str = "testdb";
 
@@ -56,7 +60,9 @@ fn updating_without_search_benchmark(c: &mut Criterion) {
     let (k1, v1) = (b"foo".to_vec(), b"bar".to_vec());
     let (k2, v2) = (b"fenecans".to_vec(), b"barracks".to_vec());
 
-    store.set(&k1, &v1, None).expect(&format!("set {:?}", k1));
+    store
+        .set(&k1, &v1, None)
+        .unwrap_or_else(|_| panic!("set {:?}", k1));
     c.bench_function(
         &format!(
             "update(no ttl): '{}'",
@@ -65,7 +71,9 @@ fn updating_without_search_benchmark(c: &mut Criterion) {
         |b| b.iter_with_large_drop(|| store.set(black_box(&k2), black_box(&v2), black_box(None))),
     );
 
-    store.set(&k1, &v1, ttl).expect(&format!("set {:?}", k1));
+    store
+        .set(&k1, &v1, ttl)
+        .unwrap_or_else(|_| panic!("set {:?}", k1));
     c.bench_function(
         &format!("update(ttl): '{}'", String::from_utf8(k2.clone()).unwrap(),),
         |b| b.iter_with_large_drop(|| store.set(black_box(&k2), black_box(&v2), black_box(ttl))),
@@ -79,7 +87,9 @@ fn updating_with_search_benchmark(c: &mut Criterion) {
     let (k1, v1) = (b"foo".to_vec(), b"bar".to_vec());
     let (k2, v2) = (b"fenecans".to_vec(), b"barracks".to_vec());
 
-    store.set(&k1, &v1, None).expect(&format!("set {:?}", k1));
+    store
+        .set(&k1, &v1, None)
+        .unwrap_or_else(|_| panic!("set {:?}", k1));
     c.bench_function(
         &format!(
             "update(no ttl) with search: '{}'",
@@ -88,7 +98,9 @@ fn updating_with_search_benchmark(c: &mut Criterion) {
         |b| b.iter_with_large_drop(|| store.set(black_box(&k2), black_box(&v2), black_box(None))),
     );
 
-    store.set(&k1, &v1, ttl).expect(&format!("set {:?}", k1));
+    store
+        .set(&k1, &v1, ttl)
+        .unwrap_or_else(|_| panic!("set {:?}", k1));
     c.bench_function(
         &format!(
             "update(ttl) with search: '{}'",
@@ -106,7 +118,9 @@ fn getting_without_search_benchmark(c: &mut Criterion) {
     let records = get_records();
 
     for (k, v) in &records {
-        store.set(k, v, None).expect(&format!("set {:?}", k));
+        store
+            .set(k, v, None)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
     }
     for (k, _) in &records {
         c.bench_function(
@@ -116,7 +130,9 @@ fn getting_without_search_benchmark(c: &mut Criterion) {
     }
 
     for (k, v) in &records {
-        store.set(k, v, ttl).expect(&format!("set {:?}", k));
+        store
+            .set(k, v, ttl)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
     }
     for (k, _) in &records {
         c.bench_function(
@@ -133,7 +149,9 @@ fn getting_with_search_benchmark(c: &mut Criterion) {
     let records = get_records();
 
     for (k, v) in &records {
-        store.set(k, v, None).expect(&format!("set {:?}", k));
+        store
+            .set(k, v, None)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
     }
     for (k, _) in &records {
         c.bench_function(
@@ -146,7 +164,9 @@ fn getting_with_search_benchmark(c: &mut Criterion) {
     }
 
     for (k, v) in &records {
-        store.set(k, v, ttl).expect(&format!("set {:?}", k));
+        store
+            .set(k, v, ttl)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
     }
     for (k, _) in &records {
         c.bench_function(
@@ -159,13 +179,70 @@ fn getting_with_search_benchmark(c: &mut Criterion) {
     }
 }
 
+// Long keys
+fn long_keys_benchmark(c: &mut Criterion) {
+    let mut store = Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+    store.clear().expect("clear store");
+    let v = b"bar".to_vec();
+
+    // from a key that fits in a buffer, to one of `Store::DEFAULT_MAX_KEY_SIZE`
+    for key_size in [1_024usize, 8_192, Store::DEFAULT_MAX_KEY_SIZE] {
+        let k = vec![b'k'; key_size];
+        store
+            .set(&k, &v, None)
+            .unwrap_or_else(|_| panic!("set key of {} bytes", key_size));
+
+        // updating hashes the key, and compares it in full with the key in the file
+        c.bench_function(&format!("update(no ttl): {} byte key", key_size), |b| {
+            b.iter_with_large_drop(|| store.set(black_box(&k), black_box(&v), black_box(None)))
+        });
+
+        c.bench_function(&format!("get(no ttl): {} byte key", key_size), |b| {
+            b.iter_with_large_drop(|| store.get(black_box(&k)))
+        });
+    }
+}
+
+// Bulk loading
+fn bulk_loading_benchmark(c: &mut Criterion) {
+    let mut store = Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+    let records: Vec<(Vec<u8>, Vec<u8>)> = (0..10_000u32)
+        .map(|i| (format!("key-{}", i).into_bytes(), b"value".to_vec()))
+        .collect();
+
+    // both include clearing the store, so that every key is a new one
+    c.bench_function("set loop: 10000 keys", |b| {
+        b.iter(|| {
+            store.clear().expect("clear store");
+            for (k, v) in &records {
+                store
+                    .set(black_box(k), black_box(v), None)
+                    .expect("set key");
+            }
+        })
+    });
+
+    c.bench_function("bulk load: 10000 keys", |b| {
+        b.iter(|| {
+            store.clear().expect("clear store");
+            let mut loader = store.bulk_loader().expect("bulk loader");
+            for (k, v) in &records {
+                loader.add(black_box(k), black_box(v), 0).expect("add key");
+            }
+            loader.finish().expect("finish load")
+        })
+    });
+}
+
 // Searching
 fn searching_without_pagination_benchmark(c: &mut Criterion) {
     let mut store = Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
     store.clear().expect("clear store");
     let records = get_records();
     for (k, v) in &records {
-        store.set(k, v, None).expect(&format!("set {:?}", k));
+        store
+            .set(k, v, None)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
     }
 
     for (k, _) in &records {
@@ -187,7 +264,9 @@ fn searching_with_pagination_benchmark(c: &mut Criterion) {
     store.clear().expect("clear store");
     let records = get_records();
     for (k, v) in &records {
-        store.set(k, v, None).expect(&format!("set {:?}", k));
+        store
+            .set(k, v, None)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
     }
 
     for (k, _) in &records {
@@ -213,7 +292,9 @@ fn deleting_benchmark(c: &mut Criterion) {
         let mut store = Store::new(STORE_PATH, None, None, None, Some(0), is_with_search)
             .expect("create store");
 
-        store.set(&k, &v, ttl).expect(&format!("set {:?}", k));
+        store
+            .set(&k, &v, ttl)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
         store
     };
 
@@ -281,7 +362,9 @@ fn clearing_benchmark(c: &mut Criterion) {
         store.clear().expect("clear store");
         let records = get_records();
         for (k, v) in &records {
-            store.set(k, v, ttl).expect(&format!("set {:?}", k));
+            store
+                .set(k, v, ttl)
+                .unwrap_or_else(|_| panic!("set {:?}", k));
         }
         store
     };
@@ -327,15 +410,19 @@ fn compacting_benchmark(c: &mut Criterion) {
         store.clear().expect("clear store");
         let records = get_records();
         for (k, v) in &records[..3] {
-            store.set(k, v, Some(1)).expect(&format!("set {:?}", k));
+            store
+                .set(k, v, Some(1))
+                .unwrap_or_else(|_| panic!("set {:?}", k));
         }
 
         for (k, v) in &records[3..] {
-            store.set(k, v, None).expect(&format!("set {:?}", k));
+            store
+                .set(k, v, None)
+                .unwrap_or_else(|_| panic!("set {:?}", k));
         }
 
         for (k, _) in &records[2..3] {
-            store.delete(k).expect(&format!("delete {:?}", k));
+            store.delete(k).unwrap_or_else(|_| panic!("delete {:?}", k));
         }
         store
     };
@@ -357,6 +444,80 @@ fn compacting_benchmark(c: &mut Criterion) {
     });
 }
 
+fn getting_during_compaction_benchmark(c: &mut Criterion) {
+    let mut store = StoreBuilder::new(STORE_PATH)
+        .compaction_interval(1)
+        .build()
+        .expect("create store");
+    store.clear().expect("clear store");
+    // many keys, half of them overwritten, so that each background compaction has a lot to copy
+    let keys: Vec<Vec<u8>> = (0..50_000)
+        .map(|i| format!("key{}", i).into_bytes())
+        .collect();
+    for k in keys.iter().chain(keys.iter().step_by(2)) {
+        store
+            .set(k, b"value", None)
+            .unwrap_or_else(|_| panic!("set {:?}", k));
+    }
+
+    let store = Arc::new(Mutex::new(store));
+    let is_done = Arc::new(AtomicBool::new(false));
+    // keep writing, so that the background compactions have writes to catch up on
+    let writer = {
+        let store = store.clone();
+        let is_done = is_done.clone();
+        let keys = keys.clone();
+        thread::spawn(move || {
+            for k in keys.iter().cycle() {
+                if is_done.load(Ordering::Relaxed) {
+                    break;
+                }
+                store
+                    .lock()
+                    .expect("lock store")
+                    .set(k, b"value", None)
+                    .unwrap_or_else(|_| panic!("set {:?}", k));
+                thread::sleep(Duration::from_micros(100));
+            }
+        })
+    };
+
+    // the gets are made at a steady rate, each one's latency counted from when it was due, so that a get
+    // held up by a compaction counts against the gets that were due in the meantime too.
+    // The time reported is the p99 latency of a get, rather than the mean.
+    let get_interval = Duration::from_millis(1);
+    let gets_per_iteration = 100;
+    let mut group = c.benchmark_group("compaction under writes");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+    group.bench_function("get p99", |b| {
+        b.iter_custom(|iters| {
+            let count = iters as usize * gets_per_iteration;
+            let start = Instant::now();
+            let mut latencies: Vec<Duration> = (0..count)
+                .map(|i| {
+                    let due = start + get_interval * i as u32;
+                    if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                        thread::sleep(wait);
+                    }
+
+                    let k = &keys[(i * 7) % keys.len()];
+                    let _ = black_box(store.lock().expect("lock store").get(k));
+                    due.elapsed()
+                })
+                .collect();
+            latencies.sort();
+            let p99 = latencies[count * 99 / 100];
+            p99 * iters as u32
+        })
+    });
+    group.finish();
+
+    is_done.store(true, Ordering::Relaxed);
+    writer.join().expect("join writer");
+}
+
 fn get_records() -> Vec<(Vec<u8>, Vec<u8>)> {
     [
         ("hey", "English"),
@@ -380,10 +541,13 @@ criterion_group!(
     updating_with_search_benchmark,
     getting_without_search_benchmark,
     getting_with_search_benchmark,
+    long_keys_benchmark,
+    bulk_loading_benchmark,
     searching_without_pagination_benchmark,
     searching_with_pagination_benchmark,
     deleting_benchmark,
     clearing_benchmark,
     compacting_benchmark,
+    getting_during_compaction_benchmark,
 );
 criterion_main!(benches);
@@ -0,0 +1,68 @@
+//! A tiny key-value HTTP service backed by scdb, using the `AsyncStore` so that
+//! the disk I/O of the store does not stall the tokio runtime's worker threads.
+//!
+//! Run it with `cargo run --example axum_scdb --features tokio` and then try:
+//!
+//! ```sh
+//! curl -X PUT -d 'English' http://localhost:3000/hey
+//! curl http://localhost:3000/hey
+//! curl -X DELETE http://localhost:3000/hey
+//! ```
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use scdb::{AsyncStore, Store};
+
+/// Converts any scdb error into a 500 response
+fn internal_error(err: scdb::ScdbError) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+async fn get_value(
+    State(store): State<AsyncStore>,
+    Path(key): Path<String>,
+) -> Result<Vec<u8>, (StatusCode, String)> {
+    match store.get(key.as_bytes()).await.map_err(internal_error)? {
+        Some(v) => Ok(v),
+        None => Err((StatusCode::NOT_FOUND, format!("'{}' not found", key))),
+    }
+}
+
+async fn set_value(
+    State(store): State<AsyncStore>,
+    Path(key): Path<String>,
+    body: Bytes,
+) -> Result<StatusCode, (StatusCode, String)> {
+    store
+        .set(key.as_bytes(), &body, None)
+        .await
+        .map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_value(
+    State(store): State<AsyncStore>,
+    Path(key): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    store.delete(key.as_bytes()).await.map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tokio::main]
+async fn main() {
+    let store =
+        Store::new("db", Some(1000), Some(1), Some(10), Some(1800), true).expect("create store");
+    let store = AsyncStore::new(store);
+
+    let app = Router::new()
+        .route("/{key}", get(get_value).put(set_value).delete(delete_value))
+        .with_state(store);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+        .await
+        .expect("bind to port 3000");
+    println!("Listening on http://127.0.0.1:3000");
+    axum::serve(listener, app).await.expect("serve");
+}
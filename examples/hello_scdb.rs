@@ -59,7 +59,14 @@ fn main() {
     // Getting the values (this is similar to what is in `get_all(&mut store, &keys)` function
     let data: Vec<(&str, Option<Vec<u8>>)> = keys
         .iter()
-        .map(|k| (*k, store.get(k.as_bytes()).expect(&format!("get {}", k))))
+        .map(|k| {
+            (
+                *k,
+                store
+                    .get(k.as_bytes())
+                    .unwrap_or_else(|_| panic!("get {}", k)),
+            )
+        })
         .collect();
     pprint_data!("After inserting data", &data);
 
@@ -117,7 +124,7 @@ fn main() {
     for k in keys_to_delete {
         store
             .delete(k.as_bytes())
-            .expect(&format!("delete key {}", k));
+            .unwrap_or_else(|_| panic!("delete key {}", k));
     }
 
     let data = get_all(&mut store, &keys);
@@ -134,6 +141,13 @@ fn main() {
 /// Gets all from store for the given keys
 fn get_all<'a>(store: &mut Store, keys: &Vec<&'a str>) -> Vec<(&'a str, Option<Vec<u8>>)> {
     keys.iter()
-        .map(|k| (*k, store.get(k.as_bytes()).expect(&format!("get {}", k))))
+        .map(|k| {
+            (
+                *k,
+                store
+                    .get(k.as_bytes())
+                    .unwrap_or_else(|_| panic!("get {}", k)),
+            )
+        })
         .collect()
 }
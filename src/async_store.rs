@@ -0,0 +1,186 @@
+use crate::error::Result;
+use crate::internal::acquire_lock;
+use crate::{ScdbError, SearchMode, Store};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// An async facade over [Store], for use on the [tokio](https://tokio.rs) runtime
+///
+/// Every operation of [Store] blocks the calling thread on disk I/O (and on any compaction going on
+/// in the background), stalling the other tasks on that worker thread.
+/// [AsyncStore] instead runs each operation on tokio's blocking thread pool via
+/// [tokio::task::spawn_blocking].
+///
+/// It is cheap to clone, with all clones sharing the same underlying [Store]. It is only
+/// available when the `tokio` feature is enabled.
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::{AsyncStore, Store};
+///
+/// # #[tokio::main]
+/// # async fn main() -> scdb::Result<()> {
+/// let store = AsyncStore::new(Store::new("db", None, None, None, None, true)?);
+/// # store.clear().await?;
+/// store.set(b"foo", b"bar", None).await?;
+/// assert_eq!(store.get(b"foo").await?, Some(b"bar".to_vec()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncStore {
+    store: Arc<Mutex<Store>>,
+}
+
+impl AsyncStore {
+    /// Creates a new async facade over the given [Store]
+    pub fn new(store: Store) -> Self {
+        Self {
+            store: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Sets the given key value in the store, just like [Store::set]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set], or with [ScdbError::Io] if
+    /// the blocking task panics.
+    pub async fn set(&self, k: &[u8], v: &[u8], ttl: Option<u64>) -> Result<()> {
+        let (k, v) = (k.to_vec(), v.to_vec());
+        self.run(move |store| store.set(&k, &v, ttl)).await
+    }
+
+    /// Returns the value corresponding to the given key, just like [Store::get]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::get], or with [ScdbError::Io] if
+    /// the blocking task panics.
+    pub async fn get(&self, k: &[u8]) -> Result<Option<Vec<u8>>> {
+        let k = k.to_vec();
+        self.run(move |store| store.get(&k)).await
+    }
+
+    /// Deletes the key-value for the given key, just like [Store::delete]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::delete], or with [ScdbError::Io] if
+    /// the blocking task panics.
+    pub async fn delete(&self, k: &[u8]) -> Result<bool> {
+        let k = k.to_vec();
+        self.run(move |store| store.delete(&k)).await
+    }
+
+    /// Clears all data in the store, just like [Store::clear]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::clear], or with [ScdbError::Io] if
+    /// the blocking task panics.
+    pub async fn clear(&self) -> Result<()> {
+        self.run(|store| store.clear()).await
+    }
+
+    /// Searches for unexpired keys containing the given search term, in [SearchMode::Contains],
+    /// just like [Store::search]
+    ///
+    /// To get only the keys that start with the whole term, use [AsyncStore::search_with_mode]
+    /// with [SearchMode::Prefix].
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::search], or with [ScdbError::Io] if
+    /// the blocking task panics.
+    pub async fn search(
+        &self,
+        term: &[u8],
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.search_with_mode(term, skip, limit, SearchMode::Contains)
+            .await
+    }
+
+    /// Searches for unexpired keys matching the given search term, just like [Store::search_with_mode]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::search_with_mode], or with [ScdbError::Io] if
+    /// the blocking task panics.
+    pub async fn search_with_mode(
+        &self,
+        term: &[u8],
+        skip: u64,
+        limit: u64,
+        mode: SearchMode,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let term = term.to_vec();
+        self.run(move |store| store.search_with_mode(&term, skip, limit, mode))
+            .await
+    }
+
+    /// Runs the given operation on the store in tokio's blocking thread pool
+    async fn run<F, T>(&self, op: F) -> Result<T>
+    where
+        F: FnOnce(&mut Store) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut store = acquire_lock!(store)?;
+            op(&mut store)
+        })
+        .await
+        .map_err(|e| ScdbError::Io(io::Error::other(e)))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::fs;
+
+    const STORE_PATH: &str = "db_async";
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn async_store_works() {
+        let store = Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        let store = AsyncStore::new(store);
+        store.clear().await.expect("clear store");
+
+        let handles: Vec<_> = ["hey", "hi", "hola"]
+            .into_iter()
+            .map(|k| {
+                let store = store.clone();
+                tokio::spawn(async move { store.set(k.as_bytes(), b"salut", None).await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.expect("join").expect("set");
+        }
+
+        assert_eq!(
+            store.get(b"hi").await.expect("get hi"),
+            Some(b"salut".to_vec())
+        );
+        assert_eq!(
+            store.search(b"ho", 0, 0).await.expect("search ho"),
+            vec![(b"hola".to_vec(), b"salut".to_vec())]
+        );
+
+        assert!(store.delete(b"hi").await.expect("delete hi"));
+        assert_eq!(store.get(b"hi").await.expect("get hi"), None);
+        assert_eq!(store.search(b"h", 0, 0).await.expect("search h").len(), 2);
+
+        store.clear().await.expect("clear store");
+        assert_eq!(store.get(b"hey").await.expect("get hey"), None);
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+}
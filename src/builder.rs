@@ -0,0 +1,308 @@
+use crate::clock::{Clock, SystemClock};
+use crate::error::{Result, ScdbError};
+use crate::hasher::{KeyHasher, Xxh3Hasher};
+use crate::probing::Probing;
+use crate::Store;
+use std::sync::Arc;
+
+/// A builder for configuring and opening a [Store]
+///
+/// It allows setting only those configurations that differ from the defaults
+/// instead of passing every one of them to [Store::new].
+/// See the docs of [Store] for what each configuration means.
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::StoreBuilder;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut store = StoreBuilder::new("db")
+///     .max_keys(1000)
+///     .compaction_interval(1800)
+///     .is_search_enabled(true)
+///     .max_value_size(1024)
+///     .build()?;
+/// # store.clear()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StoreBuilder {
+    pub(crate) store_path: String,
+    pub(crate) max_keys: Option<u64>,
+    pub(crate) redundant_blocks: Option<u16>,
+    pub(crate) pool_capacity: Option<usize>,
+    pub(crate) compaction_interval: Option<u32>,
+    pub(crate) is_search_enabled: bool,
+    pub(crate) max_index_key_len: Option<u32>,
+    pub(crate) max_key_size: Option<usize>,
+    pub(crate) max_value_size: Option<usize>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) stale_grace_secs: u64,
+    pub(crate) hasher: Arc<dyn KeyHasher>,
+    pub(crate) is_durable: bool,
+    pub(crate) auto_grow: bool,
+    pub(crate) high_water_mark: Option<f64>,
+    pub(crate) is_high_water_mark_strict: bool,
+    pub(crate) db_file_name: Option<String>,
+    pub(crate) index_file_name: Option<String>,
+    pub(crate) compact_on_open: bool,
+    pub(crate) must_exist: bool,
+    pub(crate) probing: Probing,
+}
+
+impl StoreBuilder {
+    /// Creates a new builder for the store whose data is to be kept in the `store_path` directory
+    pub fn new(store_path: &str) -> Self {
+        Self {
+            store_path: store_path.to_string(),
+            max_keys: None,
+            redundant_blocks: None,
+            pool_capacity: None,
+            compaction_interval: None,
+            is_search_enabled: false,
+            max_index_key_len: None,
+            max_key_size: None,
+            max_value_size: None,
+            clock: Arc::new(SystemClock),
+            stale_grace_secs: 0,
+            hasher: Arc::new(Xxh3Hasher),
+            is_durable: false,
+            auto_grow: false,
+            high_water_mark: None,
+            is_high_water_mark_strict: false,
+            db_file_name: None,
+            index_file_name: None,
+            compact_on_open: false,
+            must_exist: false,
+            probing: Probing::None,
+        }
+    }
+
+    /// Sets the maximum number of key-value pairs to store in store.
+    /// Default: [Store::DEFAULT_MAX_KEYS] i.e. 1 million
+    pub fn max_keys(mut self, max_keys: u64) -> Self {
+        self.max_keys = Some(max_keys);
+        self
+    }
+
+    /// Sets the number of redundant index blocks to mitigate hash collisions.
+    /// Default: [Store::DEFAULT_REDUNDANT_BLOCKS] i.e. 1
+    pub fn redundant_blocks(mut self, redundant_blocks: u16) -> Self {
+        self.redundant_blocks = Some(redundant_blocks);
+        self
+    }
+
+    /// Sets the number of buffers to hold in memory as cache's for the store.
+    /// Default: [Store::DEFAULT_POOL_CAPACITY] i.e. 5
+    pub fn pool_capacity(mut self, pool_capacity: usize) -> Self {
+        self.pool_capacity = Some(pool_capacity);
+        self
+    }
+
+    /// Sets the interval in seconds at which the store is compacted in the background.
+    /// If 0, no background compaction is done. If a compaction is still in progress when the interval
+    /// elapses, that background compaction is skipped rather than queued.
+    /// Default: [Store::DEFAULT_COMPACTION_INTERVAL] i.e. 3600 (1 hour)
+    pub fn compaction_interval(mut self, compaction_interval: u32) -> Self {
+        self.compaction_interval = Some(compaction_interval);
+        self
+    }
+
+    /// Sets whether the search capability of the store is enabled. Default: false
+    pub fn is_search_enabled(mut self, is_search_enabled: bool) -> Self {
+        self.is_search_enabled = is_search_enabled;
+        self
+    }
+
+    /// Sets the number of bytes at the start of each key that are indexed for search.
+    /// Searches match keys by these first bytes, so the bigger it is, the fewer the keys each search
+    /// has to check, but the bigger the search index file. It is ignored if search is not enabled
+    /// and for search index files that already exist. Default: [Store::DEFAULT_MAX_INDEX_KEY_LEN] i.e. 3
+    ///
+    /// Note that every key is kept in full in the search index, once for each of its first
+    /// `max_index_key_len` bytes (i.e. each prefix it is searchable by). So a key of `n` bytes takes up
+    /// at least `min(n, max_index_key_len) * (n + 42)` bytes in the search index file, besides the `n + 25`
+    /// bytes (plus the value) it takes up in the database file. For long keys, e.g. URLs, the search index
+    /// can thus dwarf the data. Setting it to 0 keeps no search index at all, just like disabling search.
+    pub fn max_index_key_len(mut self, max_index_key_len: u32) -> Self {
+        self.max_index_key_len = Some(max_index_key_len);
+        self
+    }
+
+    /// Sets the maximum size in bytes of any key to be set in the store.
+    /// Keys bigger than this are rejected by `set` before they are hashed or compared with the keys
+    /// already in the index, so that an accidentally huge key can't slow down the whole store.
+    /// Default: [Store::DEFAULT_MAX_KEY_SIZE] i.e. 65536 (64 KiB)
+    pub fn max_key_size(mut self, max_key_size: usize) -> Self {
+        self.max_key_size = Some(max_key_size);
+        self
+    }
+
+    /// Sets the maximum size in bytes of any value to be set in the store.
+    /// Values bigger than this are rejected by `set` before anything is written to disk.
+    /// Default: no limit, other than the maximum size a key-value entry can have on disk
+    pub fn max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = Some(max_value_size);
+        self
+    }
+
+    /// Sets the clock used to compute and check the expiry of key-value pairs.
+    /// Default: [SystemClock]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the number of seconds for which an expired key-value pair is still treated as unexpired by every read
+    /// (`get`, `peek`, `get_versioned`, `get_range`, iteration, `search` etc.), for serving stale values while
+    /// fresh ones are being computed. [Store::get_meta] tells such values apart,
+    /// as they are marked as expired. Compaction only reclaims expired key-value pairs once their grace period
+    /// is over. Default: 0, for expired key-value pairs to be treated as missing right away
+    pub fn stale_grace_secs(mut self, stale_grace_secs: u64) -> Self {
+        self.stale_grace_secs = stale_grace_secs;
+        self
+    }
+
+    /// Sets the hasher used to place keys in the index of the database file.
+    /// An existing database file with keys can only be opened with a hasher of the same [KeyHasher::id]
+    /// as the one it was created with. Default: [Xxh3Hasher]
+    pub fn hasher(mut self, hasher: Arc<dyn KeyHasher>) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Sets whether every `set` and `delete` is made all-or-nothing across a crash, using
+    /// a write-ahead log kept in the `store_path`. This makes writes slower as each of them is synced
+    /// to disk. It is ignored by [Store::from_files] and [Store::in_memory]. Default: false
+    pub fn is_durable(mut self, is_durable: bool) -> Self {
+        self.is_durable = is_durable;
+        self
+    }
+
+    /// Sets whether the store grows by one redundant index block when a key can't be set because
+    /// all its slots in the index are taken. Growing rebuilds the whole database file (just like compaction),
+    /// so it is slow. When false, `set` fails with [crate::ScdbError::CollisionSaturated] instead.
+    /// Default: false
+    pub fn auto_grow(mut self, auto_grow: bool) -> Self {
+        self.auto_grow = auto_grow;
+        self
+    }
+
+    /// Sets the load factor (i.e. the unexpired keys over `max_keys`), between 0 and 1, at which `set`
+    /// logs a warning, giving room to grow the store before keys start failing with
+    /// [crate::ScdbError::CollisionSaturated]. Default: None, for no warnings
+    pub fn high_water_mark(mut self, high_water_mark: f64) -> Self {
+        self.high_water_mark = Some(high_water_mark);
+        self
+    }
+
+    /// Sets whether `set` fails with [crate::ScdbError::HighWaterMarkReached] for new keys, instead of
+    /// just logging a warning, once the load factor has reached the `high_water_mark`.
+    /// Keys already in the store can still be updated. Default: false
+    pub fn is_high_water_mark_strict(mut self, is_high_water_mark_strict: bool) -> Self {
+        self.is_high_water_mark_strict = is_high_water_mark_strict;
+        self
+    }
+
+    /// Sets the name of the database file in the `store_path`. Stores with different database file names
+    /// can thus share one `store_path`. The write-ahead log, if any, is named after it too,
+    /// with a ".wal" extension. Default: "dump.scdb"
+    pub fn db_file_name(mut self, db_file_name: &str) -> Self {
+        self.db_file_name = Some(db_file_name.to_string());
+        self
+    }
+
+    /// Sets the name of the search index file in the `store_path`. Default: "index.iscdb"
+    pub fn index_file_name(mut self, index_file_name: &str) -> Self {
+        self.index_file_name = Some(index_file_name.to_string());
+        self
+    }
+
+    /// Sets whether the store is compacted once as it is opened, reclaiming the space taken up by
+    /// deleted, expired or overwritten key-value pairs without waiting for the first background compaction.
+    /// This makes opening the store as slow as [Store::compact], so leave it off for huge stores whose
+    /// startup time matters. Default: false
+    pub fn compact_on_open(mut self, compact_on_open: bool) -> Self {
+        self.compact_on_open = compact_on_open;
+        self
+    }
+
+    /// Sets whether opening the store fails with a [std::io::ErrorKind::NotFound] error if its database file
+    /// does not exist yet, instead of creating a new empty store. This guards against a mistyped
+    /// `store_path` silently creating a new store when an existing one was meant. See [Store::open_existing].
+    /// It is ignored by [Store::from_files] and [Store::in_memory]. Default: false
+    pub fn must_exist(mut self, must_exist: bool) -> Self {
+        self.must_exist = must_exist;
+        self
+    }
+
+    /// Sets how other slots of an index block are looked for when the slot that a key hashes to is taken
+    /// by another key, before falling through to the next redundant block. It makes
+    /// [crate::ScdbError::CollisionSaturated] rarer without making the database file bigger, as
+    /// `redundant_blocks` do. It is ignored for database files that already have keys, which keep
+    /// the probing they were created with. Default: [Probing::None]
+    pub fn probing(mut self, probing: Probing) -> Self {
+        self.probing = probing;
+        self
+    }
+
+    /// Opens the store with the given configuration, creating it if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [crate::ScdbError::Io] if it can't write to the `store_path` say due to permissions errors,
+    /// or if the database file does not exist and [StoreBuilder::must_exist] is set
+    pub fn build(self) -> Result<Store> {
+        Store::from_builder(self)
+    }
+
+    /// Returns true if a search index is to be kept i.e. if search is enabled and
+    /// `max_index_key_len` is not 0
+    pub(crate) fn is_search_index_kept(&self) -> bool {
+        self.is_search_enabled && self.max_index_key_len != Some(0)
+    }
+
+    /// Checks that the configuration is usable, returning an [ScdbError::InvalidInput]
+    /// naming the first invalid setting if it is not
+    pub(crate) fn validate(&self) -> Result<()> {
+        let positive_settings = [
+            ("max_keys", self.max_keys),
+            ("pool_capacity", self.pool_capacity.map(|v| v as u64)),
+            ("max_key_size", self.max_key_size.map(|v| v as u64)),
+        ];
+        for (name, value) in positive_settings {
+            if value == Some(0) {
+                return Err(ScdbError::InvalidInput(format!(
+                    "{} must be greater than 0",
+                    name
+                )));
+            }
+        }
+
+        let file_names = [
+            ("db_file_name", &self.db_file_name),
+            ("index_file_name", &self.index_file_name),
+        ];
+        for (name, value) in file_names {
+            if value.as_deref() == Some("") {
+                return Err(ScdbError::InvalidInput(format!(
+                    "{} must not be empty",
+                    name
+                )));
+            }
+        }
+
+        if let Some(mark) = self.high_water_mark {
+            if !(mark > 0.0 && mark <= 1.0) {
+                return Err(ScdbError::InvalidInput(format!(
+                    "high_water_mark must be greater than 0 and at most 1, got {}",
+                    mark
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
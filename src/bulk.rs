@@ -0,0 +1,179 @@
+use crate::error::Result;
+use crate::internal::{AppendedEntry, KeyValueEntry, ValueEntry};
+use crate::Store;
+
+/// The number of bytes of key-value entries gathered in memory before they are appended to the file in one write
+const BATCH_SIZE_IN_BYTES: usize = 4 * 1024 * 1024;
+
+/// A loader for adding many key-value pairs to a [Store] much faster than calling [Store::set] for each of them
+/// e.g. when migrating a large dataset into the store
+///
+/// The key-value pairs are appended to the database file sequentially, in large batches, and the index
+/// is built in a single pass over all of them when [BulkLoader::finish] is called. Until then, none of them
+/// is visible in the store. If the loader is dropped without calling [BulkLoader::finish], or if
+/// [BulkLoader::finish] fails, the key-value pairs added are discarded, leaving the store as it was.
+///
+/// It is got via [Store::bulk_loader].
+///
+/// The loader takes exclusive ownership of the store for the whole load: it borrows the store mutably,
+/// and compaction, including that in the background, is paused until the loader is dropped.
+///
+/// Just like with [Store::set], a key that is already in the store is overwritten, and if a key is added
+/// more than once, the last of its values wins. The store is however not grown, even if it was built with
+/// [crate::StoreBuilder::auto_grow], and its high-water mark is not checked. The keys (but not the values)
+/// are kept in memory until the end of the load, and so is the index of the store during [BulkLoader::finish].
+///
+/// # Examples
+///
+/// ```rust
+/// # use scdb::Store;
+/// #
+/// # fn main() -> std::io::Result<()> {
+/// # let mut store = Store::new("db", None, None, None, None, false)?;
+/// # store.clear()?;
+/// let mut loader = store.bulk_loader()?;
+/// loader.add(&b"foo"[..], &b"bar"[..], 0)?;
+/// loader.extend(vec![("hey", "English", 0), ("hi", "Swahili", 0)])?;
+/// assert_eq!(loader.finish()?, 3);
+///
+/// assert_eq!(store.get(&b"hi"[..])?, Some(b"Swahili".to_vec()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BulkLoader<'a> {
+    store: &'a mut Store,
+    batch: Vec<u8>,
+    entries: Vec<AppendedEntry>,
+    // the index of the first entry in `entries` that is still in `batch`
+    first_unwritten: usize,
+    // the address of the first entry of the load, once it is appended to the file
+    start: Option<u64>,
+    is_finished: bool,
+}
+
+impl<'a> BulkLoader<'a> {
+    /// Creates a new loader for the given store, pausing its compaction until the loader is dropped
+    pub(crate) fn new(store: &'a mut Store) -> Self {
+        store.start_bulk_load();
+
+        Self {
+            store,
+            batch: Vec::with_capacity(BATCH_SIZE_IN_BYTES),
+            entries: vec![],
+            first_unwritten: 0,
+            start: None,
+            is_finished: false,
+        }
+    }
+
+    /// Adds the given key value to the load, to expire at `expiry` i.e. the timestamp in seconds since
+    /// the unix epoch, or never if `expiry` is 0
+    ///
+    /// An `expiry` in the past is not rejected, unlike in [Store::set_with_expiry_at], but the key-value pair
+    /// is then already expired.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [crate::ScdbError::InvalidInput] if the key is empty, if the key is bigger than the configured
+    /// `max_key_size`, if the value is bigger than the configured `max_value_size` or if the key-value pair
+    /// is too big to be saved on disk.
+    /// It may fail with [crate::ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    pub fn add(&mut self, k: &[u8], v: &[u8], expiry: u64) -> Result<()> {
+        self.store.validate_key_value(k, v)?;
+
+        let kv = KeyValueEntry::new(k, v, expiry)?.with_version(1);
+        let kv_bytes = kv.as_bytes();
+        self.entries.push(AppendedEntry {
+            key: k.to_vec(),
+            // the offset in the batch, until the batch is appended to the file
+            address: self.batch.len() as u64,
+            size: kv_bytes.len() as u32,
+            expiry,
+        });
+        self.batch.extend_from_slice(&kv_bytes);
+
+        if self.batch.len() >= BATCH_SIZE_IN_BYTES {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds all the given `(key, value, expiry)` tuples to the load, just like [BulkLoader::add]
+    ///
+    /// # Errors
+    ///
+    /// It fails with the same errors as [BulkLoader::add], stopping at the first tuple that fails.
+    pub fn extend<I, K, V>(&mut self, entries: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V, u64)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        for (k, v, expiry) in entries {
+            self.add(k.as_ref(), v.as_ref(), expiry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of key-value pairs added to the load so far, counting every time a key is added
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no key-value pair has been added to the load yet
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Indexes all the key-value pairs added to the load, making them visible in the store.
+    /// It returns the number of keys loaded i.e. without counting the keys added more than once.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [crate::ScdbError::CollisionSaturated] if all the slots of a key in the index are taken,
+    /// or with [crate::ScdbError::KeysMaxedOut] if the store would end up with more than `max_keys` unexpired keys,
+    /// in which case none of the key-value pairs of the load is kept.
+    /// It may fail with [crate::ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    pub fn finish(mut self) -> Result<u64> {
+        self.flush()?;
+        let Some(start) = self.start else {
+            return Ok(0);
+        };
+
+        // whichever way it goes, the entries are not to be discarded on drop anymore,
+        // as they are either indexed or have been discarded already
+        self.is_finished = true;
+        self.store.finish_bulk_load(&self.entries, start)
+    }
+
+    /// Appends the batch of entries gathered in memory to the file
+    fn flush(&mut self) -> Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+
+        let address = self.store.append_bulk_entries(&self.batch)?;
+        self.start.get_or_insert(address);
+        for entry in &mut self.entries[self.first_unwritten..] {
+            entry.address += address;
+        }
+
+        self.first_unwritten = self.entries.len();
+        self.batch.clear();
+        Ok(())
+    }
+}
+
+impl Drop for BulkLoader<'_> {
+    fn drop(&mut self) {
+        let discard_from = if self.is_finished { None } else { self.start };
+        if let Err(e) = self.store.end_bulk_load(discard_from) {
+            log::warn!("failed to discard the unfinished bulk load: {}", e);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::{Result, ScdbError};
+use crate::{Namespace, Store};
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+/// A minimal interface of a cache of byte keys and values, for code that is to work with
+/// any cache backend, be it a [Store], a [Namespace] of one or anything else implementing it
+///
+/// For a [Store] (and a [Namespace]), the methods map to:
+///
+/// - `get` => [Store::get]
+/// - `insert` => [Store::set] with no time-to-live, so the key-value pair never expires
+/// - `remove` => [Store::delete], returning true if the key existed
+/// - `clear` => [Store::clear] (or [Namespace::clear], leaving the rest of the store intact,
+///   but failing with [ScdbError::SearchDisabled] if search is not enabled)
+///
+/// It is also implemented for a [HashMap], as an in-memory backend e.g. for tests.
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::{Cache, Store};
+/// use std::collections::HashMap;
+///
+/// fn remember<C: Cache>(cache: &mut C) -> Result<Option<Vec<u8>>, C::Error> {
+///     cache.insert(&b"foo"[..], &b"bar"[..])?;
+///     cache.get(&b"foo"[..])
+/// }
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut store = Store::new("db", None, None, None, None, false)?;
+/// assert_eq!(remember(&mut store)?, Some(b"bar".to_vec()));
+///
+/// let mut map = HashMap::new();
+/// assert_eq!(remember(&mut map).unwrap(), Some(b"bar".to_vec()));
+/// # Ok(())
+/// # }
+/// ```
+pub trait Cache {
+    /// The error returned by the operations of the cache
+    type Error;
+
+    /// Returns the value corresponding to the given key, or None if there is none
+    fn get(&mut self, key: &[u8]) -> std::result::Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Inserts the given key value into the cache, overwriting any value the key already had
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> std::result::Result<(), Self::Error>;
+
+    /// Removes the given key from the cache, returning true if it was there
+    fn remove(&mut self, key: &[u8]) -> std::result::Result<bool, Self::Error>;
+
+    /// Removes all key-value pairs from the cache
+    fn clear(&mut self) -> std::result::Result<(), Self::Error>;
+}
+
+impl Cache for Store {
+    type Error = ScdbError;
+
+    fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Store::get(self, key)
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.set(key, value, None)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<bool> {
+        self.delete(key)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        Store::clear(self)
+    }
+}
+
+impl Cache for Namespace<'_> {
+    type Error = ScdbError;
+
+    fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Namespace::get(self, key)
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.set(key, value, None)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<bool> {
+        self.delete(key)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        Namespace::clear(self)
+    }
+}
+
+impl Cache for HashMap<Vec<u8>, Vec<u8>> {
+    type Error = Infallible;
+
+    fn get(&mut self, key: &[u8]) -> std::result::Result<Option<Vec<u8>>, Infallible> {
+        Ok(HashMap::get(self, key).cloned())
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> std::result::Result<(), Infallible> {
+        HashMap::insert(self, key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> std::result::Result<bool, Infallible> {
+        Ok(HashMap::remove(self, key).is_some())
+    }
+
+    fn clear(&mut self) -> std::result::Result<(), Infallible> {
+        HashMap::clear(self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::fmt::Debug;
+    use std::fs;
+
+    const STORE_PATH: &str = "db_cache";
+
+    /// Runs the same operations on the given cache, asserting the results are as expected
+    fn exercise<C: Cache>(cache: &mut C)
+    where
+        C::Error: Debug,
+    {
+        cache.clear().expect("clear");
+        cache.insert(&b"foo"[..], &b"bar"[..]).expect("insert foo");
+        cache
+            .insert(&b"hey"[..], &b"English"[..])
+            .expect("insert hey");
+        cache
+            .insert(&b"foo"[..], &b"baz"[..])
+            .expect("overwrite foo");
+        assert_eq!(
+            cache.get(&b"foo"[..]).expect("get foo"),
+            Some(b"baz".to_vec())
+        );
+
+        assert!(cache.remove(&b"foo"[..]).expect("remove foo"));
+        assert!(!cache.remove(&b"foo"[..]).expect("remove foo again"));
+        assert_eq!(cache.get(&b"foo"[..]).expect("get foo"), None);
+
+        cache.clear().expect("clear");
+        assert_eq!(cache.get(&b"hey"[..]).expect("get hey"), None);
+    }
+
+    #[test]
+    #[serial]
+    fn cache_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        exercise(&mut store);
+
+        store.set(&b"hi"[..], &b"root"[..], None).expect("set hi");
+        exercise(&mut store.namespaced(&b"ns:"[..]).expect("namespace"));
+        // clearing the namespace leaves the rest of the store intact
+        assert_eq!(
+            store.get(&b"hi"[..]).expect("get hi"),
+            Some(b"root".to_vec())
+        );
+
+        exercise(&mut HashMap::new());
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+}
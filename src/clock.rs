@@ -0,0 +1,55 @@
+use crate::internal::get_current_timestamp;
+use std::fmt::Debug;
+
+/// A source of the current time, used by the [crate::Store] to compute and check expiries
+///
+/// The default is the [SystemClock]. A custom clock can be passed to the
+/// [crate::StoreBuilder::clock] e.g. to advance time instantly in tests.
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::{Clock, StoreBuilder};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+///
+/// #[derive(Debug)]
+/// struct MockClock(AtomicU64);
+///
+/// impl Clock for MockClock {
+///     fn now_secs(&self) -> u64 {
+///         self.0.load(Ordering::SeqCst)
+///     }
+/// }
+///
+/// # fn main() -> std::io::Result<()> {
+/// let clock = Arc::new(MockClock(AtomicU64::new(1_000)));
+/// let mut store = StoreBuilder::new("db_clock")
+///     .compaction_interval(0)
+///     .clock(clock.clone())
+///     .build()?;
+/// store.set(&b"foo"[..], &b"bar"[..], Some(10))?;
+///
+/// // advance time beyond the expiry of 'foo'
+/// clock.0.store(1_011, Ordering::SeqCst);
+/// assert_eq!(store.get(&b"foo"[..])?, None);
+/// # drop(store);
+/// # std::fs::remove_dir_all("db_clock")?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current timestamp in seconds from unix epoch
+    fn now_secs(&self) -> u64;
+}
+
+/// The clock that reads the real system time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now_secs(&self) -> u64 {
+        get_current_timestamp()
+    }
+}
@@ -0,0 +1,113 @@
+/// The configuration of a [crate::Store] that can be kept in a file e.g. as JSON or TOML
+///
+/// Every field is optional, with the same defaults as [crate::StoreBuilder]. Unlike the builder, it has
+/// no clock or hasher as those can't be serialized; stores opened from it use the defaults for them.
+/// With the `serde` feature enabled, it implements `serde::Serialize` and `serde::Deserialize`,
+/// with any missing fields taking their defaults. See [crate::Store::from_config].
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::{Store, StoreConfig};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let config = StoreConfig {
+///     max_keys: Some(1000),
+///     compaction_interval: Some(1800),
+///     is_search_enabled: true,
+///     ..Default::default()
+/// };
+/// let mut store = Store::from_config("db", config)?;
+/// # store.clear()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct StoreConfig {
+    /// The maximum number of key-value pairs to store in store.
+    /// Default: [crate::Store::DEFAULT_MAX_KEYS] i.e. 1 million
+    pub max_keys: Option<u64>,
+    /// The number of redundant index blocks to mitigate hash collisions.
+    /// Default: [crate::Store::DEFAULT_REDUNDANT_BLOCKS] i.e. 1
+    pub redundant_blocks: Option<u16>,
+    /// The number of buffers to hold in memory as cache's for the store.
+    /// Default: [crate::Store::DEFAULT_POOL_CAPACITY] i.e. 5
+    pub pool_capacity: Option<usize>,
+    /// The interval in seconds at which the store is compacted in the background.
+    /// If 0, no background compaction is done. If a compaction is still in progress when the interval
+    /// elapses, that background compaction is skipped rather than queued.
+    /// Default: [crate::Store::DEFAULT_COMPACTION_INTERVAL] i.e. 3600 (1 hour)
+    pub compaction_interval: Option<u32>,
+    /// Whether the search capability of the store is enabled. Default: false
+    pub is_search_enabled: bool,
+    /// The number of bytes at the start of each key that are indexed for search, or 0 to keep
+    /// no search index. See [crate::StoreBuilder::max_index_key_len] for its cost.
+    /// Default: [crate::Store::DEFAULT_MAX_INDEX_KEY_LEN] i.e. 3
+    pub max_index_key_len: Option<u32>,
+    /// The maximum size in bytes of any key to be set in the store.
+    /// Default: [crate::Store::DEFAULT_MAX_KEY_SIZE] i.e. 65536 (64 KiB)
+    pub max_key_size: Option<usize>,
+    /// The maximum size in bytes of any value to be set in the store. Default: no limit
+    pub max_value_size: Option<usize>,
+    /// The number of seconds for which an expired key-value pair is still returned by `get`.
+    /// See [crate::StoreBuilder::stale_grace_secs]. Default: 0
+    pub stale_grace_secs: u64,
+    /// Whether every `set` and `delete` is made all-or-nothing across a crash. Default: false
+    pub is_durable: bool,
+    /// Whether the store grows by one redundant index block when a key can't be set because
+    /// all its slots in the index are taken. Default: false
+    pub auto_grow: bool,
+    /// The load factor, between 0 and 1, at which `set` logs a warning. Default: None, for no warnings
+    pub high_water_mark: Option<f64>,
+    /// Whether `set` fails for new keys once the load factor has reached the `high_water_mark`. Default: false
+    pub is_high_water_mark_strict: bool,
+    /// The name of the database file in the store's folder. Default: "dump.scdb"
+    pub db_file_name: Option<String>,
+    /// The name of the search index file in the store's folder. Default: "index.iscdb"
+    pub index_file_name: Option<String>,
+    /// Whether the store is compacted once as it is opened. Default: false
+    pub compact_on_open: bool,
+    /// Whether opening the store fails if its database file does not exist yet. Default: false
+    pub must_exist: bool,
+    /// How other slots of an index block are looked for when the slot that a key hashes to is taken.
+    /// See [crate::StoreBuilder::probing]. Default: [crate::Probing::None]
+    pub probing: crate::Probing,
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_works() {
+        let config: StoreConfig = serde_json::from_str(
+            r#"{"max_keys": 1000, "is_search_enabled": true, "max_index_key_len": 5}"#,
+        )
+        .expect("deserialize config");
+        assert_eq!(
+            config,
+            StoreConfig {
+                max_keys: Some(1000),
+                is_search_enabled: true,
+                max_index_key_len: Some(5),
+                ..Default::default()
+            }
+        );
+
+        let json = serde_json::to_string(&config).expect("serialize config");
+        let got: StoreConfig = serde_json::from_str(&json).expect("deserialize config");
+        assert_eq!(got, config);
+
+        let got: StoreConfig = serde_json::from_str("{}").expect("deserialize empty config");
+        assert_eq!(got, StoreConfig::default());
+
+        let err =
+            serde_json::from_str::<StoreConfig>(r#"{"max_key": 1000}"#).expect_err("unknown field");
+        assert!(err.to_string().contains("unknown field `max_key`"));
+    }
+}
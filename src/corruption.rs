@@ -0,0 +1,22 @@
+use std::fmt::{Display, Formatter};
+
+/// A problem with a key-value entry in the database file, as found by [crate::Store::verify]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    /// The address in the database file of the index entry that points to the key-value entry
+    pub index_address: u64,
+    /// The address in the database file of the key-value entry
+    pub kv_address: u64,
+    /// A description of what is wrong with the key-value entry
+    pub message: String,
+}
+
+impl Display for Corruption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key-value entry at {} (indexed at {}): {}",
+            self.kv_address, self.index_address, self.message
+        )
+    }
+}
@@ -0,0 +1,98 @@
+/// The alphabet of standard base64, as in [RFC 4648](https://www.rfc-editor.org/rfc/rfc4648#section-4)
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How [crate::Store::dump] writes the keys and values, which are arbitrary bytes, as text
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ValueEncoding {
+    /// As UTF-8, with any invalid sequences replaced by "�", and any backslashes, tabs, newlines
+    /// and other control characters escaped e.g. as "\t". It is the easiest to read, but is lossy
+    /// for bytes that are not UTF-8
+    #[default]
+    Utf8Lossy,
+    /// As standard base64, with padding. It is lossless, but is not readable
+    Base64,
+}
+
+impl ValueEncoding {
+    /// Encodes the given bytes as text that has no tabs or newlines
+    pub(crate) fn encode(&self, data: &[u8]) -> String {
+        match self {
+            ValueEncoding::Utf8Lossy => {
+                let mut text = String::with_capacity(data.len());
+                for c in String::from_utf8_lossy(data).chars() {
+                    if c.is_control() || c == '\\' {
+                        text.extend(c.escape_default());
+                    } else {
+                        text.push(c);
+                    }
+                }
+                text
+            }
+            ValueEncoding::Base64 => encode_base64(data),
+        }
+    }
+}
+
+/// Encodes the given bytes as standard base64, with padding
+fn encode_base64(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0b11_1111;
+                text.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_utf8_lossy_works() {
+        let test_table: Vec<(&[u8], &str)> = vec![
+            (b"", ""),
+            (b"hello world", "hello world"),
+            ("héllo".as_bytes(), "héllo"),
+            (b"tab\tnew\nline\r", "tab\\tnew\\nline\\r"),
+            (b"back\\slash", "back\\\\slash"),
+            (b"\x00\x1b", "\\u{0}\\u{1b}"),
+            (b"bad \xff byte", "bad \u{fffd} byte"),
+        ];
+
+        for (data, expected) in test_table {
+            assert_eq!(ValueEncoding::Utf8Lossy.encode(data), expected);
+        }
+    }
+
+    #[test]
+    fn encode_base64_works() {
+        // the test vectors of RFC 4648
+        let test_table: Vec<(&[u8], &str)> = vec![
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+            (b"\xff\xfe\t\n", "//4JCg=="),
+        ];
+
+        for (data, expected) in test_table {
+            assert_eq!(ValueEncoding::Base64.encode(data), expected);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::string::FromUtf8Error;
+
+/// A specialized [std::result::Result] for the operations of the [crate::Store]
+pub type Result<T> = std::result::Result<T, ScdbError>;
+
+/// The error returned by the operations of the [crate::Store]
+///
+/// It can be coerced into an [std::io::Error] for callers that only deal with I/O errors,
+/// and the original `ScdbError` can be recovered from that [std::io::Error] via `ScdbError::from`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ScdbError {
+    /// An I/O error when accessing the files of the store
+    Io(io::Error),
+    /// No free slot could be found in the index for the given key, even in the redundant blocks.
+    /// It happens when the number of unexpired keys in the store approaches `max_keys`.
+    CollisionSaturated(Vec<u8>),
+    /// The store has reached its capacity in terms of the number of unexpired keys it can hold
+    KeysMaxedOut,
+    /// The data in the files of the store is not what was expected e.g. an entry
+    /// spills beyond the bounds of the file
+    Corruption(String),
+    /// An argument passed to the store is not valid
+    InvalidInput(String),
+    /// Search was attempted on a store whose search capability is not enabled
+    SearchDisabled,
+    /// A value got as a string e.g. via [crate::Store::get_str] is not valid UTF-8
+    InvalidUtf8(FromUtf8Error),
+    /// A new key could not be set because the load factor of the store (i.e. its unexpired keys
+    /// over its `max_keys`) has reached the strict high-water mark it was configured with
+    HighWaterMarkReached(f64),
+}
+
+impl ScdbError {
+    /// Returns the [std::io::ErrorKind] that most closely corresponds to this error
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            ScdbError::Io(e) => e.kind(),
+            ScdbError::CollisionSaturated(_)
+            | ScdbError::KeysMaxedOut
+            | ScdbError::HighWaterMarkReached(_) => io::ErrorKind::Other,
+            ScdbError::Corruption(_) => io::ErrorKind::InvalidData,
+            ScdbError::InvalidInput(_) => io::ErrorKind::InvalidInput,
+            ScdbError::SearchDisabled => io::ErrorKind::Unsupported,
+            ScdbError::InvalidUtf8(_) => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl Display for ScdbError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScdbError::Io(e) => write!(f, "IoError: {}", e),
+            ScdbError::CollisionSaturated(key) => {
+                write!(
+                    f,
+                    "CollisionSaturatedError: no free slot for key: {:?}",
+                    key
+                )
+            }
+            ScdbError::KeysMaxedOut => write!(f, "KeysMaxedOutError: the store is full"),
+            ScdbError::Corruption(msg) => write!(f, "CorruptionError: {}", msg),
+            ScdbError::InvalidInput(msg) => write!(f, "InvalidInputError: {}", msg),
+            ScdbError::SearchDisabled => {
+                write!(
+                    f,
+                    "SearchDisabledError: search is not enabled for the store"
+                )
+            }
+            ScdbError::InvalidUtf8(e) => write!(f, "InvalidUtf8Error: {}", e),
+            ScdbError::HighWaterMarkReached(load_factor) => {
+                write!(
+                    f,
+                    "HighWaterMarkReachedError: the load factor of the store is {:.2}",
+                    load_factor
+                )
+            }
+        }
+    }
+}
+
+impl Error for ScdbError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScdbError::Io(e) => Some(e),
+            ScdbError::InvalidUtf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScdbError {
+    fn from(e: io::Error) -> Self {
+        let is_scdb_error = e
+            .get_ref()
+            .map(|inner| inner.is::<ScdbError>())
+            .unwrap_or(false);
+
+        if is_scdb_error {
+            // the unwraps are safe as we have just checked the inner error is an ScdbError
+            *e.into_inner().unwrap().downcast::<ScdbError>().unwrap()
+        } else if e.kind() == io::ErrorKind::InvalidData {
+            ScdbError::Corruption(e.to_string())
+        } else {
+            ScdbError::Io(e)
+        }
+    }
+}
+
+impl From<ScdbError> for io::Error {
+    fn from(e: ScdbError) -> Self {
+        match e {
+            ScdbError::Io(e) => e,
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scdb_error_from_io_error() {
+        let got = ScdbError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(got, ScdbError::Io(e) if e.kind() == io::ErrorKind::NotFound));
+
+        let got = ScdbError::from(io::Error::new(io::ErrorKind::InvalidData, "bad header"));
+        assert!(matches!(got, ScdbError::Corruption(msg) if msg == "bad header"));
+
+        let wrapped = io::Error::other(ScdbError::CollisionSaturated(b"foo".to_vec()));
+        let got = ScdbError::from(wrapped);
+        assert!(matches!(got, ScdbError::CollisionSaturated(k) if k == b"foo".to_vec()));
+    }
+
+    #[test]
+    fn io_error_from_scdb_error() {
+        let test_data = [
+            (ScdbError::KeysMaxedOut, io::ErrorKind::Other),
+            (ScdbError::HighWaterMarkReached(0.9), io::ErrorKind::Other),
+            (
+                ScdbError::Corruption("foo".to_string()),
+                io::ErrorKind::InvalidData,
+            ),
+            (
+                ScdbError::InvalidInput("foo".to_string()),
+                io::ErrorKind::InvalidInput,
+            ),
+            (ScdbError::SearchDisabled, io::ErrorKind::Unsupported),
+            (
+                ScdbError::InvalidUtf8(String::from_utf8(vec![255u8]).unwrap_err()),
+                io::ErrorKind::InvalidData,
+            ),
+            (
+                ScdbError::Io(io::Error::from(io::ErrorKind::NotFound)),
+                io::ErrorKind::NotFound,
+            ),
+        ];
+
+        for (err, expected) in test_data {
+            let got = io::Error::from(err);
+            assert_eq!(got.kind(), expected);
+        }
+    }
+
+    #[test]
+    fn scdb_error_survives_round_trip_through_io_error() {
+        let err = io::Error::from(ScdbError::CollisionSaturated(b"foo".to_vec()));
+        let got = ScdbError::from(err);
+        assert!(matches!(got, ScdbError::CollisionSaturated(k) if k == b"foo".to_vec()));
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt::Debug;
+use twox_hash::xxh3::hash64;
+
+/// A hash function used by the [crate::Store] to place keys in the index of the database file
+///
+/// The default is the [Xxh3Hasher]. A custom hasher can be passed to the [crate::StoreBuilder::hasher]
+/// e.g. to reduce collisions for a known distribution of keys.
+///
+/// Since the hash of a key determines where it is placed in the index, the `id` of the hasher is
+/// recorded in the database file, and opening a file that holds keys with a hasher of a different `id`
+/// fails, instead of silently losing those keys. The search index always uses the default hasher.
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::{KeyHasher, StoreBuilder};
+/// use std::sync::Arc;
+///
+/// #[derive(Debug)]
+/// struct Fnv1aHasher;
+///
+/// impl KeyHasher for Fnv1aHasher {
+///     fn id(&self) -> u32 {
+///         1
+///     }
+///
+///     fn hash(&self, key: &[u8]) -> u64 {
+///         key.iter().fold(0xcbf29ce484222325, |hash, byte| {
+///             (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+///         })
+///     }
+/// }
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut store = StoreBuilder::new("db_hasher")
+///     .hasher(Arc::new(Fnv1aHasher))
+///     .build()?;
+/// store.set(&b"foo"[..], &b"bar"[..], None)?;
+/// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+/// # drop(store);
+/// # std::fs::remove_dir_all("db_hasher")?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait KeyHasher: Debug + Send + Sync {
+    /// Returns the number identifying the hash function, to be recorded in the database file.
+    /// 0 is reserved for the [Xxh3Hasher]
+    fn id(&self) -> u32;
+
+    /// Returns the hash of the given key
+    fn hash(&self, key: &[u8]) -> u64;
+}
+
+/// The default hasher, using the [xxh3](https://github.com/Cyan4973/xxHash) hash function
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Xxh3Hasher;
+
+impl KeyHasher for Xxh3Hasher {
+    #[inline]
+    fn id(&self) -> u32 {
+        0
+    }
+
+    #[inline]
+    fn hash(&self, key: &[u8]) -> u64 {
+        hash64(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// A poor hasher that just sums up the bytes of the key
+    #[derive(Debug)]
+    struct SumHasher;
+
+    impl KeyHasher for SumHasher {
+        fn id(&self) -> u32 {
+            1
+        }
+
+        fn hash(&self, key: &[u8]) -> u64 {
+            key.iter().map(|v| *v as u64).sum()
+        }
+    }
+
+    #[test]
+    fn collision_rate_depends_on_the_hasher() {
+        let block_length: u64 = 1024;
+        let keys: Vec<String> = (0..100).map(|i| format!("key:{:05}", i)).collect();
+
+        let count_collisions = |hasher: &dyn KeyHasher| {
+            let slots: HashSet<u64> = keys
+                .iter()
+                .map(|k| hasher.hash(k.as_bytes()) % block_length)
+                .collect();
+            keys.len() - slots.len()
+        };
+
+        let xxh3_collisions = count_collisions(&Xxh3Hasher);
+        let sum_collisions = count_collisions(&SumHasher);
+        // the keys differ only in a few digits so their sums cluster together
+        assert!(
+            xxh3_collisions * 4 < sum_collisions,
+            "xxh3: {}, sum: {}",
+            xxh3_collisions,
+            sum_collisions
+        );
+    }
+}
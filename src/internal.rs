@@ -1,12 +1,23 @@
-pub(crate) use buffers::BufferPool;
+pub(crate) use buffers::{
+    db_file_exists, AppendedEntry, BufferPool, KeyValuePair, PoolIterator, PrefixIterator,
+    DEFAULT_POOL_CAPACITY,
+};
 pub(crate) use entries::headers::db_file_header::DbFileHeader;
-pub(crate) use entries::headers::shared::Header;
-pub(crate) use entries::values::key_value::KeyValueEntry;
+pub(crate) use entries::headers::inverted_index_header::DEFAULT_MAX_INDEX_KEY_LEN;
+pub(crate) use entries::headers::shared::{
+    Header, DEFAULT_DB_MAX_KEYS, DEFAULT_DB_REDUNDANT_BLOCKS,
+};
+pub(crate) use entries::values::key_value::{
+    KeyValueEntry, KEY_VALUE_MIN_SIZE_IN_BYTES, VERSION_SIZE_IN_BYTES,
+};
 pub(crate) use entries::values::shared::ValueEntry;
-pub(crate) use hash::get_hash;
-pub(crate) use inverted_index::InvertedIndex;
-pub(crate) use macros::acquire_lock;
-pub(crate) use utils::{get_current_timestamp, initialize_db_folder, slice_to_array};
+pub(crate) use hash::{get_hash, HeaderHasher};
+pub(crate) use inverted_index::{InvertedIndex, ListWalkGuard};
+pub(crate) use macros::{acquire_lock, try_acquire_lock};
+pub(crate) use utils::{
+    get_current_timestamp, initialize_db_folder, path_to_bytes, slice_to_array,
+};
+pub(crate) use wal::WriteAheadLog;
 
 mod buffers;
 mod entries;
@@ -14,3 +25,4 @@ mod hash;
 mod inverted_index;
 mod macros;
 mod utils;
+mod wal;
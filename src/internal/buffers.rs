@@ -1,4 +1,8 @@
 mod buffer;
+mod iterator;
 mod pool;
 
-pub(crate) use pool::BufferPool;
+pub(crate) use iterator::{PoolIterator, PrefixIterator};
+pub(crate) use pool::{
+    db_file_exists, AppendedEntry, BufferPool, KeyValuePair, DEFAULT_POOL_CAPACITY,
+};
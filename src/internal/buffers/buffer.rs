@@ -1,7 +1,9 @@
-use crate::internal::entries::values::key_value::{KeyValueEntry, OFFSET_FOR_KEY_IN_KV_ARRAY};
+use crate::internal::entries::values::key_value::{
+    KeyValueEntry, FLAGS_SIZE_IN_BYTES, IS_DELETED_FLAG, OFFSET_FOR_KEY_IN_KV_ARRAY,
+};
 use crate::internal::entries::values::shared::ValueEntry;
 use crate::internal::macros::validate_bounds;
-use crate::internal::utils::TRUE_AS_BYTE;
+use crate::internal::utils::add_error_context;
 use std::cmp::min;
 use std::fmt::{Display, Formatter};
 use std::io;
@@ -56,6 +58,27 @@ impl Buffer {
         self.left_offset <= address && address < self.right_offset
     }
 
+    /// Checks if the `size` bytes starting at the given address are all in this buffer
+    #[inline]
+    pub(crate) fn contains_span(&self, address: u64, size: u64) -> bool {
+        self.left_offset <= address && address + size <= self.right_offset
+    }
+
+    /// Checks if the whole key-value entry at the given address is in this buffer,
+    /// going by the size at the start of the entry.
+    /// Entries bigger than a buffer e.g. those with very long keys never are.
+    #[inline]
+    pub(crate) fn contains_kv_entry(&self, address: u64) -> bool {
+        if !self.contains_span(address, 4) {
+            return false;
+        }
+
+        let offset = (address - self.left_offset) as usize;
+        let mut size_bytes = [0u8; 4];
+        size_bytes.copy_from_slice(&self.data[offset..offset + 4]);
+        self.contains_span(address, u32::from_be_bytes(size_bytes) as u64)
+    }
+
     /// Appends the data to the end of the array
     /// It returns the address (or offset) where the data was appended
     ///
@@ -82,7 +105,7 @@ impl Buffer {
         validate_bounds!(
             (address, address + data_length as u64),
             (self.left_offset, self.right_offset),
-            "address out of bounds"
+            "replace"
         )?;
 
         let start = (address - self.left_offset) as usize;
@@ -92,14 +115,21 @@ impl Buffer {
     }
 
     /// Returns the Some(Value) at the given address if the key there corresponds to the given key
-    /// Otherwise, it returns None
+    /// and the entry is neither deleted nor expired. Otherwise, it returns None
     /// This is to handle hash collisions.
+    /// `now` is the current timestamp (less any grace period), used to check whether the value is expired
     #[inline]
-    pub(crate) fn get_value(&self, address: u64, key: &[u8]) -> io::Result<Option<Value>> {
+    pub(crate) fn get_value(
+        &self,
+        address: u64,
+        key: &[u8],
+        now: u64,
+    ) -> io::Result<Option<Value>> {
         let offset = (address - self.left_offset) as usize;
-        let entry = KeyValueEntry::from_data_array(&self.data, offset)?;
-        let value = if entry.key == key {
-            Some(Value::from(&entry))
+        let entry = KeyValueEntry::from_data_array(&self.data, offset)
+            .map_err(|e| add_error_context(e, "get value", address))?;
+        let value = if entry.key == key && !entry.is_deleted && !entry.is_expired(now) {
+            Some(Value::new(&entry, now))
         } else {
             None
         };
@@ -113,7 +143,7 @@ impl Buffer {
         validate_bounds!(
             (address, address + size as u64),
             (self.left_offset, self.right_offset),
-            "address out of bounds"
+            "read"
         )?;
         let offset = (address - self.left_offset) as usize;
         let data_array = self.data[offset..offset + size].to_vec();
@@ -130,7 +160,7 @@ impl Buffer {
                 address + key_size as u64 + OFFSET_FOR_KEY_IN_KV_ARRAY as u64
             ),
             (self.left_offset, self.right_offset),
-            "address out of bounds"
+            "check key"
         )?;
         let key_offset = (address - self.left_offset) as usize + OFFSET_FOR_KEY_IN_KV_ARRAY;
         let key_in_data = &self.data[key_offset..key_offset + key_size];
@@ -146,19 +176,20 @@ impl Buffer {
         key: &[u8],
     ) -> io::Result<Option<()>> {
         let key_size = key.len();
+        // the flags, that come right after the key, are to be in this buffer too
         validate_bounds!(
             (
                 address,
-                address + key_size as u64 + OFFSET_FOR_KEY_IN_KV_ARRAY as u64
+                address + (OFFSET_FOR_KEY_IN_KV_ARRAY + key_size + FLAGS_SIZE_IN_BYTES) as u64
             ),
             (self.left_offset, self.right_offset),
-            "address out of bounds"
+            "delete key-value entry"
         )?;
         let key_offset = (address - self.left_offset) as usize + OFFSET_FOR_KEY_IN_KV_ARRAY;
         let key_in_data = &self.data[key_offset..key_offset + key_size];
         if key_in_data == key {
-            let is_deleted_offset = key_offset + key_size;
-            self.data[is_deleted_offset] = TRUE_AS_BYTE;
+            let flags_offset = key_offset + key_size;
+            self.data[flags_offset] |= IS_DELETED_FLAG;
             Ok(Some(()))
         } else {
             Ok(None)
@@ -176,11 +207,13 @@ impl Display for Buffer {
     }
 }
 
-impl From<&KeyValueEntry<'_>> for Value {
-    fn from(entry: &KeyValueEntry<'_>) -> Self {
+impl Value {
+    /// Creates a Value from the given key-value entry, marking it as stale if the entry
+    /// is deleted or is expired as of `now`, the current timestamp
+    pub(crate) fn new(entry: &KeyValueEntry<'_>, now: u64) -> Self {
         Self {
             data: entry.value.to_vec(),
-            is_stale: entry.is_deleted || entry.is_expired(),
+            is_stale: entry.is_deleted || entry.is_expired(now),
         }
     }
 }
@@ -198,17 +231,19 @@ mod tests {
     const CAPACITY: usize = 4098;
 
     #[test]
-    fn value_from_key_value_entry() {
+    fn value_new_works() {
         let test_table = vec![
             (
-                KeyValueEntry::new(&b"never_expires"[..], &b"barer"[..], 0),
+                KeyValueEntry::new(&b"never_expires"[..], &b"barer"[..], 0)
+                    .expect("new key value entry"),
                 Value {
                     data: vec![98, 97, 114, 101, 114],
                     is_stale: false,
                 },
             ),
             (
-                KeyValueEntry::new(&b"expires"[..], &b"Hallelujah"[..], 1666023836u64),
+                KeyValueEntry::new(&b"expires"[..], &b"Hallelujah"[..], 1666023836u64)
+                    .expect("new key value entry"),
                 Value {
                     data: vec![72, 97, 108, 108, 101, 108, 117, 106, 97, 104],
                     is_stale: true,
@@ -219,7 +254,8 @@ mod tests {
                     &b"not_expired"[..],
                     &b"bar"[..],
                     get_current_timestamp() * 2,
-                ),
+                )
+                .expect("new key value entry"),
                 Value {
                     data: vec![98, 97, 114],
                     is_stale: false,
@@ -228,7 +264,7 @@ mod tests {
         ];
 
         for (kv, expected) in test_table {
-            assert_eq!(&Value::from(&kv), &expected);
+            assert_eq!(&Value::new(&kv, get_current_timestamp()), &expected);
         }
     }
 
@@ -251,6 +287,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn buffer_contains_kv_entry() {
+        let buf = Buffer::new(79, &KV_DATA_ARRAY[..], CAPACITY);
+        // the same entry, cut short by the end of the buffer
+        let short_buf = Buffer::new(79, &KV_DATA_ARRAY[..22], CAPACITY);
+        let test_table = vec![
+            (&buf, 79u64, true),
+            (&buf, 78u64, false),
+            (&buf, 100u64, false),
+            (&short_buf, 79u64, false),
+        ];
+
+        for (buf, addr, expected) in test_table {
+            assert_eq!(expected, buf.contains_kv_entry(addr), "address: {}", addr);
+        }
+    }
+
     #[test]
     fn buffer_can_append() {
         let data = &[72, 97, 108, 108, 101, 108, 117, 106, 97, 104];
@@ -336,17 +389,21 @@ mod tests {
     #[test]
     fn buffer_get_value() {
         let buf = Buffer::new(79, &KV_DATA_ARRAY[..], CAPACITY);
-        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0);
+        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0).expect("new key value entry");
 
         let test_table = vec![
-            (79u64, b"foo", Some(Value::from(&kv))),
+            (
+                79u64,
+                b"foo",
+                Some(Value::new(&kv, get_current_timestamp())),
+            ),
             (79u64, b"bar", None),
         ];
 
         for (addr, k, expected) in test_table {
             let v = buf
-                .get_value(addr, &k[..])
-                .expect(&format!("gets value for {:?}", &k));
+                .get_value(addr, &k[..], get_current_timestamp())
+                .unwrap_or_else(|_| panic!("gets value for {:?}", &k));
             assert_eq!(v, expected);
         }
     }
@@ -358,7 +415,7 @@ mod tests {
         let test_table = vec![(84u64, b"foo"), (84u64, b"bar")];
 
         for (addr, k) in test_table {
-            let v = buf.get_value(addr, &k[..]);
+            let v = buf.get_value(addr, &k[..], get_current_timestamp());
             assert!(v.is_err());
         }
     }
@@ -397,7 +454,7 @@ mod tests {
         for (addr, k, expected) in test_table {
             let v = buf
                 .addr_belongs_to_key(addr, &k[..])
-                .expect(&format!("gets value for {:?}", &k));
+                .unwrap_or_else(|_| panic!("gets value for {:?}", &k));
             assert_eq!(v, expected);
         }
     }
@@ -415,14 +472,14 @@ mod tests {
         ];
 
         for (addr, k) in test_table {
-            let v = buf.addr_belongs_to_key(addr, &k[..]);
+            let v = buf.addr_belongs_to_key(addr, k);
             assert!(v.is_err());
         }
     }
 
     #[test]
     fn buffer_try_delete_kv_entry() {
-        let mut post_delete_data = KV_DATA_ARRAY.clone();
+        let mut post_delete_data = KV_DATA_ARRAY;
         post_delete_data[11] = 1;
 
         let test_table = vec![
@@ -434,7 +491,7 @@ mod tests {
             let mut buf = Buffer::new(79, &KV_DATA_ARRAY[..], CAPACITY);
             let v = buf
                 .try_delete_kv_entry(addr, &k[..])
-                .expect(&format!("gets value for {:?}", &k));
+                .unwrap_or_else(|_| panic!("gets value for {:?}", &k));
             assert_eq!(v, expected_response);
             assert_eq!(buf.data, expected_data);
         }
@@ -453,8 +510,12 @@ mod tests {
         ];
 
         for (addr, k) in test_table {
-            let v = buf.try_delete_kv_entry(addr, &k[..]);
+            let v = buf.try_delete_kv_entry(addr, k);
             assert!(v.is_err());
         }
+
+        // a buffer that ends right after the key, without the flags
+        let mut buf = Buffer::new(79, &KV_DATA_ARRAY[..11], CAPACITY);
+        assert!(buf.try_delete_kv_entry(79, &b"foo"[..]).is_err());
     }
 }
@@ -0,0 +1,173 @@
+use crate::internal::entries::headers::shared::INDEX_ENTRY_SIZE_IN_BYTES;
+use crate::internal::{
+    slice_to_array, BufferPool, DbFileHeader, Header, InvertedIndex, ListWalkGuard,
+};
+use crate::SearchMode;
+use std::io;
+use std::sync::MutexGuard;
+
+/// Reads an item of type `T` from the key-value entry at the given address of the buffer pool.
+/// It returns None if the entry is to be skipped e.g. if it is deleted or expired
+pub(crate) type EntryReader<T> = fn(&mut BufferPool, u64) -> io::Result<Option<T>>;
+
+/// An iterator that lazily walks the index blocks of the database file,
+/// yielding an item for every live key-value entry that the index points to.
+///
+/// It holds the lock on the buffer pool for as long as it lives, so no writes
+/// (or background compaction) can happen on the store until it is dropped.
+pub(crate) struct PoolIterator<'a, T> {
+    pool: MutexGuard<'a, BufferPool>,
+    header: DbFileHeader,
+    read_entry: EntryReader<T>,
+    block_cursor: u64,
+    block: Vec<u8>,
+    entry_cursor: usize,
+}
+
+impl<'a, T> PoolIterator<'a, T> {
+    /// Creates a new iterator over the buffer pool, reading each entry using `read_entry`
+    pub(crate) fn new(
+        pool: MutexGuard<'a, BufferPool>,
+        header: &DbFileHeader,
+        read_entry: EntryReader<T>,
+    ) -> Self {
+        Self {
+            pool,
+            header: header.clone(),
+            read_entry,
+            block_cursor: 0,
+            block: vec![],
+            entry_cursor: 0,
+        }
+    }
+
+    /// Returns the next non-zero key-value address in the index, loading
+    /// the next index block from file when the current one is exhausted
+    fn next_kv_address(&mut self) -> io::Result<Option<u64>> {
+        let entry_size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
+
+        loop {
+            if self.entry_cursor >= self.block.len() {
+                if self.block_cursor >= self.header.get_number_of_index_blocks() {
+                    return Ok(None);
+                }
+
+                self.block = self
+                    .pool
+                    .read_index_block(self.block_cursor, &self.header)?;
+                self.block_cursor += 1;
+                self.entry_cursor = 0;
+            }
+
+            let lower = self.entry_cursor;
+            let upper = lower + entry_size;
+            self.entry_cursor = upper;
+
+            let kv_address = u64::from_be_bytes(slice_to_array(&self.block[lower..upper])?);
+            if kv_address != 0 {
+                return Ok(Some(kv_address));
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for PoolIterator<'a, T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let kv_address = match self.next_kv_address() {
+                Ok(Some(v)) => v,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match (self.read_entry)(&mut self.pool, kv_address) {
+                Ok(Some(v)) => return Some(Ok(v)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// An iterator that lazily walks the list of keys sharing the same prefix in the inverted index,
+/// yielding the key-value pair for every live entry whose key matches the given term,
+/// as dictated by the search mode.
+///
+/// Just like [PoolIterator], it holds the locks on the buffer pool and on the inverted index
+/// for as long as it lives.
+pub(crate) struct PrefixIterator<'a> {
+    pool: MutexGuard<'a, BufferPool>,
+    index: MutexGuard<'a, InvertedIndex>,
+    term: Vec<u8>,
+    mode: SearchMode,
+    root_address: u64,
+    next_address: Option<u64>,
+    guard: ListWalkGuard,
+}
+
+impl<'a> PrefixIterator<'a> {
+    /// Creates a new iterator over the key-value pairs whose keys match `term` in the given search `mode`
+    pub(crate) fn new(
+        pool: MutexGuard<'a, BufferPool>,
+        mut index: MutexGuard<'a, InvertedIndex>,
+        term: &[u8],
+        mode: SearchMode,
+    ) -> io::Result<Self> {
+        let root_address = index.get_list_root_address(term)?;
+        let guard = index.list_walk_guard(root_address.unwrap_or(0));
+
+        Ok(Self {
+            pool,
+            index,
+            term: term.to_vec(),
+            mode,
+            root_address: root_address.unwrap_or(0),
+            next_address: root_address,
+            guard,
+        })
+    }
+
+    /// Returns the kv address of the next matching entry in the list of the term's prefix,
+    /// moving along the list from one entry to the next
+    fn next_kv_address(&mut self) -> io::Result<Option<u64>> {
+        while let Some(address) = self.next_address {
+            self.guard.step()?;
+            let (next_address, kv_address) =
+                self.index.read_list_entry(address, &self.term, self.mode)?;
+            // The zero check is for data corruption
+            self.next_address = if next_address == self.root_address || next_address == 0 {
+                None
+            } else {
+                Some(next_address)
+            };
+
+            if kv_address.is_some() {
+                return Ok(kv_address);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for PrefixIterator<'a> {
+    type Item = io::Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let kv_address = match self.next_kv_address() {
+                Ok(Some(v)) => v,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match self.pool.read_key_value(kv_address) {
+                Ok(Some(v)) => return Some(Ok(v)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
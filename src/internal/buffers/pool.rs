@@ -1,23 +1,37 @@
+use crate::clock::{Clock, SystemClock};
+use crate::corruption::Corruption;
+use crate::hasher::{KeyHasher, Xxh3Hasher};
 use crate::internal::buffers::buffer::{Buffer, Value};
+use crate::internal::entries::headers::db_file_header::DB_FILE_TITLE;
 use crate::internal::entries::headers::shared::{HEADER_SIZE_IN_BYTES, INDEX_ENTRY_SIZE_IN_BYTES};
-use crate::internal::entries::index::Index;
-use crate::internal::entries::values::key_value::OFFSET_FOR_KEY_IN_KV_ARRAY;
+use crate::internal::entries::values::key_value::{
+    get_version_offset, get_version_size, EXPIRY_SIZE_IN_BYTES, FLAGS_SIZE_IN_BYTES,
+    HAS_VERSION_FLAG, IS_DELETED_FLAG, OFFSET_FOR_KEY_IN_KV_ARRAY, VERSION_SIZE_IN_BYTES,
+};
 use crate::internal::entries::values::shared::ValueEntry;
 use crate::internal::macros::validate_bounds;
-use crate::internal::utils::{get_vm_page_size, TRUE_AS_BYTE};
+use crate::internal::utils::{add_error_context, get_vm_page_size};
 use crate::internal::{
-    acquire_lock, slice_to_array, DbFileHeader, Header, InvertedIndex, KeyValueEntry,
+    slice_to_array, DbFileHeader, Header, InvertedIndex, KeyValueEntry, WriteAheadLog,
+    KEY_VALUE_MIN_SIZE_IN_BYTES,
 };
+use crate::meta::ValueMeta;
+use crate::probing::Probing;
+use crate::stats::{CacheCounters, CompactionPreview, FragmentationReport, IoCounters};
+use crate::storage::{CountingFile, StorageFile};
+use crate::telemetry;
+use crate::ScdbError;
 use std::cmp::{max, min};
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::{Display, Formatter};
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::fs::OpenOptions;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::{fs, io};
 
-const DEFAULT_POOL_CAPACITY: usize = 5;
+pub(crate) const DEFAULT_POOL_CAPACITY: usize = 5;
 
 /// A pool of Buffers.
 ///
@@ -35,11 +49,104 @@ pub(crate) struct BufferPool {
     redundant_blocks: Option<u16>,
     kv_buffers: VecDeque<Buffer>,
     index_buffers: BTreeMap<u64, Buffer>,
-    pub(crate) file: File,
-    pub(crate) file_path: PathBuf,
+    clock: Arc<dyn Clock>,
+    stale_grace_secs: u64,
+    hasher: Arc<dyn KeyHasher>,
+    pub(crate) cache_counters: Arc<CacheCounters>,
+    pub(crate) io_counters: Arc<IoCounters>,
+    dangling_bytes: Option<u64>,
+    live_keys: Option<u64>,
+    wal: Option<WriteAheadLog>,
+    // incremented on every change to the layout of the file e.g. clearing it, so that a compaction
+    // done in steps can tell whether it has to be started afresh
+    generation: u64,
+    // the writes made to the file since the compaction in progress, if any, was started,
+    // for the compaction to catch up on them
+    compaction_changes: Option<CompactionChanges>,
+    pub(crate) file: Box<dyn StorageFile>,
+    pub(crate) file_path: Option<PathBuf>,
     pub(crate) file_size: u64,
 }
 
+/// The key, value and expiry of a key-value entry
+pub(crate) type KeyValueWithExpiry = (Vec<u8>, Vec<u8>, u64);
+
+/// The key and value of a key-value entry
+pub(crate) type KeyValuePair = (Vec<u8>, Vec<u8>);
+
+/// A key-value entry appended to the file via [BufferPool::append_unbuffered], yet to be indexed
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AppendedEntry {
+    pub(crate) key: Vec<u8>,
+    pub(crate) address: u64,
+    pub(crate) size: u32,
+    pub(crate) expiry: u64,
+}
+
+/// The state of a compaction of the file of a [BufferPool] that is done in steps
+/// via [BufferPool::start_compaction], [BufferPool::continue_compaction] and [BufferPool::finish_compaction]
+pub(crate) struct Compaction {
+    header: DbFileHeader,
+    new_header: DbFileHeader,
+    new_file: Box<dyn StorageFile>,
+    new_file_path: Option<PathBuf>,
+    new_file_offset: u64,
+    next_block: u64,
+    live_entries: u64,
+    search_entries: Option<Vec<(Vec<u8>, u64, u64)>>,
+    now: u64,
+    generation: u64,
+}
+
+impl Compaction {
+    /// Returns true if all index blocks of the file have been copied into the new file
+    pub(crate) fn is_copied(&self) -> bool {
+        self.next_block >= self.header.number_of_index_blocks
+    }
+
+    /// Discards this compaction, leaving the file being compacted as is and removing the new file
+    /// if it is on the file system
+    pub(crate) fn abort(self) -> io::Result<()> {
+        let Compaction {
+            new_file,
+            new_file_path,
+            ..
+        } = self;
+
+        drop(new_file);
+        if let Some(new_file_path) = new_file_path {
+            fs::remove_file(new_file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the given live key-value entry to the new file, returning the address where it was appended
+    fn copy_entry(&mut self, kv: &KeyValueEntry<'_>, kv_byte_array: &[u8]) -> io::Result<u64> {
+        let new_file_offset = self.new_file_offset;
+        self.new_file.seek(SeekFrom::Start(new_file_offset))?;
+        self.new_file.write_all(kv_byte_array)?;
+
+        // the search index is only updated when the compaction is finished
+        if let Some(entries) = &mut self.search_entries {
+            entries.push((kv.key.to_vec(), new_file_offset, kv.expiry));
+        }
+
+        self.new_file_offset += kv_byte_array.len() as u64;
+        self.live_entries += 1;
+        Ok(new_file_offset)
+    }
+}
+
+/// The writes made to the file while a compaction is in progress, that were made to the parts
+/// of the file that the compaction had already copied
+#[derive(Debug, Default)]
+struct CompactionChanges {
+    /// The addresses of the index entries that were updated
+    index_entries: BTreeSet<u64>,
+    /// The addresses of the key-value entries that were updated in place e.g. marked as deleted
+    kv_entries: BTreeSet<u64>,
+}
+
 impl BufferPool {
     /// Creates a new BufferPool with the given `capacity` number of Buffers and
     /// for the file at the given path (creating it if necessary)
@@ -50,25 +157,63 @@ impl BufferPool {
         redundant_blocks: Option<u16>,
         buffer_size: Option<usize>,
     ) -> io::Result<Self> {
-        let buffer_size = buffer_size.unwrap_or(get_vm_page_size() as usize);
-        let capacity = capacity.unwrap_or(DEFAULT_POOL_CAPACITY);
+        recover_from_interrupted_compaction(file_path)?;
 
-        let should_create_new = !file_path.exists();
-        let mut file = OpenOptions::new()
+        let file = OpenOptions::new()
             .write(true)
             .read(true)
-            .create(should_create_new)
+            .create(true)
+            .truncate(false)
             .open(file_path)?;
 
+        Self::from_file(
+            capacity,
+            Box::new(file),
+            Some(file_path.into()),
+            max_keys,
+            redundant_blocks,
+            buffer_size,
+        )
+    }
+
+    /// Creates a new BufferPool with the given `capacity` number of Buffers over the given file,
+    /// initializing it if it is empty. The `file_path` is None if the file is not on the file system
+    /// e.g. if it is in memory.
+    pub(crate) fn from_file(
+        capacity: Option<usize>,
+        file: Box<dyn StorageFile>,
+        file_path: Option<PathBuf>,
+        max_keys: Option<u64>,
+        redundant_blocks: Option<u16>,
+        buffer_size: Option<usize>,
+    ) -> io::Result<Self> {
+        let capacity = capacity.unwrap_or(DEFAULT_POOL_CAPACITY);
+        let io_counters: Arc<IoCounters> = Default::default();
+        let mut file: Box<dyn StorageFile> = Box::new(CountingFile::new(file, io_counters.clone()));
+
+        let should_create_new = file.seek(SeekFrom::End(0))? == 0;
         let header = if should_create_new {
+            let buffer_size = buffer_size.unwrap_or(get_vm_page_size() as usize);
             let header = DbFileHeader::new(max_keys, redundant_blocks, Some(buffer_size as u32));
             header.initialize_file(&mut file)?;
             header
         } else {
-            DbFileHeader::from_file(&mut file)?
+            let mut header = DbFileHeader::from_file(&mut file)?;
+            // the entries written from now on may have versions, which older releases would misread,
+            // so a file of the earlier format is marked as being of the current one
+            if header.title != DB_FILE_TITLE {
+                header.title = DB_FILE_TITLE.to_string();
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&header.as_bytes())?;
+            }
+            header
         };
 
-        let file_size = file.seek(SeekFrom::End(0))?;
+        // the buffers should align with the blocks of the file, which may have been created
+        // on a machine with a different page size
+        let buffer_size = header.block_size as usize;
+
+        let file_size = get_logical_file_size(&mut file, &header)?;
 
         let index_capacity = get_index_capacity(header.number_of_index_blocks as usize, capacity);
         let kv_capacity = capacity - index_capacity;
@@ -82,48 +227,410 @@ impl BufferPool {
             key_values_start_point: header.key_values_start_point,
             kv_buffers: VecDeque::with_capacity(kv_capacity),
             index_buffers: Default::default(),
+            clock: Arc::new(SystemClock),
+            stale_grace_secs: 0,
+            hasher: Arc::new(Xxh3Hasher),
+            cache_counters: Default::default(),
+            io_counters,
+            dangling_bytes: None,
+            live_keys: None,
+            wal: None,
+            generation: 0,
+            compaction_changes: None,
             file,
             file_size,
-            file_path: file_path.into(),
+            file_path,
         };
 
         Ok(v)
     }
 
+    /// Sets the clock used to check whether entries are expired
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Sets the number of seconds for which expired entries are still treated as unexpired i.e. returned
+    /// by every read, counted as live and kept on compaction
+    pub(crate) fn set_stale_grace_secs(&mut self, stale_grace_secs: u64) {
+        self.stale_grace_secs = stale_grace_secs;
+    }
+
+    /// Returns the timestamp that entries are checked against to tell whether they are gone for good i.e.
+    /// the current timestamp less the grace period during which expired entries are still served
+    #[inline(always)]
+    fn get_stale_cutoff(&self) -> u64 {
+        self.clock.now_secs().saturating_sub(self.stale_grace_secs)
+    }
+
+    /// Sets the hasher used to place keys in the index, recording its id in the header of the file
+    /// if the file has no key-value entries yet.
+    ///
+    /// It fails if the file has key-value entries that were placed by a hasher of a different id.
+    pub(crate) fn set_hasher(&mut self, hasher: Arc<dyn KeyHasher>) -> io::Result<()> {
+        let mut header = DbFileHeader::from_file(&mut self.file)?;
+        if header.hasher_id != hasher.id() && self.file_size <= header.key_values_start_point {
+            header = header.with_hasher(hasher.clone());
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(&header.as_bytes())?;
+            self.index_buffers.clear();
+            self.generation += 1;
+        }
+
+        header.attach_hasher(hasher.clone())?;
+        self.hasher = hasher;
+        Ok(())
+    }
+
+    /// Sets the probing used to look for another slot for a key in an index block, recording it in the header
+    /// of the file if the file has no key-value entries yet. Otherwise, the file keeps the probing it was
+    /// created with.
+    pub(crate) fn set_probing(&mut self, probing: Probing) -> io::Result<()> {
+        let header = DbFileHeader::from_file(&mut self.file)?;
+        if header.probing != probing && self.file_size <= header.key_values_start_point {
+            let header = header.with_probing(probing);
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(&header.as_bytes())?;
+            self.index_buffers.clear();
+            self.generation += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the header of the file, attaching to it the hasher of this pool
+    pub(crate) fn read_header(&mut self) -> io::Result<DbFileHeader> {
+        let mut header = DbFileHeader::from_file(&mut self.file)?;
+        header.attach_hasher(self.hasher.clone())?;
+        Ok(header)
+    }
+
+    /// Sets the write-ahead log that makes every write of a key-value entry all-or-nothing across a crash.
+    /// [BufferPool::replay_wal] should be called first, to make any writes left pending in it by a crash
+    pub(crate) fn set_wal(&mut self, wal: WriteAheadLog) {
+        self.wal = Some(wal);
+    }
+
+    /// Makes the writes left pending in the given write-ahead log by a crash, and then clears the log
+    pub(crate) fn replay_wal(&mut self, wal: &mut WriteAheadLog) -> io::Result<()> {
+        let pending_writes = wal.get_pending_writes()?;
+        if !pending_writes.is_empty() {
+            for (address, data) in &pending_writes {
+                self.file.seek(SeekFrom::Start(*address))?;
+                self.file.write_all(data)?;
+            }
+
+            self.file.sync_all()?;
+            let header = DbFileHeader::from_file(&mut self.file)?;
+            self.file_size = get_logical_file_size(&mut self.file, &header)?;
+            self.kv_buffers.clear();
+            self.index_buffers.clear();
+            self.dangling_bytes = None;
+            self.live_keys = None;
+            self.generation += 1;
+        }
+
+        wal.clear()
+    }
+
+    /// Flushes all data written to the file attached to this buffer pool to disk
+    pub(crate) fn sync_file(&mut self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+
     /// Appends a given data array to the file attached to this buffer pool
     /// It returns the address where the data was appended
-    pub(crate) fn append(&mut self, data: &mut Vec<u8>) -> io::Result<u64> {
+    ///
+    /// The data is written at the end of the last key-value entry i.e. at `file_size`,
+    /// which is before the actual end of the file if space was preallocated via [BufferPool::preallocate]
+    pub(crate) fn append(&mut self, data: &mut [u8]) -> io::Result<u64> {
         // loop in reverse, starting at the back
         // since the latest kv_buffers are the ones updated when new changes occur
         for buf in self.kv_buffers.iter_mut().rev() {
             if buf.can_append(self.file_size) {
-                let addr = buf.append(data.clone());
-                self.file_size = buf.right_offset;
-                self.file.seek(SeekFrom::End(0))?;
+                let addr = buf.append(data.to_vec());
+                self.file.seek(SeekFrom::Start(self.file_size))?;
                 self.file.write_all(data)?;
+                self.file_size = buf.right_offset;
                 return Ok(addr);
             }
         }
 
-        let start = self.file.seek(SeekFrom::End(0))?;
+        let start = self.file.seek(SeekFrom::Start(self.file_size))?;
         let new_file_size = start + data.len() as u64;
         self.file.write_all(data)?;
         self.file_size = new_file_size;
         Ok(start)
     }
 
+    /// Appends the given key-value entry data to the file, updating the index at `index_address`
+    /// to point to it. It returns the address where the data was appended.
+    ///
+    /// If there is a write-ahead log, the append and the index update are all-or-nothing across a crash
+    pub(crate) fn append_kv_entry(
+        &mut self,
+        index_address: u64,
+        data: &mut [u8],
+    ) -> io::Result<u64> {
+        let kv_address = self.file_size.to_be_bytes();
+        self.log_writes(&[
+            (u64::from_be_bytes(kv_address), data),
+            (index_address, &kv_address),
+        ])?;
+
+        let kv_address = self.append(data)?;
+        self.update_index(index_address, &kv_address.to_be_bytes())?;
+
+        self.commit_writes()?;
+        self.live_keys = self.live_keys.map(|v| v + 1);
+        Ok(kv_address)
+    }
+
+    /// Records the given writes in the write-ahead log, if any, before they are made
+    #[inline]
+    fn log_writes(&mut self, writes: &[(u64, &[u8])]) -> io::Result<()> {
+        match &mut self.wal {
+            None => Ok(()),
+            Some(wal) => wal.log(writes),
+        }
+    }
+
+    /// Syncs the file to disk and clears the write-ahead log, if any, after the logged writes are made
+    #[inline]
+    fn commit_writes(&mut self) -> io::Result<()> {
+        match &mut self.wal {
+            None => Ok(()),
+            Some(wal) => {
+                self.file.sync_all()?;
+                wal.clear()
+            }
+        }
+    }
+
+    /// Records the update of the index entry at the given address, for the compaction in progress, if any,
+    /// to catch up on
+    #[inline]
+    fn record_index_change(&mut self, address: u64) {
+        if let Some(changes) = &mut self.compaction_changes {
+            changes.index_entries.insert(address);
+        }
+    }
+
+    /// Records the update in place of the key-value entry at the given address, for the compaction
+    /// in progress, if any, to catch up on
+    #[inline]
+    fn record_kv_change(&mut self, kv_address: u64) {
+        if let Some(changes) = &mut self.compaction_changes {
+            changes.kv_entries.insert(kv_address);
+        }
+    }
+
+    /// Appends the given data, made up of whole key-value entries, to the end of the file without
+    /// indexing it, nor caching it in the kv buffers. It returns the address where the data was appended.
+    ///
+    /// The entries are to be indexed later via [BufferPool::index_appended_entries], or else
+    /// discarded via [BufferPool::discard_appended].
+    pub(crate) fn append_unbuffered(&mut self, data: &[u8]) -> io::Result<u64> {
+        let start = self.file.seek(SeekFrom::Start(self.file_size))?;
+        self.file.write_all(data)?;
+        self.file_size = start + data.len() as u64;
+        Ok(start)
+    }
+
+    /// Indexes the given entries that were appended via [BufferPool::append_unbuffered], in the order in which
+    /// they were appended, so the last entry of a key is the one that is indexed. Any entry of the key that
+    /// was already in the file is replaced by it, just like it would be by a `set`.
+    ///
+    /// The index is read into memory, updated in a single pass over the entries and then written back,
+    /// only the index blocks that changed being written. It returns the number of entries indexed.
+    ///
+    /// # Errors
+    ///
+    /// Nothing is changed if it fails e.g. with [ScdbError::CollisionSaturated] if all the slots of a key
+    /// in the index are taken, or with [ScdbError::KeysMaxedOut] if the keys would be more than `max_keys`.
+    pub(crate) fn index_appended_entries(
+        &mut self,
+        header: &DbFileHeader,
+        entries: &[AppendedEntry],
+    ) -> io::Result<u64> {
+        let Some(load_start) = entries.first().map(|e| e.address) else {
+            return Ok(0);
+        };
+
+        let mut latest: HashMap<&[u8], usize> = HashMap::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            latest.insert(&entry.key, i);
+        }
+
+        let index_len = (self.key_values_start_point - HEADER_SIZE_IN_BYTES) as usize;
+        let mut index = vec![0u8; index_len];
+        self.file.seek(SeekFrom::Start(HEADER_SIZE_IN_BYTES))?;
+        self.file.read_exact(&mut index)?;
+
+        let block_size = header.net_block_size as usize;
+        let mut dirty_blocks = vec![false; header.number_of_index_blocks as usize];
+        let mut index_writes: Vec<(u64, [u8; 8])> = Vec::with_capacity(latest.len());
+        let mut version_writes: Vec<(u64, [u8; 8])> = vec![];
+        let mut replaced: Vec<u64> = vec![];
+        let mut new_keys = 0u64;
+        let mut superseded_bytes = 0u64;
+
+        for (i, entry) in entries.iter().enumerate() {
+            if latest.get(&entry.key[..]) != Some(&i) {
+                superseded_bytes += entry.size as u64;
+                continue;
+            }
+
+            let mut slot = None;
+            for index_offset in header.get_index_offsets(&entry.key) {
+                let pos = (index_offset - HEADER_SIZE_IN_BYTES) as usize;
+                let kv_address_bytes = &index[pos..pos + INDEX_ENTRY_SIZE_IN_BYTES as usize];
+                let kv_address = u64::from_be_bytes(slice_to_array(kv_address_bytes)?);
+
+                if kv_address == 0 {
+                    slot = Some((index_offset, None));
+                    break;
+                }
+
+                // only one entry of each key in this load is indexed, so a slot already taken
+                // by another entry of this load belongs to some other key
+                if kv_address < load_start
+                    && self.addr_belongs_to_key(kv_address_bytes, &entry.key)?
+                {
+                    slot = Some((index_offset, Some(kv_address)));
+                    break;
+                }
+            }
+
+            let Some((index_offset, prev_kv_address)) = slot else {
+                return Err(io::Error::other(ScdbError::CollisionSaturated(
+                    entry.key.clone(),
+                )));
+            };
+
+            let pos = (index_offset - HEADER_SIZE_IN_BYTES) as usize;
+            let kv_address = entry.address.to_be_bytes();
+            index[pos..pos + INDEX_ENTRY_SIZE_IN_BYTES as usize].copy_from_slice(&kv_address);
+            dirty_blocks[pos / block_size] = true;
+            index_writes.push((index_offset, kv_address));
+
+            match prev_kv_address {
+                None => new_keys += 1,
+                Some(prev_kv_address) => {
+                    // the entries of the load start at version 1, so the version is bumped
+                    // past that of the entry being replaced
+                    let version = self.read_version(prev_kv_address)?.saturating_add(1);
+                    if version > 1 {
+                        let version_address =
+                            entry.address + get_version_offset(entry.key.len()) as u64;
+                        version_writes.push((version_address, version.to_be_bytes()));
+                    }
+                    replaced.push(prev_kv_address);
+                }
+            }
+        }
+
+        if new_keys > 0 && self.get_live_keys(header)? + new_keys > header.max_keys {
+            return Err(io::Error::other(ScdbError::KeysMaxedOut));
+        }
+
+        let logged_writes: Vec<(u64, &[u8])> = index_writes
+            .iter()
+            .chain(version_writes.iter())
+            .map(|(address, data)| (*address, &data[..]))
+            .collect();
+        // the appended entries are not in the log, so they are synced to disk before the index
+        // that points to them is logged, or a replay after a crash could index unwritten entries
+        if self.wal.is_some() {
+            self.file.sync_all()?;
+        }
+        self.log_writes(&logged_writes)?;
+        for (address, _) in &index_writes {
+            self.record_index_change(*address);
+        }
+
+        for (block, _) in dirty_blocks.iter().enumerate().filter(|(_, &v)| v) {
+            let start = block * block_size;
+            let end = min(start + block_size, index_len);
+            self.file
+                .seek(SeekFrom::Start(HEADER_SIZE_IN_BYTES + start as u64))?;
+            self.file.write_all(&index[start..end])?;
+        }
+
+        for (address, data) in &version_writes {
+            self.file.seek(SeekFrom::Start(*address))?;
+            self.file.write_all(data)?;
+        }
+
+        // the cached index blocks are out of date
+        self.index_buffers.clear();
+        self.commit_writes()?;
+
+        for prev_kv_address in replaced {
+            self.mark_dangling(prev_kv_address)?;
+        }
+
+        let indexed = latest.len() as u64;
+        self.live_keys = self.live_keys.map(|v| v + indexed);
+        self.dangling_bytes = self.dangling_bytes.map(|v| v + superseded_bytes);
+        Ok(indexed)
+    }
+
+    /// Discards everything appended to the file from the given address onwards, truncating the file
+    /// there. It is for dropping entries appended via [BufferPool::append_unbuffered] that are not to be indexed.
+    /// Any space preallocated at the end of the file is dropped too.
+    pub(crate) fn discard_appended(&mut self, address: u64) -> io::Result<()> {
+        validate_bounds!(
+            (address, address),
+            (self.key_values_start_point, self.file_size),
+            "discard appended"
+        )?;
+        self.generation += 1;
+        self.file.set_len(address)?;
+        self.file_size = address;
+        Ok(())
+    }
+
+    /// Preallocates space for key-value entries of `estimated_bytes` in total, extending the file
+    /// to `key_values_start_point + estimated_bytes` in one go instead of growing it with every append.
+    ///
+    /// Appends still start at the end of the last key-value entry i.e. `file_size`. The preallocation is
+    /// recorded in the header, for that end to be found when the file is reopened. The preallocated space
+    /// that is still unused is dropped, and the record of it reset, when the file is cleared or compacted.
+    pub(crate) fn preallocate(&mut self, estimated_bytes: u64) -> io::Result<()> {
+        let new_len = self.key_values_start_point + estimated_bytes;
+        if new_len > self.file.seek(SeekFrom::End(0))? {
+            // recorded first, so that the end of the last entry is looked for whenever the file is reopened
+            let mut header = DbFileHeader::from_file(&mut self.file)?;
+            if !header.is_preallocated {
+                header.is_preallocated = true;
+                self.file.seek(SeekFrom::Start(0))?;
+                self.file.write_all(&header.as_bytes())?;
+            }
+
+            self.file.set_len(new_len)?;
+        }
+
+        Ok(())
+    }
+
     /// Updates the index at the given address with the new data.
     ///
     /// # Errors
     /// - This will fail if the data could spill into the key-value entry section or in the header section e.g.
-    /// if the address is less than [HEADER_SIZE_IN_BYTES]
-    /// or (address + data length) is greater than or equal [BufferPool.key_values_start_point]
+    ///   if the address is less than [HEADER_SIZE_IN_BYTES]
+    ///   or (address + data length) is greater than or equal [BufferPool.key_values_start_point]
     pub(crate) fn update_index(&mut self, address: u64, data: &[u8]) -> io::Result<()> {
         validate_bounds!(
             (address, address + data.len() as u64),
             (HEADER_SIZE_IN_BYTES, self.key_values_start_point),
-            "The data is outside the index bounds"
+            "update index"
         )?;
+        for address in
+            (address..address + data.len() as u64).step_by(INDEX_ENTRY_SIZE_IN_BYTES as usize)
+        {
+            self.record_index_change(address);
+        }
 
         for (_, buf) in self.index_buffers.iter_mut() {
             if buf.contains(address) {
@@ -138,127 +645,513 @@ impl BufferPool {
     }
 
     /// Clears all data on disk and memory making it like a new store
+    /// with the same `max_keys`, `redundant_blocks` and block size as the current file
     pub(crate) fn clear_file(&mut self) -> io::Result<()> {
-        let header = DbFileHeader::new(self.max_keys, self.redundant_blocks, None);
+        self.reinitialize_file(None)
+    }
+
+    /// Clears all data on disk and memory, re-initializing the file for the new `max_keys`.
+    /// The index region is thus shrunk (or grown) to fit the new capacity.
+    pub(crate) fn clear_file_with_max_keys(&mut self, max_keys: u64) -> io::Result<()> {
+        self.max_keys = Some(max_keys);
+        self.reinitialize_file(Some(max_keys))
+    }
+
+    /// Re-initializes the file with a header for the given `max_keys` (default: that of the current file),
+    /// clearing all data on disk and in memory
+    fn reinitialize_file(&mut self, max_keys: Option<u64>) -> io::Result<()> {
+        let current_header = DbFileHeader::from_file(&mut self.file)?;
+        let header = DbFileHeader::new(
+            Some(max_keys.unwrap_or(current_header.max_keys)),
+            Some(current_header.redundant_blocks),
+            Some(current_header.block_size),
+        )
+        .with_hasher(self.hasher.clone())
+        .with_probing(current_header.probing);
         self.file_size = header.initialize_file(&mut self.file)?;
+        self.reset_buffers(&header);
+        self.cache_counters.reset();
+        self.io_counters.reset();
+        self.dangling_bytes = Some(0);
+        self.live_keys = Some(0);
+        self.generation += 1;
+        Ok(())
+    }
+
+    /// Re-opens the file at `file_path` (if any), discarding everything cached in memory, so as to pick up
+    /// the changes made to the file by other processes e.g. compaction, which replaces the file.
+    /// It returns the header as currently found in the file
+    pub(crate) fn reload_file(&mut self) -> io::Result<DbFileHeader> {
+        if let Some(file_path) = &self.file_path {
+            let file = OpenOptions::new().write(true).read(true).open(file_path)?;
+            self.file = Box::new(CountingFile::new(Box::new(file), self.io_counters.clone()));
+        }
+
+        let header = self.read_header()?;
+        self.file_size = get_logical_file_size(&mut self.file, &header)?;
+        self.reset_buffers(&header);
+        self.dangling_bytes = None;
+        self.live_keys = None;
+        self.generation += 1;
+        Ok(header)
+    }
+
+    /// Clears the buffers in memory, re-distributing the capacity between the index and
+    /// the key-value buffers to fit the given header
+    fn reset_buffers(&mut self, header: &DbFileHeader) {
+        self.key_values_start_point = header.key_values_start_point;
+        self.buffer_size = header.block_size as usize;
+
+        let capacity = self.kv_capacity + self.index_capacity;
+        self.index_capacity = get_index_capacity(header.number_of_index_blocks as usize, capacity);
+        self.kv_capacity = capacity - self.index_capacity;
+
         self.index_buffers.clear();
         self.kv_buffers.clear();
-        Ok(())
     }
 
     /// This removes any deleted or expired entries from the file. It must first lock the buffer and the file.
     /// In order to be more efficient, it creates a new file, copying only that data which is not deleted or expired
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(bytes_before = self.file_size, bytes_after = tracing::field::Empty)
+        )
+    )]
     pub(crate) fn compact_file(
         &mut self,
         search_index: &mut Option<&mut InvertedIndex>,
     ) -> io::Result<()> {
-        let folder = self.file_path.parent().unwrap_or_else(|| Path::new("/"));
-        let new_file_path = folder.join("tmp__compact.scdb");
-        let mut new_file = OpenOptions::new()
-            .write(true)
-            .read(true)
-            .create(true)
-            .open(&new_file_path)?;
+        let header = self.read_header()?;
+        self.rebuild_file(search_index, &header)?;
+        telemetry::record_span_field("bytes_after", self.file_size);
+        telemetry::record_compaction();
+        telemetry::record_file_size(self.file_size);
+        Ok(())
+    }
 
-        let header: DbFileHeader = DbFileHeader::from_file(&mut self.file)?;
+    /// Removes any deleted or expired entries from the file just like [BufferPool::compact_file], but checks
+    /// `is_cancelled` before copying each index block into the new file. Once it is set, the compaction
+    /// is aborted, leaving the file as is and removing the new file.
+    ///
+    /// It returns true if the file was compacted, or false if the compaction was cancelled
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(bytes_before = self.file_size, bytes_after = tracing::field::Empty)
+        )
+    )]
+    pub(crate) fn compact_file_cancellable(
+        &mut self,
+        search_index: &mut Option<&mut InvertedIndex>,
+        is_cancelled: &AtomicBool,
+    ) -> io::Result<bool> {
+        let mut compaction = self.start_compaction(search_index.is_some())?;
+        while !compaction.is_copied() {
+            if is_cancelled.load(Ordering::Acquire) {
+                self.compaction_changes = None;
+                compaction.abort()?;
+                return Ok(false);
+            }
 
-        // Add headers to new file
-        new_file.seek(SeekFrom::Start(0))?;
-        new_file.write_all(&header.as_bytes())?;
+            // the file can't be written to in the meantime, as the pool is borrowed all through
+            self.continue_compaction(&mut compaction, 1)?;
+        }
 
-        let file = Mutex::new(&self.file);
+        self.finish_compaction(compaction, search_index)?;
+        telemetry::record_span_field("bytes_after", self.file_size);
+        telemetry::record_compaction();
+        telemetry::record_file_size(self.file_size);
+        Ok(true)
+    }
 
-        let mut index = Index::new(&file, &header);
+    /// Rebuilds the given search index from the live entries in the file, without touching the file,
+    /// returning the number of keys added to it.
+    /// This reclaims the space in the search index taken up by deleted, expired or overwritten keys
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub(crate) fn rebuild_search_index(
+        &mut self,
+        header: &DbFileHeader,
+        search_index: &mut InvertedIndex,
+    ) -> io::Result<u64> {
+        let now = self.get_stale_cutoff();
+        let mut count = 0u64;
+        search_index.clear()?;
+
+        for block in 0..header.number_of_index_blocks {
+            let index_block = self.read_index_block(block, header)?;
+            for kv_address in index_block.chunks_exact(INDEX_ENTRY_SIZE_IN_BYTES as usize) {
+                let kv_address = u64::from_be_bytes(slice_to_array(kv_address)?);
+                if kv_address != 0 {
+                    let size = self.read_kv_size(kv_address)?;
+                    let buf = self.read_kv_bytes(kv_address, size)?;
+                    let kv = KeyValueEntry::from_data_array(&buf, 0)
+                        .map_err(|e| add_error_context(e, "rebuild search index", kv_address))?;
+                    if !kv.is_expired(now) && !kv.is_deleted {
+                        search_index.add(kv.key, kv_address, kv.expiry)?;
+                        count += 1;
+                    }
+                }
+            }
+        }
 
-        let idx_entry_size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
-        let zero = vec![0u8; idx_entry_size];
-        let mut idx_offset = HEADER_SIZE_IN_BYTES;
-        let mut new_file_offset = header.key_values_start_point;
+        Ok(count)
+    }
+
+    /// Grows the file (and the search index) by one redundant index block, so that keys whose slots
+    /// in the index are all taken can be inserted. Just like compaction, it removes any deleted
+    /// or expired entries from the file. It returns the new header of the file
+    pub(crate) fn grow_file(
+        &mut self,
+        search_index: &mut Option<&mut InvertedIndex>,
+    ) -> io::Result<DbFileHeader> {
+        let header = self.read_header()?;
+        let redundant_blocks = header.redundant_blocks.checked_add(1).ok_or_else(|| {
+            io::Error::other(format!(
+                "redundant blocks can't grow beyond {}",
+                header.redundant_blocks
+            ))
+        })?;
+        let new_header = DbFileHeader::new(
+            Some(header.max_keys),
+            Some(redundant_blocks),
+            Some(header.block_size),
+        )
+        .with_hasher(self.hasher.clone())
+        .with_probing(header.probing);
 
-        // clear the search index so as to begin its reconstruction
         if let Some(idx) = search_index.as_deref_mut() {
-            idx.clear()?;
+            idx.clear_with_redundant_blocks(redundant_blocks)?;
         }
 
-        for index_block in &mut index {
-            let index_block = index_block?;
-            // write index block into new file
-            new_file.seek(SeekFrom::Start(idx_offset))?;
-            new_file.write_all(&index_block)?;
+        self.rebuild_file(search_index, &new_header)?;
+        self.redundant_blocks = Some(redundant_blocks);
+        Ok(new_header)
+    }
 
-            let len = index_block.len();
-            let mut idx_block_cursor: usize = 0;
-            while idx_block_cursor < len {
-                let lower = idx_block_cursor;
-                let upper = lower + idx_entry_size;
-                let idx_bytes = index_block[lower..upper].to_vec();
+    /// Rebuilds the file for the `new_header`, copying over only the entries that are not deleted or expired.
+    /// The index blocks of the current file are copied to the same positions in the new file
+    /// so the `new_header` should have at least as many index blocks, with the same size, as the current header.
+    fn rebuild_file(
+        &mut self,
+        search_index: &mut Option<&mut InvertedIndex>,
+        new_header: &DbFileHeader,
+    ) -> io::Result<()> {
+        let mut compaction = self.start_rebuild(new_header.clone(), search_index.is_some())?;
+        self.continue_compaction(&mut compaction, u64::MAX)?;
+        self.finish_compaction(compaction, search_index)
+    }
+
+    /// Starts a compaction of the file that is done in steps via [BufferPool::continue_compaction]
+    /// and [BufferPool::finish_compaction], between which the pool can be used for other operations.
+    /// `is_search_enabled` is whether the search index is to be rebuilt when the compaction is finished.
+    pub(crate) fn start_compaction(&mut self, is_search_enabled: bool) -> io::Result<Compaction> {
+        let header = self.read_header()?;
+        self.start_rebuild(header, is_search_enabled)
+    }
 
+    /// Starts a rebuild of the file for the `new_header`, creating the new file with its header
+    /// and empty index blocks
+    fn start_rebuild(
+        &mut self,
+        new_header: DbFileHeader,
+        is_search_enabled: bool,
+    ) -> io::Result<Compaction> {
+        let new_file_path = self
+            .file_path
+            .as_ref()
+            .map(|file_path| get_compaction_file_path(file_path));
+        let mut new_file: Box<dyn StorageFile> = match &new_file_path {
+            // the new file is to replace the current one, so its I/O is counted too
+            Some(new_file_path) => Box::new(CountingFile::new(
+                Box::new(
+                    OpenOptions::new()
+                        .write(true)
+                        .read(true)
+                        .create(true)
+                        // any stale data left behind by an interrupted compaction is discarded
+                        .truncate(true)
+                        .open(new_file_path)?,
+                ),
+                self.io_counters.clone(),
+            )),
+            None => Box::new(Cursor::new(vec![])),
+        };
+
+        // Add headers and empty index blocks to new file
+        new_header.initialize_file(&mut new_file)?;
+        self.compaction_changes = Some(CompactionChanges::default());
+
+        Ok(Compaction {
+            header: DbFileHeader::from_file(&mut self.file)?,
+            new_file_offset: new_header.key_values_start_point,
+            new_header,
+            new_file,
+            new_file_path,
+            next_block: 0,
+            live_entries: 0,
+            search_entries: is_search_enabled.then(Vec::new),
+            // entries that expired within the grace period are kept, as they are still served
+            now: self.get_stale_cutoff(),
+            generation: self.generation,
+        })
+    }
+
+    /// Copies the live entries of the next `max_blocks` index blocks of the file into the new file
+    /// of the given compaction.
+    ///
+    /// The writes made to the blocks already copied are caught up on by [BufferPool::finish_compaction].
+    /// It returns false, copying nothing, if the layout of the file has changed since the compaction was started
+    /// e.g. if it was cleared, in which case the compaction should be discarded and started afresh.
+    pub(crate) fn continue_compaction(
+        &mut self,
+        compaction: &mut Compaction,
+        max_blocks: u64,
+    ) -> io::Result<bool> {
+        if compaction.generation != self.generation {
+            return Ok(false);
+        }
+
+        let header = compaction.header.clone();
+        let net_block_size = header.get_net_block_size();
+        let idx_entry_size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
+        let zero = vec![0u8; idx_entry_size];
+        let end_block = compaction
+            .next_block
+            .saturating_add(max_blocks)
+            .min(header.number_of_index_blocks);
+
+        for block in compaction.next_block..end_block {
+            let index_block = self.read_index_block(block, &header)?;
+            let block_offset = HEADER_SIZE_IN_BYTES + block * net_block_size;
+            let mut idx_offset = block_offset;
+            // write index block into new file
+            compaction.new_file.seek(SeekFrom::Start(idx_offset))?;
+            compaction.new_file.write_all(&index_block)?;
+
+            // the block is copied as it is now, so the writes made to it so far need no catching up on
+            if let Some(changes) = &mut self.compaction_changes {
+                let block_end = block_offset + net_block_size;
+                let copied: Vec<u64> = changes
+                    .index_entries
+                    .range(block_offset..block_end)
+                    .copied()
+                    .collect();
+                for address in copied {
+                    changes.index_entries.remove(&address);
+                }
+            }
+
+            for idx_bytes in index_block.chunks_exact(idx_entry_size) {
                 if idx_bytes != zero {
-                    let kv_byte_array = get_kv_bytes(&file, &idx_bytes)?;
-                    let kv = KeyValueEntry::from_data_array(&kv_byte_array, 0)?;
-                    if !kv.is_expired() && !kv.is_deleted {
-                        let kv_size = kv_byte_array.len() as u64;
-                        // insert key value
-                        new_file.seek(SeekFrom::Start(new_file_offset))?;
-                        new_file.write_all(&kv_byte_array)?;
-
-                        // update index
-                        new_file.seek(SeekFrom::Start(idx_offset))?;
-                        new_file.write_all(&new_file_offset.to_be_bytes())?;
-
-                        // update search index
-                        if let Some(idx) = search_index.as_deref_mut() {
-                            idx.add(kv.key, new_file_offset, kv.expiry)?;
-                        }
+                    let kv_address = u64::from_be_bytes(slice_to_array(idx_bytes)?);
+                    if let Some(changes) = &mut self.compaction_changes {
+                        changes.kv_entries.remove(&kv_address);
+                    }
 
-                        // move forward in iteration
-                        new_file_offset += kv_size;
+                    let size = self.read_kv_size(kv_address)?;
+                    let kv_byte_array = self.read_kv_bytes(kv_address, size)?;
+                    let kv = KeyValueEntry::from_data_array(&kv_byte_array, 0)?;
+                    let new_kv_address = if !kv.is_expired(compaction.now) && !kv.is_deleted {
+                        compaction.copy_entry(&kv, &kv_byte_array)?
                     } else {
                         // if expired or deleted, update index to zero
-                        new_file.seek(SeekFrom::Start(idx_offset))?;
-                        new_file.write_all(&zero)?;
-                    }
+                        0
+                    };
+
+                    // update index
+                    compaction.new_file.seek(SeekFrom::Start(idx_offset))?;
+                    compaction
+                        .new_file
+                        .write_all(&new_kv_address.to_be_bytes())?;
                 }
 
-                idx_block_cursor = upper;
                 idx_offset += INDEX_ENTRY_SIZE_IN_BYTES;
             }
         }
 
-        self.kv_buffers.clear();
-        self.index_buffers.clear();
-        self.file = new_file;
-        self.file_size = new_file_offset;
+        compaction.next_block = end_block;
+        Ok(true)
+    }
+
+    /// Copies into the new file of the given compaction the writes made to the file, since the compaction
+    /// was started, to the index blocks that had already been copied. Only the key-value entries of the index
+    /// entries that were updated e.g. to point to entries appended since, or whose entries were updated
+    /// in place e.g. marked as deleted, are copied afresh.
+    ///
+    /// It returns the number of bytes taken up in the new file by the entries that were copied earlier
+    /// and are thus replaced.
+    fn catch_up_compaction(&mut self, compaction: &mut Compaction) -> io::Result<u64> {
+        let Some(CompactionChanges {
+            mut index_entries,
+            kv_entries,
+        }) = self.compaction_changes.take()
+        else {
+            return Ok(0);
+        };
+
+        // the entries updated in place are copied afresh via the index entries that point to them
+        if !kv_entries.is_empty() {
+            let header = self.read_header()?;
+            for kv_address in kv_entries {
+                let size = self.read_kv_size(kv_address)?;
+                let kv_byte_array = self.read_kv_bytes(kv_address, size)?;
+                let kv = KeyValueEntry::from_data_array(&kv_byte_array, 0)?;
+                for idx_address in header.get_index_offsets(kv.key) {
+                    let addr_bytes = self.read_index(idx_address)?;
+                    let addr = u64::from_be_bytes(slice_to_array(&addr_bytes)?);
+                    if addr == kv_address {
+                        index_entries.insert(idx_address);
+                        break;
+                    }
+
+                    // the key has since been set afresh, which is caught up on via its index entry
+                    if addr != 0 && self.addr_belongs_to_key(&addr_bytes, kv.key)? {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut replaced: HashSet<u64> = HashSet::new();
+        let mut replaced_bytes = 0u64;
+        for idx_address in index_entries {
+            let mut copied_kv_address = [0u8; INDEX_ENTRY_SIZE_IN_BYTES as usize];
+            compaction.new_file.seek(SeekFrom::Start(idx_address))?;
+            compaction.new_file.read_exact(&mut copied_kv_address)?;
+            let copied_kv_address = u64::from_be_bytes(copied_kv_address);
+            if copied_kv_address != 0 {
+                let mut size = [0u8; 4];
+                compaction
+                    .new_file
+                    .seek(SeekFrom::Start(copied_kv_address))?;
+                compaction.new_file.read_exact(&mut size)?;
+                replaced_bytes += u32::from_be_bytes(size) as u64;
+                replaced.insert(copied_kv_address);
+                compaction.live_entries -= 1;
+            }
+
+            let kv_address = u64::from_be_bytes(slice_to_array(&self.read_index(idx_address)?)?);
+            let mut new_kv_address = 0;
+            if kv_address != 0 {
+                let size = self.read_kv_size(kv_address)?;
+                let kv_byte_array = self.read_kv_bytes(kv_address, size)?;
+                let kv = KeyValueEntry::from_data_array(&kv_byte_array, 0)?;
+                if !kv.is_expired(compaction.now) && !kv.is_deleted {
+                    new_kv_address = compaction.copy_entry(&kv, &kv_byte_array)?;
+                }
+            }
+
+            compaction.new_file.seek(SeekFrom::Start(idx_address))?;
+            compaction
+                .new_file
+                .write_all(&new_kv_address.to_be_bytes())?;
+        }
+
+        if let Some(entries) = &mut compaction.search_entries {
+            if !replaced.is_empty() {
+                entries.retain(|(_, kv_address, _)| !replaced.contains(kv_address));
+            }
+        }
+
+        Ok(replaced_bytes)
+    }
+
+    /// Replaces the file with the new file of the given compaction, and rebuilds the given search index
+    /// from the entries copied into it.
+    ///
+    /// It should be called, while still holding the lock on the pool, right after the call to
+    /// [BufferPool::continue_compaction] that copied the last index block i.e. after which
+    /// [Compaction::is_copied] is true. Any writes made to the file between the steps of the compaction
+    /// are caught up on first.
+    pub(crate) fn finish_compaction(
+        &mut self,
+        mut compaction: Compaction,
+        search_index: &mut Option<&mut InvertedIndex>,
+    ) -> io::Result<()> {
+        let replaced_bytes = self.catch_up_compaction(&mut compaction)?;
+        let Compaction {
+            new_header,
+            mut new_file,
+            new_file_path,
+            new_file_offset,
+            live_entries,
+            search_entries,
+            ..
+        } = compaction;
+
+        if let Some(idx) = search_index.as_deref_mut() {
+            idx.clear()?;
+            for (key, kv_address, expiry) in search_entries.unwrap_or_default() {
+                idx.add(&key, kv_address, expiry)?;
+            }
+        }
 
-        fs::remove_file(&self.file_path)?;
-        fs::rename(&new_file_path, &self.file_path)?;
+        self.reset_buffers(&new_header);
+        self.file_size = new_file_offset;
+        // only the live entries were copied, so they are all the keys in the new file
+        self.dangling_bytes = Some(replaced_bytes);
+        self.live_keys = Some(live_entries);
+        self.generation += 1;
+
+        match (&self.file_path, &new_file_path) {
+            (Some(file_path), Some(new_file_path)) => {
+                self.file = new_file;
+                fs::remove_file(file_path)?;
+                fs::rename(new_file_path, file_path)?;
+            }
+            _ => {
+                // a file not on the file system can't be replaced, so its data is overwritten
+                new_file.seek(SeekFrom::Start(0))?;
+                self.file.seek(SeekFrom::Start(0))?;
+                io::copy(&mut new_file, &mut self.file)?;
+                self.file.set_len(new_file_offset)?;
+            }
+        }
 
         Ok(())
     }
 
     /// Returns the Some(Value) at the given address if the key there corresponds to the given key
-    /// Otherwise, it returns None
+    /// and the entry is neither deleted nor expired for longer than the grace period. Otherwise, it returns None
     /// This is to handle hash collisions.
+    ///
+    /// It returns an InvalidData error if the address is not within the key-value entries of the file
+    /// e.g. if the index is corrupted.
     pub(crate) fn get_value(&mut self, kv_address: u64, key: &[u8]) -> io::Result<Option<Value>> {
         if kv_address == 0 {
             return Ok(None);
         }
+        self.validate_kv_address(kv_address, "get value")?;
 
+        let now = self.get_stale_cutoff();
         // loop in reverse, starting at the back
         // since the latest kv_buffers are the ones updated when new changes occur
         for buf in self.kv_buffers.iter_mut().rev() {
-            if buf.contains(kv_address) {
-                return buf.get_value(kv_address, key);
+            if buf.contains_kv_entry(kv_address) {
+                self.cache_counters.record_kv(true);
+                return buf.get_value(kv_address, key, now);
             }
         }
 
+        self.cache_counters.record_kv(false);
+
         if self.kv_buffers.len() >= self.kv_capacity {
             self.kv_buffers.pop_front();
         }
 
         let mut buf: Vec<u8> = vec![0; self.buffer_size];
         self.file.seek(SeekFrom::Start(kv_address))?;
-        let bytes_read = self.file.read(&mut buf)?;
+        // any preallocated space after the last entry is left out of the buffer,
+        // as it is yet to be appended to
+        let bytes_read = self
+            .file
+            .read(&mut buf)?
+            .min(self.file_size.saturating_sub(kv_address) as usize);
 
         // update kv_buffers only upto actual data read (cater for partially filled buffer)
         self.kv_buffers.push_back(Buffer::new(
@@ -267,10 +1160,47 @@ impl BufferPool {
             self.buffer_size,
         ));
 
-        let entry = KeyValueEntry::from_data_array(&buf, 0)?;
+        // entries bigger than a buffer e.g. those with very long keys are read whole from file
+        let size = u32::from_be_bytes(slice_to_array(&buf[..4])?);
+        if size as usize > bytes_read && kv_address + size as u64 <= self.file_size {
+            buf = self.read_kv_bytes(kv_address, size)?;
+        }
+
+        let entry = KeyValueEntry::from_data_array(&buf, 0)
+            .map_err(|e| add_error_context(e, "get value", kv_address))?;
+
+        let value = if entry.key == key && !entry.is_deleted && !entry.is_expired(now) {
+            Some(Value::new(&entry, now))
+        } else {
+            None
+        };
+
+        Ok(value)
+    }
+
+    /// Returns the Some(Value) at the given address if the key there corresponds to the given key,
+    /// just like [BufferPool::get_value], but without loading the entry into the buffers on a cache miss.
+    /// The cache counters are left untouched too, so peeking affects neither the eviction order nor the stats.
+    pub(crate) fn peek_value(&mut self, kv_address: u64, key: &[u8]) -> io::Result<Option<Value>> {
+        if kv_address == 0 {
+            return Ok(None);
+        }
+        self.validate_kv_address(kv_address, "peek value")?;
+
+        for buf in self.kv_buffers.iter().rev() {
+            if buf.contains_kv_entry(kv_address) {
+                return buf.get_value(kv_address, key, self.get_stale_cutoff());
+            }
+        }
+
+        let size = self.read_kv_size(kv_address)?;
+        let buf = self.read_kv_bytes(kv_address, size)?;
+        let entry = KeyValueEntry::from_data_array(&buf, 0)
+            .map_err(|e| add_error_context(e, "peek value", kv_address))?;
 
-        let value = if entry.key == key && !entry.is_expired() {
-            Some(Value::from(&entry))
+        let now = self.get_stale_cutoff();
+        let value = if entry.key == key && !entry.is_deleted && !entry.is_expired(now) {
+            Some(Value::new(&entry, now))
         } else {
             None
         };
@@ -286,58 +1216,103 @@ impl BufferPool {
         key: &[u8],
     ) -> io::Result<Option<()>> {
         let key_size = key.len();
-        let addr_for_is_deleted = kv_address + OFFSET_FOR_KEY_IN_KV_ARRAY as u64 + key_size as u64;
+        let key_span = OFFSET_FOR_KEY_IN_KV_ARRAY as u64 + key_size as u64;
+        let addr_for_flags = kv_address + key_span;
         // loop in reverse, starting at the back
         // since the latest kv_buffers are the ones updated when new changes occur
+        let mut is_deleted_in_buffer = false;
         for buf in self.kv_buffers.iter_mut().rev() {
-            if buf.contains(kv_address) && buf.try_delete_kv_entry(kv_address, key)?.is_some() {
-                self.file.seek(SeekFrom::Start(addr_for_is_deleted))?;
-                self.file.write_all(&[TRUE_AS_BYTE])?;
-                return Ok(Some(()));
+            if buf.contains_span(kv_address, key_span + FLAGS_SIZE_IN_BYTES as u64)
+                && buf.try_delete_kv_entry(kv_address, key)?.is_some()
+            {
+                is_deleted_in_buffer = true;
+                break;
             }
         }
 
-        let key_in_data =
-            extract_key_as_byte_array_from_file(&mut self.file, kv_address, key_size)?;
-        if key_in_data == key {
-            self.file.seek(SeekFrom::Start(addr_for_is_deleted))?;
-            self.file.write_all(&[TRUE_AS_BYTE])?;
+        if is_deleted_in_buffer
+            || extract_key_as_byte_array_from_file(&mut self.file, kv_address, key_size)? == key
+        {
+            self.record_kv_change(kv_address);
+            let mut flags = [0u8];
+            self.file.seek(SeekFrom::Start(addr_for_flags))?;
+            self.file.read_exact(&mut flags)?;
+            flags[0] |= IS_DELETED_FLAG;
+            self.log_writes(&[(addr_for_flags, &flags)])?;
+            self.file.seek(SeekFrom::Start(addr_for_flags))?;
+            self.file.write_all(&flags)?;
+            self.commit_writes()?;
             Ok(Some(()))
         } else {
             Ok(None)
         }
     }
 
+    /// Updates the expiry of the key-value entry for the given key at the given kv_address,
+    /// both on file and in memory
+    pub(crate) fn update_expiry(
+        &mut self,
+        kv_address: u64,
+        key: &[u8],
+        expiry: u64,
+    ) -> io::Result<()> {
+        // the expiry comes right after the key and the flags
+        let expiry_address =
+            kv_address + (OFFSET_FOR_KEY_IN_KV_ARRAY + key.len() + FLAGS_SIZE_IN_BYTES) as u64;
+        let expiry_bytes = expiry.to_be_bytes();
+
+        self.record_kv_change(kv_address);
+        self.log_writes(&[(expiry_address, &expiry_bytes)])?;
+        self.file.seek(SeekFrom::Start(expiry_address))?;
+        self.file.write_all(&expiry_bytes)?;
+        self.commit_writes()?;
+
+        // the buffers holding the old expiry are stale
+        let expiry_end = expiry_address + expiry_bytes.len() as u64 - 1;
+        self.kv_buffers
+            .retain(|buf| !buf.contains(expiry_address) && !buf.contains(expiry_end));
+        Ok(())
+    }
+
     /// Checks to see if the given kv address is for the given key.
     /// Note that this returns true for expired keys as long as compaction has not yet been done.
     /// This avoids duplicate entries for the same key being tracked in separate index entries
     ///
-    /// It also returns false if the address goes beyond the size of the file
+    /// It also returns false if the address is not within the key-value entries of the file
     pub(crate) fn addr_belongs_to_key(
         &mut self,
         kv_address: &[u8],
         key: &[u8],
     ) -> io::Result<bool> {
         let kv_address = u64::from_be_bytes(slice_to_array(kv_address)?);
-        if kv_address >= self.file_size {
+        let key_span = OFFSET_FOR_KEY_IN_KV_ARRAY as u64 + key.len() as u64;
+        if self.validate_kv_address(kv_address, "check key").is_err() {
             return Ok(false);
         }
 
         // loop in reverse, starting at the back
         // since the latest kv_buffers are the ones updated when new changes occur
         for buf in self.kv_buffers.iter_mut().rev() {
-            if buf.contains(kv_address) {
+            if buf.contains_span(kv_address, key_span) {
+                self.cache_counters.record_kv(true);
                 return buf.addr_belongs_to_key(kv_address, key);
             }
         }
 
+        self.cache_counters.record_kv(false);
+
         if self.kv_buffers.len() >= self.kv_capacity {
             self.kv_buffers.pop_front();
         }
 
         let mut buf: Vec<u8> = vec![0; self.buffer_size];
         self.file.seek(SeekFrom::Start(kv_address))?;
-        let bytes_read = self.file.read(&mut buf)?;
+        // any preallocated space after the last entry is left out of the buffer,
+        // as it is yet to be appended to
+        let bytes_read = self
+            .file
+            .read(&mut buf)?
+            .min(self.file_size.saturating_sub(kv_address) as usize);
 
         // update kv_buffers only upto actual data read (cater for partially filled buffer)
         self.kv_buffers.push_back(Buffer::new(
@@ -346,6 +1321,16 @@ impl BufferPool {
             self.buffer_size,
         ));
 
+        // keys longer than a buffer are compared straight from file
+        if key_span > bytes_read as u64 {
+            if kv_address + key_span > self.file_size {
+                return Ok(false);
+            }
+            let key_in_file =
+                extract_key_as_byte_array_from_file(&mut self.file, kv_address, key.len())?;
+            return Ok(key_in_file == key);
+        }
+
         let key_in_file = &buf[OFFSET_FOR_KEY_IN_KV_ARRAY..OFFSET_FOR_KEY_IN_KV_ARRAY + key.len()];
         let value = key_in_file == key;
         Ok(value)
@@ -360,7 +1345,8 @@ impl BufferPool {
     pub(crate) fn read_index(&mut self, address: u64) -> io::Result<Vec<u8>> {
         validate_bounds!(
             (address, address + INDEX_ENTRY_SIZE_IN_BYTES),
-            (HEADER_SIZE_IN_BYTES, self.key_values_start_point)
+            (HEADER_SIZE_IN_BYTES, self.key_values_start_point),
+            "read index"
         )?;
 
         let size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
@@ -368,11 +1354,14 @@ impl BufferPool {
         // starts from buffer with lowest left_offset, which I expect to have more keys
         for (i, buf) in self.index_buffers.iter() {
             if buf.contains(address) {
+                self.cache_counters.record_index(true);
                 return buf.read_at(address, size);
             }
             last_buf.replace(*i);
         }
 
+        self.cache_counters.record_index(false);
+
         if self.index_buffers.len() >= self.index_capacity {
             if let Some(k) = last_buf {
                 self.index_buffers.remove(&k);
@@ -393,20 +1382,49 @@ impl BufferPool {
         Ok(data_array)
     }
 
+    /// Reads the index at the given address just like [BufferPool::read_index], but without loading it
+    /// into the buffers on a cache miss, and without touching the cache counters
+    ///
+    /// # Errors
+    ///
+    /// If the address is less than [HEADER_SIZE_IN_BYTES] or [BufferPool.key_values_start_point],
+    /// an InvalidData error is returned
+    pub(crate) fn peek_index(&mut self, address: u64) -> io::Result<Vec<u8>> {
+        validate_bounds!(
+            (address, address + INDEX_ENTRY_SIZE_IN_BYTES),
+            (HEADER_SIZE_IN_BYTES, self.key_values_start_point),
+            "peek index"
+        )?;
+
+        let size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
+        for buf in self.index_buffers.values() {
+            if buf.contains(address) {
+                return buf.read_at(address, size);
+            }
+        }
+
+        let mut data_array = vec![0u8; size];
+        self.file.seek(SeekFrom::Start(address))?;
+        self.file.read_exact(&mut data_array)?;
+        Ok(data_array)
+    }
+
     /// Gets all the key-value pairs that correspond to the given list of key-value addresses
     pub(crate) fn get_many_key_values(
         &mut self,
         kv_addresses: &[u64],
     ) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
         let mut results: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+        let now = self.get_stale_cutoff();
 
         for kv_address in kv_addresses {
             let kv_address = *kv_address;
             let size = self.read_kv_size(kv_address)?;
             let buf = self.read_kv_bytes(kv_address, size)?;
-            let entry = KeyValueEntry::from_data_array(&buf, 0)?;
+            let entry = KeyValueEntry::from_data_array(&buf, 0)
+                .map_err(|e| add_error_context(e, "get many key values", kv_address))?;
 
-            if !entry.is_expired() && !entry.is_deleted {
+            if !entry.is_expired(now) && !entry.is_deleted {
                 results.push((entry.key.to_vec(), entry.value.to_vec()));
             }
         }
@@ -414,6 +1432,583 @@ impl BufferPool {
         Ok(results)
     }
 
+    /// Gets up to `count` unexpired key-value pairs, walking the index from the slot at the given position,
+    /// counting from the first slot of the first index block.
+    ///
+    /// It also returns the position of the slot right after the last one looked at, or None if the end
+    /// of the index was reached
+    pub(crate) fn scan_key_values(
+        &mut self,
+        header: &DbFileHeader,
+        start: u64,
+        count: usize,
+    ) -> io::Result<(Vec<KeyValuePair>, Option<u64>)> {
+        let items_per_block = header.items_per_index_block;
+        let number_of_slots = items_per_block * header.number_of_index_blocks;
+        let idx_entry_size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
+        let mut results: Vec<KeyValuePair> = Vec::with_capacity(count.min(1024));
+        let mut position = start;
+
+        while position < number_of_slots && results.len() < count {
+            let index_block = self.read_index_block(position / items_per_block, header)?;
+            let first_slot = (position % items_per_block) as usize;
+
+            for idx_bytes in index_block.chunks_exact(idx_entry_size).skip(first_slot) {
+                position += 1;
+                let kv_address = u64::from_be_bytes(slice_to_array(idx_bytes)?);
+                if kv_address != 0 {
+                    if let Some(pair) = self.read_key_value(kv_address)? {
+                        results.push(pair);
+                        if results.len() >= count {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        let next = (position < number_of_slots).then_some(position);
+        Ok((results, next))
+    }
+
+    /// Gets the key-value pairs at the given list of key-value addresses, in the same order as the addresses.
+    ///
+    /// Unlike [BufferPool::get_many_key_values], the addresses need not come from the index: any address
+    /// that is out of the bounds of the key-value entries, or at which no entry fits in the file, gives `None`
+    /// instead of an error, just as an expired or deleted entry does.
+    pub(crate) fn get_key_values_at(
+        &mut self,
+        kv_addresses: &[u64],
+    ) -> io::Result<Vec<Option<KeyValuePair>>> {
+        let now = self.get_stale_cutoff();
+        let min_size = KEY_VALUE_MIN_SIZE_IN_BYTES as u64;
+        let mut results = Vec::with_capacity(kv_addresses.len());
+
+        for &kv_address in kv_addresses {
+            if kv_address < self.key_values_start_point
+                || kv_address.saturating_add(min_size) > self.file_size
+            {
+                results.push(None);
+                continue;
+            }
+
+            let size = self.read_kv_size(kv_address)?;
+            if (size as u64) < min_size || kv_address + size as u64 > self.file_size {
+                results.push(None);
+                continue;
+            }
+
+            let buf = self.read_kv_bytes(kv_address, size)?;
+            let key_size = u32::from_be_bytes(slice_to_array(&buf[4..8])?);
+            let flags = buf.get(OFFSET_FOR_KEY_IN_KV_ARRAY + key_size as usize);
+            let version_size = flags.map_or(0, |flags| get_version_size(*flags)) as u64;
+            if key_size as u64 + version_size + min_size > size as u64 {
+                results.push(None);
+                continue;
+            }
+
+            let entry = KeyValueEntry::from_data_array(&buf, 0)
+                .map_err(|e| add_error_context(e, "get key values at", kv_address))?;
+            if !entry.is_expired(now) && !entry.is_deleted {
+                results.push(Some((entry.key.to_vec(), entry.value.to_vec())));
+            } else {
+                results.push(None);
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Reads the index block at the given `block` position (0-based) directly from file
+    pub(crate) fn read_index_block(
+        &mut self,
+        block: u64,
+        header: &DbFileHeader,
+    ) -> io::Result<Vec<u8>> {
+        let block_size = header.get_net_block_size();
+        let mut buf = vec![0u8; block_size as usize];
+        self.file
+            .seek(SeekFrom::Start(HEADER_SIZE_IN_BYTES + block * block_size))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Reads the key-value pair at the given address directly from file.
+    /// It returns None if the entry is expired or deleted
+    pub(crate) fn read_key_value(
+        &mut self,
+        kv_address: u64,
+    ) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let size = self.read_kv_size(kv_address)?;
+        let buf = self.read_kv_bytes(kv_address, size)?;
+        let entry = KeyValueEntry::from_data_array(&buf, 0)
+            .map_err(|e| add_error_context(e, "read key value", kv_address))?;
+
+        if !entry.is_expired(self.get_stale_cutoff()) && !entry.is_deleted {
+            Ok(Some((entry.key.to_vec(), entry.value.to_vec())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the key, value and expiry of the key-value entry at the given address directly from file.
+    /// It returns None if the entry is expired or deleted
+    pub(crate) fn read_key_value_and_expiry(
+        &mut self,
+        kv_address: u64,
+    ) -> io::Result<Option<KeyValueWithExpiry>> {
+        let size = self.read_kv_size(kv_address)?;
+        let buf = self.read_kv_bytes(kv_address, size)?;
+        let entry = KeyValueEntry::from_data_array(&buf, 0)
+            .map_err(|e| add_error_context(e, "read key value and expiry", kv_address))?;
+
+        if !entry.is_expired(self.get_stale_cutoff()) && !entry.is_deleted {
+            Ok(Some((
+                entry.key.to_vec(),
+                entry.value.to_vec(),
+                entry.expiry,
+            )))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the value and expiry of the key-value entry at the given address directly from file.
+    /// It returns None if the key there is not the given key, or if the entry is expired or deleted
+    pub(crate) fn read_value_and_expiry(
+        &mut self,
+        kv_address: u64,
+        key: &[u8],
+    ) -> io::Result<Option<(Vec<u8>, u64)>> {
+        let size = self.read_kv_size(kv_address)?;
+        let buf = self.read_kv_bytes(kv_address, size)?;
+        let entry = KeyValueEntry::from_data_array(&buf, 0)
+            .map_err(|e| add_error_context(e, "read value and expiry", kv_address))?;
+
+        if entry.key == key && !entry.is_expired(self.get_stale_cutoff()) && !entry.is_deleted {
+            Ok(Some((entry.value.to_vec(), entry.expiry)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the value and version of the key-value entry at the given address directly from file.
+    /// It returns None if the key there is not the given key, or if the entry is expired or deleted
+    pub(crate) fn read_value_and_version(
+        &mut self,
+        kv_address: u64,
+        key: &[u8],
+    ) -> io::Result<Option<(Vec<u8>, u64)>> {
+        let size = self.read_kv_size(kv_address)?;
+        let buf = self.read_kv_bytes(kv_address, size)?;
+        let entry = KeyValueEntry::from_data_array(&buf, 0)
+            .map_err(|e| add_error_context(e, "read value and version", kv_address))?;
+
+        if entry.key == key && !entry.is_expired(self.get_stale_cutoff()) && !entry.is_deleted {
+            Ok(Some((entry.value.to_vec(), entry.version.unwrap_or(0))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads only the version of the key-value entry at the given address directly from file,
+    /// whether or not it is expired or deleted. Entries written before versions were kept are at version 0
+    pub(crate) fn read_version(&mut self, kv_address: u64) -> io::Result<u64> {
+        // skip the size (4 bytes) of the entry
+        let mut key_size_buf = [0u8; 4];
+        self.file.seek(SeekFrom::Start(kv_address + 4))?;
+        self.file.read_exact(&mut key_size_buf)?;
+        let key_size = u32::from_be_bytes(key_size_buf);
+
+        // flags, expiry and version come right after the key
+        const VERSION_OFFSET: usize = FLAGS_SIZE_IN_BYTES + EXPIRY_SIZE_IN_BYTES;
+        let mut buf = [0u8; VERSION_OFFSET + VERSION_SIZE_IN_BYTES as usize];
+        self.file.seek(SeekFrom::Current(key_size as i64))?;
+        self.file.read_exact(&mut buf[..VERSION_OFFSET])?;
+        if buf[0] & HAS_VERSION_FLAG == 0 {
+            return Ok(0);
+        }
+
+        self.file.read_exact(&mut buf[VERSION_OFFSET..])?;
+        Ok(u64::from_be_bytes(slice_to_array(&buf[VERSION_OFFSET..])?))
+    }
+
+    /// Reads only `len` bytes of the value of the key-value entry at the given address directly from file,
+    /// starting at the `start`th byte of the value, without reading the rest of the value.
+    /// It returns None if the key there is not the given key, or if the entry is expired or deleted.
+    ///
+    /// It fails with [ScdbError::InvalidInput] if the range goes beyond the end of the value
+    pub(crate) fn read_value_range(
+        &mut self,
+        kv_address: u64,
+        key: &[u8],
+        start: u64,
+        len: u64,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let mut sizes_buf = [0u8; OFFSET_FOR_KEY_IN_KV_ARRAY];
+        self.file.seek(SeekFrom::Start(kv_address))?;
+        self.file.read_exact(&mut sizes_buf)?;
+        let size = u32::from_be_bytes(slice_to_array(&sizes_buf[..4])?);
+        let key_size = u32::from_be_bytes(slice_to_array(&sizes_buf[4..])?);
+        if key_size as usize != key.len() {
+            return Ok(None);
+        }
+
+        // key, flags and expiry are contiguous
+        let mut buf = vec![0u8; key.len() + FLAGS_SIZE_IN_BYTES + EXPIRY_SIZE_IN_BYTES];
+        self.file.read_exact(&mut buf)?;
+        let flags = buf[key.len()];
+        let expiry = u64::from_be_bytes(slice_to_array(&buf[key.len() + FLAGS_SIZE_IN_BYTES..])?);
+        let is_expired = expiry != 0 && expiry < self.get_stale_cutoff();
+        if &buf[..key.len()] != key || flags & IS_DELETED_FLAG != 0 || is_expired {
+            return Ok(None);
+        }
+
+        let version_size = get_version_size(flags);
+        let value_size = size
+            .checked_sub(key_size + KEY_VALUE_MIN_SIZE_IN_BYTES + version_size)
+            .ok_or_else(|| {
+                add_error_context(
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("entry size {} is too small for its key", size),
+                    ),
+                    "read value range",
+                    kv_address,
+                )
+            })?;
+        let is_in_bounds = start
+            .checked_add(len)
+            .is_some_and(|end| end <= value_size as u64);
+        if !is_in_bounds {
+            return Err(io::Error::from(ScdbError::InvalidInput(format!(
+                "range of {} bytes from {} is out of bounds of the value of {} bytes",
+                len, start, value_size
+            ))));
+        }
+
+        let mut range = vec![0u8; len as usize];
+        self.file
+            .seek(SeekFrom::Current(version_size as i64 + start as i64))?;
+        self.file.read_exact(&mut range)?;
+        Ok(Some(range))
+    }
+
+    /// Reads the value, expiry and deleted flag of the key-value entry at the given address directly
+    /// from file. Unlike [BufferPool::read_value_and_expiry], it returns deleted and expired entries too,
+    /// only returning None if the key there is not the given key
+    pub(crate) fn read_value_meta(
+        &mut self,
+        kv_address: u64,
+        key: &[u8],
+    ) -> io::Result<Option<ValueMeta>> {
+        let size = self.read_kv_size(kv_address)?;
+        let buf = self.read_kv_bytes(kv_address, size)?;
+        let entry = KeyValueEntry::from_data_array(&buf, 0)
+            .map_err(|e| add_error_context(e, "read value meta", kv_address))?;
+
+        if entry.key == key {
+            Ok(Some(ValueMeta::new(&entry, self.clock.now_secs())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads only the key of the key-value entry at the given address directly from file,
+    /// without reading its value. It returns None if the entry is expired or deleted
+    pub(crate) fn read_key(&mut self, kv_address: u64) -> io::Result<Option<Vec<u8>>> {
+        Ok(self
+            .read_live_key_and_expiry(kv_address)?
+            .map(|(key, _)| key))
+    }
+
+    /// Reads only the key of the key-value entry at the given address directly from file, together with
+    /// the number of seconds it has left to live, or None if it never expires, without reading its value.
+    /// It returns None if the entry is expired or deleted
+    pub(crate) fn read_key_and_ttl(
+        &mut self,
+        kv_address: u64,
+    ) -> io::Result<Option<(Vec<u8>, Option<u64>)>> {
+        let now = self.clock.now_secs();
+        Ok(self
+            .read_live_key_and_expiry(kv_address)?
+            .map(|(key, expiry)| (key, (expiry != 0).then(|| expiry.saturating_sub(now)))))
+    }
+
+    /// Reads only the key and the expiry of the key-value entry at the given address directly from file,
+    /// without reading its value. It returns None if the entry is expired or deleted
+    fn read_live_key_and_expiry(&mut self, kv_address: u64) -> io::Result<Option<(Vec<u8>, u64)>> {
+        // skip the size (4 bytes) of the entry
+        let mut key_size_buf = [0u8; 4];
+        self.file.seek(SeekFrom::Start(kv_address + 4))?;
+        self.file.read_exact(&mut key_size_buf)?;
+        let key_size = u32::from_be_bytes(key_size_buf) as usize;
+
+        // key, flags and expiry are contiguous
+        let mut buf = vec![0u8; key_size + FLAGS_SIZE_IN_BYTES + EXPIRY_SIZE_IN_BYTES];
+        self.file.read_exact(&mut buf)?;
+        let is_deleted = buf[key_size] & IS_DELETED_FLAG != 0;
+        let expiry = u64::from_be_bytes(slice_to_array(&buf[key_size + FLAGS_SIZE_IN_BYTES..])?);
+        let is_expired = expiry != 0 && expiry < self.get_stale_cutoff();
+
+        if !is_expired && !is_deleted {
+            buf.truncate(key_size);
+            Ok(Some((buf, expiry)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the size of the key-value entry at the given address directly from file,
+    /// returning None if the entry is expired or deleted
+    pub(crate) fn read_live_entry_size(&mut self, kv_address: u64) -> io::Result<Option<u32>> {
+        let (size, _, _, is_live) = self.read_entry_size_and_liveness(kv_address)?;
+        Ok(is_live.then_some(size))
+    }
+
+    /// Reads the sizes of the key and of the value of the key-value entry at the given address
+    /// directly from file, returning None if the entry is expired or deleted
+    pub(crate) fn read_live_key_and_value_sizes(
+        &mut self,
+        kv_address: u64,
+    ) -> io::Result<Option<(u32, u32)>> {
+        let (_, key_size, value_size, is_live) = self.read_entry_size_and_liveness(kv_address)?;
+        Ok(is_live.then_some((key_size, value_size)))
+    }
+
+    /// Reads the size of the key-value entry at the given address, and the sizes of its key and value,
+    /// directly from file, together with whether it is neither expired nor deleted
+    fn read_entry_size_and_liveness(
+        &mut self,
+        kv_address: u64,
+    ) -> io::Result<(u32, u32, u32, bool)> {
+        let (size, key_size, value_size, is_deleted, is_expired) =
+            self.read_entry_size_and_flags(kv_address)?;
+        Ok((size, key_size, value_size, !is_expired && !is_deleted))
+    }
+
+    /// Reads the size of the key-value entry at the given address, and the sizes of its key and value,
+    /// directly from file, together with whether it is deleted and whether it is expired
+    fn read_entry_size_and_flags(
+        &mut self,
+        kv_address: u64,
+    ) -> io::Result<(u32, u32, u32, bool, bool)> {
+        let mut sizes_buf = [0u8; OFFSET_FOR_KEY_IN_KV_ARRAY];
+        self.file.seek(SeekFrom::Start(kv_address))?;
+        self.file.read_exact(&mut sizes_buf)?;
+        let size = u32::from_be_bytes(slice_to_array(&sizes_buf[..4])?);
+        let key_size = u32::from_be_bytes(slice_to_array(&sizes_buf[4..])?);
+
+        // flags and expiry come right after the key
+        let mut flags_buf = [0u8; FLAGS_SIZE_IN_BYTES + EXPIRY_SIZE_IN_BYTES];
+        self.file.seek(SeekFrom::Current(key_size as i64))?;
+        self.file.read_exact(&mut flags_buf)?;
+        let is_deleted = flags_buf[0] & IS_DELETED_FLAG != 0;
+        let expiry = u64::from_be_bytes(slice_to_array(&flags_buf[FLAGS_SIZE_IN_BYTES..])?);
+        let is_expired = expiry != 0 && expiry < self.get_stale_cutoff();
+        let value_size = size.saturating_sub(
+            key_size + KEY_VALUE_MIN_SIZE_IN_BYTES + get_version_size(flags_buf[0]),
+        );
+
+        Ok((size, key_size, value_size, is_deleted, is_expired))
+    }
+
+    /// Returns the estimated number of bytes in the file taken up by deleted, expired or
+    /// overwritten key-value entries i.e. the bytes that compaction would reclaim.
+    ///
+    /// The first time it is called after the file is opened, it walks the whole index to
+    /// find the live entries. Thereafter, the count is kept up to date by [BufferPool::mark_dangling]
+    /// except for entries that expire in the meantime.
+    pub(crate) fn get_dangling_bytes(&mut self, header: &DbFileHeader) -> io::Result<u64> {
+        if let Some(v) = self.dangling_bytes {
+            return Ok(v);
+        }
+
+        self.count_live_entries(header)?;
+        Ok(self.dangling_bytes.unwrap_or_default())
+    }
+
+    /// Returns the number of unexpired, undeleted keys in the file.
+    ///
+    /// Just like [BufferPool::get_dangling_bytes], the first time it is called after the file is opened
+    /// or reloaded, it walks the whole index. Thereafter, the count is kept up to date by
+    /// [BufferPool::append_kv_entry] and [BufferPool::mark_dangling], and is set afresh by
+    /// [BufferPool::finish_compaction]. Entries that expire in the meantime are counted until the next compaction.
+    pub(crate) fn get_live_keys(&mut self, header: &DbFileHeader) -> io::Result<u64> {
+        if let Some(v) = self.live_keys {
+            return Ok(v);
+        }
+
+        self.count_live_entries(header)?;
+        Ok(self.live_keys.unwrap_or_default())
+    }
+
+    /// Walks the whole index to tally the bytes of the live, expired and deleted key-value entries
+    /// that compaction would keep or drop, without changing the file. It refreshes the dangling bytes
+    /// and the live keys along the way, just like [BufferPool::get_fragmentation]
+    pub(crate) fn get_compaction_preview(
+        &mut self,
+        header: &DbFileHeader,
+    ) -> io::Result<CompactionPreview> {
+        let mut preview = CompactionPreview {
+            total_bytes: self.file_size - self.key_values_start_point,
+            ..Default::default()
+        };
+        for block in 0..header.number_of_index_blocks {
+            let index_block = self.read_index_block(block, header)?;
+            for kv_address in index_block.chunks_exact(INDEX_ENTRY_SIZE_IN_BYTES as usize) {
+                let kv_address = u64::from_be_bytes(slice_to_array(kv_address)?);
+                if kv_address != 0 {
+                    let (size, _, _, is_deleted, is_expired) =
+                        self.read_entry_size_and_flags(kv_address)?;
+                    let size = size as u64;
+                    if is_deleted {
+                        preview.deleted_entries += 1;
+                        preview.deleted_bytes += size;
+                    } else if is_expired {
+                        preview.expired_entries += 1;
+                        preview.expired_bytes += size;
+                    } else {
+                        preview.live_entries += 1;
+                        preview.live_bytes += size;
+                    }
+                }
+            }
+        }
+
+        self.dangling_bytes = Some(preview.reclaimable_bytes());
+        self.live_keys = Some(preview.live_entries);
+        Ok(preview)
+    }
+
+    /// Walks the whole index to report how much of the file is taken up by live key-value entries
+    /// and how much by dead ones, refreshing the dangling bytes and the live keys along the way
+    pub(crate) fn get_fragmentation(
+        &mut self,
+        header: &DbFileHeader,
+    ) -> io::Result<FragmentationReport> {
+        let (live_bytes, live_entries) = self.count_live_entries(header)?;
+        let total_bytes = self.file_size - self.key_values_start_point;
+        Ok(FragmentationReport {
+            total_bytes,
+            live_bytes,
+            dead_bytes: total_bytes.saturating_sub(live_bytes),
+            live_entries,
+        })
+    }
+
+    /// Walks the whole index to count the live key-value entries, updating both
+    /// the dangling bytes and the live keys. It returns the total size of the live entries
+    /// and their number.
+    fn count_live_entries(&mut self, header: &DbFileHeader) -> io::Result<(u64, u64)> {
+        let mut live_bytes = 0u64;
+        let mut live_keys = 0u64;
+        for block in 0..header.number_of_index_blocks {
+            let index_block = self.read_index_block(block, header)?;
+            for kv_address in index_block.chunks_exact(INDEX_ENTRY_SIZE_IN_BYTES as usize) {
+                let kv_address = u64::from_be_bytes(slice_to_array(kv_address)?);
+                if kv_address != 0 {
+                    if let Some(size) = self.read_live_entry_size(kv_address)? {
+                        live_bytes += size as u64;
+                        live_keys += 1;
+                    }
+                }
+            }
+        }
+
+        let total_bytes = self.file_size - self.key_values_start_point;
+        self.dangling_bytes = Some(total_bytes.saturating_sub(live_bytes));
+        self.live_keys = Some(live_keys);
+        Ok((live_bytes, live_keys))
+    }
+
+    /// Walks the whole index to count the expired or deleted key-value entries it still points to,
+    /// returning their number and their total size in bytes
+    pub(crate) fn count_expired_entries(
+        &mut self,
+        header: &DbFileHeader,
+    ) -> io::Result<(u64, u64)> {
+        let mut count = 0u64;
+        let mut total_bytes = 0u64;
+        for block in 0..header.number_of_index_blocks {
+            let index_block = self.read_index_block(block, header)?;
+            for kv_address in index_block.chunks_exact(INDEX_ENTRY_SIZE_IN_BYTES as usize) {
+                let kv_address = u64::from_be_bytes(slice_to_array(kv_address)?);
+                if kv_address != 0 {
+                    let (size, _, _, is_live) = self.read_entry_size_and_liveness(kv_address)?;
+                    if !is_live {
+                        count += 1;
+                        total_bytes += size as u64;
+                    }
+                }
+            }
+        }
+
+        Ok((count, total_bytes))
+    }
+
+    /// Walks the whole index, zeroing the index entries that point to expired key-value entries
+    /// so that those entries can no longer be reached, returning their keys.
+    ///
+    /// The entries themselves are left in the file, for compaction to remove.
+    pub(crate) fn remove_expired_from_index(
+        &mut self,
+        header: &DbFileHeader,
+    ) -> io::Result<Vec<Vec<u8>>> {
+        let now = self.get_stale_cutoff();
+        let zero = [0u8; INDEX_ENTRY_SIZE_IN_BYTES as usize];
+        let mut keys = vec![];
+
+        for block in 0..header.number_of_index_blocks {
+            let index_block = self.read_index_block(block, header)?;
+            let mut idx_address = HEADER_SIZE_IN_BYTES + block * header.get_net_block_size();
+            for kv_address in index_block.chunks_exact(INDEX_ENTRY_SIZE_IN_BYTES as usize) {
+                let kv_address = u64::from_be_bytes(slice_to_array(kv_address)?);
+                if kv_address != 0 {
+                    let size = self.read_kv_size(kv_address)?;
+                    let buf = self.read_kv_bytes(kv_address, size)?;
+                    let kv = KeyValueEntry::from_data_array(&buf, 0)
+                        .map_err(|e| add_error_context(e, "remove expired", kv_address))?;
+                    if kv.is_expired(now) && !kv.is_deleted {
+                        self.log_writes(&[(idx_address, &zero)])?;
+                        self.update_index(idx_address, &zero)?;
+                        self.commit_writes()?;
+                        keys.push(kv.key.to_vec());
+                    }
+                }
+
+                idx_address += INDEX_ENTRY_SIZE_IN_BYTES;
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Adds the size of the key-value entry at the given address to the dangling bytes,
+    /// and removes it from the live keys, if that entry is still live.
+    /// It should be called just before the entry is overwritten or deleted.
+    pub(crate) fn mark_dangling(&mut self, kv_address: u64) -> io::Result<()> {
+        if self.dangling_bytes.is_some() || self.live_keys.is_some() {
+            if let Some(size) = self.read_live_entry_size(kv_address)? {
+                self.dangling_bytes = self.dangling_bytes.map(|v| v + size as u64);
+                self.live_keys = self.live_keys.map(|v| v.saturating_sub(1));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the given kv_address is within the key-value entries of the file i.e. in
+    /// `[key_values_start_point, file_size)`, or else returns an InvalidData error naming the `operation`
+    #[inline(always)]
+    fn validate_kv_address(&self, kv_address: u64, operation: &str) -> io::Result<()> {
+        validate_bounds!(
+            (kv_address, kv_address + 1),
+            (self.key_values_start_point, self.file_size),
+            operation
+        )
+    }
+
     /// Reads the key-value byte array directly from file given address and size
     #[inline(always)]
     fn read_kv_bytes(&mut self, kv_address: u64, size: u32) -> io::Result<Vec<u8>> {
@@ -423,6 +2018,103 @@ impl BufferPool {
         Ok(buf)
     }
 
+    /// Checks every key-value entry that the index points to, returning the problems found with them
+    /// instead of failing on the first one. It only reads from the file, by-passing the buffers.
+    pub(crate) fn verify(&mut self, header: &DbFileHeader) -> io::Result<Vec<Corruption>> {
+        let file_size = self.file.seek(SeekFrom::End(0))?;
+        let entry_size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
+        let mut corruptions = vec![];
+
+        for block in 0..header.number_of_index_blocks {
+            let index_block = self.read_index_block(block, header)?;
+            let block_address = HEADER_SIZE_IN_BYTES + block * header.net_block_size;
+
+            for (i, addr) in index_block.chunks_exact(entry_size).enumerate() {
+                let kv_address = u64::from_be_bytes(slice_to_array(addr)?);
+                if kv_address == 0 {
+                    continue;
+                }
+
+                let index_address = block_address + (i * entry_size) as u64;
+                if let Some(message) =
+                    self.check_kv_entry(header, index_address, kv_address, file_size)?
+                {
+                    corruptions.push(Corruption {
+                        index_address,
+                        kv_address,
+                        message,
+                    });
+                }
+            }
+        }
+
+        Ok(corruptions)
+    }
+
+    /// Checks the key-value entry at `kv_address` that the index entry at `index_address` points to,
+    /// returning a description of the problem with it, if any
+    fn check_kv_entry(
+        &mut self,
+        header: &DbFileHeader,
+        index_address: u64,
+        kv_address: u64,
+        file_size: u64,
+    ) -> io::Result<Option<String>> {
+        let min_size = KEY_VALUE_MIN_SIZE_IN_BYTES as u64;
+        if kv_address < header.key_values_start_point || kv_address + min_size > file_size {
+            return Ok(Some(format!(
+                "address is out of the bounds {}-{} of the key-value entries",
+                header.key_values_start_point, file_size
+            )));
+        }
+
+        let size = self.read_kv_size(kv_address)?;
+        if (size as u64) < min_size {
+            return Ok(Some(format!(
+                "size {} is less than the minimum {} bytes of an entry",
+                size, min_size
+            )));
+        } else if kv_address + size as u64 > file_size {
+            return Ok(Some(format!(
+                "entry of size {} spills beyond the end of the file at {}",
+                size, file_size
+            )));
+        }
+
+        let data = self.read_kv_bytes(kv_address, size)?;
+        let key_size = u32::from_be_bytes(slice_to_array(&data[4..8])?);
+        if (key_size as u64) + min_size > size as u64 {
+            return Ok(Some(format!(
+                "size {} is too small to hold a key of size {}",
+                size, key_size
+            )));
+        }
+
+        let flags = data[OFFSET_FOR_KEY_IN_KV_ARRAY + key_size as usize];
+        if flags > IS_DELETED_FLAG | HAS_VERSION_FLAG {
+            return Ok(Some(format!("invalid flags {}", flags)));
+        } else if (key_size + get_version_size(flags)) as u64 + min_size > size as u64 {
+            return Ok(Some(format!(
+                "size {} is too small to hold a key of size {} and a version",
+                size, key_size
+            )));
+        }
+
+        let kv = KeyValueEntry::from_data_array(&data, 0)?;
+
+        if !header
+            .get_index_offsets(kv.key)
+            .any(|offset| offset == index_address)
+        {
+            return Ok(Some(format!(
+                "key {:?} does not belong at this index entry",
+                kv.key
+            )));
+        }
+
+        Ok(None)
+    }
+
     /// Reads the size of a key-value entry directly from file
     #[inline(always)]
     fn read_kv_size(&mut self, kv_address: u64) -> io::Result<u32> {
@@ -471,7 +2163,7 @@ impl Display for BufferPool {
 
 /// Extracts the byte array for the key from a given file
 fn extract_key_as_byte_array_from_file(
-    file: &mut File,
+    file: &mut dyn StorageFile,
     kv_address: u64,
     key_size: usize,
 ) -> io::Result<Vec<u8>> {
@@ -490,23 +2182,75 @@ fn get_index_capacity(num_of_index_blocks: usize, capacity: usize) -> usize {
     max(1, min(num_of_index_blocks, index_capacity))
 }
 
-/// Reads a byte array for a key-value entry at the given address in the file
-fn get_kv_bytes(file: &Mutex<&File>, address: &[u8]) -> io::Result<Vec<u8>> {
-    let mut file = acquire_lock!(file)?;
-    let address = u64::from_be_bytes(slice_to_array(address)?);
+/// Returns the size of the given file up to the end of its last key-value entry. This is less than
+/// the actual length of the file if space was preallocated at its end by [BufferPool::preallocate].
+///
+/// Preallocated space is zero-filled, so if the header records a preallocation and the file ends in zeros,
+/// the key-value entries are walked from the `key_values_start_point` to find the last one, as each of them
+/// starts with its non-zero size. Files without any preallocation are never walked, even if their last
+/// value happens to end in zeros.
+fn get_logical_file_size(
+    file: &mut Box<dyn StorageFile>,
+    header: &DbFileHeader,
+) -> io::Result<u64> {
+    let key_values_start_point = header.key_values_start_point;
+    let file_len = file.seek(SeekFrom::End(0))?;
+    if !header.is_preallocated || file_len < key_values_start_point + 4 {
+        return Ok(file_len);
+    }
+
+    let mut size_buf = [0u8; 4];
+    file.seek(SeekFrom::Start(file_len - 4))?;
+    file.read_exact(&mut size_buf)?;
+    if size_buf != [0u8; 4] {
+        return Ok(file_len);
+    }
+
+    let mut address = key_values_start_point;
+    while address + 4 <= file_len {
+        file.seek(SeekFrom::Start(address))?;
+        file.read_exact(&mut size_buf)?;
+        let size = u32::from_be_bytes(size_buf);
+        if size == 0 {
+            break;
+        }
+        address += size as u64;
+    }
+
+    Ok(address.min(file_len))
+}
+
+/// Returns the path of the temporary file into which the db file at `file_path` is compacted
+///
+/// It is named after the db file so that the db files in the same folder are compacted into different files.
+fn get_compaction_file_path(file_path: &Path) -> PathBuf {
+    let folder = file_path.parent().unwrap_or_else(|| Path::new("/"));
+    let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+    folder.join(format!("tmp__compact.{}", file_name))
+}
 
-    // get size of the whole key value entry
-    let mut size_bytes: [u8; 4] = [0; 4];
-    file.seek(SeekFrom::Start(address))?;
-    file.read_exact(&mut size_bytes)?;
-    let size = u32::from_be_bytes(size_bytes);
+/// Returns true if the db file at `file_path` exists, or would be restored by
+/// [recover_from_interrupted_compaction] as a compaction was interrupted while replacing it
+pub(crate) fn db_file_exists(file_path: &Path) -> bool {
+    file_path.exists() || get_compaction_file_path(file_path).exists()
+}
 
-    // get the key value entry itself, basing on the size it has
-    let mut data = vec![0u8; size as usize];
-    file.seek(SeekFrom::Start(address))?;
-    file.read_exact(&mut data)?;
+/// Cleans up after a compaction of the db file at `file_path` that was interrupted e.g. by a crash.
+///
+/// If the db file exists, any temporary compaction file is stale so it is removed.
+/// Otherwise, the crash happened after the old db file was removed but before the fully written
+/// temporary file was renamed to replace it, so the rename is completed.
+fn recover_from_interrupted_compaction(file_path: &Path) -> io::Result<()> {
+    let compaction_file_path = get_compaction_file_path(file_path);
+    if !compaction_file_path.exists() {
+        return Ok(());
+    }
 
-    Ok(data)
+    if file_path.exists() {
+        fs::remove_file(&compaction_file_path)
+    } else {
+        fs::rename(&compaction_file_path, file_path)
+    }
 }
 
 #[cfg(test)]
@@ -514,6 +2258,8 @@ mod tests {
     use super::*;
     use crate::internal::entries::values::key_value::KEY_VALUE_MIN_SIZE_IN_BYTES;
     use crate::internal::get_current_timestamp;
+    use crate::stats::CacheStats;
+    use crate::SearchMode;
     use serial_test::serial;
 
     #[test]
@@ -539,7 +2285,7 @@ mod tests {
 
         let test_data: Vec<(Config<'_>, Expected)> = vec![
             (
-                (None, &Path::new(file_name), None, None, None),
+                (None, Path::new(file_name), None, None, None),
                 Expected {
                     buffer_size: get_vm_page_size() as usize,
                     max_keys: None,
@@ -549,7 +2295,7 @@ mod tests {
                 },
             ),
             (
-                (Some(60), &Path::new(file_name), None, None, None),
+                (Some(60), Path::new(file_name), None, None, None),
                 Expected {
                     buffer_size: get_vm_page_size() as usize,
                     max_keys: None,
@@ -559,7 +2305,7 @@ mod tests {
                 },
             ),
             (
-                (None, &Path::new(file_name), Some(360), None, None),
+                (None, Path::new(file_name), Some(360), None, None),
                 Expected {
                     buffer_size: get_vm_page_size() as usize,
                     max_keys: Some(360),
@@ -569,7 +2315,7 @@ mod tests {
                 },
             ),
             (
-                (None, &Path::new(file_name), None, Some(4), None),
+                (None, Path::new(file_name), None, Some(4), None),
                 Expected {
                     buffer_size: get_vm_page_size() as usize,
                     max_keys: None,
@@ -579,7 +2325,7 @@ mod tests {
                 },
             ),
             (
-                (None, &Path::new(file_name), None, None, Some(2048)),
+                (None, Path::new(file_name), None, None, Some(2048)),
                 Expected {
                     buffer_size: 2048,
                     max_keys: None,
@@ -591,7 +2337,7 @@ mod tests {
         ];
 
         // delete the file so that BufferPool::new() can reinitialize it.
-        fs::remove_file(&file_name).ok();
+        fs::remove_file(file_name).ok();
 
         for ((capacity, file_path, max_keys, redundant_blocks, buffer_size), expected) in test_data
         {
@@ -601,11 +2347,12 @@ mod tests {
             assert_eq!(&got.buffer_size, &expected.buffer_size);
             assert_eq!(&got.max_keys, &expected.max_keys);
             assert_eq!(&got.redundant_blocks, &expected.redundant_blocks);
-            assert_eq!(&got.file_path, &expected.file_path);
+            assert_eq!(got.file_path.as_ref(), Some(&expected.file_path));
             assert_eq!(&got.file_size, &expected.file_size);
 
             // delete the file so that BufferPool::new() can reinitialize it for the next iteration
-            fs::remove_file(&got.file_path).expect(&format!("delete file {:?}", &got.file_path));
+            fs::remove_file(got.file_path.as_ref().unwrap())
+                .unwrap_or_else(|_| panic!("delete file {:?}", &got.file_path));
         }
     }
 
@@ -621,11 +2368,11 @@ mod tests {
         );
         let file_name = "testdb.scdb";
         let test_data: Vec<Config<'_>> = vec![
-            (None, &Path::new(file_name), None, None, None),
-            (Some(60), &Path::new(file_name), None, None, None),
-            (None, &Path::new(file_name), Some(360), None, None),
-            (None, &Path::new(file_name), None, Some(4), None),
-            (None, &Path::new(file_name), None, None, Some(2048)),
+            (None, Path::new(file_name), None, None, None),
+            (Some(60), Path::new(file_name), None, None, None),
+            (None, Path::new(file_name), Some(360), None, None),
+            (None, Path::new(file_name), None, Some(4), None),
+            (None, Path::new(file_name), None, None, Some(2048)),
         ];
 
         for (capacity, file_path, max_keys, redundant_blocks, buffer_size) in test_data {
@@ -637,19 +2384,120 @@ mod tests {
                     .expect("new buffer pool");
             assert_eq!(&first, &second);
             // delete the file so that BufferPool::new() can reinitialize it for the next iteration
-            fs::remove_file(&first.file_path)
-                .expect(&format!("delete file {:?}", &first.file_path));
+            fs::remove_file(first.file_path.as_ref().unwrap())
+                .unwrap_or_else(|_| panic!("delete file {:?}", &first.file_path));
         }
     }
 
+    #[test]
+    #[serial]
+    fn new_with_existing_file_uses_its_block_size() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        // simulate a file created on a machine with a different page size
+        let other_page_size = 2 * get_vm_page_size() as usize;
+        let mut first = BufferPool::new(
+            None,
+            Path::new(file_name),
+            None,
+            None,
+            Some(other_page_size),
+        )
+        .expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut first.file).expect("get header");
+        insert_key_value_entry(&mut first, &header, &kv);
+
+        let mut second =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        assert_eq!(second.buffer_size, other_page_size);
+
+        let kv_address = get_kv_address(&mut second, &header, &kv);
+        let got = second
+            .get_value(kv_address, kv.key)
+            .expect("get value")
+            .unwrap();
+        assert_eq!(got, Value::new(&kv, get_current_timestamp()));
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn new_with_existing_legacy_file_upgrades_its_title() {
+        use crate::internal::entries::headers::db_file_header::LEGACY_DB_FILE_TITLE;
+
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut first =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut first.file).expect("get header");
+        insert_key_value_entry(&mut first, &header, &kv);
+        first.file.seek(SeekFrom::Start(0)).expect("seek start");
+        first
+            .file
+            .write_all(LEGACY_DB_FILE_TITLE.as_bytes())
+            .expect("write legacy title");
+        drop(first);
+
+        let mut second =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let got_header = DbFileHeader::from_file(&mut second.file).expect("get header");
+        assert_eq!(got_header.title, DB_FILE_TITLE);
+
+        let kv_address = get_kv_address(&mut second, &header, &kv);
+        let got = second
+            .get_value(kv_address, kv.key)
+            .expect("get value")
+            .unwrap();
+        assert_eq!(got, Value::new(&kv, get_current_timestamp()));
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn replay_wal_works() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+
+        // log the writes of the key-value entry, as though a crash happened before they were made
+        let kv_address = pool.file_size;
+        let index_address = header.get_index_offset(kv.key);
+        let mut wal = WriteAheadLog::from_file(Box::new(Cursor::new(vec![])));
+        wal.log(&[
+            (kv_address, &kv.as_bytes()),
+            (index_address, &kv_address.to_be_bytes()),
+        ])
+        .expect("log writes");
+
+        pool.replay_wal(&mut wal).expect("replay wal");
+
+        assert_eq!(get_kv_address(&mut pool, &header, &kv), kv_address);
+        let got = pool
+            .get_value(kv_address, kv.key)
+            .expect("get value")
+            .unwrap();
+        assert_eq!(got, Value::new(&kv, get_current_timestamp()));
+        assert_eq!(pool.file_size, get_actual_file_size(file_name));
+        assert_eq!(
+            wal.get_pending_writes().expect("get pending writes"),
+            vec![]
+        );
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
     #[test]
     #[serial]
     fn append_to_file() {
         let file_name = "testdb.scdb";
         let mut data = vec![72u8, 97, 108, 108, 101, 108, 117, 106, 97, 104];
         let data_length = data.len();
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
         let initial_file_size = get_pool_file_size(&mut pool);
 
         pool.append(&mut data).expect("append data");
@@ -663,7 +2511,7 @@ mod tests {
         assert_eq!(bytes_read, data_length);
         assert_eq!(data_in_file, data);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -675,8 +2523,8 @@ mod tests {
         let mut data = vec![72u8, 97, 108, 108, 101, 108, 117, 106, 97, 104];
         let data_length = data.len();
 
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let initial_offset = get_actual_file_size(file_name);
         write_to_file(file_name, initial_offset, initial_data);
@@ -707,7 +2555,7 @@ mod tests {
         assert_eq!(first_buf.right_offset, final_file_size);
         assert_eq!(first_buf.data, [initial_data.to_vec(), data].concat());
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -719,13 +2567,13 @@ mod tests {
         let data = old_index.to_be_bytes();
         let data_length = data.len();
         let new_data = new_index.to_be_bytes();
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
         let offset = HEADER_SIZE_IN_BYTES + 5;
         let initial_file_size = get_pool_file_size(&mut pool);
         write_to_file(file_name, offset, &data);
 
-        pool.update_index(offset, &mut new_data.to_vec())
+        pool.update_index(offset, new_data.as_ref())
             .expect("replace data");
 
         let final_file_size = get_pool_file_size(&mut pool);
@@ -737,7 +2585,7 @@ mod tests {
         assert_eq!(bytes_read, data_length);
         assert_eq!(data_in_file, new_data);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -748,11 +2596,11 @@ mod tests {
         let old_index: u64 = 890;
         let new_index: u64 = 6783;
         let initial_data = old_index.to_be_bytes();
-        let mut new_data = new_index.to_be_bytes();
+        let new_data = new_index.to_be_bytes();
         let new_data_length = new_data.len();
 
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let initial_offset = HEADER_SIZE_IN_BYTES + 4;
         let initial_file_size = get_pool_file_size(&mut pool);
@@ -760,7 +2608,7 @@ mod tests {
         write_to_file(file_name, initial_offset, &initial_data);
         append_index_buffers(&mut pool, &[(initial_offset, &initial_data[..])][..]);
 
-        pool.update_index(initial_offset, &mut new_data)
+        pool.update_index(initial_offset, &new_data)
             .expect("replaces data in buffer");
 
         let (data_in_file, bytes_read) = read_from_file(file_name, initial_offset, new_data_length);
@@ -779,7 +2627,7 @@ mod tests {
         assert_eq!(buf.right_offset, initial_offset + new_data_length as u64);
         assert_eq!(buf.data, new_data);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -790,10 +2638,10 @@ mod tests {
         let old_index: u64 = 890;
         let new_index: u64 = 6783;
         let initial_data = old_index.to_be_bytes();
-        let mut new_data = new_index.to_be_bytes();
+        let new_data = new_index.to_be_bytes();
 
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         append_index_buffers(
             &mut pool,
@@ -807,11 +2655,22 @@ mod tests {
         ];
 
         for address in addresses {
-            let response = pool.update_index(*address, &mut new_data);
-            assert!(response.is_err());
+            let err = pool
+                .update_index(*address, &new_data)
+                .expect_err("update out of bounds");
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "update index: span {}-{} is out of bounds for {}-{}",
+                    address,
+                    address + 8,
+                    HEADER_SIZE_IN_BYTES,
+                    pool.key_values_start_point
+                )
+            );
         }
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -821,10 +2680,10 @@ mod tests {
         let initial_data = &[76u8, 67, 56];
         let initial_data_length = initial_data.len() as u64;
 
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
-        let expected = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let expected =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let initial_offset = get_actual_file_size(file_name);
         write_to_file(file_name, initial_offset, initial_data);
@@ -835,112 +2694,629 @@ mod tests {
             &[(initial_offset, &initial_data[..]), (0, &header_array[..])][..],
         );
 
-        pool.clear_file().expect("file cleared");
-        assert_eq!(&pool, &expected);
+        pool.clear_file().expect("file cleared");
+        assert_eq!(&pool, &expected);
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn clear_file_with_max_keys_works() {
+        let file_name = "testdb.scdb";
+        // pre-clean up for right results
+        fs::remove_file(file_name).ok();
+
+        let mut pool = BufferPool::new(None, Path::new(file_name), Some(10_000), None, None)
+            .expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        insert_key_value_entry(&mut pool, &header, &kv);
+
+        pool.clear_file_with_max_keys(10).expect("file cleared");
+
+        let expected_header = DbFileHeader::new(Some(10), None, None);
+        let got_header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        assert_eq!(&got_header, &expected_header);
+        assert_eq!(pool.max_keys, Some(10));
+        assert_eq!(
+            pool.key_values_start_point,
+            expected_header.key_values_start_point
+        );
+        assert_eq!(pool.file_size, expected_header.key_values_start_point);
+        assert_eq!(
+            get_actual_file_size(file_name),
+            expected_header.key_values_start_point
+        );
+        assert!(pool.kv_buffers.is_empty());
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    fn get_logical_file_size_works() {
+        let mut header = DbFileHeader::new(Some(10), None, None);
+        let kv_start = header.key_values_start_point;
+        // an entry of 12 bytes whose value ends in zeros, followed by 20 bytes of zero padding
+        let mut data = header.as_bytes();
+        data.resize(kv_start as usize, 0);
+        data.extend_from_slice(&[0, 0, 0, 12, 1, 1, 1, 1, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0u8; 20]);
+        let file_len = data.len() as u64;
+        let mut file: Box<dyn StorageFile> = Box::new(Cursor::new(data));
+
+        // without a recorded preallocation, the trailing zeros are taken to be part of the last entry
+        let got = get_logical_file_size(&mut file, &header).expect("get logical file size");
+        assert_eq!(got, file_len);
+
+        header.is_preallocated = true;
+        let got = get_logical_file_size(&mut file, &header).expect("get logical file size");
+        assert_eq!(got, kv_start + 12);
+    }
+
+    #[test]
+    #[serial]
+    fn preallocate_works() {
+        let file_name = "testdb.scdb";
+        fs::remove_file(file_name).ok();
+        let kv1 = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let kv2 =
+            KeyValueEntry::new(&b"foo"[..], &b"baracuda"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        let kv_start = header.key_values_start_point;
+
+        pool.preallocate(1000).expect("preallocate");
+        assert_eq!(get_actual_file_size(file_name), kv_start + 1000);
+        assert_eq!(pool.file_size, kv_start);
+        let got = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        assert!(got.is_preallocated);
+
+        // a smaller preallocation is a no-op
+        pool.preallocate(10).expect("preallocate");
+        assert_eq!(get_actual_file_size(file_name), kv_start + 1000);
+
+        pool.append_kv_entry(header.get_index_offset(kv1.key), &mut kv1.as_bytes())
+            .expect("append kv1");
+        assert_eq!(get_kv_address(&mut pool, &header, &kv1), kv_start);
+        assert_eq!(pool.file_size, kv_start + kv1.size as u64);
+        assert_eq!(get_actual_file_size(file_name), kv_start + 1000);
+
+        // the end of the last entry is found when the file is reopened
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        assert_eq!(pool.file_size, kv_start + kv1.size as u64);
+        pool.append_kv_entry(header.get_index_offset(kv2.key), &mut kv2.as_bytes())
+            .expect("append kv2");
+        let kv2_address = get_kv_address(&mut pool, &header, &kv2);
+        assert_eq!(kv2_address, kv_start + kv1.size as u64);
+
+        for (kv, address) in [(&kv1, kv_start), (&kv2, kv2_address)] {
+            let got = pool.get_value(address, kv.key).expect("get value").unwrap();
+            assert_eq!(got, Value::new(kv, get_current_timestamp()));
+        }
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn compact_file_works() {
+        let file_name = "testdb.scdb";
+        let index_file_name = "testdb.iscdb";
+        // pre-clean up for right results
+        fs::remove_file(file_name).ok();
+
+        let never_expires =
+            KeyValueEntry::new(&b"never_expires"[..], &b"bar"[..], 0).expect("new key value entry");
+        let deleted =
+            KeyValueEntry::new(&b"deleted"[..], &b"bok"[..], 0).expect("new key value entry");
+        // 1666023836u64 is some past timestamp in October 2022
+        let expired = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64)
+            .expect("new key value entry");
+        let not_expired = KeyValueEntry::new(
+            &b"not_expired"[..],
+            &b"bar"[..],
+            get_current_timestamp() * 2,
+        )
+        .expect("new key value entry");
+        // Limit the max_keys to 10 otherwise the memory will be consumed when we try to get all data in file
+        let mut pool = BufferPool::new(None, Path::new(file_name), Some(10), Some(1), None)
+            .expect("new buffer pool");
+
+        append_kv_buffers(&mut pool, &[(0, &[76u8, 79][..])][..]);
+
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+
+        // insert key value pairs in pool
+        insert_key_value_entry(&mut pool, &header, &never_expires);
+        insert_key_value_entry(&mut pool, &header, &deleted);
+        insert_key_value_entry(&mut pool, &header, &expired);
+        insert_key_value_entry(&mut pool, &header, &not_expired);
+
+        // delete the key-value to be deleted
+        delete_key_value(&mut pool, &header, &deleted);
+
+        let initial_file_size = get_actual_file_size(file_name);
+        let mut search_index = InvertedIndex::new(Path::new(index_file_name), None, None, None)
+            .expect("create search index");
+
+        pool.compact_file(&mut Some(&mut search_index))
+            .expect("compact file");
+
+        let final_file_size = get_actual_file_size(file_name);
+        let (data_in_file, _) = read_from_file(file_name, 0, final_file_size as usize);
+        let pool_file_size = get_pool_file_size(&mut pool);
+
+        let buffer_len = pool.kv_buffers.len();
+
+        let expected_file_size_reduction = deleted.size as u64 + expired.size as u64;
+        let expired_kv_address = get_kv_address(&mut pool, &header, &expired);
+        let deleted_kv_address = get_kv_address(&mut pool, &header, &deleted);
+
+        assert_eq!(buffer_len, 0);
+        assert_eq!(pool_file_size, final_file_size);
+        assert_eq!(
+            initial_file_size - final_file_size,
+            expected_file_size_reduction
+        );
+        assert_eq!(expired_kv_address, 0);
+        assert_eq!(deleted_kv_address, 0);
+        // the live keys are counted as they are copied, rather than by walking the index afresh
+        assert_eq!(pool.live_keys, Some(2));
+
+        assert!(key_value_exists(&data_in_file, &header, &never_expires));
+        assert!(key_value_exists(&data_in_file, &header, &not_expired));
+        assert!(!key_value_exists(&data_in_file, &header, &deleted));
+        assert!(!key_value_exists(&data_in_file, &header, &expired));
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+        fs::remove_file(index_file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn compaction_in_steps_works() {
+        let file_name = "testdb.scdb";
+        let index_file_name = "testdb.iscdb";
+        // pre-clean up for right results
+        fs::remove_file(file_name).ok();
+        fs::remove_file(index_file_name).ok();
+
+        let never_expires =
+            KeyValueEntry::new(&b"never_expires"[..], &b"bar"[..], 0).expect("new key value entry");
+        let deleted =
+            KeyValueEntry::new(&b"deleted"[..], &b"bok"[..], 0).expect("new key value entry");
+        // 1666023836u64 is some past timestamp in October 2022
+        let expired = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64)
+            .expect("new key value entry");
+        let deleted_midway = KeyValueEntry::new(&b"deleted_midway"[..], &b"bar"[..], 0)
+            .expect("new key value entry");
+        let updated =
+            KeyValueEntry::new(&b"updated"[..], &b"old"[..], 0).expect("new key value entry");
+        let updated_midway =
+            KeyValueEntry::new(&b"updated"[..], &b"new"[..], 0).expect("new key value entry");
+        let set_midway =
+            KeyValueEntry::new(&b"set_midway"[..], &b"bar"[..], 0).expect("new key value entry");
+        // many keys so as to have many index blocks
+        let mut pool = BufferPool::new(None, Path::new(file_name), Some(4_000), Some(1), None)
+            .expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        assert!(header.number_of_index_blocks > 2);
+
+        insert_key_value_entry(&mut pool, &header, &never_expires);
+        insert_key_value_entry(&mut pool, &header, &deleted);
+        insert_key_value_entry(&mut pool, &header, &expired);
+        insert_key_value_entry(&mut pool, &header, &deleted_midway);
+        insert_key_value_entry(&mut pool, &header, &updated);
+        delete_key_value(&mut pool, &header, &deleted);
+
+        // a change to the layout of the file between the steps makes the compaction stale
+        let mut compaction = pool.start_compaction(true).expect("start compaction");
+        assert!(pool
+            .continue_compaction(&mut compaction, 1)
+            .expect("continue compaction"));
+        pool.reload_file().expect("reload file");
+        assert!(!pool
+            .continue_compaction(&mut compaction, 1)
+            .expect("continue stale compaction"));
+        compaction.abort().expect("abort compaction");
+
+        // reads between the steps change nothing
+        let mut compaction = pool.start_compaction(true).expect("restart compaction");
+        let mut steps = 0;
+        while !compaction.is_copied() {
+            let kv_address = get_kv_address(&mut pool, &header, &never_expires);
+            assert!(pool
+                .get_value(kv_address, never_expires.key)
+                .expect("get value")
+                .is_some());
+            assert!(pool
+                .continue_compaction(&mut compaction, 1)
+                .expect("continue compaction"));
+            steps += 1;
+        }
+        assert_eq!(steps, header.number_of_index_blocks);
+
+        // writes between the steps, to the blocks already copied, are caught up on when it is finished
+        insert_key_value_entry(&mut pool, &header, &set_midway);
+        insert_key_value_entry(&mut pool, &header, &updated_midway);
+        let kv_address = get_kv_address(&mut pool, &header, &deleted_midway);
+        pool.try_delete_kv_entry(kv_address, deleted_midway.key)
+            .expect("delete in place");
+        let initial_file_size = get_actual_file_size(file_name);
+
+        let mut search_index = InvertedIndex::new(Path::new(index_file_name), None, None, None)
+            .expect("create search index");
+        pool.finish_compaction(compaction, &mut Some(&mut search_index))
+            .expect("finish compaction");
+
+        let final_file_size = get_actual_file_size(file_name);
+        let (data_in_file, _) = read_from_file(file_name, 0, final_file_size as usize);
+        assert_eq!(get_pool_file_size(&mut pool), final_file_size);
+        // the copies of the entries updated midway are left dangling in the new file
+        assert_eq!(
+            initial_file_size - final_file_size,
+            deleted.size as u64 + expired.size as u64
+        );
+        assert_eq!(
+            pool.dangling_bytes,
+            Some(updated.size as u64 + deleted_midway.size as u64)
+        );
+        assert_eq!(pool.live_keys, Some(3));
+        assert!(key_value_exists(&data_in_file, &header, &never_expires));
+        assert!(key_value_exists(&data_in_file, &header, &set_midway));
+        assert!(!key_value_exists(&data_in_file, &header, &deleted));
+        assert!(!key_value_exists(&data_in_file, &header, &expired));
+        assert!(!key_value_exists(&data_in_file, &header, &deleted_midway));
+        let kv_address = get_kv_address(&mut pool, &header, &updated_midway);
+        assert_eq!(
+            pool.get_value(kv_address, updated_midway.key)
+                .expect("get updated value")
+                .map(|v| v.data),
+            Some(b"new".to_vec())
+        );
+        assert_eq!(
+            search_index
+                .search(&b"se"[..], 0, 0, SearchMode::Contains)
+                .expect("search se"),
+            vec![get_kv_address(&mut pool, &header, &set_midway)]
+        );
+        assert_eq!(
+            search_index
+                .search(&b"up"[..], 0, 0, SearchMode::Contains)
+                .expect("search up"),
+            vec![kv_address]
+        );
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+        fs::remove_file(index_file_name)
+            .unwrap_or_else(|_| panic!("delete file {}", &index_file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn rebuild_search_index_works() {
+        let file_name = "testdb.scdb";
+        let index_file_name = "testdb.iscdb";
+        // pre-clean up for right results
+        fs::remove_file(file_name).ok();
+        fs::remove_file(index_file_name).ok();
+
+        let never_expires =
+            KeyValueEntry::new(&b"never_expires"[..], &b"bar"[..], 0).expect("new key value entry");
+        let deleted =
+            KeyValueEntry::new(&b"deleted"[..], &b"bok"[..], 0).expect("new key value entry");
+        // 1666023836u64 is some past timestamp in October 2022
+        let expired = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64)
+            .expect("new key value entry");
+        let mut pool = BufferPool::new(None, Path::new(file_name), Some(10), Some(1), None)
+            .expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &never_expires);
+        insert_key_value_entry(&mut pool, &header, &deleted);
+        insert_key_value_entry(&mut pool, &header, &expired);
+        delete_key_value(&mut pool, &header, &deleted);
+        let never_expires_kv_address = get_kv_address(&mut pool, &header, &never_expires);
+
+        let mut search_index = InvertedIndex::new(Path::new(index_file_name), None, None, None)
+            .expect("create search index");
+        // a stale entry to be dropped by the rebuild
+        search_index
+            .add(&b"stale"[..], 4000, 0)
+            .expect("add stale key");
+        let initial_file_size = get_actual_file_size(file_name);
+
+        let count = pool
+            .rebuild_search_index(&header, &mut search_index)
+            .expect("rebuild search index");
+
+        assert_eq!(count, 1);
+
+        assert_eq!(get_actual_file_size(file_name), initial_file_size);
+        for (term, expected) in [
+            (&b"n"[..], vec![never_expires_kv_address]),
+            (b"d", vec![]),
+            (b"e", vec![]),
+            (b"s", vec![]),
+        ] {
+            let got = search_index
+                .search(term, 0, 0, SearchMode::Prefix)
+                .expect("search");
+            assert_eq!(got, expected);
+        }
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+        fs::remove_file(index_file_name)
+            .unwrap_or_else(|_| panic!("delete file {}", &index_file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn compact_file_discards_stale_compaction_file() {
+        let file_name = "testdb.scdb";
+        let compaction_file_name = "tmp__compact.testdb.scdb";
+        // pre-clean up for right results
+        fs::remove_file(file_name).ok();
+
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool = BufferPool::new(None, Path::new(file_name), Some(10), Some(1), None)
+            .expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &kv);
+
+        // a compaction that was interrupted leaves behind a bigger file full of junk
+        let stale_data = vec![255u8; 3 * pool.file_size as usize];
+        fs::write(compaction_file_name, &stale_data).expect("write stale compaction file");
+
+        pool.compact_file(&mut None).expect("compact file");
+
+        let final_file_size = get_actual_file_size(file_name);
+        assert_eq!(final_file_size, get_pool_file_size(&mut pool));
+        assert_eq!(
+            final_file_size,
+            header.key_values_start_point + kv.size as u64
+        );
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+        let got = pool
+            .get_value(kv_address, kv.key)
+            .expect("get value")
+            .unwrap();
+        assert_eq!(got, Value::new(&kv, get_current_timestamp()));
+        assert_eq!(get_kv_address(&mut pool, &header, &kv), kv_address);
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn new_recovers_from_interrupted_compaction() {
+        let file_name = "testdb.scdb";
+        let compaction_file_name = "tmp__compact.testdb.scdb";
+        // pre-clean up for right results
+        fs::remove_file(file_name).ok();
+
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &kv);
+        drop(pool);
+
+        // a stale compaction file is removed when the db file exists
+        fs::write(compaction_file_name, b"junk").expect("write stale compaction file");
+        let pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        assert!(!Path::new(compaction_file_name).exists());
+        drop(pool);
+
+        // a crash after the db file is removed but before the compaction file is renamed
+        fs::rename(file_name, compaction_file_name).expect("move db file");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        assert!(!Path::new(compaction_file_name).exists());
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+        let got = pool
+            .get_value(kv_address, kv.key)
+            .expect("get value")
+            .unwrap();
+        assert_eq!(got, Value::new(&kv, get_current_timestamp()));
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn verify_works() {
+        let file_name = "testdb.scdb";
+        // pre-clean up for right results
+        fs::remove_file(file_name).ok();
+
+        let entries = [
+            KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0).expect("new key value entry"),
+            KeyValueEntry::new(&b"fore"[..], &b"span"[..], 0).expect("new key value entry"),
+            KeyValueEntry::new(&b"bar"[..], &b"port"[..], 0).expect("new key value entry"),
+            KeyValueEntry::new(&b"pig"[..], &b"dan"[..], 0).expect("new key value entry"),
+        ];
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        for kv in &entries {
+            insert_key_value_entry(&mut pool, &header, kv);
+        }
+        assert_eq!(pool.verify(&header).expect("verify"), vec![]);
+
+        let file_size = pool.file_size;
+        let addresses: Vec<(u64, u64)> = entries
+            .iter()
+            .map(|kv| {
+                let index_address = header.get_index_offset(kv.key);
+                (index_address, get_kv_address(&mut pool, &header, kv))
+            })
+            .collect();
+
+        // an index entry pointing beyond the file
+        pool.update_index(addresses[0].0, &(file_size + 40).to_be_bytes())
+            .expect("corrupt index");
+        // an entry whose size spills beyond the file
+        pool.file
+            .seek(SeekFrom::Start(addresses[1].1))
+            .expect("seek to entry");
+        pool.file
+            .write_all(&(file_size as u32).to_be_bytes())
+            .expect("corrupt size");
+        // an index entry pointing to an entry of another key
+        pool.update_index(addresses[2].0, &addresses[3].1.to_be_bytes())
+            .expect("corrupt index");
+
+        let got = pool.verify(&header).expect("verify");
+        let got: Vec<(u64, u64)> = got
+            .iter()
+            .map(|c| (c.index_address, c.kv_address))
+            .collect();
+        let mut expected = vec![
+            (addresses[0].0, file_size + 40),
+            (addresses[1].0, addresses[1].1),
+            (addresses[2].0, addresses[3].1),
+        ];
+        let mut got = got;
+        expected.sort();
+        got.sort();
+        assert_eq!(got, expected);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
-    fn compact_file_works() {
+    fn get_value_works() {
         let file_name = "testdb.scdb";
-        let index_file_name = "testdb.iscdb";
-        // pre-clean up for right results
-        fs::remove_file(&file_name).ok();
-
-        let never_expires = KeyValueEntry::new(&b"never_expires"[..], &b"bar"[..], 0);
-        let deleted = KeyValueEntry::new(&b"deleted"[..], &b"bok"[..], 0);
-        // 1666023836u64 is some past timestamp in October 2022
-        let expired = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64);
-        let not_expired = KeyValueEntry::new(
-            &b"not_expired"[..],
-            &b"bar"[..],
-            get_current_timestamp() * 2,
-        );
-        // Limit the max_keys to 10 otherwise the memory will be consumed when we try to get all data in file
-        let mut pool = BufferPool::new(None, &Path::new(file_name), Some(10), Some(1), None)
-            .expect("new buffer pool");
-
-        append_kv_buffers(&mut pool, &[(0, &[76u8, 79][..])][..]);
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
-        // insert key value pairs in pool
-        insert_key_value_entry(&mut pool, &header, &never_expires);
-        insert_key_value_entry(&mut pool, &header, &deleted);
-        insert_key_value_entry(&mut pool, &header, &expired);
-        insert_key_value_entry(&mut pool, &header, &not_expired);
-
-        // delete the key-value to be deleted
-        delete_key_value(&mut pool, &header, &deleted);
-
-        let initial_file_size = get_actual_file_size(file_name);
-        let mut search_index = InvertedIndex::new(&Path::new(index_file_name), None, None, None)
-            .expect("create search index");
+        insert_key_value_entry(&mut pool, &header, &kv);
 
-        pool.compact_file(&mut Some(&mut search_index))
-            .expect("compact file");
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+        let got = pool
+            .get_value(kv_address, kv.key)
+            .expect("get value")
+            .unwrap();
+        let expected = Value::new(&kv, get_current_timestamp());
 
-        let final_file_size = get_actual_file_size(file_name);
-        let (data_in_file, _) = read_from_file(file_name, 0, final_file_size as usize);
-        let pool_file_size = get_pool_file_size(&mut pool);
+        assert_eq!(got, expected);
 
-        let buffer_len = pool.kv_buffers.len();
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
 
-        let expected_file_size_reduction = deleted.size as u64 + expired.size as u64;
-        let expired_kv_address = get_kv_address(&mut pool, &header, &expired);
-        let deleted_kv_address = get_kv_address(&mut pool, &header, &deleted);
+    #[test]
+    #[serial]
+    fn get_value_out_of_bounds() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
-        assert_eq!(buffer_len, 0);
-        assert_eq!(pool_file_size, final_file_size);
-        assert_eq!(
-            initial_file_size - final_file_size,
-            expected_file_size_reduction
-        );
-        assert_eq!(expired_kv_address, 0);
-        assert_eq!(deleted_kv_address, 0);
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &kv);
+        let file_size = get_actual_file_size(file_name);
 
-        assert!(key_value_exists(&data_in_file, &header, &never_expires));
-        assert!(key_value_exists(&data_in_file, &header, &not_expired));
-        assert!(!key_value_exists(&data_in_file, &header, &deleted));
-        assert!(!key_value_exists(&data_in_file, &header, &expired));
+        // past the end of the file, or in the index blocks
+        for kv_address in [file_size, file_size + 100, HEADER_SIZE_IN_BYTES] {
+            for got in [
+                pool.get_value(kv_address, kv.key),
+                pool.peek_value(kv_address, kv.key),
+            ] {
+                match got {
+                    Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+                    Ok(v) => panic!("expected an InvalidData error, got {:?}", v),
+                }
+            }
+        }
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
-        fs::remove_file(&index_file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
-    fn get_value_works() {
+    fn get_value_works_for_empty_values() {
         let file_name = "testdb.scdb";
-        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"kv"[..], &b""[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
+        // the entry is the last thing in the file, so its value ends right at the end of the buffer
         insert_key_value_entry(&mut pool, &header, &kv);
 
         let kv_address = get_kv_address(&mut pool, &header, &kv);
+        let expected = Value::new(&kv, get_current_timestamp());
+        assert_eq!(expected.data, Vec::<u8>::new());
+        // the first read is from the file, the second from the buffer
+        for _ in 0..2 {
+            let got = pool
+                .get_value(kv_address, kv.key)
+                .expect("get value")
+                .unwrap();
+            assert_eq!(got, expected);
+        }
         let got = pool
-            .get_value(kv_address, kv.key)
-            .expect("get value")
+            .peek_value(kv_address, kv.key)
+            .expect("peek value")
             .unwrap();
-        let expected = Value::from(&kv);
-
         assert_eq!(got, expected);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn peek_value_and_peek_index_leave_buffers_intact() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &kv);
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+        let idx_addr = header.get_index_offset(kv.key);
+        pool.kv_buffers.clear();
+        pool.index_buffers.clear();
+        pool.cache_counters.reset();
+
+        let got = pool
+            .peek_value(kv_address, kv.key)
+            .expect("peek value")
+            .unwrap();
+        assert_eq!(got, Value::new(&kv, get_current_timestamp()));
+        assert_eq!(
+            pool.peek_index(idx_addr).expect("peek index"),
+            kv_address.to_be_bytes().to_vec()
+        );
+        assert_eq!(
+            pool.peek_value(kv_address, &b"foo"[..])
+                .expect("peek value"),
+            None
+        );
+
+        assert!(pool.kv_buffers.is_empty());
+        assert!(pool.index_buffers.is_empty());
+        assert_eq!(pool.cache_counters.snapshot(), CacheStats::default());
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
     fn get_value_from_buffer() {
         let file_name = "testdb.scdb";
-        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -954,7 +3330,7 @@ mod tests {
             .unwrap();
 
         // delete underlying file first
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
 
         // the second get must be getting value from memory
         let got = pool
@@ -962,7 +3338,7 @@ mod tests {
             .expect("get value second time")
             .unwrap();
 
-        let expected = Value::from(&kv);
+        let expected = Value::new(&kv, get_current_timestamp());
 
         assert_eq!(got, expected);
     }
@@ -972,9 +3348,10 @@ mod tests {
     fn get_value_expired() {
         let file_name = "testdb.scdb";
         // 1666023836u64 is some past timestamp in October 2022 so this is expired
-        let kv = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64)
+            .expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -985,16 +3362,16 @@ mod tests {
 
         assert!(got.is_none());
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
     fn get_value_deleted() {
         let file_name = "testdb.scdb";
-        let kv = KeyValueEntry::new(&b"deleted"[..], &b"bar"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"deleted"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1007,7 +3384,42 @@ mod tests {
         let got = pool.get_value(kv_address, kv.key).expect("get value");
         assert!(got.is_none());
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn get_value_after_delete_returns_none() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"deleted"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &kv);
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+        pool.get_value(kv_address, kv.key)
+            .expect("get value")
+            .expect("value exists");
+
+        pool.try_delete_kv_entry(kv_address, kv.key)
+            .expect("delete kv entry");
+
+        // from the buffers
+        assert_eq!(pool.get_value(kv_address, kv.key).expect("get value"), None);
+        assert_eq!(
+            pool.peek_value(kv_address, kv.key).expect("peek value"),
+            None
+        );
+
+        // from the file
+        pool.kv_buffers.clear();
+        assert_eq!(
+            pool.peek_value(kv_address, kv.key).expect("peek value"),
+            None
+        );
+        assert_eq!(pool.get_value(kv_address, kv.key).expect("get value"), None);
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -1022,15 +3434,15 @@ mod tests {
             (b"ninety-nine".to_vec(), b"millenium".to_vec()),
         ];
 
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
         let mut addresses: Vec<u64> = vec![];
 
         for (k, v) in &test_data {
-            let kv = KeyValueEntry::new(k, v, 0);
+            let kv = KeyValueEntry::new(k, v, 0).expect("new key value entry");
             insert_key_value_entry(&mut pool, &header, &kv);
             let kv_address = get_kv_address(&mut pool, &header, &kv);
             addresses.push(kv_address);
@@ -1042,7 +3454,7 @@ mod tests {
 
         assert_eq!(got, test_data);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -1060,8 +3472,8 @@ mod tests {
             (b"holla".to_vec(), b"pension".to_vec()),
         ];
 
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1069,7 +3481,7 @@ mod tests {
 
         // Add non-expired
         for (k, v) in &non_expired {
-            let kv = KeyValueEntry::new(k, v, 0);
+            let kv = KeyValueEntry::new(k, v, 0).expect("new key value entry");
             insert_key_value_entry(&mut pool, &header, &kv);
             let kv_address = get_kv_address(&mut pool, &header, &kv);
             addresses.push(kv_address);
@@ -1078,7 +3490,7 @@ mod tests {
         // Add expired
         for (k, v) in &expired {
             // 1666023836u64 is some past timestamp in October 2022 so this is expired
-            let kv = KeyValueEntry::new(k, v, 1666023836u64);
+            let kv = KeyValueEntry::new(k, v, 1666023836u64).expect("new key value entry");
             insert_key_value_entry(&mut pool, &header, &kv);
             let kv_address = get_kv_address(&mut pool, &header, &kv);
             addresses.push(kv_address);
@@ -1090,7 +3502,178 @@ mod tests {
 
         assert_eq!(got, non_expired);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn read_key_and_read_key_value_work() {
+        let file_name = "testdb.scdb";
+        let live = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let empty_value =
+            KeyValueEntry::new(&b"empty"[..], &b""[..], 0).expect("new key value entry");
+        // 1666023836u64 is some past timestamp in October 2022 so this is expired
+        let expired = KeyValueEntry::new(&b"hey"[..], &b"man"[..], 1666023836u64)
+            .expect("new key value entry");
+        let deleted =
+            KeyValueEntry::new(&b"deleted"[..], &b"pension"[..], 0).expect("new key value entry");
+
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+
+        let mut addresses: Vec<u64> = vec![];
+        for kv in [&live, &empty_value, &expired, &deleted] {
+            insert_key_value_entry(&mut pool, &header, kv);
+            addresses.push(get_kv_address(&mut pool, &header, kv));
+        }
+        pool.try_delete_kv_entry(addresses[3], deleted.key)
+            .expect("delete kv entry");
+
+        let expected_keys = [Some(b"kv".to_vec()), Some(b"empty".to_vec()), None, None];
+        let expected_key_values = [
+            Some((b"kv".to_vec(), b"bar".to_vec())),
+            Some((b"empty".to_vec(), vec![])),
+            None,
+            None,
+        ];
+        for (i, addr) in addresses.iter().enumerate() {
+            let key = pool.read_key(*addr).expect("read key");
+            assert_eq!(key, expected_keys[i]);
+            let key_value = pool.read_key_value(*addr).expect("read key value");
+            assert_eq!(key_value, expected_key_values[i]);
+        }
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn get_dangling_bytes_and_mark_dangling_work() {
+        let file_name = "testdb.scdb";
+        fs::remove_file(file_name).ok();
+        let live = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        // 1666023836u64 is some past timestamp in October 2022 so this is expired
+        let expired = KeyValueEntry::new(&b"hey"[..], &b"man"[..], 1666023836u64)
+            .expect("new key value entry");
+        let to_delete =
+            KeyValueEntry::new(&b"deleted"[..], &b"pension"[..], 0).expect("new key value entry");
+        let updated =
+            KeyValueEntry::new(&b"kv"[..], &b"barer"[..], 0).expect("new key value entry");
+
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        for kv in [&live, &expired, &to_delete] {
+            insert_key_value_entry(&mut pool, &header, kv);
+        }
+        let to_delete_address = get_kv_address(&mut pool, &header, &to_delete);
+        pool.try_delete_kv_entry(to_delete_address, to_delete.key)
+            .expect("delete kv entry");
+
+        // the file is walked to find the dangling bytes
+        let got = pool
+            .get_dangling_bytes(&header)
+            .expect("get dangling bytes");
+        assert_eq!(got, (expired.size + to_delete.size) as u64);
+
+        // overwriting a live entry makes it dangle
+        let live_address = get_kv_address(&mut pool, &header, &live);
+        pool.mark_dangling(live_address).expect("mark dangling");
+        insert_key_value_entry(&mut pool, &header, &updated);
+        let got = pool
+            .get_dangling_bytes(&header)
+            .expect("get dangling bytes");
+        assert_eq!(got, (expired.size + to_delete.size + live.size) as u64);
+
+        // entries already dangling are not counted twice
+        pool.mark_dangling(to_delete_address)
+            .expect("mark dangling");
+        let got = pool
+            .get_dangling_bytes(&header)
+            .expect("get dangling bytes");
+        assert_eq!(got, (expired.size + to_delete.size + live.size) as u64);
+
+        pool.clear_file().expect("clear file");
+        let got = pool
+            .get_dangling_bytes(&header)
+            .expect("get dangling bytes");
+        assert_eq!(got, 0);
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn get_live_keys_works() {
+        let file_name = "testdb.scdb";
+        fs::remove_file(file_name).ok();
+        let live = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        // 1666023836u64 is some past timestamp in October 2022 so this is expired
+        let expired = KeyValueEntry::new(&b"hey"[..], &b"man"[..], 1666023836u64)
+            .expect("new key value entry");
+        let to_delete =
+            KeyValueEntry::new(&b"deleted"[..], &b"pension"[..], 0).expect("new key value entry");
+
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        for kv in [&live, &expired, &to_delete] {
+            insert_key_value_entry(&mut pool, &header, kv);
+        }
+
+        // the file is walked to find the live keys
+        assert_eq!(pool.get_live_keys(&header).expect("get live keys"), 2);
+
+        // deleting a live entry removes it from the count, only once
+        let to_delete_address = get_kv_address(&mut pool, &header, &to_delete);
+        for _ in 0..2 {
+            pool.mark_dangling(to_delete_address)
+                .expect("mark dangling");
+            pool.try_delete_kv_entry(to_delete_address, to_delete.key)
+                .expect("delete kv entry");
+        }
+        assert_eq!(pool.get_live_keys(&header).expect("get live keys"), 1);
+
+        // appending adds to the count
+        let new_kv = KeyValueEntry::new(&b"new"[..], &b"foo"[..], 0).expect("new key value entry");
+        pool.append_kv_entry(header.get_index_offset(new_kv.key), &mut new_kv.as_bytes())
+            .expect("append kv entry");
+        assert_eq!(pool.get_live_keys(&header).expect("get live keys"), 2);
+
+        pool.clear_file().expect("clear file");
+        assert_eq!(pool.get_live_keys(&header).expect("get live keys"), 0);
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn read_index_block_works() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+
+        insert_key_value_entry(&mut pool, &header, &kv);
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+
+        let idx_addr = header.get_index_offset(kv.key);
+        let block_size = header.net_block_size;
+        let block = (idx_addr - HEADER_SIZE_IN_BYTES) / block_size;
+        let offset_in_block = ((idx_addr - HEADER_SIZE_IN_BYTES) % block_size) as usize;
+
+        let got = pool
+            .read_index_block(block, &header)
+            .expect("read index block");
+        assert_eq!(got.len() as u64, block_size);
+        assert_eq!(
+            &got[offset_in_block..offset_in_block + 8],
+            &kv_address.to_be_bytes()[..]
+        );
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -1108,27 +3691,27 @@ mod tests {
             (b"ninety-nine".to_vec(), b"millenium".to_vec()),
         ];
 
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
         let mut addresses: Vec<u64> = vec![];
 
         for (k, v) in &non_deleted {
-            let kv = KeyValueEntry::new(k, v, 0);
+            let kv = KeyValueEntry::new(k, v, 0).expect("new key value entry");
             insert_key_value_entry(&mut pool, &header, &kv);
             let kv_address = get_kv_address(&mut pool, &header, &kv);
             addresses.push(kv_address);
         }
 
         for (k, v) in &deleted {
-            let kv = KeyValueEntry::new(k, v, 0);
+            let kv = KeyValueEntry::new(k, v, 0).expect("new key value entry");
             insert_key_value_entry(&mut pool, &header, &kv);
             let kv_address = get_kv_address(&mut pool, &header, &kv);
             addresses.push(kv_address);
             pool.try_delete_kv_entry(kv_address, k)
-                .expect(&format!("try delete key: {:?} of addr {}", k, kv_address));
+                .unwrap_or_else(|_| panic!("try delete key: {:?} of addr {}", k, kv_address));
         }
 
         let got = pool
@@ -1137,17 +3720,65 @@ mod tests {
 
         assert_eq!(got, non_deleted);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn get_key_values_at_works() {
+        let file_name = "testdb.scdb";
+        let test_data: Vec<(Vec<u8>, Vec<u8>)> = vec![
+            (b"kv".to_vec(), b"bar".to_vec()),
+            (b"hey".to_vec(), b"man".to_vec()),
+            (b"holla".to_vec(), b"pension".to_vec()),
+        ];
+
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+
+        let mut addresses: Vec<u64> = vec![];
+
+        for (k, v) in &test_data {
+            let kv = KeyValueEntry::new(k, v, 0).expect("new key value entry");
+            insert_key_value_entry(&mut pool, &header, &kv);
+            let kv_address = get_kv_address(&mut pool, &header, &kv);
+            addresses.push(kv_address);
+        }
+
+        let last_address = addresses[addresses.len() - 1];
+        addresses.extend([
+            0,
+            header.key_values_start_point - 1,
+            last_address + 1,
+            pool.file_size,
+            u64::MAX,
+        ]);
+
+        let got = pool
+            .get_key_values_at(&addresses)
+            .expect("get key values at");
+
+        let expected: Vec<Option<(Vec<u8>, Vec<u8>)>> = test_data
+            .into_iter()
+            .map(Some)
+            .chain([None, None, None, None, None])
+            .collect();
+        assert_eq!(got, expected);
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
     fn addr_belongs_to_key_works() {
         let file_name = "testdb.scdb";
-        let kv1 = KeyValueEntry::new(&b"never"[..], &b"bar"[..], 0);
-        let kv2 = KeyValueEntry::new(&b"foo"[..], &b"baracuda"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv1 = KeyValueEntry::new(&b"never"[..], &b"bar"[..], 0).expect("new key value entry");
+        let kv2 =
+            KeyValueEntry::new(&b"foo"[..], &b"baracuda"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1169,7 +3800,7 @@ mod tests {
             .addr_belongs_to_key(&kv2_index_address, kv1.key)
             .expect("addr_belongs_to_key kv2"));
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
@@ -1177,9 +3808,10 @@ mod tests {
     fn addr_belongs_to_key_expired_returns_true() {
         let file_name = "testdb.scdb";
         // 1666023836u64 is some past timestamp in October 2022 so this is expired
-        let kv = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64)
+            .expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1190,16 +3822,16 @@ mod tests {
             .addr_belongs_to_key(&kv_index_address, kv.key)
             .expect("addr_belongs_to_key kv"));
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
     fn addr_belongs_to_key_works_out_of_bounds() {
         let file_name = "testdb.scdb";
-        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1210,18 +3842,23 @@ mod tests {
         assert!(!pool
             .addr_belongs_to_key(&file_size, kv.key)
             .expect("addr_belongs_to_key kv"));
+        // an address in the index blocks
+        assert!(!pool
+            .addr_belongs_to_key(&HEADER_SIZE_IN_BYTES.to_be_bytes(), kv.key)
+            .expect("addr_belongs_to_key kv"));
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
     fn try_delete_kv_entry_works() {
         let file_name = "testdb.scdb";
-        let kv1 = KeyValueEntry::new(&b"never"[..], &b"bar"[..], 0);
-        let kv2 = KeyValueEntry::new(&b"foo"[..], &b"baracuda"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv1 = KeyValueEntry::new(&b"never"[..], &b"bar"[..], 0).expect("new key value entry");
+        let kv2 =
+            KeyValueEntry::new(&b"foo"[..], &b"baracuda"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1231,11 +3868,11 @@ mod tests {
         let kv1_index_address = get_kv_address(&mut pool, &header, &kv1);
 
         let resp = pool
-            .try_delete_kv_entry(kv1_index_address, &kv2.key)
+            .try_delete_kv_entry(kv1_index_address, kv2.key)
             .expect("try delete kv1 with kv2 key");
         assert!(resp.is_none());
         assert_eq!(
-            pool.get_value(kv1_index_address, &kv1.key).unwrap(),
+            pool.get_value(kv1_index_address, kv1.key).unwrap(),
             Some(Value {
                 data: vec![98u8, 97, 114],
                 is_stale: false,
@@ -1243,27 +3880,55 @@ mod tests {
         );
 
         let resp = pool
-            .try_delete_kv_entry(kv1_index_address, &kv1.key)
+            .try_delete_kv_entry(kv1_index_address, kv1.key)
             .expect("try delete kv1 with kv1 key");
         assert!(resp.is_some());
-        assert_eq!(
-            pool.get_value(kv1_index_address, &kv1.key).unwrap(),
-            Some(Value {
-                data: vec![98u8, 97, 114],
-                is_stale: true,
-            })
-        );
+        assert_eq!(pool.get_value(kv1_index_address, kv1.key).unwrap(), None);
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn cache_counters_record_hits_and_misses() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &kv);
+        let idx_addr = header.get_index_offset(kv.key);
+
+        // first reads are misses, the subsequent ones hits
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+        pool.read_index(idx_addr).expect("read index");
+        pool.read_index(idx_addr).expect("read index");
+        pool.get_value(kv_address, kv.key).expect("get value");
+        pool.get_value(kv_address, kv.key).expect("get value");
+        pool.addr_belongs_to_key(&kv_address.to_be_bytes(), kv.key)
+            .expect("addr belongs to key");
+
+        let expected = CacheStats {
+            kv_hits: 2,
+            kv_misses: 1,
+            index_hits: 1,
+            index_misses: 1,
+        };
+        assert_eq!(pool.cache_counters.snapshot(), expected);
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        pool.clear_file().expect("clear file");
+        assert_eq!(pool.cache_counters.snapshot(), CacheStats::default());
+
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     #[test]
     #[serial]
     fn read_index_works() {
         let file_name = "testdb.scdb";
-        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1277,16 +3942,50 @@ mod tests {
             kv_address.to_be_bytes()
         );
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+    }
+
+    #[test]
+    #[serial]
+    fn read_index_loads_whole_buffer_from_file() {
+        let file_name = "testdb.scdb";
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
+        let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
+        insert_key_value_entry(&mut pool, &header, &kv);
+        let index_address = header.get_index_offset(kv.key);
+        let kv_address = get_kv_address(&mut pool, &header, &kv);
+        pool.index_buffers.clear();
+
+        // the buffer is read from file into a zero-filled vector of the buffer size
+        assert_eq!(
+            pool.read_index(index_address).expect("read index")[..],
+            kv_address.to_be_bytes()
+        );
+        let buf = pool.index_buffers.get(&index_address).expect("get buffer");
+        assert_eq!(buf.data.len(), pool.buffer_size);
+
+        // the second read is got from memory
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
+        assert_eq!(
+            buf.read_at(index_address, INDEX_ENTRY_SIZE_IN_BYTES as usize)
+                .expect("read at")[..],
+            kv_address.to_be_bytes()
+        );
+        assert_eq!(
+            pool.read_index(index_address).expect("read index")[..],
+            kv_address.to_be_bytes()
+        );
     }
 
     #[test]
     #[serial]
     fn read_at_works_out_of_bounds() {
         let file_name = "testdb.scdb";
-        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0);
-        let mut pool = BufferPool::new(None, &Path::new(file_name), None, None, None)
-            .expect("new buffer pool");
+        let kv = KeyValueEntry::new(&b"kv"[..], &b"bar"[..], 0).expect("new key value entry");
+        let mut pool =
+            BufferPool::new(None, Path::new(file_name), None, None, None).expect("new buffer pool");
 
         let header = DbFileHeader::from_file(&mut pool.file).expect("get header");
 
@@ -1302,7 +4001,7 @@ mod tests {
             assert!(pool.read_index(addr).is_err());
         }
 
-        fs::remove_file(&file_name).expect(&format!("delete file {}", &file_name));
+        fs::remove_file(file_name).unwrap_or_else(|_| panic!("delete file {}", &file_name));
     }
 
     /// Returns the actual file size of the file at the given path
@@ -1310,14 +4009,13 @@ mod tests {
         let mut file = OpenOptions::new()
             .read(true)
             .open(file_path)
-            .expect(&format!("open file {}", file_path));
+            .unwrap_or_else(|_| panic!("open file {}", file_path));
         file.seek(SeekFrom::End(0)).expect("get file size")
     }
 
     /// Extracts the pool's file_size attribute
     fn get_pool_file_size(pool: &mut BufferPool) -> u64 {
-        let initial_file_size = pool.file_size;
-        initial_file_size
+        pool.file_size
     }
 
     /// Manually increments the pool's file_size attribute
@@ -1331,9 +4029,9 @@ mod tests {
         let mut file = OpenOptions::new()
             .read(true)
             .open(file_name)
-            .expect(&format!("open the file: {}", file_name));
+            .unwrap_or_else(|_| panic!("open the file: {}", file_name));
         file.seek(SeekFrom::Start(addr))
-            .expect(&format!("seek to addr {}", addr));
+            .unwrap_or_else(|_| panic!("seek to addr {}", addr));
 
         let mut data_in_file: Vec<u8> = vec![0; buf_size];
         let bytes_read = file.read(&mut data_in_file).expect("read file");
@@ -1346,10 +4044,10 @@ mod tests {
         let mut file = OpenOptions::new()
             .write(true)
             .open(file_path)
-            .expect(&format!("open the file: {}", file_path));
+            .unwrap_or_else(|_| panic!("open the file: {}", file_path));
 
         file.seek(SeekFrom::Start(addr))
-            .expect(&format!("seek to {}", addr));
+            .unwrap_or_else(|_| panic!("seek to {}", addr));
 
         file.write_all(data).expect("write all data to file");
     }
@@ -1386,23 +4084,20 @@ mod tests {
         let idx_addr = header.get_index_offset(kv.key);
         let kv_addr = pool
             .append(&mut kv.as_bytes())
-            .expect(&format!("inserts key value {:?}", &kv));
+            .unwrap_or_else(|_| panic!("inserts key value {:?}", &kv));
 
         pool.update_index(idx_addr, &kv_addr.to_be_bytes())
-            .expect(&format!("updates index of {:?}", &kv));
+            .unwrap_or_else(|_| panic!("updates index of {:?}", &kv));
     }
 
     /// Checks whether a given key value entry exists in the data array got from the file
-    fn key_value_exists(data: &Vec<u8>, header: &DbFileHeader, kv: &KeyValueEntry<'_>) -> bool {
+    fn key_value_exists(data: &[u8], header: &DbFileHeader, kv: &KeyValueEntry<'_>) -> bool {
         let idx_item_size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
         let idx_addr = header.get_index_offset(kv.key) as usize;
         let kv_addr = data[idx_addr..idx_addr + idx_item_size].to_vec();
         if kv_addr != vec![0u8; idx_item_size] {
             let kv_addr = u64::from_be_bytes(slice_to_array(&kv_addr[..]).expect("slice to array"));
-            match KeyValueEntry::from_data_array(data, kv_addr as usize) {
-                Ok(_) => true,
-                Err(_) => false,
-            }
+            KeyValueEntry::from_data_array(data, kv_addr as usize).is_ok()
         } else {
             false
         }
@@ -1421,7 +4116,7 @@ mod tests {
             .seek(SeekFrom::Start(index_address))
             .expect("seek to index");
         pool.file
-            .read(&mut kv_address)
+            .read_exact(&mut kv_address)
             .expect("reads value at index address");
 
         kv_address
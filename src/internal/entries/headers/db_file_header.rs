@@ -1,11 +1,19 @@
 use crate::internal;
 use crate::internal::entries::headers::shared::{
-    DerivedHeaderProps, Header, DEFAULT_DB_MAX_KEYS, DEFAULT_DB_REDUNDANT_BLOCKS,
-    HEADER_SIZE_IN_BYTES,
+    extract_title, DerivedHeaderProps, Header, DEFAULT_DB_MAX_KEYS, DEFAULT_DB_REDUNDANT_BLOCKS,
+    HEADER_SIZE_IN_BYTES, INDEX_ENTRY_SIZE_IN_BYTES,
 };
-use crate::internal::utils;
+use crate::internal::{utils, HeaderHasher};
+use crate::{KeyHasher, Probing, ScdbError};
 use std::fmt::{Display, Formatter};
 use std::io;
+use std::sync::Arc;
+
+/// The title at the start of every db file, identifying it and the version of its format
+pub(crate) const DB_FILE_TITLE: &str = "Scdb versn 0.002";
+/// The title of db files of the format from before key-value entries could have versions.
+/// They are still read, as their entries are read just the same in the current format
+pub(crate) const LEGACY_DB_FILE_TITLE: &str = "Scdb versn 0.001";
 
 #[derive(Debug, PartialEq, Clone, Eq, Ord, PartialOrd)]
 pub(crate) struct DbFileHeader {
@@ -17,6 +25,11 @@ pub(crate) struct DbFileHeader {
     pub(crate) number_of_index_blocks: u64,
     pub(crate) key_values_start_point: u64,
     pub(crate) net_block_size: u64,
+    pub(crate) hasher_id: u32,
+    pub(crate) hasher: HeaderHasher,
+    pub(crate) probing: Probing,
+    /// Whether space was ever preallocated at the end of the file, after its last key-value entry
+    pub(crate) is_preallocated: bool,
 }
 
 impl DbFileHeader {
@@ -31,7 +44,7 @@ impl DbFileHeader {
         let block_size = block_size.unwrap_or_else(utils::get_vm_page_size);
         let derived_props = DerivedHeaderProps::new(block_size, max_keys, redundant_blocks);
         Self {
-            title: "Scdb versn 0.001".to_string(),
+            title: DB_FILE_TITLE.to_string(),
             block_size,
             max_keys,
             redundant_blocks,
@@ -39,7 +52,58 @@ impl DbFileHeader {
             number_of_index_blocks: derived_props.number_of_index_blocks,
             key_values_start_point: derived_props.values_start_point,
             net_block_size: derived_props.net_block_size,
+            hasher_id: 0,
+            hasher: HeaderHasher::default(),
+            probing: Probing::None,
+            is_preallocated: false,
+        }
+    }
+
+    /// Sets the probing used to look for another slot for a key in an index block when
+    /// the slot the key hashes to is taken by another key
+    pub(crate) fn with_probing(mut self, probing: Probing) -> Self {
+        self.probing = probing;
+        self
+    }
+
+    /// Returns the offsets of all the slots in the index that the given key can be placed in,
+    /// in the order in which they are to be checked: the slots probed in the first index block,
+    /// then those in the second index block, and so on
+    pub(crate) fn get_index_offsets(&self, key: &[u8]) -> impl Iterator<Item = u64> + '_ {
+        let hash = self.hash_key(key);
+        let items_per_index_block = self.items_per_index_block;
+        let probes = self.probing.get_probes(items_per_index_block);
+
+        (0..self.number_of_index_blocks).flat_map(move |block| {
+            let block_offset = HEADER_SIZE_IN_BYTES + block * self.net_block_size;
+            (0..=probes).map(move |i| {
+                let step = self.probing.get_step(i, items_per_index_block);
+                let item = (hash + step) % items_per_index_block;
+                block_offset + item * INDEX_ENTRY_SIZE_IN_BYTES
+            })
+        })
+    }
+
+    /// Sets the hasher used to place keys in the index, recording its id in the header
+    pub(crate) fn with_hasher(mut self, hasher: Arc<dyn KeyHasher>) -> Self {
+        self.hasher_id = hasher.id();
+        self.hasher = HeaderHasher(hasher);
+        self
+    }
+
+    /// Attaches the hasher to a header read from file, failing if its id is not the one recorded
+    /// in the header as the keys in the file would otherwise not be found
+    pub(crate) fn attach_hasher(&mut self, hasher: Arc<dyn KeyHasher>) -> io::Result<()> {
+        if hasher.id() != self.hasher_id {
+            return Err(io::Error::from(ScdbError::InvalidInput(format!(
+                "hasher mismatch: the db file was created with hasher {} but hasher {} was given",
+                self.hasher_id,
+                hasher.id()
+            ))));
         }
+
+        self.hasher = HeaderHasher(hasher);
+        Ok(())
     }
 }
 
@@ -59,6 +123,11 @@ impl Header for DbFileHeader {
         self.net_block_size
     }
 
+    #[inline(always)]
+    fn hash_key(&self, key: &[u8]) -> u64 {
+        self.hasher.get_hash(key, self.items_per_index_block)
+    }
+
     fn as_bytes(&self) -> Vec<u8> {
         self.title
             .as_bytes()
@@ -66,7 +135,10 @@ impl Header for DbFileHeader {
             .chain(&self.block_size.to_be_bytes())
             .chain(&self.max_keys.to_be_bytes())
             .chain(&self.redundant_blocks.to_be_bytes())
-            .chain(&[0u8; 70])
+            .chain(&self.hasher_id.to_be_bytes())
+            .chain(&self.probing.as_bytes())
+            .chain(&[self.is_preallocated as u8])
+            .chain(&[0u8; 62])
             .map(|v| v.to_owned())
             .collect()
     }
@@ -82,11 +154,17 @@ impl Header for DbFileHeader {
             ));
         }
 
-        let title = String::from_utf8(data[0..16].to_owned())
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let title = if data[..16] == *LEGACY_DB_FILE_TITLE.as_bytes() {
+            LEGACY_DB_FILE_TITLE.to_string()
+        } else {
+            extract_title(data, DB_FILE_TITLE)?
+        };
         let block_size = u32::from_be_bytes(internal::slice_to_array::<4>(&data[16..20])?);
         let max_keys = u64::from_be_bytes(internal::slice_to_array::<8>(&data[20..28])?);
         let redundant_blocks = u16::from_be_bytes(internal::slice_to_array::<2>(&data[28..30])?);
+        let hasher_id = u32::from_be_bytes(internal::slice_to_array::<4>(&data[30..34])?);
+        let probing = Probing::from_data_array(&internal::slice_to_array::<3>(&data[34..37])?)?;
+        let is_preallocated = data[37] != 0;
         let derived_props = DerivedHeaderProps::new(block_size, max_keys, redundant_blocks);
 
         let header = Self {
@@ -98,6 +176,11 @@ impl Header for DbFileHeader {
             number_of_index_blocks: derived_props.number_of_index_blocks,
             key_values_start_point: derived_props.values_start_point,
             net_block_size: derived_props.net_block_size,
+            hasher_id,
+            // the actual hasher is to be attached by whoever knows it, via `attach_hasher`
+            hasher: HeaderHasher::default(),
+            probing,
+            is_preallocated,
         };
 
         Ok(header)
@@ -106,7 +189,7 @@ impl Header for DbFileHeader {
 
 impl Display for DbFileHeader {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "DbFileHeader {{ title: {}, block_size: {}, max_keys: {}, redundant_blocks: {}, items_per_index_block: {}, number_of_index_blocks: {}, key_values_start_point: {}, net_block_size: {}}}",
+        write!(f, "DbFileHeader {{ title: {}, block_size: {}, max_keys: {}, redundant_blocks: {}, items_per_index_block: {}, number_of_index_blocks: {}, key_values_start_point: {}, net_block_size: {}, hasher_id: {}, probing: {:?}, is_preallocated: {}}}",
                self.title,
                self.block_size,
                self.max_keys,
@@ -114,7 +197,10 @@ impl Display for DbFileHeader {
                self.items_per_index_block,
                self.number_of_index_blocks,
                self.key_values_start_point,
-               self.net_block_size)
+               self.net_block_size,
+               self.hasher_id,
+               self.probing,
+               self.is_preallocated)
     }
 }
 
@@ -161,9 +247,9 @@ mod tests {
     #[serial]
     fn db_file_header_as_bytes_works() {
         let block_size_bytes = get_vm_page_size().to_be_bytes().to_vec();
-        // title: Scdb versn 0.001
+        // title: Scdb versn 0.002
         let title_bytes = vec![
-            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 49,
+            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 50,
         ];
         let reserve_bytes = vec![0u8; 70];
         type Record = (Option<u64>, Option<u16>, Vec<u8>);
@@ -171,7 +257,7 @@ mod tests {
             (
                 None,
                 None,
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -183,7 +269,7 @@ mod tests {
             (
                 Some(24_000_000),
                 None,
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -195,7 +281,7 @@ mod tests {
             (
                 None,
                 Some(9),
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -207,7 +293,7 @@ mod tests {
             (
                 Some(24_000_000),
                 Some(5),
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000u64 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -229,15 +315,15 @@ mod tests {
     fn db_file_header_from_data_array() {
         let block_size = get_vm_page_size();
         let block_size_bytes = block_size.to_be_bytes().to_vec();
-        // title: Scdb versn 0.001
+        // title: Scdb versn 0.002
         let title_bytes = vec![
-            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 49,
+            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 50,
         ];
         let reserve_bytes = vec![0u8; 70];
         type Record = (Vec<u8>, DbFileHeader);
         let test_table: Vec<Record> = vec![
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -248,7 +334,7 @@ mod tests {
                 generate_header(1_000_000, 1, block_size),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -259,7 +345,7 @@ mod tests {
                 generate_header(24_000_000, 1, block_size),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -270,7 +356,7 @@ mod tests {
                 generate_header(1_000_000, 9, block_size),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000u64 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -293,13 +379,13 @@ mod tests {
     fn db_file_header_from_data_array_out_of_bounds() {
         let block_size = get_vm_page_size();
         let block_size_bytes = block_size.to_be_bytes().to_vec();
-        // title: Scdb versn 0.001
+        // title: Scdb versn 0.002
         let title_bytes = vec![
-            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 49,
+            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 50,
         ];
         let reserve_bytes = vec![0u8; 70];
         let test_table: Vec<Vec<u8>> = vec![
-            vec![
+            [
                 title_bytes[2..].to_vec(), // title is truncated
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -307,7 +393,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes[..3].to_vec(), // block_size is truncated
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -315,7 +401,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 15, 66, 64], // max_keys is truncated
@@ -323,7 +409,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -331,7 +417,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -347,21 +433,70 @@ mod tests {
         }
     }
 
+    #[test]
+    #[serial]
+    fn db_file_header_from_data_array_with_wrong_title() {
+        let mut data_array = generate_header(1_000, 1, get_vm_page_size()).as_bytes();
+        data_array[..16].copy_from_slice(b"ScdbIndex v0.001");
+
+        let got = DbFileHeader::from_data_array(&data_array).expect_err("wrong title");
+        assert_eq!(got.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            got.to_string(),
+            "invalid header title: expected \"Scdb versn 0.002\", found \"ScdbIndex v0.001\""
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn db_file_header_from_data_array_with_legacy_title() {
+        let mut data_array = generate_header(1_000, 1, get_vm_page_size()).as_bytes();
+        data_array[..16].copy_from_slice(LEGACY_DB_FILE_TITLE.as_bytes());
+
+        let got = DbFileHeader::from_data_array(&data_array).expect("legacy title");
+        assert_eq!(got.title, LEGACY_DB_FILE_TITLE);
+        assert_eq!(got.max_keys, 1_000);
+        assert_eq!(got.as_bytes(), data_array);
+    }
+
+    #[test]
+    fn db_file_header_records_hasher_id() {
+        let header = generate_header(1_000, 1, get_vm_page_size()).with_hasher(Arc::new(IdHasher));
+        let data_array = header.as_bytes();
+        assert_eq!(&data_array[30..34], &[0u8, 0, 0, 9]);
+
+        let mut got = DbFileHeader::from_data_array(&data_array).expect("from data array");
+        assert_eq!(got.hasher_id, 9);
+
+        let err = got
+            .attach_hasher(Arc::new(crate::Xxh3Hasher))
+            .expect_err("hasher mismatch");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        got.attach_hasher(Arc::new(IdHasher))
+            .expect("attach hasher");
+        assert_eq!(got, header);
+        assert_eq!(
+            got.get_index_offset(b"foo"),
+            header.get_index_offset(b"foo")
+        );
+    }
+
     #[test]
     #[serial]
     fn db_file_header_from_file() {
         let file_path = "testdb.scdb";
         let block_size = get_vm_page_size();
         let block_size_bytes = block_size.to_be_bytes().to_vec();
-        // title: Scdb versn 0.001
+        // title: Scdb versn 0.002
         let title_bytes = vec![
-            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 49,
+            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 50,
         ];
         let reserve_bytes = vec![0u8; 70];
         type Record = (Vec<u8>, DbFileHeader);
         let test_table: Vec<Record> = vec![
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -372,7 +507,7 @@ mod tests {
                 generate_header(1_000_000, 1, block_size),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -383,7 +518,7 @@ mod tests {
                 generate_header(24_000_000, 1, block_size),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -394,7 +529,7 @@ mod tests {
                 generate_header(1_000_000, 9, block_size),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000u64 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -413,7 +548,7 @@ mod tests {
             assert_eq!(&got, &expected);
         }
 
-        std::fs::remove_file(&file_path).expect("delete the test db file");
+        std::fs::remove_file(file_path).expect("delete the test db file");
     }
 
     #[test]
@@ -422,13 +557,13 @@ mod tests {
         let file_path = "testdb.scdb";
         let block_size = get_vm_page_size();
         let block_size_bytes = block_size.to_be_bytes().to_vec();
-        // title: Scdb versn 0.001
+        // title: Scdb versn 0.002
         let title_bytes = vec![
-            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 49,
+            83u8, 99, 100, 98, 32, 118, 101, 114, 115, 110, 32, 48, 46, 48, 48, 50,
         ];
         let reserve_bytes = vec![0u8; 70];
         let test_table: Vec<Vec<u8>> = vec![
-            vec![
+            [
                 title_bytes[2..].to_vec(), // title is truncated
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -436,7 +571,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes[..3].to_vec(), // block_size is truncated
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -444,7 +579,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 15, 66, 64], // max_keys is truncated
@@ -452,7 +587,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -460,7 +595,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -477,7 +612,7 @@ mod tests {
             assert!(got.is_err());
         }
 
-        std::fs::remove_file(&file_path).expect("delete the test db file");
+        std::fs::remove_file(file_path).expect("delete the test db file");
     }
 
     #[test]
@@ -520,6 +655,72 @@ mod tests {
         }
     }
 
+    #[test]
+    #[serial]
+    fn db_file_header_get_index_offsets() {
+        let db_header = DbFileHeader::new(Some(1000), Some(1), Some(80));
+        let initial_offset = db_header.get_index_offset(b"foo");
+        let offsets: Vec<u64> = db_header.get_index_offsets(b"foo").collect();
+        let expected: Vec<u64> = (0..db_header.number_of_index_blocks)
+            .map(|i| initial_offset + i * db_header.net_block_size)
+            .collect();
+        assert_eq!(offsets, expected);
+
+        // with the IdHasher, "foo" hashes to slot 3 of the 10 slots of each block
+        let db_header = db_header.with_hasher(Arc::new(IdHasher));
+        let db_header = DbFileHeader {
+            number_of_index_blocks: 2,
+            ..db_header
+        };
+        let slot_offset = |block: u64, slot: u64| {
+            HEADER_SIZE_IN_BYTES
+                + block * db_header.net_block_size
+                + slot * INDEX_ENTRY_SIZE_IN_BYTES
+        };
+        let test_table: Vec<(Probing, Vec<u64>)> = vec![
+            (Probing::None, vec![slot_offset(0, 3), slot_offset(1, 3)]),
+            (
+                Probing::Linear(2),
+                vec![
+                    slot_offset(0, 3),
+                    slot_offset(0, 4),
+                    slot_offset(0, 5),
+                    slot_offset(1, 3),
+                    slot_offset(1, 4),
+                    slot_offset(1, 5),
+                ],
+            ),
+            (
+                Probing::Quadratic(3),
+                vec![
+                    slot_offset(0, 3),
+                    slot_offset(0, 4),
+                    slot_offset(0, 7),
+                    slot_offset(0, 2),
+                    slot_offset(1, 3),
+                    slot_offset(1, 4),
+                    slot_offset(1, 7),
+                    slot_offset(1, 2),
+                ],
+            ),
+        ];
+
+        for (probing, expected) in test_table {
+            let db_header = db_header.clone().with_probing(probing);
+            let offsets: Vec<u64> = db_header.get_index_offsets(b"foo").collect();
+            assert_eq!(offsets, expected, "{:?}", probing);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn db_file_header_probing_round_trips() {
+        let db_header = DbFileHeader::new(None, None, None).with_probing(Probing::Quadratic(7));
+        let got = DbFileHeader::from_data_array(&db_header.as_bytes()).expect("from data array");
+        assert_eq!(got.probing, Probing::Quadratic(7));
+        assert_eq!(got, db_header);
+    }
+
     /// Generates a DbFileHeader basing on the inputs supplied. This is just a helper for tests
     fn generate_header(max_keys: u64, redundant_blocks: u16, block_size: u32) -> DbFileHeader {
         let items_per_index_block =
@@ -530,7 +731,7 @@ mod tests {
         let key_values_start_point = 100 + (net_block_size * number_of_index_blocks);
 
         DbFileHeader {
-            title: "Scdb versn 0.001".to_string(),
+            title: "Scdb versn 0.002".to_string(),
             block_size,
             max_keys,
             redundant_blocks,
@@ -538,6 +739,24 @@ mod tests {
             number_of_index_blocks,
             key_values_start_point,
             net_block_size,
+            hasher_id: 0,
+            hasher: HeaderHasher::default(),
+            probing: Probing::None,
+            is_preallocated: false,
+        }
+    }
+
+    /// A hasher that uses the key's length as its hash
+    #[derive(Debug)]
+    struct IdHasher;
+
+    impl KeyHasher for IdHasher {
+        fn id(&self) -> u32 {
+            9
+        }
+
+        fn hash(&self, key: &[u8]) -> u64 {
+            key.len() as u64
         }
     }
 
@@ -547,6 +766,7 @@ mod tests {
             .write(true)
             .read(true)
             .create(true)
+            .truncate(true)
             .open(file_path)?;
         file.seek(SeekFrom::Start(0))?;
         file.write_all(data_array)?;
@@ -1,11 +1,13 @@
 use crate::internal::entries::headers::shared::{
-    DerivedHeaderProps, Header, DEFAULT_DB_MAX_KEYS, HEADER_SIZE_IN_BYTES,
+    extract_title, DerivedHeaderProps, Header, DEFAULT_DB_MAX_KEYS, HEADER_SIZE_IN_BYTES,
 };
 use crate::internal::utils;
 use std::fmt::{Display, Formatter};
 use std::io;
 
 pub(crate) const DEFAULT_MAX_INDEX_KEY_LEN: u32 = 3;
+/// The title at the start of every search index file, identifying it and the version of its format
+pub(crate) const INVERTED_INDEX_FILE_TITLE: &str = "ScdbIndex v0.001";
 
 #[derive(Debug, PartialEq, Clone, Eq, Ord, PartialOrd)]
 pub(crate) struct InvertedIndexHeader {
@@ -35,7 +37,7 @@ impl InvertedIndexHeader {
         let derived_props = DerivedHeaderProps::new(block_size, max_keys, redundant_blocks);
 
         Self {
-            title: "ScdbIndex v0.001".to_string(),
+            title: INVERTED_INDEX_FILE_TITLE.to_string(),
             block_size,
             max_keys,
             redundant_blocks,
@@ -88,8 +90,7 @@ impl Header for InvertedIndexHeader {
             ));
         }
 
-        let title = String::from_utf8(data[0..16].to_owned())
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let title = extract_title(data, INVERTED_INDEX_FILE_TITLE)?;
         let block_size = u32::from_be_bytes(utils::slice_to_array::<4>(&data[16..20])?);
         let max_keys = u64::from_be_bytes(utils::slice_to_array::<8>(&data[20..28])?);
         let redundant_blocks = u16::from_be_bytes(utils::slice_to_array::<2>(&data[28..30])?);
@@ -210,7 +211,7 @@ mod tests {
                 None,
                 None,
                 None,
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 3_000_000u64 */ vec![0, 0, 0, 0, 0, 45, 198, 192],
@@ -224,7 +225,7 @@ mod tests {
                 Some(24_000_000),
                 None,
                 None,
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -238,7 +239,7 @@ mod tests {
                 None,
                 Some(9),
                 None,
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 3_000_000u64 */ vec![0, 0, 0, 0, 0, 45, 198, 192],
@@ -252,7 +253,7 @@ mod tests {
                 None,
                 None,
                 Some(9),
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 9_000_000u64 */ vec![0, 0, 0, 0, 0, 137, 84, 64],
@@ -266,7 +267,7 @@ mod tests {
                 Some(24_000_000),
                 Some(5),
                 Some(9),
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000u64 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -298,7 +299,7 @@ mod tests {
         type Record = (Vec<u8>, InvertedIndexHeader);
         let test_table: Vec<Record> = vec![
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -310,7 +311,7 @@ mod tests {
                 generate_header(1_000_000, 1, block_size, 3),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -322,7 +323,7 @@ mod tests {
                 generate_header(24_000_000, 1, block_size, 9),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -334,7 +335,7 @@ mod tests {
                 generate_header(1_000_000, 9, block_size, 3),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000u64 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -364,7 +365,7 @@ mod tests {
         ];
         let reserve_bytes = vec![0u8; 66];
         let test_table: Vec<Vec<u8>> = vec![
-            vec![
+            [
                 title_bytes[2..].to_vec(), // title is truncated
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -373,7 +374,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes[..3].to_vec(), // block_size is truncated
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -382,7 +383,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 15, 66, 64], // max_keys is truncated
@@ -391,7 +392,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -400,7 +401,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -409,7 +410,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -426,6 +427,20 @@ mod tests {
         }
     }
 
+    #[test]
+    #[serial]
+    fn search_file_header_from_data_array_with_wrong_title() {
+        let mut data_array = generate_header(1_000, 1, get_vm_page_size(), 3).as_bytes();
+        data_array[..16].copy_from_slice(b"Scdb versn 0.001");
+
+        let got = InvertedIndexHeader::from_data_array(&data_array).expect_err("wrong title");
+        assert_eq!(got.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            got.to_string(),
+            "invalid header title: expected \"ScdbIndex v0.001\", found \"Scdb versn 0.001\""
+        );
+    }
+
     #[test]
     #[serial]
     fn search_file_header_from_file() {
@@ -440,7 +455,7 @@ mod tests {
         type Record = (Vec<u8>, InvertedIndexHeader);
         let test_table: Vec<Record> = vec![
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -452,7 +467,7 @@ mod tests {
                 generate_header(1_000_000, 1, block_size, 3),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -464,7 +479,7 @@ mod tests {
                 generate_header(24_000_000, 1, block_size, 3),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 1_000_000u64 */ vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -476,7 +491,7 @@ mod tests {
                 generate_header(1_000_000, 9, block_size, 3),
             ),
             (
-                vec![
+                [
                     title_bytes.clone(),
                     block_size_bytes.clone(),
                     /* max_keys 24_000_000u64 */ vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -496,7 +511,7 @@ mod tests {
             assert_eq!(&got, &expected);
         }
 
-        std::fs::remove_file(&file_path).expect("delete the test db file");
+        std::fs::remove_file(file_path).expect("delete the test db file");
     }
 
     #[test]
@@ -511,7 +526,7 @@ mod tests {
         ];
         let reserve_bytes = vec![0u8; 66];
         let test_table: Vec<Vec<u8>> = vec![
-            vec![
+            [
                 title_bytes[2..].to_vec(), // title is truncated
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 0, 15, 66, 64],
@@ -520,7 +535,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes[..3].to_vec(), // block_size is truncated
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -529,7 +544,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 15, 66, 64], // max_keys is truncated
@@ -538,7 +553,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -547,7 +562,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -556,7 +571,7 @@ mod tests {
                 reserve_bytes.clone(),
             ]
             .concat(),
-            vec![
+            [
                 title_bytes.clone(),
                 block_size_bytes.clone(),
                 vec![0, 0, 0, 0, 1, 110, 54, 0],
@@ -574,7 +589,7 @@ mod tests {
             assert!(got.is_err());
         }
 
-        std::fs::remove_file(&file_path).expect("delete the test db file");
+        std::fs::remove_file(file_path).expect("delete the test db file");
     }
 
     #[test]
@@ -645,6 +660,7 @@ mod tests {
             .write(true)
             .read(true)
             .create(true)
+            .truncate(true)
             .open(file_path)?;
         file.seek(SeekFrom::Start(0))?;
         file.write_all(data_array)?;
@@ -1,7 +1,7 @@
 use crate::internal::get_hash;
-use std::fs::File;
+use crate::storage::StorageFile;
 use std::io;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::SeekFrom;
 
 pub(crate) const INDEX_ENTRY_SIZE_IN_BYTES: u64 = 8;
 pub(crate) const HEADER_SIZE_IN_BYTES: u64 = 100;
@@ -25,7 +25,7 @@ pub(crate) trait Header: Sized {
     fn from_data_array(data: &[u8]) -> io::Result<Self>;
 
     /// Extracts the header from a database file
-    fn from_file(file: &mut File) -> io::Result<Self> {
+    fn from_file(file: &mut dyn StorageFile) -> io::Result<Self> {
         file.seek(SeekFrom::Start(0))?;
         let mut buf = [0u8; HEADER_SIZE_IN_BYTES as usize];
         let data_len = file.read(&mut buf)?;
@@ -42,11 +42,16 @@ pub(crate) trait Header: Sized {
         Self::from_data_array(&buf)
     }
 
+    /// Generates the hash of the key, to get the position in the index block that the key corresponds to
+    fn hash_key(&self, key: &[u8]) -> u64 {
+        get_hash(key, self.get_items_per_index_block())
+    }
+
     /// Computes the offset for the given key in the first index block.
     /// It uses the meta data in this header
     /// i.e. number of items per block and the `INDEX_ENTRY_SIZE_IN_BYTES`
     fn get_index_offset(&self, key: &[u8]) -> u64 {
-        let hash = get_hash(key, self.get_items_per_index_block());
+        let hash = self.hash_key(key);
         HEADER_SIZE_IN_BYTES + (hash * INDEX_ENTRY_SIZE_IN_BYTES)
     }
 
@@ -70,7 +75,7 @@ pub(crate) trait Header: Sized {
 
     /// Initializes the underlying file, giving it the header and the index place holders
     /// and truncating it. It returns the new file size
-    fn initialize_file(&self, file: &mut File) -> io::Result<u64> {
+    fn initialize_file(&self, file: &mut dyn StorageFile) -> io::Result<u64> {
         let header_bytes = self.as_bytes();
         let header_length = header_bytes.len() as u64;
         debug_assert_eq!(header_length, 100);
@@ -87,6 +92,24 @@ pub(crate) trait Header: Sized {
     }
 }
 
+/// Extracts the title from the header data array, failing with an [io::ErrorKind::InvalidData] error
+/// if it is not the `expected` title e.g. if the file is not an scdb file
+pub(crate) fn extract_title(data: &[u8], expected: &str) -> io::Result<String> {
+    let title = &data[0..16];
+    if title != expected.as_bytes() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "invalid header title: expected {:?}, found {:?}",
+                expected,
+                String::from_utf8_lossy(title)
+            ),
+        ));
+    }
+
+    Ok(expected.to_string())
+}
+
 /// A struct containing the common properties derived
 /// from other properties of headers
 pub(crate) struct DerivedHeaderProps {
@@ -1,11 +1,11 @@
 use crate::internal;
-use crate::internal::entries::values::shared::ValueEntry;
+use crate::internal::entries::values::shared::{get_entry_size, ValueEntry};
 use crate::internal::macros::safe_slice;
 use crate::internal::utils::{bool_to_byte_array, byte_array_to_bool};
+use crate::storage::StorageFile;
 use std::fmt::Debug;
-use std::fs::File;
 use std::io;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::SeekFrom;
 
 pub(crate) const INVERTED_INDEX_ENTRY_MIN_SIZE_IN_BYTES: u32 = 4 + 4 + 1 + 1 + 8 + 8 + 8 + 8;
 
@@ -25,6 +25,8 @@ pub(crate) struct InvertedIndexEntry<'a> {
 
 impl<'a> InvertedIndexEntry<'a> {
     /// Creates a new InvertedIndexEntry
+    ///
+    /// It fails with [crate::ScdbError::InvalidInput] if the entry would be too big for its size to fit in a `u32`
     pub(crate) fn new(
         index_key: &'a [u8],
         key: &'a [u8],
@@ -33,12 +35,15 @@ impl<'a> InvertedIndexEntry<'a> {
         kv_address: u64,
         next_offset: u64,
         previous_offset: u64,
-    ) -> Self {
-        let key_size = key.len() as u32;
+    ) -> io::Result<Self> {
+        let size = get_entry_size(&[
+            key.len() as u64,
+            index_key.len() as u64,
+            INVERTED_INDEX_ENTRY_MIN_SIZE_IN_BYTES as u64,
+        ])?;
         let index_key_size = index_key.len() as u32;
-        let size = key_size + index_key_size + INVERTED_INDEX_ENTRY_MIN_SIZE_IN_BYTES;
 
-        Self {
+        Ok(Self {
             size,
             index_key_size,
             key,
@@ -49,7 +54,7 @@ impl<'a> InvertedIndexEntry<'a> {
             next_offset,
             previous_offset,
             is_deleted: false,
-        }
+        })
     }
 
     /// Updates the next offset of a given entry on the given file at the given address
@@ -57,7 +62,7 @@ impl<'a> InvertedIndexEntry<'a> {
     #[inline(always)]
     pub(crate) fn update_next_offset_on_file(
         &self,
-        file: &mut File,
+        file: &mut dyn StorageFile,
         entry_addr: u64,
         new_next_offset: u64,
     ) -> io::Result<()> {
@@ -73,7 +78,7 @@ impl<'a> InvertedIndexEntry<'a> {
     #[inline(always)]
     pub(crate) fn update_previous_offset_on_file(
         &self,
-        file: &mut File,
+        file: &mut dyn StorageFile,
         entry_addr: u64,
         new_prev_offset: u64,
     ) -> io::Result<()> {
@@ -196,6 +201,7 @@ impl<'a> ValueEntry<'a> for InvertedIndexEntry<'a> {
 mod tests {
     use super::*;
     use crate::internal::get_current_timestamp;
+    use crate::ScdbError;
 
     const SEARCH_ENTRY_BYTE_ARRAY: [u8; 47] = [
         /* size: 47u32*/ 0u8, 0, 0, 47, /* index key size: 2u32*/ 0, 0, 0, 2,
@@ -205,9 +211,25 @@ mod tests {
         0, 0, 0, 0, 0, 0, 90, /* kv_address: 100u64 */ 0, 0, 0, 0, 0, 0, 0, 100,
     ];
 
+    #[test]
+    fn inverted_index_entry_new_rejects_sizes_too_big_for_u32() {
+        // the zeroed memory is never touched, so it is not actually taken up
+        let max_key_len = (u32::MAX - INVERTED_INDEX_ENTRY_MIN_SIZE_IN_BYTES - 2) as usize;
+        let key = vec![0u8; max_key_len + 1];
+
+        let entry = InvertedIndexEntry::new(&b"fo"[..], &key[..max_key_len], 0, true, 100, 0, 0)
+            .expect("new inverted index entry");
+        assert_eq!(entry.size, u32::MAX);
+
+        let got = InvertedIndexEntry::new(&b"fo"[..], &key[..], 0, true, 100, 0, 0)
+            .map_err(ScdbError::from);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+    }
+
     #[test]
     fn search_entry_from_data_array() {
-        let expected = InvertedIndexEntry::new(&b"fo"[..], &b"foo"[..], 0, false, 100, 900, 90);
+        let expected = InvertedIndexEntry::new(&b"fo"[..], &b"foo"[..], 0, false, 100, 900, 90)
+            .expect("new inverted index entry");
         let got = InvertedIndexEntry::from_data_array(&SEARCH_ENTRY_BYTE_ARRAY[..], 0)
             .expect("search entry from data array");
         assert_eq!(
@@ -219,7 +241,8 @@ mod tests {
 
     #[test]
     fn search_entry_from_data_array_with_offset() {
-        let expected = InvertedIndexEntry::new(&b"fo"[..], &b"foo"[..], 0, false, 100, 900, 90);
+        let expected = InvertedIndexEntry::new(&b"fo"[..], &b"foo"[..], 0, false, 100, 900, 90)
+            .expect("new inverted index entry");
         let data_array: Vec<u8> = [89u8, 78u8]
             .iter()
             .chain(&SEARCH_ENTRY_BYTE_ARRAY)
@@ -247,7 +270,8 @@ mod tests {
 
     #[test]
     fn search_entry_as_bytes() {
-        let entry = InvertedIndexEntry::new(&b"fo"[..], &b"foo"[..], 0, false, 100, 900, 90);
+        let entry = InvertedIndexEntry::new(&b"fo"[..], &b"foo"[..], 0, false, 100, 900, 90)
+            .expect("new inverted index entry");
         let expected = SEARCH_ENTRY_BYTE_ARRAY.to_vec();
         let got = entry.as_bytes();
         assert_eq!(
@@ -260,7 +284,8 @@ mod tests {
     #[test]
     fn entry_is_expired_works() {
         let never_expires =
-            InvertedIndexEntry::new(&b"ne"[..], &b"never_expires"[..], 0, false, 100, 900, 90);
+            InvertedIndexEntry::new(&b"ne"[..], &b"never_expires"[..], 0, false, 100, 900, 90)
+                .expect("new inverted index entry");
         // 1666023836u64 is some past timestamp in October 2022
         let expired = InvertedIndexEntry::new(
             &b"exp"[..],
@@ -270,7 +295,8 @@ mod tests {
             100,
             900,
             90,
-        );
+        )
+        .expect("new inverted index entry");
         let not_expired = InvertedIndexEntry::new(
             &b"no"[..],
             &b"not_expired"[..],
@@ -279,10 +305,11 @@ mod tests {
             100,
             900,
             90,
-        );
+        )
+        .expect("new inverted index entry");
 
-        assert!(!never_expires.is_expired());
-        assert!(!not_expired.is_expired());
-        assert!(expired.is_expired());
+        assert!(!never_expires.is_expired(get_current_timestamp()));
+        assert!(!not_expired.is_expired(get_current_timestamp()));
+        assert!(expired.is_expired(get_current_timestamp()));
     }
 }
@@ -1,12 +1,22 @@
 use crate::internal;
-use crate::internal::entries::values::shared::ValueEntry;
+use crate::internal::entries::values::shared::{get_entry_size, ValueEntry};
 use crate::internal::macros::safe_slice;
-use crate::internal::utils::{bool_to_byte_array, byte_array_to_bool};
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::io;
 
-pub(crate) const KEY_VALUE_MIN_SIZE_IN_BYTES: u32 = 4 + 4 + 8 + 1;
+pub(crate) const KEY_VALUE_MIN_SIZE_IN_BYTES: u32 =
+    (OFFSET_FOR_KEY_IN_KV_ARRAY + FLAGS_SIZE_IN_BYTES + EXPIRY_SIZE_IN_BYTES) as u32;
 pub(crate) const OFFSET_FOR_KEY_IN_KV_ARRAY: usize = 8;
+/// The size of the flags of an entry, which come right after the key
+pub(crate) const FLAGS_SIZE_IN_BYTES: usize = 1;
+/// The size of the expiry of an entry, which comes right after the flags
+pub(crate) const EXPIRY_SIZE_IN_BYTES: usize = 8;
+/// The size of the version of an entry that has one, which comes right after the expiry
+pub(crate) const VERSION_SIZE_IN_BYTES: u32 = 8;
+/// The flag, in the byte right after the key, of a deleted entry
+pub(crate) const IS_DELETED_FLAG: u8 = 0b01;
+/// The flag, in the byte right after the key, of an entry that has a version
+pub(crate) const HAS_VERSION_FLAG: u8 = 0b10;
 
 #[derive(Debug, PartialEq)]
 pub(crate) struct KeyValueEntry<'a> {
@@ -15,6 +25,7 @@ pub(crate) struct KeyValueEntry<'a> {
     pub(crate) key: &'a [u8],
     pub(crate) expiry: u64,
     pub(crate) is_deleted: bool,
+    pub(crate) version: Option<u64>,
     pub(crate) value: &'a [u8],
 }
 
@@ -23,18 +34,66 @@ impl<'a> KeyValueEntry<'a> {
     /// `key` is the byte array of the key
     /// `value` is the byte array of the value
     /// `expiry` is the timestamp (in seconds from unix epoch)
-    pub(crate) fn new(key: &'a [u8], value: &'a [u8], expiry: u64) -> Self {
+    ///
+    /// It fails with [crate::ScdbError::InvalidInput] if the entry, with room for a version,
+    /// would be too big for its size to fit in a `u32`
+    pub(crate) fn new(key: &'a [u8], value: &'a [u8], expiry: u64) -> io::Result<Self> {
+        // room is left for a version so that `with_version` can't overflow the size
+        get_entry_size(&[
+            key.len() as u64,
+            value.len() as u64,
+            KEY_VALUE_MIN_SIZE_IN_BYTES as u64,
+            VERSION_SIZE_IN_BYTES as u64,
+        ])?;
         let key_size = key.len() as u32;
         let size = key_size + KEY_VALUE_MIN_SIZE_IN_BYTES + value.len() as u32;
 
-        Self {
+        Ok(Self {
             size,
             key_size,
             key,
             expiry,
             value,
             is_deleted: false,
+            version: None,
+        })
+    }
+
+    /// Gives the entry the given version, which is incremented every time the key is set
+    pub(crate) fn with_version(mut self, version: u64) -> Self {
+        if self.version.is_none() {
+            self.size += VERSION_SIZE_IN_BYTES;
+        }
+        self.version = Some(version);
+        self
+    }
+
+    /// Returns the byte, right after the key, holding the flags of this entry
+    fn get_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.is_deleted {
+            flags |= IS_DELETED_FLAG;
+        }
+        if self.version.is_some() {
+            flags |= HAS_VERSION_FLAG;
         }
+        flags
+    }
+}
+
+/// Returns the offset, from the start of an entry whose key is `key_size` bytes long, of its version if it has one
+#[inline(always)]
+pub(crate) fn get_version_offset(key_size: usize) -> usize {
+    OFFSET_FOR_KEY_IN_KV_ARRAY + key_size + FLAGS_SIZE_IN_BYTES + EXPIRY_SIZE_IN_BYTES
+}
+
+/// Returns the size of the version of an entry whose flags, the byte right after its key, are `flags`
+#[inline(always)]
+pub(crate) fn get_version_size(flags: u8) -> u32 {
+    if flags & HAS_VERSION_FLAG == 0 {
+        0
+    } else {
+        VERSION_SIZE_IN_BYTES
     }
 }
 
@@ -53,27 +112,42 @@ impl<'a> ValueEntry<'a> for KeyValueEntry<'a> {
         let key_size = u32::from_be_bytes(internal::slice_to_array(key_size_slice)?);
 
         let k_size = key_size as usize;
-        let key = safe_slice!(data, offset + 8, offset + 8 + k_size, data_len)?;
+        let key_offset = offset + OFFSET_FOR_KEY_IN_KV_ARRAY;
+        let key = safe_slice!(data, key_offset, key_offset + k_size, data_len)?;
 
-        let is_deleted_slice =
-            safe_slice!(data, offset + 8 + k_size, offset + k_size + 9, data_len)?;
-        let is_deleted = byte_array_to_bool(is_deleted_slice);
+        let flags_offset = key_offset + k_size;
+        let flags_slice = safe_slice!(
+            data,
+            flags_offset,
+            flags_offset + FLAGS_SIZE_IN_BYTES,
+            data_len
+        )?;
+        let flags = flags_slice[0];
+        let is_deleted = flags & IS_DELETED_FLAG != 0;
 
-        let expiry_slice = safe_slice!(data, offset + 9 + k_size, offset + k_size + 17, data_len)?;
+        let expiry_offset = flags_offset + FLAGS_SIZE_IN_BYTES;
+        let expiry_slice = safe_slice!(
+            data,
+            expiry_offset,
+            expiry_offset + EXPIRY_SIZE_IN_BYTES,
+            data_len
+        )?;
         let expiry = u64::from_be_bytes(internal::slice_to_array(expiry_slice)?);
 
-        let value_size = (size - key_size - KEY_VALUE_MIN_SIZE_IN_BYTES) as usize;
-        let value = if value_size > 0 {
-            safe_slice!(
-                data,
-                offset + k_size + 17,
-                offset + k_size + 17 + value_size,
-                data_len
-            )?
+        let mut value_offset = expiry_offset + EXPIRY_SIZE_IN_BYTES;
+        let version = if flags & HAS_VERSION_FLAG == 0 {
+            None
         } else {
-            "".as_bytes()
+            let version_end = value_offset + VERSION_SIZE_IN_BYTES as usize;
+            let version_slice = safe_slice!(data, value_offset, version_end, data_len)?;
+            value_offset = version_end;
+            Some(u64::from_be_bytes(internal::slice_to_array(version_slice)?))
         };
 
+        let value_size =
+            (size - key_size - KEY_VALUE_MIN_SIZE_IN_BYTES - get_version_size(flags)) as usize;
+        let value = safe_slice!(data, value_offset, value_offset + value_size, data_len)?;
+
         let entry = Self {
             size,
             key_size,
@@ -81,28 +155,50 @@ impl<'a> ValueEntry<'a> for KeyValueEntry<'a> {
             expiry,
             value,
             is_deleted,
+            version,
         };
         Ok(entry)
     }
 
     fn as_bytes(&self) -> Vec<u8> {
+        let version_bytes = self.version.map(u64::to_be_bytes);
         self.size
             .to_be_bytes()
             .iter()
             .chain(&self.key_size.to_be_bytes())
             .chain(self.key)
-            .chain(bool_to_byte_array(self.is_deleted))
+            .chain(&[self.get_flags()])
             .chain(&self.expiry.to_be_bytes())
+            .chain(version_bytes.iter().flatten())
             .chain(self.value)
             .map(|v| v.to_owned())
             .collect()
     }
 }
 
+impl Display for KeyValueEntry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let key_hex: String = self.key.iter().map(|b| format!("{:02x}", b)).collect();
+        write!(
+            f,
+            "KeyValueEntry {{ key: {:?} (0x{}), value_len: {}, expiry: ",
+            String::from_utf8_lossy(self.key),
+            key_hex,
+            self.value.len()
+        )?;
+        match self.expiry {
+            0 => write!(f, "never")?,
+            expiry => write!(f, "{}", expiry)?,
+        }
+        write!(f, ", is_deleted: {} }}", self.is_deleted)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::internal::get_current_timestamp;
+    use crate::ScdbError;
 
     const KV_DATA_ARRAY: [u8; 23] = [
         /* size: 23u32*/ 0u8, 0, 0, 23, /* key size: 3u32*/ 0, 0, 0, 3,
@@ -112,7 +208,7 @@ mod tests {
 
     #[test]
     fn key_value_entry_from_data_array() {
-        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0);
+        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0).expect("new key value entry");
         let got = KeyValueEntry::from_data_array(&KV_DATA_ARRAY[..], 0)
             .expect("key value from data array");
         assert_eq!(&got, &kv, "got = {:?}, expected = {:?}", &got, &kv);
@@ -120,7 +216,7 @@ mod tests {
 
     #[test]
     fn key_value_entry_from_data_array_with_offset() {
-        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0);
+        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0).expect("new key value entry");
         let data_array: Vec<u8> = [89u8, 78u8]
             .iter()
             .chain(&KV_DATA_ARRAY)
@@ -131,6 +227,22 @@ mod tests {
         assert_eq!(&got, &kv, "got = {:?}, expected = {:?}", &got, &kv);
     }
 
+    #[test]
+    fn key_value_entry_new_rejects_sizes_too_big_for_u32() {
+        // the zeroed memory is never touched, so it is not actually taken up
+        let max_value_len =
+            (u32::MAX - KEY_VALUE_MIN_SIZE_IN_BYTES - VERSION_SIZE_IN_BYTES - 3) as usize;
+        let value = vec![0u8; max_value_len + 1];
+
+        let kv = KeyValueEntry::new(&b"foo"[..], &value[..max_value_len], 0)
+            .expect("new key value entry")
+            .with_version(1);
+        assert_eq!(kv.size, u32::MAX);
+
+        let got = KeyValueEntry::new(&b"foo"[..], &value[..], 0).map_err(ScdbError::from);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+    }
+
     #[test]
     fn key_value_entry_from_data_array_with_out_of_bounds_offset() {
         let data_array: Vec<u8> = [89u8, 78u8]
@@ -149,7 +261,7 @@ mod tests {
             /* key */ 102, 111, 111, /* is_deleted */ 0, /* expiry 0u64 */ 0, 0, 0,
             0, 0, 0, 0, 0, /* value: "" */
         ];
-        let expected = KeyValueEntry::new(&b"foo"[..], &b""[..], 0);
+        let expected = KeyValueEntry::new(&b"foo"[..], &b""[..], 0).expect("new key value entry");
         let got =
             KeyValueEntry::from_data_array(&data_array, 0).expect("key value from data array");
         assert_eq!(
@@ -161,25 +273,83 @@ mod tests {
 
     #[test]
     fn key_value_as_bytes() {
-        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0);
+        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0).expect("new key value entry");
         let kv_vec = KV_DATA_ARRAY.to_vec();
         let got = kv.as_bytes();
         assert_eq!(&got, &kv_vec, "got = {:?}, expected = {:?}", &got, &kv_vec);
     }
 
+    #[test]
+    fn key_value_with_version_round_trips() {
+        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0)
+            .expect("new key value entry")
+            .with_version(258);
+        let data_array = vec![
+            /* size: 31u32*/ 0u8, 0, 0, 31, /* key size: 3u32*/ 0, 0, 0, 3,
+            /* key */ 102, 111, 111, /* flags: has version */ 2,
+            /* expiry 0u64 */ 0, 0, 0, 0, 0, 0, 0, 0, /* version 258u64 */ 0, 0, 0, 0, 0,
+            0, 1, 2, /* value */ 98, 97, 114,
+        ];
+        assert_eq!(kv.as_bytes(), data_array);
+
+        let got =
+            KeyValueEntry::from_data_array(&data_array, 0).expect("key value from data array");
+        assert_eq!(&got, &kv);
+        assert_eq!(got.version, Some(258));
+        assert_eq!(got.value, &b"bar"[..]);
+
+        // a deleted versioned entry keeps its version
+        let mut deleted = data_array.clone();
+        deleted[11] |= IS_DELETED_FLAG;
+        let got = KeyValueEntry::from_data_array(&deleted, 0).expect("key value from data array");
+        assert!(got.is_deleted);
+        assert_eq!(got.version, Some(258));
+    }
+
+    #[test]
+    fn get_version_offset_works() {
+        let kv = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0)
+            .expect("new key value entry")
+            .with_version(258);
+        let offset = get_version_offset(kv.key.len());
+        let data_array = kv.as_bytes();
+        let version = &data_array[offset..offset + VERSION_SIZE_IN_BYTES as usize];
+        assert_eq!(version, &258u64.to_be_bytes()[..]);
+    }
+
+    #[test]
+    fn key_value_display_works() {
+        let mut kv = KeyValueEntry::new(&b"foo\xff"[..], &b"bar"[..], 1666023836u64)
+            .expect("new key value entry");
+        assert_eq!(
+            kv.to_string(),
+            "KeyValueEntry { key: \"foo\u{fffd}\" (0x666f6fff), value_len: 3, expiry: 1666023836, is_deleted: false }"
+        );
+
+        kv.expiry = 0;
+        kv.is_deleted = true;
+        assert_eq!(
+            kv.to_string(),
+            "KeyValueEntry { key: \"foo\u{fffd}\" (0x666f6fff), value_len: 3, expiry: never, is_deleted: true }"
+        );
+    }
+
     #[test]
     fn key_value_is_expired_works() {
-        let never_expires = KeyValueEntry::new(&b"never_expires"[..], &b"bar"[..], 0);
+        let never_expires =
+            KeyValueEntry::new(&b"never_expires"[..], &b"bar"[..], 0).expect("new key value entry");
         // 1666023836u64 is some past timestamp in October 2022
-        let expired = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64);
+        let expired = KeyValueEntry::new(&b"expires"[..], &b"bar"[..], 1666023836u64)
+            .expect("new key value entry");
         let not_expired = KeyValueEntry::new(
             &b"not_expired"[..],
             &b"bar"[..],
             get_current_timestamp() * 2,
-        );
+        )
+        .expect("new key value entry");
 
-        assert!(!never_expires.is_expired());
-        assert!(!not_expired.is_expired());
-        assert!(expired.is_expired());
+        assert!(!never_expires.is_expired(get_current_timestamp()));
+        assert!(!not_expired.is_expired(get_current_timestamp()));
+        assert!(expired.is_expired(get_current_timestamp()));
     }
 }
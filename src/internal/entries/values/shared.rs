@@ -1,6 +1,22 @@
-use crate::internal::get_current_timestamp;
+use crate::ScdbError;
 use std::io;
 
+/// Adds up the sizes of the parts of an entry into the size of the whole entry, as saved in its `u32`
+/// size field, failing with [ScdbError::InvalidInput] if it is too big to fit in that field
+pub(crate) fn get_entry_size(part_sizes: &[u64]) -> io::Result<u32> {
+    let size = part_sizes
+        .iter()
+        .try_fold(0u64, |total, &part| total.checked_add(part))
+        .unwrap_or(u64::MAX);
+    u32::try_from(size).map_err(|_| {
+        io::Error::from(ScdbError::InvalidInput(format!(
+            "entry size {} exceeds the maximum of {} bytes",
+            size,
+            u32::MAX
+        )))
+    })
+}
+
 pub(crate) trait ValueEntry<'a>: Sized {
     /// Gets the expiry of the value entry
     fn get_expiry(&self) -> u64;
@@ -11,14 +27,15 @@ pub(crate) trait ValueEntry<'a>: Sized {
     /// Retrieves the byte array that represents the value entry.
     fn as_bytes(&self) -> Vec<u8>;
 
-    /// Returns true if key has lived for longer than its time-to-live
+    /// Returns true if key has lived for longer than its time-to-live, given the
+    /// current timestamp `now` in seconds from unix epoch.
     /// It will always return false if time-to-live was never set
-    fn is_expired(&self) -> bool {
+    fn is_expired(&self, now: u64) -> bool {
         let expiry = self.get_expiry();
         if expiry == 0 {
             false
         } else {
-            expiry < get_current_timestamp()
+            expiry < now
         }
     }
 }
@@ -1,3 +1,6 @@
+use crate::{KeyHasher, Xxh3Hasher};
+use std::cmp::Ordering;
+use std::sync::Arc;
 use twox_hash::xxh3::hash64;
 
 /// Generates the hash of the key for the given block length
@@ -7,6 +10,44 @@ pub(crate) fn get_hash(key: &[u8], block_length: u64) -> u64 {
     hash % block_length
 }
 
+/// The hasher of a header, compared (and ordered) by its id
+#[derive(Debug, Clone)]
+pub(crate) struct HeaderHasher(pub(crate) Arc<dyn KeyHasher>);
+
+impl HeaderHasher {
+    /// Generates the hash of the key for the given block length
+    #[inline(always)]
+    pub(crate) fn get_hash(&self, key: &[u8], block_length: u64) -> u64 {
+        self.0.hash(key) % block_length
+    }
+}
+
+impl Default for HeaderHasher {
+    fn default() -> Self {
+        Self(Arc::new(Xxh3Hasher))
+    }
+}
+
+impl PartialEq for HeaderHasher {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id() == other.0.id()
+    }
+}
+
+impl Eq for HeaderHasher {}
+
+impl PartialOrd for HeaderHasher {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeaderHasher {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.id().cmp(&other.0.id())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
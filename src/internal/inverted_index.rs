@@ -1,14 +1,20 @@
+use crate::clock::{Clock, SystemClock};
 use crate::internal::entries::headers::inverted_index_header::InvertedIndexHeader;
 use crate::internal::entries::headers::shared::{HEADER_SIZE_IN_BYTES, INDEX_ENTRY_SIZE_IN_BYTES};
-use crate::internal::entries::values::inverted_index_entry::InvertedIndexEntry;
+use crate::internal::entries::values::inverted_index_entry::{
+    InvertedIndexEntry, INVERTED_INDEX_ENTRY_MIN_SIZE_IN_BYTES,
+};
 use crate::internal::macros::validate_bounds;
 use crate::internal::utils::get_vm_page_size;
 use crate::internal::{slice_to_array, Header, ValueEntry};
+use crate::storage::StorageFile;
+use crate::{ScdbError, SearchMode};
 use memchr::memmem;
 use std::cmp::min;
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 const ZERO_U64_BYTES: [u8; 8] = 0u64.to_be_bytes();
 
@@ -16,12 +22,14 @@ const ZERO_U64_BYTES: [u8; 8] = 0u64.to_be_bytes();
 /// using full text search
 #[derive(Debug)]
 pub(crate) struct InvertedIndex {
-    file: File,
+    file: Box<dyn StorageFile>,
     max_index_key_len: u32,
     values_start_point: u64,
-    pub(crate) file_path: PathBuf,
+    pub(crate) file_path: Option<PathBuf>,
     file_size: u64,
     header: InvertedIndexHeader,
+    clock: Arc<dyn Clock>,
+    stale_grace_secs: u64,
 }
 
 impl InvertedIndex {
@@ -36,15 +44,34 @@ impl InvertedIndex {
         db_max_keys: Option<u64>,
         db_redundant_blocks: Option<u16>,
     ) -> io::Result<Self> {
-        let block_size = get_vm_page_size();
-
-        let should_create_new = !file_path.exists();
-        let mut file = OpenOptions::new()
+        let file = OpenOptions::new()
             .write(true)
             .read(true)
-            .create(should_create_new)
+            .create(true)
+            .truncate(false)
             .open(file_path)?;
 
+        Self::from_file(
+            Box::new(file),
+            Some(file_path.into()),
+            max_index_key_len,
+            db_max_keys,
+            db_redundant_blocks,
+        )
+    }
+
+    /// Initializes a new Inverted Index over the given file, initializing it if it is empty.
+    /// The `file_path` is None if the file is not on the file system e.g. if it is in memory.
+    pub(crate) fn from_file(
+        mut file: Box<dyn StorageFile>,
+        file_path: Option<PathBuf>,
+        max_index_key_len: Option<u32>,
+        db_max_keys: Option<u64>,
+        db_redundant_blocks: Option<u16>,
+    ) -> io::Result<Self> {
+        let block_size = get_vm_page_size();
+
+        let should_create_new = file.seek(SeekFrom::End(0))? == 0;
         let header = if should_create_new {
             let header = InvertedIndexHeader::new(
                 db_max_keys,
@@ -64,14 +91,39 @@ impl InvertedIndex {
             file,
             max_index_key_len: header.max_index_key_len,
             values_start_point: header.values_start_point,
-            file_path: file_path.into(),
+            file_path,
             file_size,
             header,
+            clock: Arc::new(SystemClock),
+            stale_grace_secs: 0,
         };
 
         Ok(v)
     }
 
+    /// Returns the maximum number of bytes of a key that are indexed as its prefixes
+    pub(crate) fn get_max_index_key_len(&self) -> u32 {
+        self.max_index_key_len
+    }
+
+    /// Sets the clock used to check whether entries are expired
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Sets the number of seconds for which expired entries are still treated as unexpired,
+    /// to match the grace period of the buffer pool
+    pub(crate) fn set_stale_grace_secs(&mut self, stale_grace_secs: u64) {
+        self.stale_grace_secs = stale_grace_secs;
+    }
+
+    /// Returns the timestamp that entries are checked against to tell whether they are gone for good i.e.
+    /// the current timestamp less the grace period during which expired entries are still served
+    #[inline(always)]
+    fn get_stale_cutoff(&self) -> u64 {
+        self.clock.now_secs().saturating_sub(self.stale_grace_secs)
+    }
+
     /// Adds a key's kv address in the corresponding prefixes' lists to update the inverted index
     pub(crate) fn add(&mut self, key: &[u8], kv_address: u64, expiry: u64) -> io::Result<()> {
         let upper_bound = min(key.len() as u32, self.max_index_key_len) + 1;
@@ -98,13 +150,9 @@ impl InvertedIndex {
 
                 index_block += 1;
                 if index_block >= self.header.number_of_index_blocks {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!(
-                            "CollisionSaturatedError: no free slot for key: {:?}",
-                            prefix
-                        ),
-                    ));
+                    return Err(io::Error::other(ScdbError::CollisionSaturated(
+                        prefix.to_vec(),
+                    )));
                 }
             }
         }
@@ -121,7 +169,34 @@ impl InvertedIndex {
     ///
     /// If `limit` is 0, all items are returned since it would make no sense for someone to search
     /// for zero items.
-    pub(crate) fn search(&mut self, term: &[u8], skip: u64, limit: u64) -> io::Result<Vec<u64>> {
+    ///
+    /// The keys are matched against the `term` as dictated by the search `mode`.
+    pub(crate) fn search(
+        &mut self,
+        term: &[u8],
+        skip: u64,
+        limit: u64,
+        mode: SearchMode,
+    ) -> io::Result<Vec<u64>> {
+        self.search_with_filter(term, skip, limit, mode, |_| true)
+    }
+
+    /// Returns the kv_addresses of the keys matching the given term, just like [InvertedIndex::search],
+    /// but only of those whose expiry (0 for keys that never expire) passes the given `filter`.
+    ///
+    /// The filter is applied before `skip` and `limit`, so that only the entries that pass it count
+    /// towards them.
+    pub(crate) fn search_with_filter<F>(
+        &mut self,
+        term: &[u8],
+        skip: u64,
+        limit: u64,
+        mode: SearchMode,
+        filter: F,
+    ) -> io::Result<Vec<u64>>
+    where
+        F: Fn(u64) -> bool,
+    {
         let prefix_len = min(term.len(), self.max_index_key_len as usize);
         let prefix = &term[..prefix_len];
 
@@ -137,7 +212,8 @@ impl InvertedIndex {
             if addr == ZERO_U64_BYTES {
                 return Ok(vec![]);
             } else if self.addr_belongs_to_prefix(&addr, prefix)? {
-                return self.get_matched_kv_addrs_for_prefix(term, &addr, skip, limit);
+                return self
+                    .get_matched_kv_addrs_for_prefix(term, &addr, skip, limit, mode, filter);
             }
 
             index_block += 1;
@@ -146,33 +222,110 @@ impl InvertedIndex {
         Ok(vec![])
     }
 
+    /// Returns the address of the first entry in the list of keys that share the same prefix
+    /// (or index key) as `term`, or None if there is no such list
+    pub(crate) fn get_list_root_address(&mut self, term: &[u8]) -> io::Result<Option<u64>> {
+        let prefix_len = min(term.len(), self.max_index_key_len as usize);
+        match self.find_prefix_root_address(&term[..prefix_len])? {
+            Some((_, addr)) => Ok(Some(u64::from_be_bytes(slice_to_array(&addr)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the entry at the given address in a list of keys, returning the address of the next
+    /// entry in the list, and the kv address of the entry if its key matches `term`, as dictated
+    /// by the search `mode`, and it has not expired
+    pub(crate) fn read_list_entry(
+        &mut self,
+        address: u64,
+        term: &[u8],
+        mode: SearchMode,
+    ) -> io::Result<(u64, Option<u64>)> {
+        let entry_bytes = read_entry_bytes(&mut self.file, address)?;
+        let entry = InvertedIndexEntry::from_data_array(&entry_bytes, 0)?;
+        let is_match = match mode {
+            SearchMode::Prefix => entry.key.starts_with(term),
+            SearchMode::Contains => memmem::find(entry.key, term).is_some(),
+        };
+
+        if !entry.is_expired(self.get_stale_cutoff()) && is_match {
+            Ok((entry.next_offset, Some(entry.kv_address)))
+        } else {
+            Ok((entry.next_offset, None))
+        }
+    }
+
+    /// Returns true if the given key, in full, is in the index and has not expired
+    ///
+    /// Unlike [InvertedIndex::search], which only requires the keys in the list of the key's
+    /// (capped) prefix to contain the term, it requires an exact match, so it is correct even for
+    /// keys longer than `max_index_key_len`.
+    pub(crate) fn contains_key_indexed(&mut self, key: &[u8]) -> io::Result<bool> {
+        let root_addr = match self.get_list_root_address(key)? {
+            Some(addr) => addr,
+            None => return Ok(false),
+        };
+        let now = self.get_stale_cutoff();
+
+        let mut guard = self.list_walk_guard(root_addr);
+        let mut addr = root_addr;
+        loop {
+            guard.step()?;
+            let entry_bytes = read_entry_bytes(&mut self.file, addr)?;
+            let entry = InvertedIndexEntry::from_data_array(&entry_bytes, 0)?;
+
+            if entry.key == key {
+                return Ok(!entry.is_deleted && !entry.is_expired(now));
+            }
+
+            addr = entry.next_offset;
+            // The zero check is for data corruption
+            if addr == root_addr || addr == 0 {
+                return Ok(false);
+            }
+        }
+    }
+
     /// Deletes the key's kv address from all prefixes' lists in the inverted index
     pub(crate) fn remove(&mut self, key: &[u8]) -> io::Result<()> {
         let upper_bound = min(key.len() as u32, self.max_index_key_len) + 1;
 
         for i in 1u32..upper_bound {
             let prefix = &key[..i as usize];
+            if let Some((index_offset, addr)) = self.find_prefix_root_address(prefix)? {
+                self.remove_key_for_prefix(index_offset, &addr, key)?;
+            }
+        }
 
-            let mut index_block = 0;
-            let index_offset = self.header.get_index_offset(prefix);
+        Ok(())
+    }
 
-            loop {
-                let index_offset = self
-                    .header
-                    .get_index_offset_in_nth_block(index_offset, index_block)?;
-                let addr = self.read_entry_address(index_offset)?;
+    /// Updates the expiry of the given key in the lists of all its prefixes
+    pub(crate) fn update_expiry(&mut self, key: &[u8], expiry: u64) -> io::Result<()> {
+        let upper_bound = min(key.len() as u32, self.max_index_key_len) + 1;
 
-                if addr == ZERO_U64_BYTES {
-                    // prefix does not exist
-                    break;
-                } else if self.addr_belongs_to_prefix(&addr, prefix)? {
-                    self.remove_key_for_prefix(index_offset, &addr, key)?;
-                    break;
-                }
+        for i in 1u32..upper_bound {
+            let prefix = &key[..i as usize];
+            if let Some((_, root_addr)) = self.find_prefix_root_address(prefix)? {
+                let root_addr = u64::from_be_bytes(slice_to_array(&root_addr)?);
+                let mut guard = self.list_walk_guard(root_addr);
+                let mut addr = root_addr;
+                loop {
+                    guard.step()?;
+                    let entry_bytes = read_entry_bytes(&mut self.file, addr)?;
+                    let mut entry = InvertedIndexEntry::from_data_array(&entry_bytes, 0)?;
+
+                    if entry.key == key && !entry.is_deleted {
+                        entry.expiry = expiry;
+                        write_entry_to_file(&mut self.file, addr, &entry)?;
+                        break;
+                    }
 
-                index_block += 1;
-                if index_block >= self.header.number_of_index_blocks {
-                    break;
+                    addr = entry.next_offset;
+                    // The zero check is for data corruption
+                    if addr == root_addr || addr == 0 {
+                        break;
+                    }
                 }
             }
         }
@@ -180,16 +333,93 @@ impl InvertedIndex {
         Ok(())
     }
 
+    /// Returns the index offset and the address of the root entry of the list for the given prefix,
+    /// or None if the prefix does not exist
+    fn find_prefix_root_address(&mut self, prefix: &[u8]) -> io::Result<Option<(u64, Vec<u8>)>> {
+        let mut index_block = 0;
+        let index_offset = self.header.get_index_offset(prefix);
+
+        while index_block < self.header.number_of_index_blocks {
+            let index_offset = self
+                .header
+                .get_index_offset_in_nth_block(index_offset, index_block)?;
+            let addr = self.read_entry_address(index_offset)?;
+
+            if addr == ZERO_U64_BYTES {
+                // prefix does not exist
+                return Ok(None);
+            } else if self.addr_belongs_to_prefix(&addr, prefix)? {
+                return Ok(Some((index_offset, addr)));
+            }
+
+            index_block += 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Returns a guard for walking the list whose root entry is at `root_addr`, allowing as many steps
+    /// as there can be entries in the file. A list that takes more steps than that to loop back to its root
+    /// must have a corrupted `next_offset` pointing back into the middle of the list.
+    pub(crate) fn list_walk_guard(&self, root_addr: u64) -> ListWalkGuard {
+        let values_size = self.file_size.saturating_sub(self.values_start_point);
+        ListWalkGuard {
+            root_addr,
+            remaining_steps: values_size / INVERTED_INDEX_ENTRY_MIN_SIZE_IN_BYTES as u64,
+        }
+    }
+
+    /// Re-opens the search index file (if it has a `file_path`), re-reading its header,
+    /// so as to pick up the changes made to the file by other processes
+    pub(crate) fn reload_file(&mut self) -> io::Result<()> {
+        if let Some(file_path) = &self.file_path {
+            let file = OpenOptions::new().write(true).read(true).open(file_path)?;
+            self.file = Box::new(file);
+        }
+
+        let header = InvertedIndexHeader::from_file(&mut self.file)?;
+        self.file_size = self.file.seek(SeekFrom::End(0))?;
+        self.max_index_key_len = header.max_index_key_len;
+        self.values_start_point = header.values_start_point;
+        self.header = header;
+        Ok(())
+    }
+
+    /// Flushes all data written to the search index file to disk
+    pub(crate) fn sync_file(&mut self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+
     /// Clears all the data in the search index, except the header, and its original
     /// variables
     pub(crate) fn clear(&mut self) -> io::Result<()> {
+        self.clear_with_max_keys(self.header.max_keys)
+    }
+
+    /// Clears all the data in the search index, re-initializing it for the new `max_keys`
+    /// so that its index region is shrunk (or grown) to fit the new capacity
+    pub(crate) fn clear_with_max_keys(&mut self, max_keys: u64) -> io::Result<()> {
+        self.reinitialize_file(max_keys, self.header.redundant_blocks)
+    }
+
+    /// Clears all the data in the search index, re-initializing it for the new `redundant_blocks`
+    /// so that its index region is grown (or shrunk) by the difference in redundant blocks
+    pub(crate) fn clear_with_redundant_blocks(&mut self, redundant_blocks: u16) -> io::Result<()> {
+        self.reinitialize_file(self.header.max_keys, redundant_blocks)
+    }
+
+    /// Re-initializes the file with a header for the given `max_keys` and `redundant_blocks`,
+    /// clearing all its data
+    fn reinitialize_file(&mut self, max_keys: u64, redundant_blocks: u16) -> io::Result<()> {
         let header = InvertedIndexHeader::new(
-            Some(self.header.max_keys),
-            Some(self.header.redundant_blocks),
+            Some(max_keys),
+            Some(redundant_blocks),
             Some(self.header.block_size),
             Some(self.max_index_key_len),
         );
         self.file_size = header.initialize_file(&mut self.file)?;
+        self.values_start_point = header.values_start_point;
+        self.header = header;
         Ok(())
     }
 
@@ -201,8 +431,10 @@ impl InvertedIndex {
         key: &[u8],
     ) -> io::Result<()> {
         let mut root_addr = u64::from_be_bytes(slice_to_array(root_addr)?);
+        let mut guard = self.list_walk_guard(root_addr);
         let mut addr = root_addr;
         loop {
+            guard.step()?;
             let entry_bytes = read_entry_bytes(&mut self.file, addr)?;
             let mut entry = InvertedIndexEntry::from_data_array(&entry_bytes, 0)?;
 
@@ -277,26 +509,39 @@ impl InvertedIndex {
         Ok(())
     }
 
-    /// Returns the kv_addresses of all items whose db key contain the given `term`
-    fn get_matched_kv_addrs_for_prefix(
+    /// Returns the kv_addresses of all items whose db key contain (or start with, in [SearchMode::Prefix])
+    /// the given `term`, and whose expiry passes the given `filter`
+    fn get_matched_kv_addrs_for_prefix<F>(
         &mut self,
         term: &[u8],
         prefix_root_addr: &[u8],
         skip: u64,
         limit: u64,
-    ) -> io::Result<Vec<u64>> {
+        mode: SearchMode,
+        filter: F,
+    ) -> io::Result<Vec<u64>>
+    where
+        F: Fn(u64) -> bool,
+    {
         let mut matched_addresses: Vec<u64> = vec![];
         let term_finder = memmem::Finder::new(term);
+        let is_match = |key: &[u8]| match mode {
+            SearchMode::Prefix => key.starts_with(term),
+            SearchMode::Contains => term_finder.find(key).is_some(),
+        };
         let mut skipped = 0u64;
         let should_slice = limit > 0;
+        let now = self.get_stale_cutoff();
 
         let root_addr = u64::from_be_bytes(slice_to_array(prefix_root_addr)?);
+        let mut guard = self.list_walk_guard(root_addr);
         let mut addr = root_addr;
         loop {
+            guard.step()?;
             let entry_bytes = read_entry_bytes(&mut self.file, addr)?;
             let entry = InvertedIndexEntry::from_data_array(&entry_bytes, 0)?;
 
-            if !entry.is_expired() && term_finder.find(entry.key).is_some() {
+            if !entry.is_expired(now) && filter(entry.expiry) && is_match(entry.key) {
                 if skipped < skip {
                     skipped += 1;
                 } else {
@@ -331,9 +576,11 @@ impl InvertedIndex {
         expiry: u64,
     ) -> io::Result<()> {
         let root_address = u64::from_be_bytes(slice_to_array(root_address)?);
+        let mut guard = self.list_walk_guard(root_address);
         let mut addr = root_address;
 
         loop {
+            guard.step()?;
             let entry_bytes = read_entry_bytes(&mut self.file, addr)?;
             let mut entry = InvertedIndexEntry::from_data_array(&entry_bytes, 0)?;
 
@@ -352,7 +599,7 @@ impl InvertedIndex {
                     kv_address,
                     root_address,
                     addr,
-                );
+                )?;
 
                 let new_entry_len =
                     write_entry_to_file(&mut self.file, self.file_size, &new_entry)?;
@@ -398,7 +645,7 @@ impl InvertedIndex {
     ) -> io::Result<()> {
         let new_addr = self.file.seek(SeekFrom::End(0))?;
         let entry =
-            InvertedIndexEntry::new(prefix, key, expiry, true, kv_address, new_addr, new_addr);
+            InvertedIndexEntry::new(prefix, key, expiry, true, kv_address, new_addr, new_addr)?;
         let entry_as_bytes = entry.as_bytes();
         self.file.write_all(&entry_as_bytes)?;
 
@@ -419,7 +666,8 @@ impl InvertedIndex {
     fn read_entry_address(&mut self, address: u64) -> io::Result<Vec<u8>> {
         validate_bounds!(
             (address, address + INDEX_ENTRY_SIZE_IN_BYTES),
-            (HEADER_SIZE_IN_BYTES, self.values_start_point)
+            (HEADER_SIZE_IN_BYTES, self.values_start_point),
+            "read search index entry address"
         )?;
 
         let size = INDEX_ENTRY_SIZE_IN_BYTES as usize;
@@ -471,9 +719,35 @@ impl PartialEq for InvertedIndex {
     }
 }
 
+/// A bound on the number of entries visited while walking a list of the inverted index,
+/// so that a list corrupted into a cycle that skips its root fails instead of looping forever
+pub(crate) struct ListWalkGuard {
+    root_addr: u64,
+    remaining_steps: u64,
+}
+
+impl ListWalkGuard {
+    /// Records a step to the next entry in the list, failing with [io::ErrorKind::InvalidData]
+    /// if the list has more entries than the file could possibly hold
+    pub(crate) fn step(&mut self) -> io::Result<()> {
+        if self.remaining_steps == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "search index list at {} does not cycle back to its root",
+                    self.root_addr
+                ),
+            ));
+        }
+
+        self.remaining_steps -= 1;
+        Ok(())
+    }
+}
+
 /// Reads a byte array for an entry at the given address in a file.
 /// It returns None if the data ended prematurely
-fn read_entry_bytes(file: &mut File, address: u64) -> io::Result<Vec<u8>> {
+fn read_entry_bytes(file: &mut dyn StorageFile, address: u64) -> io::Result<Vec<u8>> {
     let mut size_buf = [0u8; 4];
     file.seek(SeekFrom::Start(address))?;
     file.read_exact(&mut size_buf)?;
@@ -489,7 +763,7 @@ fn read_entry_bytes(file: &mut File, address: u64) -> io::Result<Vec<u8>> {
 /// Writes a given entry to the file at the given address, returning the number of bytes written
 #[inline(always)]
 fn write_entry_to_file(
-    file: &mut File,
+    file: &mut dyn StorageFile,
     address: u64,
     entry: &InvertedIndexEntry<'_>,
 ) -> io::Result<usize> {
@@ -510,6 +784,9 @@ mod tests {
     use crate::internal::get_current_timestamp;
     use serial_test::serial;
 
+    /// A search term with its `skip` and `limit`, and the kv addresses expected back
+    type SearchTestCase<'a> = ((&'a str, u64, u64), Vec<u64>);
+
     #[test]
     #[serial]
     fn new_with_non_existing_file() {
@@ -526,7 +803,7 @@ mod tests {
 
         let test_data: Vec<(Config<'_>, Expected)> = vec![
             (
-                (&Path::new(file_name), None, None, None),
+                (Path::new(file_name), None, None, None),
                 Expected {
                     max_index_key_len: DEFAULT_MAX_INDEX_KEY_LEN,
                     values_start_point: InvertedIndexHeader::new(None, None, None, None)
@@ -536,7 +813,7 @@ mod tests {
                 },
             ),
             (
-                (&Path::new(file_name), Some(10), None, None),
+                (Path::new(file_name), Some(10), None, None),
                 Expected {
                     max_index_key_len: 10,
                     values_start_point: InvertedIndexHeader::new(None, None, None, Some(10))
@@ -547,7 +824,7 @@ mod tests {
                 },
             ),
             (
-                (&Path::new(file_name), None, Some(360), None),
+                (Path::new(file_name), None, Some(360), None),
                 Expected {
                     max_index_key_len: DEFAULT_MAX_INDEX_KEY_LEN,
                     values_start_point: InvertedIndexHeader::new(Some(360), None, None, None)
@@ -558,7 +835,7 @@ mod tests {
                 },
             ),
             (
-                (&Path::new(file_name), None, None, Some(4)),
+                (Path::new(file_name), None, None, Some(4)),
                 Expected {
                     max_index_key_len: DEFAULT_MAX_INDEX_KEY_LEN,
                     values_start_point: InvertedIndexHeader::new(None, Some(4), None, None)
@@ -571,7 +848,7 @@ mod tests {
         ];
 
         // delete the file so that SearchIndex::new() can reinitialize it.
-        fs::remove_file(&file_name).ok();
+        fs::remove_file(file_name).ok();
 
         for ((file_path, max_index_key_len, max_keys, redundant_blocks), expected) in test_data {
             let got = InvertedIndex::new(file_path, max_index_key_len, max_keys, redundant_blocks)
@@ -579,11 +856,12 @@ mod tests {
 
             assert_eq!(&got.max_index_key_len, &expected.max_index_key_len);
             assert_eq!(&got.values_start_point, &expected.values_start_point);
-            assert_eq!(&got.file_path, &expected.file_path);
+            assert_eq!(got.file_path.as_ref(), Some(&expected.file_path));
             assert_eq!(&got.file_size, &expected.file_size);
 
             // delete the file so that SearchIndex::new() can reinitialize it for the next iteration
-            fs::remove_file(&got.file_path).expect(&format!("delete file {:?}", &got.file_path));
+            fs::remove_file(got.file_path.as_ref().unwrap())
+                .unwrap_or_else(|_| panic!("delete file {:?}", &got.file_path));
         }
     }
 
@@ -593,10 +871,10 @@ mod tests {
         type Config<'a> = (&'a Path, Option<u32>, Option<u64>, Option<u16>);
         let file_name = "testdb.iscdb";
         let test_data: Vec<Config<'_>> = vec![
-            (&Path::new(file_name), None, None, None),
-            (&Path::new(file_name), Some(7), None, None),
-            (&Path::new(file_name), None, Some(3000), None),
-            (&Path::new(file_name), None, None, Some(6)),
+            (Path::new(file_name), None, None, None),
+            (Path::new(file_name), Some(7), None, None),
+            (Path::new(file_name), None, Some(3000), None),
+            (Path::new(file_name), None, None, Some(6)),
         ];
 
         for (file_path, max_index_key_len, max_keys, redundant_blocks) in test_data {
@@ -609,8 +887,8 @@ mod tests {
 
             assert_eq!(&first, &second);
             // delete the file so that SearchIndex::new() can reinitialize it for the next iteration
-            fs::remove_file(&first.file_path)
-                .expect(&format!("delete file {:?}", &first.file_path));
+            fs::remove_file(first.file_path.as_ref().unwrap())
+                .unwrap_or_else(|_| panic!("delete file {:?}", &first.file_path));
         }
     }
 
@@ -652,7 +930,8 @@ mod tests {
         test_search_results(&mut search, &expected_results);
 
         // delete the index file
-        fs::remove_file(&search.file_path).expect(&format!("delete file {:?}", &search.file_path));
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
     }
 
     #[test]
@@ -680,7 +959,7 @@ mod tests {
         for (key, offset, expiry) in updates {
             search
                 .add(key.as_bytes(), offset, expiry)
-                .expect(&format!("update key offset {}", key));
+                .unwrap_or_else(|_| panic!("update key offset {}", key));
         }
 
         let expected_results = vec![
@@ -703,7 +982,8 @@ mod tests {
         test_search_results(&mut search, &expected_results);
 
         // delete the index file
-        fs::remove_file(&search.file_path).expect(&format!("delete file {:?}", &search.file_path));
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
     }
 
     #[test]
@@ -748,7 +1028,8 @@ mod tests {
         test_search_results(&mut search, &expected_results);
 
         // delete the index file
-        fs::remove_file(&search.file_path).expect(&format!("delete file {:?}", &search.file_path));
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
     }
 
     #[test]
@@ -792,7 +1073,89 @@ mod tests {
         test_search_results(&mut search, &expected_results);
 
         // delete the index file
-        fs::remove_file(&search.file_path).expect(&format!("delete file {:?}", &search.file_path));
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
+    }
+
+    #[test]
+    #[serial]
+    fn contains_key_indexed_works() {
+        let file_name = "testdb.iscdb";
+        let now = get_current_timestamp();
+        let test_data = vec![
+            ("foo", 20, 0),
+            ("food", 60, now + 3600),
+            ("bar", 600, now - 3600), // expired
+            ("barricade", 900, 0),
+        ];
+
+        let mut search = create_search_index(file_name, &test_data);
+        search.remove("foo".as_bytes()).expect("delete foo");
+
+        let test_table = vec![
+            ("food", true),
+            ("barricade", true),
+            // keys merely starting with, or contained in, indexed keys are not in the index
+            ("barr", false),
+            ("barricad", false),
+            ("fo", false),
+            ("barricades", false),
+            // deleted or expired keys are not in the index
+            ("foo", false),
+            ("bar", false),
+            ("pig", false),
+        ];
+        for (key, expected) in test_table {
+            let got = search
+                .contains_key_indexed(key.as_bytes())
+                .unwrap_or_else(|_| panic!("contains key {}", key));
+            assert_eq!(got, expected, "{}", key);
+        }
+
+        // delete the index file
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
+    }
+
+    #[test]
+    #[serial]
+    fn corrupted_list_fails_instead_of_looping() {
+        let file_name = "testdb.iscdb";
+        let test_data = vec![("foo", 20, 0), ("fore", 160, 0), ("food", 60, 0)];
+        let mut search = create_search_index(file_name, &test_data);
+
+        // point the last entry of the list of "f" back to the second one, skipping the root
+        let (_, root_addr) = search
+            .find_prefix_root_address(&b"f"[..])
+            .expect("find root")
+            .expect("root of f");
+        let root_addr = u64::from_be_bytes(slice_to_array(&root_addr).expect("root address"));
+        let root_bytes = read_entry_bytes(&mut search.file, root_addr).expect("read root");
+        let root = InvertedIndexEntry::from_data_array(&root_bytes, 0).expect("parse root");
+        let last_bytes =
+            read_entry_bytes(&mut search.file, root.previous_offset).expect("read last");
+        let last = InvertedIndexEntry::from_data_array(&last_bytes, 0).expect("parse last");
+        last.update_next_offset_on_file(&mut search.file, root.previous_offset, root.next_offset)
+            .expect("corrupt last entry");
+
+        let is_cycle_error = |r: io::Result<()>| {
+            matches!(r, Err(e) if e.kind() == io::ErrorKind::InvalidData
+                && e.to_string().contains("does not cycle back to its root"))
+        };
+        assert!(is_cycle_error(
+            search
+                .search(&b"f"[..], 0, 0, SearchMode::Contains)
+                .map(|_| ())
+        ));
+        assert!(is_cycle_error(search.add(&b"fig"[..], 90, 0)));
+        assert!(is_cycle_error(search.update_expiry(&b"fix"[..], 0)));
+        assert!(is_cycle_error(search.remove(&b"fix"[..])));
+
+        // lists that are not corrupted still work
+        test_search_results(&mut search, &[(("foo", 0, 0), vec![20, 60])]);
+
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
     }
 
     #[test]
@@ -834,19 +1197,100 @@ mod tests {
         test_search_results(&mut search, &expected_results);
 
         // delete the index file
-        fs::remove_file(&search.file_path).expect(&format!("delete file {:?}", &search.file_path));
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
+    }
+
+    #[test]
+    #[serial]
+    fn search_with_filter_works() {
+        let file_name = "testdb.iscdb";
+        let now = get_current_timestamp();
+        let test_data = vec![
+            ("foo", 20, 0),
+            ("food", 60, now + 10),
+            ("fore", 160, now + 3600),
+            ("fort", 200, now + 7200),
+            ("bar", 600, now + 3600),
+        ];
+
+        let mut search = create_search_index(file_name, &test_data);
+        let min_expiry = now + 100;
+        let has_min_ttl = |expiry: u64| expiry == 0 || expiry >= min_expiry;
+
+        let expected_results = vec![
+            (("f", 0u64, 0u64), vec![20u64, 160, 200]),
+            // the filtered out entries do not count towards skip and limit
+            (("f", 1, 0), vec![160, 200]),
+            (("f", 1, 1), vec![160]),
+            (("fo", 0, 2), vec![20, 160]),
+            (("food", 0, 0), vec![]),
+            (("bar", 0, 0), vec![600]),
+        ];
+        for ((term, skip, limit), expected) in expected_results {
+            let got = search
+                .search_with_filter(
+                    term.as_bytes(),
+                    skip,
+                    limit,
+                    SearchMode::Contains,
+                    has_min_ttl,
+                )
+                .unwrap_or_else(|_| panic!("search {}", term));
+            assert_eq!(got, expected, "{} {} {}", term, skip, limit);
+        }
+
+        // delete the index file
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
+    }
+
+    #[test]
+    #[serial]
+    fn search_with_prefix_mode_works() {
+        let file_name = "testdb.iscdb";
+        let test_data = vec![
+            ("foo", 20, 0),
+            ("foobar", 60, 0),
+            ("foofoob", 160, 0),
+            ("barfoob", 600, 0),
+        ];
+
+        let mut search = create_search_index(file_name, &test_data);
+
+        let expected_results = vec![
+            (("foo", SearchMode::Prefix), vec![20u64, 60, 160]),
+            (("foo", SearchMode::Contains), vec![20, 60, 160]),
+            // terms longer than the max_index_key_len are matched on the full term
+            (("foob", SearchMode::Prefix), vec![60]),
+            (("foob", SearchMode::Contains), vec![60, 160]),
+            // keys that contain the term but do not share its prefix are missed
+            (("oob", SearchMode::Prefix), vec![]),
+            (("oob", SearchMode::Contains), vec![]),
+        ];
+
+        for ((term, mode), expected) in expected_results {
+            let got = search
+                .search(term.as_bytes(), 0, 0, mode)
+                .unwrap_or_else(|_| panic!("search {} {:?}", term, mode));
+            assert_eq!(got, expected, "term: {}, mode: {:?}", term, mode);
+        }
+
+        // delete the index file
+        fs::remove_file(search.file_path.as_ref().unwrap())
+            .unwrap_or_else(|_| panic!("delete file {:?}", &search.file_path));
     }
 
     /// Initializes a new SearchIndex and adds the given test_data
     fn create_search_index(file_name: &str, test_data: &Vec<(&str, u64, u64)>) -> InvertedIndex {
-        let mut search = InvertedIndex::new(&Path::new(file_name), None, None, None)
+        let mut search = InvertedIndex::new(Path::new(file_name), None, None, None)
             .expect("create a new instance of SearchIndex");
         search.clear().expect("clear the search");
         // add a series of keys and their offsets
         for (key, offset, expiry) in test_data {
             search
                 .add(key.as_bytes(), *offset, *expiry)
-                .expect(&format!("add key offset {}", key));
+                .unwrap_or_else(|_| panic!("add key offset {}", key));
         }
 
         search
@@ -854,14 +1298,11 @@ mod tests {
 
     /// tests the search index's search to see if when searched, the expected results
     /// are returned
-    fn test_search_results(
-        idx: &mut InvertedIndex,
-        expected_results: &Vec<((&str, u64, u64), Vec<u64>)>,
-    ) {
+    fn test_search_results(idx: &mut InvertedIndex, expected_results: &[SearchTestCase<'_>]) {
         for ((term, skip, limit), expected) in expected_results {
             let got = idx
-                .search(term.as_bytes(), *skip, *limit)
-                .expect(&format!("search {}", term));
+                .search(term.as_bytes(), *skip, *limit, SearchMode::Contains)
+                .unwrap_or_else(|_| panic!("search {}", term));
 
             assert_eq!(got, *expected);
         }
@@ -10,13 +10,32 @@ macro_rules! acquire_lock {
     };
 }
 
-/// Slices a slice safely, throwing an error if it goes out of bounds
+/// Tries to acquire the lock on a Mutex without blocking, returning Ok(None) if it is held elsewhere,
+/// or an io Error if it is poisoned
+macro_rules! try_acquire_lock {
+    ($v:expr) => {
+        match $v.try_lock() {
+            Ok(guard) => Ok(Some(guard)),
+            Err(std::sync::TryLockError::WouldBlock) => Ok(None),
+            Err(std::sync::TryLockError::Poisoned(e)) => Err(std::io::Error::new(
+                io::ErrorKind::Other,
+                format!("failed to acquire lock on database: {}", e),
+            )),
+        }
+    };
+}
+
+/// Slices a slice safely, throwing an error if it goes out of bounds.
+/// An empty slice at the very end of the data e.g. a zero-length value is within bounds
 macro_rules! safe_slice {
     ($data:expr, $start:expr, $end:expr, $max_len:expr) => {
-        if $start >= $max_len || $end > $max_len {
+        if $start > $end || $end > $max_len {
             Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("slice {} - {} out of bounds for {:?}", $start, $end, $data),
+                io::ErrorKind::InvalidData,
+                format!(
+                    "slice {}-{} is out of bounds for data of {} bytes",
+                    $start, $end, $max_len
+                ),
             ))
         } else {
             Ok(&$data[$start..$end])
@@ -25,21 +44,15 @@ macro_rules! safe_slice {
 }
 
 /// Checks if the given range is within bounds or else returns an InvalidData error
+/// naming the `operation` that was attempted
 macro_rules! validate_bounds {
-    (($actual_lower:expr, $actual_upper:expr), ($expected_lower:expr, $expected_upper:expr) $(,$message:expr)?) => {
+    (($actual_lower:expr, $actual_upper:expr), ($expected_lower:expr, $expected_upper:expr), $operation:expr) => {
         if $actual_lower < $expected_lower || $actual_upper > $expected_upper {
-            let _msg = "";
-            $(let _msg = $message;)?
-
             Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!(
-                    "{} Span {}-{} is out of bounds for {}-{}",
-                    _msg,
-                    $actual_lower,
-                    $actual_upper,
-                    $expected_lower,
-                    $expected_upper,
+                    "{}: span {}-{} is out of bounds for {}-{}",
+                    $operation, $actual_lower, $actual_upper, $expected_lower, $expected_upper,
                 ),
             ))
         } else {
@@ -50,4 +63,5 @@ macro_rules! validate_bounds {
 
 pub(crate) use acquire_lock;
 pub(crate) use safe_slice;
+pub(crate) use try_acquire_lock;
 pub(crate) use validate_bounds;
@@ -43,10 +43,60 @@ pub(crate) fn initialize_db_folder(store_path: &Path) -> io::Result<()> {
     std::fs::create_dir_all(store_path)
 }
 
+/// Converts the given path into the bytes of a key, as is i.e. without normalizing it.
+///
+/// On unix, these are the raw bytes of the path. On windows, the UTF-16 of the path is converted into
+/// [WTF-8](https://simonsapin.github.io/wtf-8/), which is plain UTF-8 except for unpaired surrogates.
+/// So paths that are valid unicode are converted into their UTF-8 on every platform.
+#[cfg(unix)]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Converts the given path into the bytes of a key, as is i.e. without normalizing it.
+///
+/// On unix, these are the raw bytes of the path. On windows, the UTF-16 of the path is converted into
+/// [WTF-8](https://simonsapin.github.io/wtf-8/), which is plain UTF-8 except for unpaired surrogates.
+/// So paths that are valid unicode are converted into their UTF-8 on every platform.
+#[cfg(windows)]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+    let mut bytes = Vec::with_capacity(path.as_os_str().len());
+    for c in char::decode_utf16(path.as_os_str().encode_wide()) {
+        match c {
+            Ok(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0u8; 4]).as_bytes()),
+            Err(e) => {
+                // unpaired surrogates are encoded just like any other 3-byte code point
+                let c = e.unpaired_surrogate();
+                bytes.extend_from_slice(&[
+                    0xE0 | (c >> 12) as u8,
+                    0x80 | ((c >> 6) & 0x3F) as u8,
+                    0x80 | (c & 0x3F) as u8,
+                ]);
+            }
+        }
+    }
+    bytes
+}
+
 /// Extracts a byte array of size N from a byte array slice
 pub(crate) fn slice_to_array<const N: usize>(data: &[u8]) -> io::Result<[u8; N]> {
-    data.try_into()
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    data.try_into().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected {} bytes but got {}", N, data.len()),
+        )
+    })
+}
+
+/// Prefixes the message of the given error with the `operation` that failed and the `address`
+/// in the file it failed at, keeping the kind of the error
+pub(crate) fn add_error_context(e: io::Error, operation: &str, address: u64) -> io::Error {
+    io::Error::new(
+        e.kind(),
+        format!("{} at address {}: {}", operation, address, e),
+    )
 }
 
 /// Converts a byte array into a boolean
@@ -99,6 +149,35 @@ mod tests {
         std::fs::remove_dir_all(store_path).expect("removes the test_db_utils folder");
     }
 
+    #[test]
+    fn path_to_bytes_works() {
+        let path = Path::new("cache/résumé.pdf");
+        assert_eq!(path_to_bytes(path), "cache/résumé.pdf".as_bytes().to_vec());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_to_bytes_keeps_non_unicode_bytes_on_unix() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(&[102, 111, 0xff, 111]));
+        assert_eq!(path_to_bytes(path), vec![102, 111, 0xff, 111]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn path_to_bytes_encodes_unpaired_surrogates_on_windows() {
+        use std::ffi::OsString;
+        use std::os::windows::ffi::OsStringExt;
+
+        let path = OsString::from_wide(&[102, 0xD800, 111]);
+        assert_eq!(
+            path_to_bytes(Path::new(&path)),
+            vec![102, 0xED, 0xA0, 0x80, 111]
+        );
+    }
+
     #[test]
     fn slice_to_array_works() {
         let data: Vec<u8> = vec![0, 1, 2, 3, 4, 5, 6, 7, 8];
@@ -111,6 +190,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn slice_to_array_fails_with_lengths() {
+        let data: Vec<u8> = vec![0, 1, 2];
+        let err = slice_to_array::<4>(&data).expect_err("slice too short");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "expected 4 bytes but got 3");
+    }
+
+    #[test]
+    fn add_error_context_works() {
+        let err = io::Error::new(io::ErrorKind::InvalidData, "expected 4 bytes but got 3");
+        let err = add_error_context(err, "get_value", 4096);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "get_value at address 4096: expected 4 bytes but got 3"
+        );
+    }
+
     #[test]
     fn byte_array_to_bool_works() {
         let test_data: [(&[u8], bool); 4] = [
@@ -0,0 +1,162 @@
+use crate::internal::macros::safe_slice;
+use crate::internal::slice_to_array;
+use crate::storage::StorageFile;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use twox_hash::xxh3::hash64;
+
+/// The size of the header of a record i.e. the length (4 bytes) and the checksum (8 bytes) of its body
+const RECORD_HEADER_SIZE_IN_BYTES: usize = 4 + 8;
+
+/// A write at a given address of the database file, as recorded in the write-ahead log
+pub(crate) type PendingWrite = (u64, Vec<u8>);
+
+/// A write-ahead log that makes a group of writes to the database file all-or-nothing across a crash
+///
+/// The writes of an operation are recorded in the log, and the log is synced to disk, before they
+/// are made to the database file. Once the database file is synced, the log is cleared.
+/// If the process crashes midway, the writes still in the log are replayed when the store is opened.
+///
+/// The log holds at most one record, of the form: `[body length (u32)][checksum of body (u64)][body]`
+/// where the body is a sequence of writes, each of the form: `[address (u64)][data length (u32)][data]`.
+/// A record whose checksum does not match was torn by a crash before any of its writes were made,
+/// so it is discarded.
+#[derive(Debug)]
+pub(crate) struct WriteAheadLog {
+    file: Box<dyn StorageFile>,
+}
+
+impl WriteAheadLog {
+    /// Creates a new write-ahead log for the file at the given path (creating it if necessary)
+    pub(crate) fn new(file_path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create(true)
+            .truncate(false)
+            .open(file_path)?;
+        Ok(Self::from_file(Box::new(file)))
+    }
+
+    /// Creates a new write-ahead log over the given file
+    pub(crate) fn from_file(file: Box<dyn StorageFile>) -> Self {
+        Self { file }
+    }
+
+    /// Records the given writes in the log, syncing it to disk, before they are made
+    pub(crate) fn log(&mut self, writes: &[(u64, &[u8])]) -> io::Result<()> {
+        let body: Vec<u8> = writes
+            .iter()
+            .flat_map(|(addr, data)| {
+                addr.to_be_bytes()
+                    .into_iter()
+                    .chain((data.len() as u32).to_be_bytes())
+                    .chain(data.iter().copied())
+            })
+            .collect();
+
+        let record: Vec<u8> = (body.len() as u32)
+            .to_be_bytes()
+            .into_iter()
+            .chain(hash64(&body).to_be_bytes())
+            .chain(body)
+            .collect();
+
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&record)?;
+        self.file.sync_all()
+    }
+
+    /// Clears the log after its writes have been made and synced to disk
+    pub(crate) fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_all()
+    }
+
+    /// Returns the writes that were recorded in the log but were not cleared
+    /// i.e. those that may not have been made to the database file
+    pub(crate) fn get_pending_writes(&mut self) -> io::Result<Vec<PendingWrite>> {
+        let mut data = vec![];
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut data)?;
+        let data_len = data.len();
+
+        if data_len < RECORD_HEADER_SIZE_IN_BYTES {
+            return Ok(vec![]);
+        }
+
+        let body_len = u32::from_be_bytes(slice_to_array(&data[0..4])?) as usize;
+        let checksum = u64::from_be_bytes(slice_to_array(&data[4..12])?);
+        let body = &data[RECORD_HEADER_SIZE_IN_BYTES..];
+        if body.len() < body_len || hash64(&body[..body_len]) != checksum {
+            // the record was torn so none of its writes were made
+            return Ok(vec![]);
+        }
+
+        let body = &body[..body_len];
+        let mut writes = vec![];
+        let mut cursor = 0;
+        while cursor < body_len {
+            let addr = u64::from_be_bytes(slice_to_array(safe_slice!(
+                body,
+                cursor,
+                cursor + 8,
+                body_len
+            )?)?);
+            let data_len = u32::from_be_bytes(slice_to_array(safe_slice!(
+                body,
+                cursor + 8,
+                cursor + 12,
+                body_len
+            )?)?) as usize;
+            let data = safe_slice!(body, cursor + 12, cursor + 12 + data_len, body_len)?;
+            writes.push((addr, data.to_vec()));
+            cursor += 12 + data_len;
+        }
+
+        Ok(writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn log_and_get_pending_writes_work() {
+        let mut wal = WriteAheadLog::from_file(Box::new(Cursor::new(vec![])));
+        assert_eq!(wal.get_pending_writes().expect("get pending"), vec![]);
+
+        wal.log(&[(900, &b"foo"[..]), (108, &[0, 0, 0, 0, 0, 0, 3, 132])])
+            .expect("log writes");
+        let expected = vec![
+            (900, b"foo".to_vec()),
+            (108, vec![0, 0, 0, 0, 0, 0, 3, 132]),
+        ];
+        assert_eq!(wal.get_pending_writes().expect("get pending"), expected);
+
+        // a new record replaces the old one
+        wal.log(&[(34, &[1u8][..])]).expect("log writes");
+        let expected = vec![(34, vec![1u8])];
+        assert_eq!(wal.get_pending_writes().expect("get pending"), expected);
+
+        wal.clear().expect("clear");
+        assert_eq!(wal.get_pending_writes().expect("get pending"), vec![]);
+    }
+
+    #[test]
+    fn torn_records_are_discarded() {
+        let mut wal = WriteAheadLog::from_file(Box::new(Cursor::new(vec![])));
+        wal.log(&[(900, &b"foo"[..]), (108, &b"bar"[..])])
+            .expect("log writes");
+        let record_len = wal.file.seek(SeekFrom::End(0)).expect("get log size");
+
+        for len in [record_len - 1, 8, 0] {
+            wal.file.set_len(len).expect("tear record");
+            assert_eq!(wal.get_pending_writes().expect("get pending"), vec![]);
+        }
+    }
+}
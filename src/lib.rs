@@ -98,7 +98,46 @@ Next:
 #![deny(missing_docs)]
 #![warn(rust_2018_idioms)]
 
+#[cfg(feature = "tokio")]
+pub use async_store::AsyncStore;
+pub use builder::StoreBuilder;
+pub use bulk::BulkLoader;
+pub use cache::Cache;
+pub use clock::{Clock, SystemClock};
+pub use config::StoreConfig;
+pub use corruption::Corruption;
+pub use dump::ValueEncoding;
+pub use error::{Result, ScdbError};
+pub use hasher::{KeyHasher, Xxh3Hasher};
+pub use merge::ConflictPolicy;
+pub use meta::ValueMeta;
+pub use namespace::Namespace;
+pub use probing::Probing;
+pub use scan::ScanCursor;
+pub use search::SearchMode;
+pub use stats::{CacheStats, CompactionPreview, FragmentationReport, IoStats, SizeHistogram};
+pub use storage::StorageFile;
 pub use store::Store;
 
+#[cfg(feature = "tokio")]
+mod async_store;
+mod builder;
+mod bulk;
+mod cache;
+mod clock;
+mod config;
+mod corruption;
+mod dump;
+mod error;
+mod hasher;
 mod internal;
+mod merge;
+mod meta;
+mod namespace;
+mod probing;
+mod scan;
+mod search;
+mod stats;
+mod storage;
 mod store;
+mod telemetry;
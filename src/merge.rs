@@ -0,0 +1,58 @@
+/// What [crate::Store::merge_from] does with a key that exists in both stores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keeps the existing key-value pair, ignoring the one from the other store
+    Skip,
+    /// Replaces the existing key-value pair with the one from the other store
+    Overwrite,
+    /// Keeps whichever of the two key-value pairs expires later, with those that never expire
+    /// being the latest. The existing one is kept if they expire at the same time
+    KeepNewerExpiry,
+}
+
+impl ConflictPolicy {
+    /// Returns true if an existing key-value pair that expires at `existing_expiry` is to be replaced by
+    /// one from the other store that expires at `new_expiry`, both being 0 if they never expire
+    pub(crate) fn should_replace(&self, existing_expiry: u64, new_expiry: u64) -> bool {
+        match self {
+            ConflictPolicy::Skip => false,
+            ConflictPolicy::Overwrite => true,
+            ConflictPolicy::KeepNewerExpiry => {
+                let as_latest = |expiry: u64| if expiry == 0 { u64::MAX } else { expiry };
+                as_latest(new_expiry) > as_latest(existing_expiry)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_replace_works() {
+        let test_data = [
+            (ConflictPolicy::Skip, 10, 20, false),
+            (ConflictPolicy::Skip, 0, 0, false),
+            (ConflictPolicy::Overwrite, 20, 10, true),
+            (ConflictPolicy::Overwrite, 0, 10, true),
+            (ConflictPolicy::KeepNewerExpiry, 10, 20, true),
+            (ConflictPolicy::KeepNewerExpiry, 20, 10, false),
+            (ConflictPolicy::KeepNewerExpiry, 20, 20, false),
+            (ConflictPolicy::KeepNewerExpiry, 20, 0, true),
+            (ConflictPolicy::KeepNewerExpiry, 0, 20, false),
+            (ConflictPolicy::KeepNewerExpiry, 0, 0, false),
+        ];
+
+        for (policy, existing_expiry, new_expiry, expected) in test_data {
+            assert_eq!(
+                policy.should_replace(existing_expiry, new_expiry),
+                expected,
+                "{:?}: existing {}, new {}",
+                policy,
+                existing_expiry,
+                new_expiry
+            );
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::internal::{KeyValueEntry, ValueEntry};
+
+/// The value of a key in the [crate::Store] together with its expiry, and whether it is deleted or expired
+///
+/// Unlike [crate::Store::get], which treats deleted and expired keys alike as missing,
+/// it is returned by [crate::Store::get_meta] for such keys too, as long as their entries
+/// are still in the database file. This allows callers to apply their own staleness policies,
+/// say, serving an expired value while a fresh one is being computed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ValueMeta {
+    /// The value of the key
+    pub data: Vec<u8>,
+    /// The timestamp (in seconds from unix epoch) at which the key expires, or 0 if it never expires
+    pub expiry: u64,
+    /// Whether the key has been deleted
+    pub is_deleted: bool,
+    /// Whether the key had expired at the time it was read
+    pub is_expired: bool,
+}
+
+impl ValueMeta {
+    /// Creates a ValueMeta from the given key-value entry, checking its expiry against `now`,
+    /// the current timestamp
+    pub(crate) fn new(entry: &KeyValueEntry<'_>, now: u64) -> Self {
+        Self {
+            data: entry.value.to_vec(),
+            expiry: entry.expiry,
+            is_deleted: entry.is_deleted,
+            is_expired: entry.is_expired(now),
+        }
+    }
+
+    /// Returns true if the value is either deleted or expired i.e. [crate::Store::get] would not return it
+    pub fn is_stale(&self) -> bool {
+        self.is_deleted || self.is_expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_meta_new_works() {
+        let now = 1_700_000_000;
+        let mut deleted =
+            KeyValueEntry::new(&b"deleted"[..], &b"baz"[..], 0).expect("new key value entry");
+        deleted.is_deleted = true;
+        let test_table = vec![
+            (
+                KeyValueEntry::new(&b"never_expires"[..], &b"bar"[..], 0)
+                    .expect("new key value entry"),
+                ValueMeta {
+                    data: b"bar".to_vec(),
+                    expiry: 0,
+                    is_deleted: false,
+                    is_expired: false,
+                },
+            ),
+            (
+                KeyValueEntry::new(&b"expired"[..], &b"foo"[..], now - 1)
+                    .expect("new key value entry"),
+                ValueMeta {
+                    data: b"foo".to_vec(),
+                    expiry: now - 1,
+                    is_deleted: false,
+                    is_expired: true,
+                },
+            ),
+            (
+                KeyValueEntry::new(&b"not_expired"[..], &b"fee"[..], now + 1)
+                    .expect("new key value entry"),
+                ValueMeta {
+                    data: b"fee".to_vec(),
+                    expiry: now + 1,
+                    is_deleted: false,
+                    is_expired: false,
+                },
+            ),
+            (
+                deleted,
+                ValueMeta {
+                    data: b"baz".to_vec(),
+                    expiry: 0,
+                    is_deleted: true,
+                    is_expired: false,
+                },
+            ),
+        ];
+
+        for (kv, expected) in test_table {
+            let got = ValueMeta::new(&kv, now);
+            assert_eq!(got.is_stale(), kv.is_deleted || kv.is_expired(now));
+            assert_eq!(got, expected);
+        }
+    }
+}
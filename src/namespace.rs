@@ -0,0 +1,211 @@
+use crate::error::{Result, ScdbError};
+use crate::search::SearchMode;
+use crate::store::validate_key;
+use crate::Store;
+
+/// A handle to the key-value pairs of a [Store] whose keys start with a given prefix
+///
+/// It allows multiple logical stores to share one database file (and one background compaction task).
+/// The prefix is transparently prepended to every key passed to it, and stripped from the keys it returns.
+/// It is got via [Store::namespaced].
+///
+/// Note that the namespaces of a store should not be prefixes of each other e.g. "user" and "users",
+/// otherwise [Namespace::search] and [Namespace::clear] of the shorter one will reach into the longer one.
+///
+/// # Examples
+///
+/// ```rust
+/// # use scdb::Store;
+/// #
+/// # fn main() -> std::io::Result<()> {
+/// # let mut store = Store::new("db", None, None, None, None, true)?;
+/// # store.clear()?;
+/// let mut sessions = store.namespaced(&b"sessions:"[..])?;
+/// sessions.set(&b"foo"[..], &b"bar"[..], None)?;
+/// assert_eq!(sessions.search(&b"f"[..], 0, 0)?, vec![(b"foo".to_vec(), b"bar".to_vec())]);
+///
+/// // clearing the namespace leaves the rest of the store intact
+/// # store.set(&b"foo"[..], &b"baz"[..], None)?;
+/// let mut sessions = store.namespaced(&b"sessions:"[..])?;
+/// sessions.clear()?;
+/// assert_eq!(sessions.get(&b"foo"[..])?, None);
+/// assert_eq!(store.get(&b"foo"[..])?, Some(b"baz".to_vec()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Namespace<'a> {
+    store: &'a mut Store,
+    prefix: Vec<u8>,
+}
+
+impl<'a> Namespace<'a> {
+    /// Creates a new namespace of the given store, for keys starting with the given prefix
+    pub(crate) fn new(store: &'a mut Store, prefix: &[u8]) -> Result<Self> {
+        if prefix.is_empty() {
+            return Err(ScdbError::InvalidInput(
+                "namespace prefix must not be empty".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            store,
+            prefix: prefix.to_vec(),
+        })
+    }
+
+    /// Returns the prefix of this namespace
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Sets the given key value in the namespace, just like [Store::set]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set].
+    pub fn set(&mut self, k: &[u8], v: &[u8], ttl: Option<u64>) -> Result<()> {
+        let key = self.to_store_key(k)?;
+        self.store.set(&key, v, ttl)
+    }
+
+    /// Returns the value corresponding to the given key in the namespace, just like [Store::get]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::get].
+    pub fn get(&mut self, k: &[u8]) -> Result<Option<Vec<u8>>> {
+        let key = self.to_store_key(k)?;
+        self.store.get(&key)
+    }
+
+    /// Deletes the key-value for the given key in the namespace, just like [Store::delete]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::delete].
+    pub fn delete(&mut self, k: &[u8]) -> Result<bool> {
+        let key = self.to_store_key(k)?;
+        self.store.delete(&key)
+    }
+
+    /// Searches for unexpired keys in the namespace that start with the given search term,
+    /// just like [Store::search_with_mode] in [SearchMode::Prefix]
+    ///
+    /// The keys returned do not have the prefix of the namespace.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::search_with_mode].
+    pub fn search(
+        &mut self,
+        term: &[u8],
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let term = [&self.prefix[..], term].concat();
+        let prefix_len = self.prefix.len();
+
+        // Only keys starting with the prefixed term are wanted; in the default contains mode,
+        // keys from other namespaces that merely contain it somewhere would be returned too
+        let key_values = self
+            .store
+            .search_with_mode(&term, skip, limit, SearchMode::Prefix)?
+            .into_iter()
+            .map(|(mut k, v)| {
+                k.drain(..prefix_len);
+                (k, v)
+            })
+            .collect();
+        Ok(key_values)
+    }
+
+    /// Deletes all key-values in the namespace, leaving the rest of the store intact
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::delete_prefix].
+    pub fn clear(&mut self) -> Result<()> {
+        self.store.delete_prefix(&self.prefix)?;
+        Ok(())
+    }
+
+    /// Converts the given key in the namespace into the key saved in the store
+    fn to_store_key(&self, k: &[u8]) -> Result<Vec<u8>> {
+        validate_key(k)?;
+        Ok([&self.prefix[..], k].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::fs;
+
+    const STORE_PATH: &str = "db_namespace";
+
+    #[test]
+    #[serial]
+    fn namespaces_are_isolated() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("clear store");
+        store.set(&b"hey"[..], &b"root"[..], None).expect("set hey");
+
+        for (prefix, value) in [(&b"en:"[..], &b"English"[..]), (b"fr:", b"French")] {
+            let mut ns = store.namespaced(prefix).expect("namespace");
+            for k in [&b"hey"[..], b"hi", b"oi"] {
+                ns.set(k, value, None).expect("set");
+            }
+        }
+
+        let mut en = store.namespaced(&b"en:"[..]).expect("namespace");
+        assert_eq!(en.get(&b"hey"[..]).expect("get"), Some(b"English".to_vec()));
+        assert_eq!(
+            en.search(&b"h"[..], 1, 1).expect("search"),
+            vec![(b"hi".to_vec(), b"English".to_vec())]
+        );
+        assert!(en.delete(&b"hi"[..]).expect("delete"));
+        assert_eq!(en.get(&b"hi"[..]).expect("get"), None);
+        assert!(matches!(en.get(&b""[..]), Err(ScdbError::InvalidInput(_))));
+
+        en.clear().expect("clear namespace");
+        assert_eq!(en.search(&b""[..], 0, 0).expect("search"), vec![]);
+
+        let mut fr = store.namespaced(&b"fr:"[..]).expect("namespace");
+        assert_eq!(fr.search(&b""[..], 0, 0).expect("search").len(), 3);
+        assert_eq!(store.get(&b"hey"[..]).expect("get"), Some(b"root".to_vec()));
+        assert!(matches!(
+            store.namespaced(&b""[..]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_skips_keys_that_only_contain_the_prefix() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("clear store");
+        store
+            .set(&b"sessions:xsessions:f"[..], &b"bar"[..], None)
+            .expect("set in store");
+
+        let mut ns = store.namespaced(&b"sessions:"[..]).expect("namespace");
+        ns.set(&b"foo"[..], &b"baz"[..], None)
+            .expect("set in namespace");
+        assert_eq!(
+            ns.search(&b"f"[..], 0, 0).expect("search"),
+            vec![(b"foo".to_vec(), b"baz".to_vec())]
+        );
+        assert_eq!(
+            ns.search(&b"x"[..], 0, 0).expect("search"),
+            vec![(b"xsessions:f".to_vec(), b"bar".to_vec())]
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+
+/// How the [crate::Store] looks for another slot for a key in an index block when the slot the key
+/// hashes to is taken by a different key, before falling through to the next (redundant) index block
+///
+/// Unlike adding redundant blocks, probing makes [crate::ScdbError::CollisionSaturated] rarer without
+/// making the index, and thus the database file, any bigger. The cost is that `get`, `set` and `delete`
+/// may have to read up to that many more slots of each index block, especially for keys that are not
+/// in the store.
+///
+/// The probing is recorded in the database file as it is created. A database file that already holds
+/// keys keeps the probing it was created with, whatever probing it is opened with.
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::{Probing, StoreBuilder};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut store = StoreBuilder::new("db_probing")
+///     .probing(Probing::Linear(4))
+///     .build()?;
+/// store.set(&b"foo"[..], &b"bar"[..], None)?;
+/// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+/// # drop(store);
+/// # std::fs::remove_dir_all("db_probing")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Probing {
+    /// Only the slot that the key hashes to is checked in each index block
+    #[default]
+    None,
+    /// Up to the given number of slots right after the one that the key hashes to are checked too,
+    /// wrapping around to the start of the index block
+    Linear(u16),
+    /// Up to the given number of other slots are checked too, at 1, 4, 9, 16... slots after the one
+    /// that the key hashes to, wrapping around to the start of the index block. This spreads out keys
+    /// that hash to neighbouring slots better than [Probing::Linear] does
+    Quadratic(u16),
+}
+
+impl Probing {
+    /// The number of bytes taken up by the probing in the header of the database file
+    pub(crate) const SIZE_IN_BYTES: usize = 3;
+
+    /// Returns the number of slots, besides the one that the key hashes to, that are checked
+    /// in each index block of `items_per_index_block` slots. No slot is checked twice
+    /// for [Probing::Linear]
+    pub(crate) fn get_probes(&self, items_per_index_block: u64) -> u64 {
+        match self {
+            Probing::None => 0,
+            Probing::Linear(probes) | Probing::Quadratic(probes) => {
+                (*probes as u64).min(items_per_index_block.saturating_sub(1))
+            }
+        }
+    }
+
+    /// Returns the distance in slots, from the slot that the key hashes to, of the `i`th slot checked
+    /// (0-based), modulo the `items_per_index_block`
+    #[inline(always)]
+    pub(crate) fn get_step(&self, i: u64, items_per_index_block: u64) -> u64 {
+        match self {
+            Probing::None | Probing::Linear(_) => i,
+            Probing::Quadratic(_) => (i % items_per_index_block).pow(2) % items_per_index_block,
+        }
+    }
+
+    /// Retrieves the byte array that represents the probing in the header of the database file
+    pub(crate) fn as_bytes(&self) -> [u8; Self::SIZE_IN_BYTES] {
+        let (kind, probes) = match self {
+            Probing::None => (0u8, 0u16),
+            Probing::Linear(probes) => (1, *probes),
+            Probing::Quadratic(probes) => (2, *probes),
+        };
+        let [high, low] = probes.to_be_bytes();
+        [kind, high, low]
+    }
+
+    /// Extracts the probing from its byte array in the header of the database file
+    pub(crate) fn from_data_array(data: &[u8; Self::SIZE_IN_BYTES]) -> io::Result<Self> {
+        let probes = u16::from_be_bytes([data[1], data[2]]);
+        match data[0] {
+            0 => Ok(Probing::None),
+            1 => Ok(Probing::Linear(probes)),
+            2 => Ok(Probing::Quadratic(probes)),
+            kind => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown probing {} in header", kind),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn as_bytes_and_from_data_array_work() {
+        for probing in [
+            Probing::None,
+            Probing::Linear(4),
+            Probing::Quadratic(u16::MAX),
+        ] {
+            let got = Probing::from_data_array(&probing.as_bytes()).expect("from data array");
+            assert_eq!(got, probing);
+        }
+
+        assert_eq!(Probing::None.as_bytes(), [0, 0, 0]);
+        assert!(Probing::from_data_array(&[3, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn get_probes_works() {
+        assert_eq!(Probing::None.get_probes(512), 0);
+        assert_eq!(Probing::Linear(4).get_probes(512), 4);
+        assert_eq!(Probing::Quadratic(4).get_probes(512), 4);
+        // no more probes than there are other slots in the block
+        assert_eq!(Probing::Linear(4).get_probes(3), 2);
+        assert_eq!(Probing::Linear(4).get_probes(1), 0);
+    }
+
+    #[test]
+    fn get_step_works() {
+        let steps: Vec<u64> = (0..5).map(|i| Probing::Linear(4).get_step(i, 10)).collect();
+        assert_eq!(steps, vec![0, 1, 2, 3, 4]);
+
+        let steps: Vec<u64> = (0..5)
+            .map(|i| Probing::Quadratic(4).get_step(i, 10))
+            .collect();
+        assert_eq!(steps, vec![0, 1, 4, 9, 6]);
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt::{Display, Formatter};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// Where [crate::Store::scan] is to resume enumerating the key-value pairs of the store from
+///
+/// It is the position of the next slot to look at in the index of the database file. Since every
+/// key keeps its slot in the index for as long as it is in the store, even across compactions,
+/// the cursor stays valid however long it is held onto, and whatever is written to the store
+/// in the meantime.
+///
+/// It can be turned into, and got back from, a number or a string e.g. to pass it to a web page
+/// and back.
+///
+/// # Examples
+///
+/// ```rust
+/// use scdb::ScanCursor;
+///
+/// let cursor = ScanCursor::default();
+/// assert_eq!(cursor.to_string(), "0");
+/// assert_eq!("0".parse::<ScanCursor>().unwrap(), cursor);
+/// assert_eq!(ScanCursor::from(0), cursor);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanCursor(pub(crate) u64);
+
+impl From<u64> for ScanCursor {
+    fn from(position: u64) -> Self {
+        Self(position)
+    }
+}
+
+impl From<ScanCursor> for u64 {
+    fn from(cursor: ScanCursor) -> Self {
+        cursor.0
+    }
+}
+
+impl Display for ScanCursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for ScanCursor {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_cursor_round_trips() {
+        for position in [0u64, 7, u64::MAX] {
+            let cursor = ScanCursor::from(position);
+            assert_eq!(u64::from(cursor), position);
+            assert_eq!(cursor.to_string().parse::<ScanCursor>(), Ok(cursor));
+        }
+
+        assert!("".parse::<ScanCursor>().is_err());
+        assert!("-1".parse::<ScanCursor>().is_err());
+    }
+}
@@ -0,0 +1,16 @@
+/// How the keys are matched against the term when searching the [crate::Store]
+///
+/// The search index buckets keys by their first `max_index_key_len` bytes, so only the keys that
+/// share those first bytes with the term are ever considered, whichever mode is used.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Matches only the keys that start with the term.
+    /// Terms longer than `max_index_key_len` are matched correctly
+    Prefix,
+    /// Matches the keys that contain the term anywhere, on a best-effort basis:
+    /// the key must also start with the first `max_index_key_len` bytes of the term,
+    /// so keys that merely contain the term but do not share its prefix are missed.
+    /// This is the mode used by [crate::Store::search]
+    #[default]
+    Contains,
+}
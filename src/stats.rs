@@ -0,0 +1,328 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::telemetry;
+
+/// A snapshot of the hits and misses of the in-memory cache (i.e. buffer pool) of the [crate::Store]
+///
+/// A hit is when the data requested was found in memory, while a miss is when it had to be read
+/// from the file. A low hit rate is a sign that `pool_capacity` is too small for the working set.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of key-value lookups that were served from memory
+    pub kv_hits: u64,
+    /// The number of key-value lookups that had to read from the file
+    pub kv_misses: u64,
+    /// The number of index lookups that were served from memory
+    pub index_hits: u64,
+    /// The number of index lookups that had to read from the file
+    pub index_misses: u64,
+}
+
+/// The counters of the hits and misses of the buffer pool, shareable across threads
+#[derive(Debug, Default)]
+pub(crate) struct CacheCounters {
+    kv_hits: AtomicU64,
+    kv_misses: AtomicU64,
+    index_hits: AtomicU64,
+    index_misses: AtomicU64,
+}
+
+impl CacheCounters {
+    /// Records a key-value lookup, as a hit if `is_hit` is true or else as a miss
+    #[inline]
+    pub(crate) fn record_kv(&self, is_hit: bool) {
+        telemetry::record_lookup("kv", is_hit);
+        let counter = if is_hit {
+            &self.kv_hits
+        } else {
+            &self.kv_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an index lookup, as a hit if `is_hit` is true or else as a miss
+    #[inline]
+    pub(crate) fn record_index(&self, is_hit: bool) {
+        telemetry::record_lookup("index", is_hit);
+        let counter = if is_hit {
+            &self.index_hits
+        } else {
+            &self.index_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            kv_hits: self.kv_hits.load(Ordering::Relaxed),
+            kv_misses: self.kv_misses.load(Ordering::Relaxed),
+            index_hits: self.index_hits.load(Ordering::Relaxed),
+            index_misses: self.index_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets all counters back to zero
+    pub(crate) fn reset(&self) {
+        self.kv_hits.store(0, Ordering::Relaxed);
+        self.kv_misses.store(0, Ordering::Relaxed);
+        self.index_hits.store(0, Ordering::Relaxed);
+        self.index_misses.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the bytes read from and written to the database file of the [crate::Store]
+///
+/// Together with [CacheStats], it shows how much of the workload actually hits the disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IoStats {
+    /// The number of bytes read from the database file
+    pub bytes_read: u64,
+    /// The number of bytes written to the database file
+    pub bytes_written: u64,
+}
+
+/// The counters of the bytes read from and written to the database file, shareable across threads
+#[derive(Debug, Default)]
+pub(crate) struct IoCounters {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl IoCounters {
+    /// Records that the given number of bytes were read
+    #[inline]
+    pub(crate) fn record_read(&self, bytes: usize) {
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records that the given number of bytes were written
+    #[inline]
+    pub(crate) fn record_written(&self, bytes: usize) {
+        self.bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters
+    pub(crate) fn snapshot(&self) -> IoStats {
+        IoStats {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets all counters back to zero
+    pub(crate) fn reset(&self) {
+        self.bytes_read.store(0, Ordering::Relaxed);
+        self.bytes_written.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A report of how much of the database file of the [crate::Store] is taken up by live key-value entries,
+/// and how much by dead ones i.e. deleted, expired or overwritten entries that compaction would reclaim
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentationReport {
+    /// The number of bytes in the database file after the index i.e. taken up by key-value entries
+    pub total_bytes: u64,
+    /// The number of bytes taken up by the live key-value entries
+    pub live_bytes: u64,
+    /// The number of bytes taken up by the dead key-value entries
+    pub dead_bytes: u64,
+    /// The number of live key-value entries
+    pub live_entries: u64,
+}
+
+impl FragmentationReport {
+    /// Returns the fraction of the key-value entry bytes that are dead, from 0 to 1, or 0 if there are none
+    pub fn ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            self.dead_bytes as f64 / self.total_bytes as f64
+        }
+    }
+
+    /// Returns the average size in bytes of the live key-value entries, or 0 if there are none
+    pub fn average_entry_size(&self) -> f64 {
+        if self.live_entries == 0 {
+            0.0
+        } else {
+            self.live_bytes as f64 / self.live_entries as f64
+        }
+    }
+}
+
+/// An estimate of what compacting the database file of the [crate::Store] would keep and reclaim,
+/// got via [crate::Store::compaction_preview] without compacting it
+///
+/// The index of the file still points to the expired and deleted key-value entries, so they are counted
+/// individually. Older versions of updated keys are not pointed to, so they are only counted together
+/// in [CompactionPreview::overwritten_bytes].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionPreview {
+    /// The number of bytes in the database file after the index i.e. taken up by key-value entries
+    pub total_bytes: u64,
+    /// The number of live key-value entries, which compaction would keep
+    pub live_entries: u64,
+    /// The number of bytes taken up by the live key-value entries
+    pub live_bytes: u64,
+    /// The number of expired key-value entries, which compaction would drop
+    pub expired_entries: u64,
+    /// The number of bytes taken up by the expired key-value entries
+    pub expired_bytes: u64,
+    /// The number of deleted key-value entries, which compaction would drop
+    pub deleted_entries: u64,
+    /// The number of bytes taken up by the deleted key-value entries
+    pub deleted_bytes: u64,
+}
+
+impl CompactionPreview {
+    /// Returns the number of bytes that compaction would reclaim i.e. all but those of the live entries
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.live_bytes)
+    }
+
+    /// Returns the number of bytes taken up by older versions of updated keys, which compaction would drop
+    pub fn overwritten_bytes(&self) -> u64 {
+        self.reclaimable_bytes()
+            .saturating_sub(self.expired_bytes + self.deleted_bytes)
+    }
+}
+
+/// The number of live keys and values of the [crate::Store] in each power-of-two range of sizes
+///
+/// The count at index `i` is of the sizes in bytes from `2^i` up to, but excluding, `2^(i+1)`, with
+/// empty values counted at index 0 alongside those of 1 byte. See [SizeHistogram::bucket_bounds].
+/// Values that routinely exceed the size of a buffer (i.e. a page of memory) are a sign that lookups
+/// for them will often have to go to the file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SizeHistogram {
+    /// The number of keys in each range of sizes, up to that of the biggest key
+    pub key_sizes: Vec<u64>,
+    /// The number of values in each range of sizes, up to that of the biggest value
+    pub value_sizes: Vec<u64>,
+}
+
+impl SizeHistogram {
+    /// Returns the smallest size, and the size just beyond the biggest, counted at the given index
+    pub fn bucket_bounds(index: usize) -> (u64, u64) {
+        let lower = if index == 0 { 0 } else { 1u64 << index };
+        (
+            lower,
+            1u64.checked_shl(index as u32 + 1).unwrap_or(u64::MAX),
+        )
+    }
+
+    /// Records a key-value pair whose key and value are of the given sizes in bytes
+    pub(crate) fn record(&mut self, key_size: u64, value_size: u64) {
+        increment_bucket(&mut self.key_sizes, key_size);
+        increment_bucket(&mut self.value_sizes, value_size);
+    }
+}
+
+/// Increments the count of the power-of-two range that the given size falls in, growing the
+/// list of counts if need be
+fn increment_bucket(buckets: &mut Vec<u64>, size: u64) {
+    let index = size.checked_ilog2().unwrap_or(0) as usize;
+    if buckets.len() <= index {
+        buckets.resize(index + 1, 0);
+    }
+    buckets[index] += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_counters_work() {
+        let counters = CacheCounters::default();
+        counters.record_kv(true);
+        counters.record_kv(true);
+        counters.record_kv(false);
+        counters.record_index(true);
+        counters.record_index(false);
+        counters.record_index(false);
+
+        let expected = CacheStats {
+            kv_hits: 2,
+            kv_misses: 1,
+            index_hits: 1,
+            index_misses: 2,
+        };
+        assert_eq!(counters.snapshot(), expected);
+
+        counters.reset();
+        assert_eq!(counters.snapshot(), CacheStats::default());
+    }
+
+    #[test]
+    fn io_counters_work() {
+        let counters = IoCounters::default();
+        counters.record_read(10);
+        counters.record_read(5);
+        counters.record_written(7);
+
+        let expected = IoStats {
+            bytes_read: 15,
+            bytes_written: 7,
+        };
+        assert_eq!(counters.snapshot(), expected);
+
+        counters.reset();
+        assert_eq!(counters.snapshot(), IoStats::default());
+    }
+
+    #[test]
+    fn fragmentation_report_works() {
+        let report = FragmentationReport {
+            total_bytes: 200,
+            live_bytes: 150,
+            dead_bytes: 50,
+            live_entries: 3,
+        };
+        assert_eq!(report.ratio(), 0.25);
+        assert_eq!(report.average_entry_size(), 50.0);
+
+        let report = FragmentationReport::default();
+        assert_eq!(report.ratio(), 0.0);
+        assert_eq!(report.average_entry_size(), 0.0);
+    }
+
+    #[test]
+    fn compaction_preview_works() {
+        let preview = CompactionPreview {
+            total_bytes: 100,
+            live_entries: 2,
+            live_bytes: 40,
+            expired_entries: 1,
+            expired_bytes: 20,
+            deleted_entries: 1,
+            deleted_bytes: 15,
+        };
+        assert_eq!(preview.reclaimable_bytes(), 60);
+        assert_eq!(preview.overwritten_bytes(), 25);
+        assert_eq!(CompactionPreview::default().reclaimable_bytes(), 0);
+        assert_eq!(CompactionPreview::default().overwritten_bytes(), 0);
+    }
+
+    #[test]
+    fn size_histogram_works() {
+        let mut histogram = SizeHistogram::default();
+        for (key_size, value_size) in [(1, 0), (3, 1), (2, 4), (3, 7), (8, 4096)] {
+            histogram.record(key_size, value_size);
+        }
+
+        assert_eq!(histogram.key_sizes, vec![1, 3, 0, 1]);
+        let mut expected_value_sizes = vec![0; 13];
+        expected_value_sizes[0] = 2;
+        expected_value_sizes[2] = 2;
+        expected_value_sizes[12] = 1;
+        assert_eq!(histogram.value_sizes, expected_value_sizes);
+
+        assert_eq!(SizeHistogram::bucket_bounds(0), (0, 2));
+        assert_eq!(SizeHistogram::bucket_bounds(1), (2, 4));
+        assert_eq!(SizeHistogram::bucket_bounds(12), (4096, 8192));
+        assert_eq!(SizeHistogram::bucket_bounds(63), (1 << 63, u64::MAX));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::stats::IoCounters;
+use crate::telemetry;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+/// The storage in which the data of a [crate::Store] is kept, usually a [File]
+///
+/// Besides reading, writing and seeking, the store needs to be able to shrink (or grow)
+/// the storage e.g. when clearing it, hence the `set_len`.
+/// It is implemented for [File] and for `Cursor<Vec<u8>>`, and can be implemented for any other
+/// handle e.g. a tempfile or ramdisk handle, to be passed to [crate::Store::from_files].
+pub trait StorageFile: Read + Write + Seek + Debug + Send {
+    /// Truncates or extends the storage to the given size in bytes,
+    /// filling any extension with zeros
+    fn set_len(&mut self, size: u64) -> io::Result<()>;
+
+    /// Ensures all data written to the storage has reached its underlying medium e.g. the disk.
+    /// Default: flushes the storage
+    fn sync_all(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl StorageFile for File {
+    #[inline]
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        File::set_len(self, size)
+    }
+
+    #[inline]
+    fn sync_all(&mut self) -> io::Result<()> {
+        File::sync_all(self)
+    }
+}
+
+impl StorageFile for Cursor<Vec<u8>> {
+    #[inline]
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.get_mut().resize(size as usize, 0);
+        Ok(())
+    }
+}
+
+impl<T: StorageFile + ?Sized> StorageFile for Box<T> {
+    #[inline]
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        (**self).set_len(size)
+    }
+
+    #[inline]
+    fn sync_all(&mut self) -> io::Result<()> {
+        (**self).sync_all()
+    }
+}
+
+/// A [StorageFile] that counts the bytes read from and written to the storage it wraps
+#[derive(Debug)]
+pub(crate) struct CountingFile {
+    inner: Box<dyn StorageFile>,
+    counters: Arc<IoCounters>,
+}
+
+impl CountingFile {
+    /// Creates a new [CountingFile] over the given storage, recording its I/O in the given counters
+    pub(crate) fn new(inner: Box<dyn StorageFile>, counters: Arc<IoCounters>) -> Self {
+        Self { inner, counters }
+    }
+}
+
+impl Read for CountingFile {
+    #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(bytes = tracing::field::Empty))
+    )]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes = self.inner.read(buf)?;
+        self.counters.record_read(bytes);
+        telemetry::record_span_field("bytes", bytes as u64);
+        Ok(bytes)
+    }
+}
+
+impl Write for CountingFile {
+    #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(bytes = tracing::field::Empty))
+    )]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bytes = self.inner.write(buf)?;
+        self.counters.record_written(bytes);
+        telemetry::record_span_field("bytes", bytes as u64);
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Seek for CountingFile {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl StorageFile for CountingFile {
+    #[inline]
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.inner.set_len(size)
+    }
+
+    #[inline]
+    fn sync_all(&mut self) -> io::Result<()> {
+        self.inner.sync_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::SeekFrom;
+
+    #[test]
+    fn counting_file_works() {
+        let counters: Arc<IoCounters> = Default::default();
+        let mut file = CountingFile::new(Box::new(Cursor::new(vec![])), counters.clone());
+
+        file.write_all(&[1u8, 2, 3, 4]).expect("write");
+        file.seek(SeekFrom::Start(1)).expect("seek");
+        let mut buf = [0u8; 2];
+        file.read_exact(&mut buf).expect("read");
+        assert_eq!(buf, [2u8, 3]);
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.bytes_written, 4);
+        assert_eq!(stats.bytes_read, 2);
+    }
+
+    #[test]
+    fn cursor_set_len_works() {
+        let mut cursor = Cursor::new(vec![1u8, 2, 3, 4]);
+        cursor.seek(SeekFrom::End(0)).expect("seek end");
+
+        StorageFile::set_len(&mut cursor, 2).expect("shrink");
+        assert_eq!(cursor.get_ref(), &vec![1u8, 2]);
+
+        StorageFile::set_len(&mut cursor, 5).expect("grow");
+        assert_eq!(cursor.get_ref(), &vec![1u8, 2, 0, 0, 0]);
+    }
+}
@@ -1,20 +1,48 @@
 use std::fmt::{Debug, Display, Formatter};
-use std::path::Path;
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{io, thread};
 
 use clokwerk::{ScheduleHandle, Scheduler, TimeUnits};
 
+use crate::builder::StoreBuilder;
+use crate::bulk::BulkLoader;
+use crate::clock::Clock;
+use crate::config::StoreConfig;
+use crate::corruption::Corruption;
+use crate::dump::ValueEncoding;
+use crate::error::{Result, ScdbError};
 use crate::internal::{
-    acquire_lock, get_current_timestamp, initialize_db_folder, slice_to_array, BufferPool,
-    DbFileHeader, Header, InvertedIndex, KeyValueEntry, ValueEntry,
+    acquire_lock, db_file_exists, initialize_db_folder, path_to_bytes, slice_to_array,
+    try_acquire_lock, AppendedEntry, BufferPool, DbFileHeader, InvertedIndex, KeyValueEntry,
+    KeyValuePair, PoolIterator, PrefixIterator, ValueEntry, WriteAheadLog, DEFAULT_DB_MAX_KEYS,
+    DEFAULT_DB_REDUNDANT_BLOCKS, DEFAULT_MAX_INDEX_KEY_LEN, DEFAULT_POOL_CAPACITY,
+    KEY_VALUE_MIN_SIZE_IN_BYTES, VERSION_SIZE_IN_BYTES,
 };
+use crate::merge::ConflictPolicy;
+use crate::meta::ValueMeta;
+use crate::namespace::Namespace;
+use crate::scan::ScanCursor;
+use crate::search::SearchMode;
+use crate::stats::{
+    CacheCounters, CacheStats, CompactionPreview, FragmentationReport, IoCounters, IoStats,
+    SizeHistogram,
+};
+use crate::storage::StorageFile;
+use crate::telemetry;
 
 const DEFAULT_DB_FILE: &str = "dump.scdb";
 const DEFAULT_SEARCH_INDEX_FILE: &str = "index.iscdb";
+const WAL_FILE_EXTENSION: &str = "wal";
 const ZERO_U64_BYTES: [u8; 8] = 0u64.to_be_bytes();
-const DEFAULT_MAX_INDEX_KEY_LEN: u32 = 3;
+/// How long to sleep between checks of a lock or flag that can't be waited on directly
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+const COMPACTION_STEP_INDEX_BLOCKS: u64 = 1;
+const MAX_COMPACTION_RESTARTS: usize = 3;
 
 /// A key-value store that persists key-value pairs to disk
 ///
@@ -24,33 +52,69 @@ const DEFAULT_MAX_INDEX_KEY_LEN: u32 = 3;
 ///
 /// # Configuration
 ///
-/// The Store has a number of configurations that are passed into the new() method
+/// The Store has a number of configurations that are passed into the new() method,
+/// or set on a [StoreBuilder]
 ///
 /// - `store_path` - required: The path to a directory where scdb should store its data
 /// - `max_keys` - default: 1 million: The maximum number of key-value pairs to store in store
 /// - `redundant_blocks` - default: 1: The store has an index to hold all the keys. This index is split
-///                                     into a fixed number of blocks basing on the virtual memory page size
-///                                     and the total number of keys to be held i.e. `max_keys`.
-///                                     Sometimes, there may be hash collision errors as the store's
-///                                     current stored keys approach `max_keys`. The closer it gets, the
-///                                     more it becomes likely see those errors. Adding redundant blocks
-///                                     helps mitigate this. Just be careful to not add too many (i.e. more than 2)
-///                                     since the higher the number of these blocks, the slower the store becomes.
+///   into a fixed number of blocks basing on the virtual memory page size
+///   and the total number of keys to be held i.e. `max_keys`.
+///   Sometimes, there may be hash collision errors as the store's
+///   current stored keys approach `max_keys`. The closer it gets, the
+///   more it becomes likely see those errors. Adding redundant blocks
+///   helps mitigate this. Just be careful to not add too many (i.e. more than 2)
+///   since the higher the number of these blocks, the slower the store becomes.
 /// - `pool_capacity` - default: 5: The number of buffers to hold in memory as cache's for the store. Each buffer
-///                                 has the size equal to the virtual memory's page size, usually 4096 bytes.
-///                                 Increasing this number will speed this store up but of course, the machine
-///                                 has a limited RAM. When this number increases to a value that clogs the RAM, performance
-///                                 suddenly degrades, and keeps getting worse from there on.
+///   has the size equal to the virtual memory's page size, usually 4096 bytes.
+///   Increasing this number will speed this store up but of course, the machine
+///   has a limited RAM. When this number increases to a value that clogs the RAM, performance
+///   suddenly degrades, and keeps getting worse from there on.
 /// - `compaction_interval` - default 3600s (1 hour): The interval at which the store is compacted to remove dangling
-///                                                     keys. Dangling keys result from either getting expired or being deleted.
-///                                                     When a `delete` operation is done, the actual key-value pair
-///                                                     is just marked as `deleted` but is not removed.
-///                                                     Something similar happens when a key-value is updated.
-///                                                     A new key-value pair is created and the old one is left unindexed.
-///                                                     Compaction is important because it reclaims this space and reduces the size
-///                                                     of the database file.
+///   keys. Dangling keys result from either getting expired or being deleted.
+///   When a `delete` operation is done, the actual key-value pair
+///   is just marked as `deleted` but is not removed.
+///   Something similar happens when a key-value is updated.
+///   A new key-value pair is created and the old one is left unindexed.
+///   Compaction is important because it reclaims this space and reduces the size
+///   of the database file. The background compaction releases the lock on the database file every
+///   few index blocks, so that other operations on big files are not held up for the whole compaction.
+///   If a compaction is still in progress when the interval elapses, be it the previous background one
+///   or one started by [Store::compact], that background compaction is skipped rather than queued.
 /// - `is_search_enabled` - Whether the search capability of the store is enabled.
-///                                     Note that when search is enabled, `set`, `delete`, `clear`, `compact` operations become slower.
+///   Note that when search is enabled, `set`, `delete`, `clear`, `compact` operations become slower.
+/// - `max_key_size` - default: 65536 (64 KiB): The maximum size in bytes of any key to be set in the store.
+///   Every `set` hashes the whole key, and compares it in full with the key in each slot it probes, so
+///   huge keys slow down the store. Only configurable via the [StoreBuilder].
+/// - `max_value_size` - default: no limit: The maximum size in bytes of any value to be set in the store.
+///   Only configurable via the [StoreBuilder].
+/// - `clock` - default: [crate::SystemClock]: The source of the current time, used to compute and check expiries.
+///   Only configurable via the [StoreBuilder].
+/// - `stale_grace_secs` - default: 0: The number of seconds for which an expired key-value pair is still returned
+///   by `get` and every other read, including `search`, for serve-stale caching. [Store::get_meta] marks such values as expired. Compaction only reclaims
+///   expired key-value pairs once their grace period is over. Only configurable via the [StoreBuilder].
+/// - `hasher` - default: [crate::Xxh3Hasher]: The hash function used to place keys in the index.
+///   Only configurable via the [StoreBuilder].
+/// - `auto_grow` - default: false: Whether to grow the store by one redundant block when a key can't
+///   be set due to hash collisions, instead of failing. Only configurable via the [StoreBuilder].
+/// - `probing` - default: [crate::Probing::None]: How other slots of an index block are looked for when the slot
+///   a key hashes to is taken by another key. Like `redundant_blocks`, it mitigates hash collisions, but without
+///   making the database file any bigger. Only configurable via the [StoreBuilder].
+/// - `db_file_name` - default: "dump.scdb": The name of the database file in the `store_path`. Giving stores
+///   different names allows them to share one `store_path`. Only configurable via the [StoreBuilder].
+/// - `index_file_name` - default: "index.iscdb": The name of the search index file in the `store_path`.
+///   Only configurable via the [StoreBuilder].
+/// - `must_exist` - default: false: Whether opening the store fails if its database file does not exist yet,
+///   instead of creating a new empty store. See [Store::open_existing].
+///
+/// # Thread safety
+///
+/// A `Store` is [Send] and [Sync], so it can be moved to, or shared with, other threads. The methods that
+/// only read its state e.g. [Store::load_factor] or [Store::cache_stats] take `&self`, so they can be called
+/// from many threads through an `Arc<Store>`. Those that may change it e.g. [Store::set] and [Store::get]
+/// (which updates the in-memory cache) take `&mut self`, so share it as an `Arc<Mutex<Store>>`
+/// (or `Arc<RwLock<Store>>`) to call them from many threads. Internally, the database file is locked
+/// for every operation, so the background compaction never interleaves with them.
 ///
 /// # Examples
 ///
@@ -103,18 +167,49 @@ const DEFAULT_MAX_INDEX_KEY_LEN: u32 = 3;
 /// # }
 /// ```
 pub struct Store {
+    // To avoid deadlocks, whenever both `buffer_pool` and `search_index` are to be locked,
+    // `buffer_pool` is always locked first, both here and in the background compaction task
     buffer_pool: Arc<Mutex<BufferPool>>,
     header: DbFileHeader,
     scheduler: Option<ScheduleHandle>,
+    is_compacting: Arc<AtomicBool>,
+    is_bulk_loading: Arc<AtomicBool>,
     search_index: Option<Arc<Mutex<InvertedIndex>>>,
+    max_key_size: usize,
+    max_value_size: Option<usize>,
+    clock: Arc<dyn Clock>,
+    cache_counters: Arc<CacheCounters>,
+    io_counters: Arc<IoCounters>,
+    auto_grow: bool,
+    max_index_key_len: Option<u32>,
+    high_water_mark: Option<f64>,
+    is_high_water_mark_strict: bool,
+    db_file_path: Option<PathBuf>,
+    index_file_path: Option<PathBuf>,
 }
 
 impl Store {
+    /// The maximum number of key-value pairs in a new store, if no `max_keys` is given
+    pub const DEFAULT_MAX_KEYS: u64 = DEFAULT_DB_MAX_KEYS;
+    /// The number of redundant index blocks of a new store, if no `redundant_blocks` is given
+    pub const DEFAULT_REDUNDANT_BLOCKS: u16 = DEFAULT_DB_REDUNDANT_BLOCKS;
+    /// The number of buffers held in memory as cache, if no `pool_capacity` is given
+    pub const DEFAULT_POOL_CAPACITY: usize = DEFAULT_POOL_CAPACITY;
+    /// The interval in seconds at which the store is compacted in the background,
+    /// if no `compaction_interval` is given
+    pub const DEFAULT_COMPACTION_INTERVAL: u32 = 3_600;
+    /// The maximum length of the prefixes of keys kept in a new search index,
+    /// if no `max_index_key_len` is given
+    pub const DEFAULT_MAX_INDEX_KEY_LEN: u32 = DEFAULT_MAX_INDEX_KEY_LEN;
+    /// The maximum size in bytes of any key to be set in the store, if no `max_key_size` is given
+    pub const DEFAULT_MAX_KEY_SIZE: usize = 65_536;
+
     /// Creates a new store instance for the db found at `store_path`
     ///
     /// # Errors
     ///
-    /// It may fail with [std::io::Error] if it can't write to the `store_path` say due to permissions errors
+    /// It fails with [ScdbError::InvalidInput] if the `store_path` exists but is not a directory e.g. a file.
+    /// It may also fail with [ScdbError::Io] if it can't write to the `store_path` say due to permissions errors
     ///
     /// # Examples
     ///
@@ -133,44 +228,338 @@ impl Store {
         pool_capacity: Option<usize>,
         compaction_interval: Option<u32>,
         is_search_enabled: bool,
-    ) -> io::Result<Self> {
-        let db_folder = Path::new(store_path);
-        let db_file_path = db_folder.join(DEFAULT_DB_FILE);
-        let search_idx_file_path = db_folder.join(DEFAULT_SEARCH_INDEX_FILE);
+    ) -> Result<Self> {
+        let builder = StoreBuilder {
+            max_keys,
+            redundant_blocks,
+            pool_capacity,
+            compaction_interval,
+            is_search_enabled,
+            ..StoreBuilder::new(store_path)
+        };
+        Self::from_builder(builder)
+    }
+
+    /// Opens the existing store at `store_path`, failing instead of creating a new empty store
+    /// if there is none e.g. because the `store_path` is mistyped
+    ///
+    /// It is otherwise just like [Store::new]. `max_keys` and `redundant_blocks` are thus only used
+    /// for a search index file that does not exist yet; the database file keeps its own.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::Io] of kind [std::io::ErrorKind::NotFound] if there is no database file
+    /// in the `store_path`. Otherwise, it may fail with the same errors as [Store::new].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scdb::Store;
+    /// use std::io::ErrorKind;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// # Store::new("db", None, None, None, None, false)?;
+    /// let store = Store::open_existing("db", None, None, None, None, false)?;
+    ///
+    /// let err = Store::open_existing("db_typo", None, None, None, None, false).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::NotFound);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_existing(
+        store_path: &str,
+        max_keys: Option<u64>,
+        redundant_blocks: Option<u16>,
+        pool_capacity: Option<usize>,
+        compaction_interval: Option<u32>,
+        is_search_enabled: bool,
+    ) -> Result<Self> {
+        let builder = StoreBuilder {
+            max_keys,
+            redundant_blocks,
+            pool_capacity,
+            compaction_interval,
+            is_search_enabled,
+            must_exist: true,
+            ..StoreBuilder::new(store_path)
+        };
+        Self::from_builder(builder)
+    }
+
+    /// Creates a new store instance for the db found at `store_path`, with the given [StoreConfig]
+    /// e.g. as read from a config file
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if any of the settings in `config` is invalid
+    /// e.g. if `max_keys` is 0. It may also fail with [ScdbError::Io] if it can't write to
+    /// the `store_path` say due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scdb::{Store, StoreConfig};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let config = StoreConfig {
+    ///     max_keys: Some(1000),
+    ///     is_search_enabled: true,
+    ///     ..Default::default()
+    /// };
+    /// let store = Store::from_config("db", config)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_config(store_path: &str, config: StoreConfig) -> Result<Self> {
+        let builder = StoreBuilder {
+            max_keys: config.max_keys,
+            redundant_blocks: config.redundant_blocks,
+            pool_capacity: config.pool_capacity,
+            compaction_interval: config.compaction_interval,
+            is_search_enabled: config.is_search_enabled,
+            max_index_key_len: config.max_index_key_len,
+            max_key_size: config.max_key_size,
+            max_value_size: config.max_value_size,
+            stale_grace_secs: config.stale_grace_secs,
+            db_file_name: config.db_file_name,
+            index_file_name: config.index_file_name,
+            is_durable: config.is_durable,
+            auto_grow: config.auto_grow,
+            high_water_mark: config.high_water_mark,
+            is_high_water_mark_strict: config.is_high_water_mark_strict,
+            compact_on_open: config.compact_on_open,
+            must_exist: config.must_exist,
+            probing: config.probing,
+            ..StoreBuilder::new(store_path)
+        };
+        Self::from_builder(builder)
+    }
+
+    /// Creates a new store instance basing on the configuration in the given [StoreBuilder]
+    pub(crate) fn from_builder(builder: StoreBuilder) -> Result<Self> {
+        builder.validate()?;
+        let db_folder = Path::new(&builder.store_path);
+        let db_file_path =
+            db_folder.join(builder.db_file_name.as_deref().unwrap_or(DEFAULT_DB_FILE));
+        let search_idx_file_path = db_folder.join(
+            builder
+                .index_file_name
+                .as_deref()
+                .unwrap_or(DEFAULT_SEARCH_INDEX_FILE),
+        );
+        // the write-ahead log is named after the db file so that stores sharing a folder have their own
+        let wal_file_path = db_file_path.with_extension(WAL_FILE_EXTENSION);
+        if db_file_path == search_idx_file_path || db_file_path == wal_file_path {
+            return Err(ScdbError::InvalidInput(format!(
+                "db file {:?}, search index file {:?} and write-ahead log {:?} must all be different",
+                db_file_path, search_idx_file_path, wal_file_path
+            )));
+        }
 
+        // otherwise, creating the db file in it fails with an obscure error from the OS
+        if db_folder.exists() && !db_folder.is_dir() {
+            return Err(ScdbError::InvalidInput(format!(
+                "store_path {:?} exists and is not a directory",
+                db_folder
+            )));
+        }
+        if builder.must_exist && !db_file_exists(&db_file_path) {
+            return Err(ScdbError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("db file {:?} does not exist", db_file_path),
+            )));
+        }
         initialize_db_folder(db_folder)?;
 
         let mut buffer_pool = BufferPool::new(
-            pool_capacity,
+            builder.pool_capacity,
             &db_file_path,
-            max_keys,
-            redundant_blocks,
+            builder.max_keys,
+            builder.redundant_blocks,
             None,
         )?;
 
-        if is_search_enabled {}
-        let search_index = if is_search_enabled {
+        // a write-ahead log left behind by a durable store may hold writes interrupted by a crash
+        if builder.is_durable || wal_file_path.exists() {
+            let mut wal = WriteAheadLog::new(&wal_file_path)?;
+            buffer_pool.replay_wal(&mut wal)?;
+
+            if builder.is_durable {
+                buffer_pool.set_wal(wal);
+            } else {
+                drop(wal);
+                fs::remove_file(&wal_file_path)?;
+            }
+        }
+
+        let search_index = if builder.is_search_index_kept() {
             let idx = InvertedIndex::new(
                 &search_idx_file_path,
-                Some(DEFAULT_MAX_INDEX_KEY_LEN),
-                max_keys,
-                redundant_blocks,
+                Some(
+                    builder
+                        .max_index_key_len
+                        .unwrap_or(Self::DEFAULT_MAX_INDEX_KEY_LEN),
+                ),
+                builder.max_keys,
+                builder.redundant_blocks,
+            )?;
+            Some(idx)
+        } else {
+            None
+        };
+
+        Self::from_parts(builder, buffer_pool, search_index)
+    }
+
+    /// Creates a new store instance over the given already-open database and search index files,
+    /// with the rest of the configuration got from the given [StoreBuilder]
+    ///
+    /// The files can be anything implementing [StorageFile] e.g. a [std::fs::File] or a
+    /// `std::io::Cursor<Vec<u8>>`. Empty files are initialized, otherwise they should have been
+    /// created by scdb. The `store_path` of the `config` is ignored,
+    /// and `search_index_file` is only used if search is enabled.
+    ///
+    /// Compacting a store whose files are not in a `store_path` copies the live data into memory
+    /// before writing it back into the database file.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] if it can't read or write the files,
+    /// or with [ScdbError::Corruption] if their headers are invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scdb::{Store, StoreBuilder};
+    /// use std::io::Cursor;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let config = StoreBuilder::new("").is_search_enabled(true);
+    /// let mut store = Store::from_files(Cursor::new(vec![]), Cursor::new(vec![]), config)?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_files<D, I>(db_file: D, search_index_file: I, config: StoreBuilder) -> Result<Self>
+    where
+        D: StorageFile + 'static,
+        I: StorageFile + 'static,
+    {
+        config.validate()?;
+        let buffer_pool = BufferPool::from_file(
+            config.pool_capacity,
+            Box::new(db_file),
+            None,
+            config.max_keys,
+            config.redundant_blocks,
+            None,
+        )?;
+
+        let search_index = if config.is_search_index_kept() {
+            let idx = InvertedIndex::from_file(
+                Box::new(search_index_file),
+                None,
+                Some(
+                    config
+                        .max_index_key_len
+                        .unwrap_or(Self::DEFAULT_MAX_INDEX_KEY_LEN),
+                ),
+                config.max_keys,
+                config.redundant_blocks,
             )?;
-            let idx = Arc::new(Mutex::new(idx));
             Some(idx)
         } else {
             None
         };
 
+        Self::from_parts(config, buffer_pool, search_index)
+    }
+
+    /// Creates a new store instance whose data is kept only in memory, with the rest of
+    /// the configuration got from the given [StoreBuilder]
+    ///
+    /// It is useful for tests and ephemeral caches as it never touches the disk.
+    /// The `store_path` of the `config` is ignored, and nothing persists after the store is dropped.
+    ///
+    /// # Errors
+    ///
+    /// It is not expected to fail, but returns a [Result] just like [Store::from_files] does.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scdb::{Store, StoreBuilder};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut store = Store::in_memory(StoreBuilder::new("").max_keys(1000))?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn in_memory(config: StoreBuilder) -> Result<Self> {
+        Self::from_files(Cursor::new(vec![]), Cursor::new(vec![]), config)
+    }
+
+    /// Creates a new store instance from its already-initialized buffer pool and search index,
+    /// with the rest of the configuration got from the given [StoreBuilder]
+    fn from_parts(
+        builder: StoreBuilder,
+        mut buffer_pool: BufferPool,
+        search_index: Option<InvertedIndex>,
+    ) -> Result<Self> {
+        buffer_pool.set_clock(builder.clock.clone());
+        buffer_pool.set_stale_grace_secs(builder.stale_grace_secs);
+        buffer_pool.set_hasher(builder.hasher.clone())?;
+        buffer_pool.set_probing(builder.probing)?;
+        let max_index_key_len = search_index.as_ref().map(|idx| idx.get_max_index_key_len());
+        let db_file_path = buffer_pool.file_path.clone();
+        let index_file_path = search_index.as_ref().and_then(|idx| idx.file_path.clone());
+        let mut search_index = search_index.map(|mut idx| {
+            idx.set_clock(builder.clock.clone());
+            idx.set_stale_grace_secs(builder.stale_grace_secs);
+            idx
+        });
+
+        // compacting only after the clock and hasher are set, so that entries are checked against them
+        if builder.compact_on_open {
+            buffer_pool.compact_file(&mut search_index.as_mut())?;
+        }
+        let search_index = search_index.map(|idx| Arc::new(Mutex::new(idx)));
+
         let header = extract_header_from_buffer_pool(&mut buffer_pool)?;
+        let cache_counters = buffer_pool.cache_counters.clone();
+        let io_counters = buffer_pool.io_counters.clone();
         let buffer_pool = Arc::new(Mutex::new(buffer_pool));
-        let scheduler = initialize_scheduler(compaction_interval, &buffer_pool, &search_index);
+        let is_compacting = Arc::new(AtomicBool::new(false));
+        let is_bulk_loading = Arc::new(AtomicBool::new(false));
+        let scheduler = initialize_scheduler(
+            builder.compaction_interval,
+            &buffer_pool,
+            &search_index,
+            &is_compacting,
+            &is_bulk_loading,
+        );
 
         let store = Self {
             buffer_pool,
             header,
             scheduler,
+            is_compacting,
+            is_bulk_loading,
             search_index,
+            max_key_size: builder.max_key_size.unwrap_or(Self::DEFAULT_MAX_KEY_SIZE),
+            max_value_size: builder.max_value_size,
+            clock: builder.clock,
+            cache_counters,
+            io_counters,
+            auto_grow: builder.auto_grow,
+            max_index_key_len,
+            high_water_mark: builder.high_water_mark,
+            is_high_water_mark_strict: builder.is_high_water_mark_strict,
+            db_file_path,
+            index_file_path,
         };
 
         Ok(store)
@@ -182,10 +571,14 @@ impl Store {
     ///
     /// # Errors
     ///
-    /// It may fail with [std::io::Error] in case the keys are maxed out i.e the store
-    /// has reached its capacity in terms of number of unexpired key-value keys it can hold
-    /// It may also fail with 'collision saturated' errors when the number of unexpired keys in the store
-    /// is almost reaching `max_keys`.
+    /// It may fail with [ScdbError::KeysMaxedOut] in case the keys are maxed out i.e the store
+    /// already holds `max_keys` unexpired keys, and the key being set is not one of them. Just like for
+    /// [Store::remaining_capacity], keys that expire in the meantime only free up room after the next compaction.
+    /// It may also fail with [ScdbError::CollisionSaturated] errors when the number of unexpired keys in the store
+    /// is almost reaching `max_keys`, unless the store was built with [StoreBuilder::auto_grow].
+    /// It fails with [ScdbError::InvalidInput] if the key is empty, if the key is bigger than the configured
+    /// `max_key_size`, if the value is bigger than the configured `max_value_size` or if the key-value pair
+    /// is too big to be saved on disk.
     ///
     /// # Examples
     ///
@@ -204,55 +597,86 @@ impl Store {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set(&mut self, k: &[u8], v: &[u8], ttl: Option<u64>) -> io::Result<()> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key_len = k.len(), value_len = v.len()))
+    )]
+    pub fn set(&mut self, k: &[u8], v: &[u8], ttl: Option<u64>) -> Result<()> {
+        validate_key(k)?;
+        validate_entry_size(k.len(), v.len(), self.max_key_size, self.max_value_size)?;
+
         let expiry = match ttl {
             None => 0u64,
-            Some(expiry) => get_current_timestamp() + expiry,
+            Some(expiry) => self.clock.now_secs() + expiry,
         };
 
-        let mut index_block = 0;
-        let index_offset = self.header.get_index_offset(k);
-        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
-
-        while index_block < self.header.number_of_index_blocks {
-            let index_offset = self
-                .header
-                .get_index_offset_in_nth_block(index_offset, index_block)?;
-            let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
-
-            if kv_offset_in_bytes == ZERO_U64_BYTES
-                || buffer_pool.addr_belongs_to_key(&kv_offset_in_bytes, k)?
-            {
-                let kv = KeyValueEntry::new(k, v, expiry);
-                let mut kv_bytes = kv.as_bytes();
-                let prev_last_offset = buffer_pool.append(&mut kv_bytes)?;
-                let kv_address = prev_last_offset.to_be_bytes();
-                buffer_pool.update_index(index_offset, &kv_address)?;
-
-                // Update the search index
-                if let Some(idx) = &self.search_index {
-                    let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
-                    idx.add(k, prev_last_offset, expiry)?;
-                }
-
-                return Ok(());
-            }
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+        telemetry::record_op("set");
+        self.set_in_pool(&mut buffer_pool, k, v, expiry)
+    }
 
-            index_block += 1;
+    /// Sets the given key value in the store, to expire at the given `expiry_epoch_secs` i.e.
+    /// the timestamp in seconds since the unix epoch
+    ///
+    /// This is like [Store::set] except that the expiry is an absolute timestamp,
+    /// rather than a time-to-live relative to the current time.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if `expiry_epoch_secs` is already in the past.
+    /// Otherwise, it may fail with the same errors as [Store::set].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// # use std::time::{SystemTime, UNIX_EPOCH};
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    ///
+    /// // set a key-value pair that expires an hour from now
+    /// store.set_with_expiry_at(&b"foo"[..], &b"bar"[..], now + 3600)?;
+    /// # assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    ///
+    /// // an expiry in the past is rejected
+    /// assert!(store.set_with_expiry_at(&b"foo"[..], &b"bar"[..], now - 3600).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key_len = k.len(), value_len = v.len()))
+    )]
+    pub fn set_with_expiry_at(&mut self, k: &[u8], v: &[u8], expiry_epoch_secs: u64) -> Result<()> {
+        validate_key(k)?;
+        validate_entry_size(k.len(), v.len(), self.max_key_size, self.max_value_size)?;
+
+        if expiry_epoch_secs < self.clock.now_secs() {
+            return Err(ScdbError::InvalidInput(format!(
+                "expiry {} is already in the past",
+                expiry_epoch_secs
+            )));
         }
 
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("CollisionSaturatedError: no free slot for key: {:?}", k),
-        ))
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+        telemetry::record_op("set");
+        self.set_in_pool(&mut buffer_pool, k, v, expiry_epoch_secs)
     }
 
     /// Returns the value corresponding to the given key
     ///
+    /// Expired keys are treated as missing, unless the store was built with [StoreBuilder::stale_grace_secs],
+    /// in which case their values are still returned until the grace period is over.
+    ///
     /// # Errors
     ///
-    /// It may fail with [std::io::Error] in case it cannot access the database file say if it deleted
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
     /// or due to permissions errors.
+    /// It fails with [ScdbError::InvalidInput] if the key is empty.
     ///
     /// # Examples
     ///
@@ -273,41 +697,84 @@ impl Store {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get(&mut self, k: &[u8]) -> io::Result<Option<Vec<u8>>> {
-        let mut index_block = 0;
-        let index_offset = self.header.get_index_offset(k);
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key_len = k.len()))
+    )]
+    pub fn get(&mut self, k: &[u8]) -> Result<Option<Vec<u8>>> {
+        validate_key(k)?;
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        telemetry::record_op("get");
+        self.get_from_pool(&mut buffer_pool, k, false)
+    }
+
+    /// Returns the value corresponding to the given key, together with its version
+    ///
+    /// The version of a key starts at 1 on its first [Store::set], and goes up by one on every
+    /// [Store::set] of that key after that, even if the key was deleted or had expired in between.
+    /// It is thus handy for optimistic concurrency, or for detecting that a value has changed.
+    /// Changing just the expiry (e.g. with [Store::touch_many]) does not bump it.
+    ///
+    /// Versions do not only ever go up though. The version of a key starts at 1 again whenever the store
+    /// loses track of its earlier entries i.e. after a [Store::compact] that drops it as deleted or expired,
+    /// a [Store::drain_expired] that removes it as expired, a [Store::clear], or a bulk load via
+    /// [Store::bulk_loader] of a key whose earlier entries are gone. So a version should only be compared
+    /// with one read while the key was still around, rather than taken as a count of all its sets.
+    ///
+    /// Keys set before versions were kept in the database file are at version 0.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.get_versioned(&b"foo"[..])?, Some((b"bar".to_vec(), 1)));
+    ///
+    /// store.set(&b"foo"[..], &b"baz"[..], None)?;
+    /// assert_eq!(store.get_versioned(&b"foo"[..])?, Some((b"baz".to_vec(), 2)));
+    ///
+    /// // It returns None for non-existent keys or expired keys
+    /// assert_eq!(store.get_versioned(&b"foo2"[..])?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_versioned(&mut self, k: &[u8]) -> Result<Option<(Vec<u8>, u64)>> {
+        validate_key(k)?;
         let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        telemetry::record_op("get");
 
-        while index_block < self.header.number_of_index_blocks {
-            let index_offset = self
-                .header
-                .get_index_offset_in_nth_block(index_offset, index_block)?;
+        for index_offset in self.header.get_index_offsets(k) {
             let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
 
             if kv_offset_in_bytes != ZERO_U64_BYTES {
                 let entry_offset = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
-
-                if let Some(v) = buffer_pool.get_value(entry_offset, k)? {
-                    return if v.is_stale {
-                        Ok(None)
-                    } else {
-                        Ok(Some(v.data))
-                    };
+                if let Some(entry) = buffer_pool.read_value_and_version(entry_offset, k)? {
+                    return Ok(Some(entry));
                 }
             }
-
-            index_block += 1;
         }
 
         Ok(None)
     }
 
-    /// Deletes the key-value for the given key
+    /// Returns only `len` bytes of the value corresponding to the given key, starting at its `start`th byte
+    ///
+    /// Only those bytes are read from the database file, instead of the whole value, making it
+    /// much cheaper than [Store::get] for reading a small part of a large value e.g. to serve
+    /// an HTTP range request.
     ///
     /// # Errors
     ///
-    /// It may fail with [std::io::Error] in case it cannot access the database file say if it deleted
-    /// or due to permissions errors.
+    /// It fails with [ScdbError::InvalidInput] if the key is empty, or if the range goes beyond
+    /// the end of the value. Otherwise, it may fail with the same errors as [Store::get].
     ///
     /// # Examples
     ///
@@ -317,62 +784,47 @@ impl Store {
     /// # fn main() -> std::io::Result<()> {
     /// # let mut  store = Store::new("db", None, None, None, None, false)?;
     /// # store.clear()?;
-    /// # store.set(&b"foo"[..], &b"bar"[..], None)?;
-    /// // if (b"foo", b"bar") exists
-    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// store.set(&b"foo"[..], &b"barracuda"[..], None)?;
+    /// assert_eq!(store.get_range(&b"foo"[..], 3, 4)?, Some(b"racu".to_vec()));
     ///
-    /// // deleting it removes it from the store
-    /// store.delete(&b"foo"[..])?;
-    /// assert_eq!(store.get(&b"foo"[..])?, None);
+    /// // It returns None for non-existent keys or expired keys
+    /// assert_eq!(store.get_range(&b"foo2"[..], 0, 1)?, None);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn delete(&mut self, k: &[u8]) -> io::Result<()> {
-        let mut index_block = 0;
-        let index_offset = self.header.get_index_offset(k);
+    pub fn get_range(&mut self, k: &[u8], start: usize, len: usize) -> Result<Option<Vec<u8>>> {
+        validate_key(k)?;
         let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        telemetry::record_op("get");
 
-        // Update the search index in a separate thread.
-        let search_handle = self.search_index.as_ref().map(|idx| {
-            let idx = idx.clone();
-            let k = k.to_vec();
-            thread::spawn(move || {
-                let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
-                idx.remove(&k)
-            })
-        });
-
-        // delete from the scdb file
-        while index_block < self.header.number_of_index_blocks {
-            let index_offset = self
-                .header
-                .get_index_offset_in_nth_block(index_offset, index_block)?;
+        for index_offset in self.header.get_index_offsets(k) {
             let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
 
             if kv_offset_in_bytes != ZERO_U64_BYTES {
                 let entry_offset = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
-
-                if let Some(()) = buffer_pool.try_delete_kv_entry(entry_offset, k)? {
-                    return Ok(());
+                let range =
+                    buffer_pool.read_value_range(entry_offset, k, start as u64, len as u64)?;
+                if range.is_some() {
+                    return Ok(range);
                 }
             }
-
-            index_block += 1;
         }
 
-        if let Some(handle) = search_handle {
-            handle.join().unwrap()?;
-        }
-
-        Ok(())
+        Ok(None)
     }
 
-    /// Clears all data in the store
+    /// Sets the given key value in the store, just like [Store::set], but only if the database file
+    /// is not locked e.g. by a compaction going on in the background
+    ///
+    /// It returns false, without writing anything, instead of waiting for the lock. This suits
+    /// latency-sensitive paths where skipping a cache write is better than stalling.
+    ///
+    /// Unlike [Store::try_get], it needs a mutable reference to the store, as a set may grow the database file
+    /// (see [StoreBuilder::auto_grow]), changing its header which the store keeps a copy of.
     ///
     /// # Errors
     ///
-    /// It may fail with [std::io::Error] in case it cannot access the database file say if it deleted
-    /// or due to permissions errors.
+    /// It may fail with the same errors as [Store::set].
     ///
     /// # Examples
     ///
@@ -381,59 +833,42 @@ impl Store {
     /// #
     /// # fn main() -> std::io::Result<()> {
     /// # let mut  store = Store::new("db", None, None, None, None, false)?;
-    /// # store.clear()?;
-    /// # store.set(&b"foo"[..], &b"bar"[..], None)?;
-    /// # store.set(&b"foo2"[..], &b"bar2"[..], None)?;
-    /// // if (b"foo", b"bar"), (b"foo2", b"bar2") exist
-    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
-    /// assert_eq!(store.get(&b"foo2"[..])?, Some(b"bar2".to_vec()));
-    /// // clear removes everything from the store
-    /// store.clear()?;
-    /// assert_eq!(store.get(&b"foo"[..])?, None);
-    /// assert_eq!(store.get(&b"foo2"[..])?, None);
+    /// if store.try_set(&b"foo"[..], &b"bar"[..], None)? {
+    ///     assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn clear(&mut self) -> io::Result<()> {
-        // Clear the search index in a separate thread
-        let search_handle = self.search_index.as_ref().map(|idx| {
-            let idx = idx.clone();
-            thread::spawn(move || {
-                let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
-                idx.clear()
-            })
-        });
+    pub fn try_set(&mut self, k: &[u8], v: &[u8], ttl: Option<u64>) -> Result<bool> {
+        validate_key(k)?;
+        validate_entry_size(k.len(), v.len(), self.max_key_size, self.max_value_size)?;
 
-        // Clear the scdb file
-        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
-        buffer_pool.clear_file()?;
+        let expiry = match ttl {
+            None => 0u64,
+            Some(expiry) => self.clock.now_secs() + expiry,
+        };
 
-        if let Some(handle) = search_handle {
-            handle.join().unwrap()?;
-        }
-        Ok(())
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = match try_acquire_lock!(buffer_pool)? {
+            Some(guard) => guard,
+            None => return Ok(false),
+        };
+        telemetry::record_op("set");
+        // the search index is only ever locked after the buffer pool, so it is free by now
+        self.set_in_pool(&mut buffer_pool, k, v, expiry)?;
+        Ok(true)
     }
 
-    /// Manually removes dangling key-value pairs in the database file
-    ///
-    /// Dangling keys result from either getting expired or being deleted.
-    /// When a `delete` operation is done, the actual key-value pair
-    /// is just marked as `deleted` but is not removed.
-    ///                                                     
-    /// Something similar happens when a key-value is updated.
-    /// A new key-value pair is created and the old one is left un-indexed.
-    /// Compaction is important because it reclaims this space and reduces the size
-    /// of the database file.
-    ///
-    /// This is done automatically for you at the set `compaction_interval` but you
-    /// may wish to do it manually for some reason.
+    /// Returns the value corresponding to the given key, just like [Store::get], but only if
+    /// the database file is not locked e.g. by a compaction going on in the background
     ///
-    /// This is a very expensive operation so use it sparingly.
+    /// If the lock is held elsewhere, it returns None instead of waiting for it, treating
+    /// the lookup as a cache miss. It needs only a shared reference to the store, so lookups
+    /// can be made from many threads at once e.g. via a [std::sync::Arc].
     ///
     /// # Errors
     ///
-    /// It may fail with [std::io::Error] in case it cannot access the database file say if it deleted
-    /// or due to permissions errors.
+    /// It may fail with the same errors as [Store::get].
     ///
     /// # Examples
     ///
@@ -441,42 +876,85 @@ impl Store {
     /// # use scdb::Store;
     /// #
     /// # fn main() -> std::io::Result<()> {
-    /// # let mut store = Store::new("db", None, None, None, None, false)?;
-    /// store.compact()?;
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// // with nothing else holding the lock, it is just like `get`
+    /// assert_eq!(store.try_get(&b"foo"[..])?, Some(b"bar".to_vec()));
     /// # Ok(())
     /// # }
     /// ```
-    pub fn compact(&mut self) -> io::Result<()> {
-        // Compact the scdb file
-        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
-        let mut search_index = match &self.search_index {
-            None => None,
-            Some(idx) => {
-                let idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
-                Some(idx)
+    pub fn try_get(&self, k: &[u8]) -> Result<Option<Vec<u8>>> {
+        validate_key(k)?;
+        match try_acquire_lock!(self.buffer_pool)? {
+            Some(mut buffer_pool) => {
+                telemetry::record_op("get");
+                self.get_from_pool(&mut buffer_pool, k, false)
             }
-        };
-
-        // Since compacting the db file disorganizes the addresses, we will rebuild
-        // the index every time compaction of db is done.
-        buffer_pool.compact_file(&mut (search_index.as_deref_mut()))
+            None => Ok(None),
+        }
     }
 
-    /// Searches for unexpired keys that start with the given search term
+    /// Returns the value corresponding to the given key, just like [Store::get], but waiting
+    /// no longer than `timeout` for the lock on the database file e.g. if a compaction is going on
+    /// in the background
     ///
-    /// It skips the first `skip` (default: 0) number of results and returns not more than
-    /// `limit` (default: 0) number of items. This is to avoid using up more memory than can be handled by the
-    /// host machine.
+    /// As the [Mutex] guarding the database file can't be waited on with a timeout, the lock is instead
+    /// polled every millisecond until the `timeout` elapses. So it may take the lock up to a millisecond
+    /// after it is released, and only after any threads already queueing for it. Like [Store::try_get],
+    /// it needs only a shared reference to the store.
     ///
-    /// If `limit` is 0, all items are returned since it would make no sense for someone to search
-    /// for zero items.
+    /// # Errors
     ///
-    /// returns a list of tuples of key-value
+    /// It fails with [ScdbError::Io] of kind [io::ErrorKind::WouldBlock] if the lock is not acquired
+    /// within the `timeout`. Otherwise, it may fail with the same errors as [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// let value = store.get_timeout(&b"foo"[..], Duration::from_millis(50))?;
+    /// assert_eq!(value, Some(b"bar".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_timeout(&self, k: &[u8], timeout: Duration) -> Result<Option<Vec<u8>>> {
+        validate_key(k)?;
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(mut buffer_pool) = try_acquire_lock!(self.buffer_pool)? {
+                telemetry::record_op("get");
+                return self.get_from_pool(&mut buffer_pool, k, false);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ScdbError::Io(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("failed to acquire lock on database within {:?}", timeout),
+                )));
+            }
+            thread::sleep(LOCK_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Returns the value corresponding to the given key, just like [Store::get],
+    /// but without loading anything into the in-memory cache
+    ///
+    /// Entries already in the cache are read from it, but on a cache miss, the entry is read
+    /// directly from the database file, leaving the cache (and [Store::cache_stats]) unchanged.
+    /// This allows maintenance tasks, say ones that read every key, not to evict the hot working set.
     ///
     /// # Errors
     ///
-    /// It may fail with [std::io::Error] in case it cannot access the database file say if it deleted
-    /// or due to permissions errors.
+    /// It may fail with the same errors as [Store::get].
     ///
     /// # Examples
     ///
@@ -484,399 +962,7395 @@ impl Store {
     /// # use scdb::Store;
     /// #
     /// # fn main() -> std::io::Result<()> {
-    /// # let mut  store = Store::new("db", None, None, None, None, true)?; // enable search
-    /// # store.clear()?;   
-    /// // imagine the store has the following key value pairs
-    /// let data = vec![
-    ///     (&b"hi"[..], &b"ooliyo"[..]),
-    ///     (&b"high"[..], &b"haiguru"[..]),
-    ///     (&b"hind"[..], &b"enyuma"[..]),
-    ///     (&b"hill"[..], &b"akasozi"[..]),
-    ///     (&b"him"[..], &b"ogwo"[..]),
-    /// ];
-    /// # let mut expected: Vec<(Vec<u8>, Vec<u8>)> = vec![];
-    /// # for (k, v) in data {
-    /// #    store.set(k, v, None)?;
-    /// #    expected.push((k.to_vec(), v.to_vec()))
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.peek(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// assert_eq!(store.peek(&b"foo2"[..])?, None);
+    /// # Ok(())
     /// # }
-    /// // search for key-values where the keys start with 'hi'
-    /// let key_values = store.search(&b"hi"[..], 0, 0)?;
-    /// assert_eq!(key_values, expected);
+    /// ```
+    pub fn peek(&mut self, k: &[u8]) -> Result<Option<Vec<u8>>> {
+        validate_key(k)?;
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        telemetry::record_op("get");
+        self.get_from_pool(&mut buffer_pool, k, true)
+    }
+
+    /// Returns the value corresponding to the given key together with its expiry, and whether
+    /// it is deleted or expired
     ///
-    /// // Or just return a few of them, say last three
-    /// let key_values = store.search(&b"hi"[..], 2, 3)?;
-    /// assert_eq!(key_values, expected[2..]);
+    /// Unlike [Store::get], it returns deleted and expired keys too, as long as their entries are still
+    /// in the database file i.e. until they are removed by compaction or [Store::drain_expired],
+    /// or the key is set again. This allows serving stale values while fresh ones are being computed.
+    /// Like [Store::peek], it reads the entry directly from the database file, leaving the cache untouched.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.delete(&b"foo"[..])?;
+    /// assert_eq!(store.get(&b"foo"[..])?, None);
+    ///
+    /// let meta = store.get_meta(&b"foo"[..])?.expect("foo's entry");
+    /// assert_eq!(meta.data, b"bar".to_vec());
+    /// assert!(meta.is_deleted);
+    /// assert!(!meta.is_expired);
+    /// assert_eq!(store.get_meta(&b"foo2"[..])?, None);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn search(
-        &mut self,
-        term: &[u8],
-        skip: u64,
-        limit: u64,
-    ) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
-        if let Some(idx) = &self.search_index {
-            let mut search_index = acquire_lock!(idx)?;
-            let offsets = search_index.search(term, skip, limit)?;
-            let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
-            buffer_pool.get_many_key_values(&offsets)
-        } else {
-            Err(io::Error::from(io::ErrorKind::Unsupported))
-        }
-    }
-}
+    pub fn get_meta(&mut self, k: &[u8]) -> Result<Option<ValueMeta>> {
+        validate_key(k)?;
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        telemetry::record_op("get");
 
-impl Debug for Store {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Store {{ buffer_pool: {:?}, header: {}}}",
-            self.buffer_pool, self.header
-        )
-    }
-}
+        for index_offset in self.header.get_index_offsets(k) {
+            let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
 
-impl Display for Store {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+            if kv_offset_in_bytes != ZERO_U64_BYTES {
+                let entry_offset = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
+                if let Some(meta) = buffer_pool.read_value_meta(entry_offset, k)? {
+                    return Ok(Some(meta));
+                }
+            }
+        }
+
+        Ok(None)
     }
-}
 
-impl Drop for Store {
-    fn drop(&mut self) {
-        if let Some(scheduler) = self.scheduler.take() {
-            scheduler.stop();
+    /// Returns the value corresponding to the given key, or if it does not exist, calls `f`
+    /// to compute the value, saves it in the store with the given `ttl` and returns it.
+    ///
+    /// The store is locked for the whole operation so that no other thread can set the same key
+    /// in the meantime. This means that `f` runs while the lock is held; so keep it short,
+    /// and do not access the same store from within it as that would deadlock.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set] and [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// // the value is computed and saved if the key does not exist
+    /// let value = store.get_or_insert_with(&b"foo"[..], None, || b"bar".to_vec())?;
+    /// assert_eq!(value, b"bar".to_vec());
+    ///
+    /// // otherwise the existing value is returned
+    /// let value = store.get_or_insert_with(&b"foo"[..], None, || b"baz".to_vec())?;
+    /// assert_eq!(value, b"bar".to_vec());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_or_insert_with<F>(&mut self, k: &[u8], ttl: Option<u64>, f: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce() -> Vec<u8>,
+    {
+        validate_key(k)?;
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+
+        if let Some(v) = self.get_from_pool(&mut buffer_pool, k, false)? {
+            telemetry::record_op("get");
+            return Ok(v);
         }
+
+        let v = f();
+        validate_entry_size(k.len(), v.len(), self.max_key_size, self.max_value_size)?;
+        let expiry = match ttl {
+            None => 0u64,
+            Some(expiry) => self.clock.now_secs() + expiry,
+        };
+        telemetry::record_op("set");
+        self.set_in_pool(&mut buffer_pool, k, &v, expiry)?;
+        Ok(v)
     }
-}
 
-/// Initializes the scheduler that is to run the background task of compacting the store
-/// If interval (in seconds) passed is 0, No scheduler is created. The default interval is 1 hour
-fn initialize_scheduler(
-    interval: Option<u32>,
-    buffer_pool: &Arc<Mutex<BufferPool>>,
-    search_index: &Option<Arc<Mutex<InvertedIndex>>>,
-) -> Option<ScheduleHandle> {
-    let interval = interval.unwrap_or(3_600u32);
+    /// Sets the given key value in the store only if the key does not exist, returning None if
+    /// it was set, or the existing value if the key already has an unexpired one
+    ///
+    /// The store is locked for the whole operation so that no other thread can set the same key
+    /// in the meantime, making it fit for claiming a named resource e.g. a lease, while finding out
+    /// who holds it if the claim fails.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set] and [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// // the value is set if the key does not exist
+    /// assert_eq!(store.set_or_get(&b"lock"[..], &b"me"[..], Some(30))?, None);
+    ///
+    /// // otherwise the existing value is returned, and left as is
+    /// assert_eq!(store.set_or_get(&b"lock"[..], &b"you"[..], Some(30))?, Some(b"me".to_vec()));
+    /// assert_eq!(store.get(&b"lock"[..])?, Some(b"me".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_or_get(&mut self, k: &[u8], v: &[u8], ttl: Option<u64>) -> Result<Option<Vec<u8>>> {
+        validate_key(k)?;
+        validate_entry_size(k.len(), v.len(), self.max_key_size, self.max_value_size)?;
 
-    if interval > 0 {
-        let mut scheduler = Scheduler::new();
-        let buffer_pool = buffer_pool.clone();
-        let search_index = search_index.as_ref().cloned();
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
 
-        scheduler.every(interval.seconds()).run(move || {
-            let mut buffer_pool: MutexGuard<'_, BufferPool> =
-                acquire_lock!(buffer_pool).expect("get lock on buffer pool");
-            // Since compacting the db file disorganizes the addresses, we will rebuild
-            // the index every time compaction of db is done
-            let mut search_index: Option<MutexGuard<'_, InvertedIndex>> = search_index
-                .as_ref()
-                .map(|v| acquire_lock!(v).expect("get lock on search index"));
-            buffer_pool
-                .compact_file(&mut (search_index.as_deref_mut()))
-                .expect("compact db file in thread");
-        });
+        if let Some(existing) = self.get_from_pool(&mut buffer_pool, k, false)? {
+            telemetry::record_op("get");
+            return Ok(Some(existing));
+        }
 
-        let handle = scheduler.watch_thread(Duration::from_millis(200));
-        Some(handle)
-    } else {
-        None
+        let expiry = match ttl {
+            None => 0u64,
+            Some(expiry) => self.clock.now_secs() + expiry,
+        };
+        telemetry::record_op("set");
+        self.set_in_pool(&mut buffer_pool, k, v, expiry)?;
+        Ok(None)
     }
-}
 
-/// Initializes the header given the buffer bool
-fn extract_header_from_buffer_pool(buffer_pool: &mut BufferPool) -> io::Result<DbFileHeader> {
-    DbFileHeader::from_file(&mut buffer_pool.file)
-}
-
-#[cfg(test)]
-mod tests {
-    #[cfg(unix)]
-    use nix::sys::wait::wait;
-    #[cfg(unix)]
-    use nix::unistd::fork;
-    #[cfg(unix)]
-    use nix::unistd::ForkResult::{Child, Parent};
-    use std::fs::OpenOptions;
-    use std::io::{Seek, SeekFrom};
-    use std::thread::JoinHandle;
-    use std::{fs, io, thread};
+    /// Returns the value corresponding to the given key, or `default` if it does not exist
+    ///
+    /// Unlike [Store::get_or_insert_with], the `default` is not saved in the store.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.get_or(&b"foo"[..], b"baz".to_vec())?, b"bar".to_vec());
+    /// assert_eq!(store.get_or(&b"foo2"[..], b"baz".to_vec())?, b"baz".to_vec());
+    /// assert_eq!(store.get(&b"foo2"[..])?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_or(&mut self, k: &[u8], default: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(self.get(k)?.unwrap_or(default))
+    }
 
-    use serial_test::serial;
+    /// Returns the value corresponding to the given key, or if it does not exist, the value
+    /// computed by calling `f`
+    ///
+    /// Unlike [Store::get_or_insert_with], the computed value is not saved in the store, and `f` is called
+    /// after the store is unlocked, so it may access the same store.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// let value = store.get_or_else(&b"foo"[..], || b"baz".to_vec())?;
+    /// assert_eq!(value, b"baz".to_vec());
+    /// assert_eq!(store.get(&b"foo"[..])?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_or_else<F>(&mut self, k: &[u8], f: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce() -> Vec<u8>,
+    {
+        Ok(self.get(k)?.unwrap_or_else(f))
+    }
 
-    use super::*;
+    /// Appends `extra` to the value of the given key, returning the length of the new value
+    ///
+    /// A key that does not exist (or has expired) is treated as having an empty value.
+    /// The store is locked while the old value is read and the new one is written, so no other thread
+    /// can change the key in the meantime. Just like [Store::set], the new value is written as a whole new
+    /// entry, expiring after the given `ttl` in seconds, or never if it is None.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set] e.g. if the new value is bigger than
+    /// the `max_value_size`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// assert_eq!(store.append_value(&b"log"[..], &b"foo;"[..], None)?, 4);
+    /// assert_eq!(store.append_value(&b"log"[..], &b"bar;"[..], None)?, 8);
+    /// assert_eq!(store.get(&b"log"[..])?, Some(b"foo;bar;".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn append_value(&mut self, k: &[u8], extra: &[u8], ttl: Option<u64>) -> Result<usize> {
+        validate_key(k)?;
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
 
-    const STORE_PATH: &str = "db";
+        let mut value = self
+            .get_from_pool(&mut buffer_pool, k, false)?
+            .unwrap_or_default();
+        value.extend_from_slice(extra);
+        validate_entry_size(k.len(), value.len(), self.max_key_size, self.max_value_size)?;
 
-    /// Asserts that two lists of Result<Option<T>> are equal
-    macro_rules! assert_list_eq {
-        ($expected:expr, $got:expr) => {
-            assert_eq!($expected.len(), $got.len());
-            for (got, expected) in $got.into_iter().zip($expected) {
-                assert_eq!(got.as_ref().unwrap(), expected.as_ref().unwrap());
-            }
+        let expiry = match ttl {
+            None => 0u64,
+            Some(expiry) => self.clock.now_secs() + expiry,
         };
+        telemetry::record_op("set");
+        self.set_in_pool(&mut buffer_pool, k, &value, expiry)?;
+        Ok(value.len())
     }
 
-    /// Converts a string slice into bytes
-    macro_rules! str_to_bytes {
-        ($v:expr) => {
-            $v.to_string().into_bytes()
+    /// Sets the given string key value in the store
+    ///
+    /// This is a convenience wrapper over [Store::set] for string keys and values,
+    /// just like `localStorage.setItem()`.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// store.set_str("foo", "bar", None)?;
+    /// assert_eq!(store.get_str("foo")?, Some("bar".to_string()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_str(&mut self, k: &str, v: &str, ttl: Option<u64>) -> Result<()> {
+        self.set(k.as_bytes(), v.as_bytes(), ttl)
+    }
+
+    /// Returns the string value corresponding to the given string key
+    ///
+    /// This is a convenience wrapper over [Store::get] for string keys and values,
+    /// just like `localStorage.getItem()`.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidUtf8] if the value stored is not valid UTF-8
+    /// e.g. if it was set as bytes via [Store::set]. Otherwise, it may fail with the same errors as [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set_str("foo", "bar", None)?;
+    /// assert_eq!(store.get_str("foo")?, Some("bar".to_string()));
+    ///
+    /// // It returns None for non-existent keys or expired keys
+    /// assert_eq!(store.get_str("foo2")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_str(&mut self, k: &str) -> Result<Option<String>> {
+        match self.get(k.as_bytes())? {
+            Some(v) => String::from_utf8(v)
+                .map(Some)
+                .map_err(ScdbError::InvalidUtf8),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes the key-value for the given string key
+    ///
+    /// This is a convenience wrapper over [Store::delete] for string keys,
+    /// just like `localStorage.removeItem()`. It returns true if an unexpired key-value pair
+    /// was found and deleted.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::delete].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// store.set_str("foo", "bar", None)?;
+    /// assert!(store.delete_str("foo")?);
+    /// assert_eq!(store.get_str("foo")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_str(&mut self, k: &str) -> Result<bool> {
+        self.delete(k.as_bytes())
+    }
+
+    /// Sets the given value in the store, with the given path as its key
+    ///
+    /// This is a convenience wrapper over [Store::set] e.g. for caching data derived from files.
+    /// The path is used as is, without being normalized or canonicalized. Its bytes are its UTF-8
+    /// on every platform, if it is valid unicode. Otherwise, they are its raw bytes on unix,
+    /// and its [WTF-8](https://simonsapin.github.io/wtf-8/) on windows.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// # use std::path::Path;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// store.set_path(Path::new("images/cat.png"), &b"thumbnail"[..], None)?;
+    /// assert_eq!(store.get_path("images/cat.png")?, Some(b"thumbnail".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_path(&mut self, k: impl AsRef<Path>, v: &[u8], ttl: Option<u64>) -> Result<()> {
+        self.set(&path_to_bytes(k.as_ref()), v, ttl)
+    }
+
+    /// Returns the value corresponding to the given path, as set by [Store::set_path]
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::get].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set_path("images/cat.png", &b"thumbnail"[..], None)?;
+    /// assert_eq!(store.get_path("images/cat.png")?, Some(b"thumbnail".to_vec()));
+    /// assert_eq!(store.get_path("images/dog.png")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_path(&mut self, k: impl AsRef<Path>) -> Result<Option<Vec<u8>>> {
+        self.get(&path_to_bytes(k.as_ref()))
+    }
+
+    /// Deletes the key-value for the given path, as set by [Store::set_path]
+    ///
+    /// It returns true if an unexpired key-value pair was found and deleted.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::delete].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// store.set_path("images/cat.png", &b"thumbnail"[..], None)?;
+    /// assert!(store.delete_path("images/cat.png")?);
+    /// assert_eq!(store.get_path("images/cat.png")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_path(&mut self, k: impl AsRef<Path>) -> Result<bool> {
+        self.delete(&path_to_bytes(k.as_ref()))
+    }
+
+    /// Deletes the key-value for the given key
+    ///
+    /// It returns true if an unexpired key-value pair was found and deleted,
+    /// or false if the key did not exist, had already expired or had already been deleted.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    /// It fails with [ScdbError::InvalidInput] if the key is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// # store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// // if (b"foo", b"bar") exists
+    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    ///
+    /// // deleting it removes it from the store
+    /// assert!(store.delete(&b"foo"[..])?);
+    /// assert_eq!(store.get(&b"foo"[..])?, None);
+    ///
+    /// // deleting a non-existent key is a no-op
+    /// assert!(!store.delete(&b"foo"[..])?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key_len = k.len()))
+    )]
+    pub fn delete(&mut self, k: &[u8]) -> Result<bool> {
+        validate_key(k)?;
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        telemetry::record_op("delete");
+        self.delete_in_pool(&mut buffer_pool, k)
+    }
+
+    /// Moves the value (and expiry) of the key `from` to the key `to`, returning false if `from` does not exist
+    ///
+    /// The value is set under `to` and `from` is deleted (updating the search index for both keys)
+    /// while the store is locked, so that no other thread sees the store midway through the rename.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if either key is empty, or if `to` already exists
+    /// and `overwrite` is false. It may also fail with the same errors as [Store::set], except
+    /// [ScdbError::KeysMaxedOut], as renaming a key does not add to the number of keys in the store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert!(store.rename_key(&b"foo"[..], &b"baz"[..], false)?);
+    /// assert_eq!(store.get(&b"foo"[..])?, None);
+    /// assert_eq!(store.get(&b"baz"[..])?, Some(b"bar".to_vec()));
+    ///
+    /// // renaming a non-existent key is a no-op
+    /// assert!(!store.rename_key(&b"foo"[..], &b"baz"[..], true)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rename_key(&mut self, from: &[u8], to: &[u8], overwrite: bool) -> Result<bool> {
+        validate_key(from)?;
+        validate_key(to)?;
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+
+        let (value, expiry) = match self.get_entry_from_pool(&mut buffer_pool, from)? {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        if from == to {
+            return Ok(true);
+        }
+
+        if !overwrite && self.get_from_pool(&mut buffer_pool, to, false)?.is_some() {
+            return Err(ScdbError::InvalidInput(format!(
+                "key {:?} already exists",
+                to
+            )));
+        }
+
+        validate_entry_size(
+            to.len(),
+            value.len(),
+            self.max_key_size,
+            self.max_value_size,
+        )?;
+        telemetry::record_op("set");
+        // `from` is deleted right after, so the store does not end up with more keys than before
+        self.set_in_pool_over_max_keys(&mut buffer_pool, to, &value, expiry)?;
+        self.delete_in_pool(&mut buffer_pool, from)?;
+        Ok(true)
+    }
+
+    /// Exchanges the values (and expiries) of the keys `a` and `b`
+    ///
+    /// Both keys are rewritten (updating the search index for both) while the store is locked,
+    /// so that no other thread sees the store midway through the swap. If only one of the keys exists,
+    /// its value is moved to the other key, just like [Store::rename_key] would, and if neither exists,
+    /// it is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if either key is empty, or if either value is bigger
+    /// than `max_value_size` once set under the other key. In that case, neither key is changed.
+    /// It may also fail with the same errors as [Store::set], except [ScdbError::KeysMaxedOut],
+    /// as swapping keys does not add to the number of keys in the store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"active"[..], &b"v1"[..], None)?;
+    /// store.set(&b"standby"[..], &b"v2"[..], None)?;
+    /// store.swap(&b"active"[..], &b"standby"[..])?;
+    /// assert_eq!(store.get(&b"active"[..])?, Some(b"v2".to_vec()));
+    /// assert_eq!(store.get(&b"standby"[..])?, Some(b"v1".to_vec()));
+    ///
+    /// // swapping with a non-existent key moves the value
+    /// store.swap(&b"active"[..], &b"next"[..])?;
+    /// assert_eq!(store.get(&b"active"[..])?, None);
+    /// assert_eq!(store.get(&b"next"[..])?, Some(b"v2".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn swap(&mut self, a: &[u8], b: &[u8]) -> Result<()> {
+        validate_key(a)?;
+        validate_key(b)?;
+        if a == b {
+            return Ok(());
+        }
+
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+        let entry_a = self.get_entry_from_pool(&mut buffer_pool, a)?;
+        let entry_b = self.get_entry_from_pool(&mut buffer_pool, b)?;
+
+        // validate both writes before making any, so that a rejected swap leaves both keys intact
+        if let Some((value, _)) = &entry_a {
+            validate_entry_size(b.len(), value.len(), self.max_key_size, self.max_value_size)?;
+        }
+        if let Some((value, _)) = &entry_b {
+            validate_entry_size(a.len(), value.len(), self.max_key_size, self.max_value_size)?;
+        }
+
+        telemetry::record_op("set");
+        // a key is only new if the other one is missing, and is thus deleted right after,
+        // so the store does not end up with more keys than before
+        for (k, entry) in [(b, entry_a), (a, entry_b)] {
+            match entry {
+                Some((value, expiry)) => {
+                    self.set_in_pool_over_max_keys(&mut buffer_pool, k, &value, expiry)?
+                }
+                None => {
+                    self.delete_in_pool(&mut buffer_pool, k)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the key-value for the given key, given a lock on the buffer pool
+    fn delete_in_pool(&self, buffer_pool: &mut BufferPool, k: &[u8]) -> Result<bool> {
+        let mut is_deleted = false;
+
+        // delete from the scdb file
+        for index_offset in self.header.get_index_offsets(k) {
+            let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
+
+            if kv_offset_in_bytes != ZERO_U64_BYTES {
+                let entry_offset = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
+
+                let is_live = buffer_pool.read_key(entry_offset)?.as_deref() == Some(k);
+                if is_live {
+                    buffer_pool.mark_dangling(entry_offset)?;
+                }
+
+                if let Some(()) = buffer_pool.try_delete_kv_entry(entry_offset, k)? {
+                    is_deleted = is_live;
+                    break;
+                }
+            }
+        }
+
+        // Update the search index while still holding the lock on the buffer pool
+        // so that its state never lags behind that of the scdb file
+        if let Some(idx) = &self.search_index {
+            let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+            idx.remove(k)?;
+        }
+
+        Ok(is_deleted)
+    }
+
+    /// Clears all data in the store
+    ///
+    /// The store is locked for the whole of the clear, and everything it has cached in memory is
+    /// dropped along with the data. Thus any other operation on the store, be it a `get` from another
+    /// thread sharing the store or the background compaction, sees either all the data from before
+    /// the clear or none of it, never a mix of the two. Other [Store]s opened on the same files
+    /// e.g. in other processes keep their caches though, and are to call [Store::refresh] to see the clear.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// # store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// # store.set(&b"foo2"[..], &b"bar2"[..], None)?;
+    /// // if (b"foo", b"bar"), (b"foo2", b"bar2") exist
+    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// assert_eq!(store.get(&b"foo2"[..])?, Some(b"bar2".to_vec()));
+    /// // clear removes everything from the store
+    /// store.clear()?;
+    /// assert_eq!(store.get(&b"foo"[..])?, None);
+    /// assert_eq!(store.get(&b"foo2"[..])?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clear(&mut self) -> Result<()> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index: Option<MutexGuard<'_, InvertedIndex>> = match &self.search_index {
+            None => None,
+            Some(idx) => Some(acquire_lock!(idx)?),
         };
+
+        // Clear the search index in a separate thread, while holding both locks
+        thread::scope(|s| {
+            let search_handle = search_index
+                .as_deref_mut()
+                .map(|idx| s.spawn(move || idx.clear()));
+
+            // Clear the scdb file
+            buffer_pool.clear_file()?;
+
+            if let Some(handle) = search_handle {
+                handle.join().unwrap()?;
+            }
+            Ok(())
+        })
     }
 
-    /// Converts an array of strings into a vector of byte arrays
-    macro_rules! to_byte_arrays_vector {
-        ($data:expr) => {
-            $data.map(|v| str_to_bytes!(v)).to_vec()
-        };
+    /// Clears all data in the store and re-initializes it with a new `max_keys`
+    ///
+    /// Unlike [`Store::clear`], which keeps the index region allocated for the original `max_keys`,
+    /// this resizes the index region of the database file (and the search index file, if search is enabled)
+    /// to fit the new capacity. It is effectively a "reset with new capacity", useful for
+    /// reclaiming disk space after a very large store is no longer needed.
+    /// Just like [`Store::clear`], other operations on the store see either the state before it or after it.
+    ///
+    /// # Errors
+    ///
+    /// It returns an [ScdbError::InvalidInput] error if `max_keys` is 0.
+    /// It may also fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db_clear_with_max_keys", None, None, None, None, false)?;
+    /// # store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// // clear the store, shrinking it to hold only upto 1000 keys
+    /// store.clear_with_max_keys(1000)?;
+    /// assert_eq!(store.get(&b"foo"[..])?, None);
+    /// # drop(store);
+    /// # std::fs::remove_dir_all("db_clear_with_max_keys")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clear_with_max_keys(&mut self, max_keys: u64) -> Result<()> {
+        if max_keys == 0 {
+            return Err(ScdbError::InvalidInput(
+                "max_keys must be greater than 0".to_string(),
+            ));
+        }
+
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index: Option<MutexGuard<'_, InvertedIndex>> = match &self.search_index {
+            None => None,
+            Some(idx) => Some(acquire_lock!(idx)?),
+        };
+
+        // Clear the search index first so that, if it fails, the scdb file is left untouched
+        // instead of being reset under a search index that still points into its old entries
+        if let Some(idx) = search_index.as_deref_mut() {
+            idx.clear_with_max_keys(max_keys)?;
+        }
+
+        // Clear the scdb file, and update the cached header to the new geometry
+        buffer_pool.clear_file_with_max_keys(max_keys)?;
+        self.header = extract_header_from_buffer_pool(&mut buffer_pool)?;
+        Ok(())
+    }
+
+    /// Manually removes dangling key-value pairs in the database file
+    ///
+    /// Dangling keys result from either getting expired or being deleted.
+    /// When a `delete` operation is done, the actual key-value pair
+    /// is just marked as `deleted` but is not removed.
+    ///                                                     
+    /// Something similar happens when a key-value is updated.
+    /// A new key-value pair is created and the old one is left un-indexed.
+    /// Compaction is important because it reclaims this space and reduces the size
+    /// of the database file.
+    ///
+    /// This is done automatically for you at the set `compaction_interval` but you
+    /// may wish to do it manually for some reason.
+    ///
+    /// This is a very expensive operation so use it sparingly.
+    /// If a compaction is already in progress e.g. in the background, it returns right away,
+    /// leaving that compaction to finish, instead of rewriting the database file a second time.
+    /// See [Store::is_compacting].
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// store.compact()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compact(&mut self) -> Result<()> {
+        let Some(_compaction_guard) = CompactionGuard::try_start(&self.is_compacting) else {
+            return Ok(());
+        };
+
+        // Compact the scdb file
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index = match &self.search_index {
+            None => None,
+            Some(idx) => {
+                let idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+                Some(idx)
+            }
+        };
+
+        // Since compacting the db file disorganizes the addresses, we will rebuild
+        // the index every time compaction of db is done.
+        buffer_pool.compact_file(&mut (search_index.as_deref_mut()))?;
+        Ok(())
+    }
+
+    /// Manually removes dangling key-value pairs in the database file just like [Store::compact],
+    /// but stops as soon as `cancel` is set e.g. by another thread enforcing a shutdown deadline
+    ///
+    /// `cancel` is checked before each index block is copied into the new database file. Once it is set,
+    /// the compaction is aborted, leaving the database file (and the search index) as they were and
+    /// removing the partially written new file.
+    ///
+    /// It returns true if the store was compacted, or false if the compaction was cancelled,
+    /// or if another compaction was already in progress e.g. in the background.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// # use std::sync::atomic::AtomicBool;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// let cancel = AtomicBool::new(false);
+    /// // share `cancel` with the thread that may cancel the compaction
+    /// let is_compacted = store.compact_cancellable(&cancel)?;
+    /// # assert!(is_compacted);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compact_cancellable(&mut self, cancel: &AtomicBool) -> Result<bool> {
+        let Some(_compaction_guard) = CompactionGuard::try_start(&self.is_compacting) else {
+            return Ok(false);
+        };
+
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index: Option<MutexGuard<'_, InvertedIndex>> = self
+            .search_index
+            .as_ref()
+            .map(|v| acquire_lock!(v))
+            .transpose()?;
+
+        let is_compacted =
+            buffer_pool.compact_file_cancellable(&mut (search_index.as_deref_mut()), cancel)?;
+        Ok(is_compacted)
+    }
+
+    /// Removes deleted, expired or overwritten keys from the search index file, leaving the database file as is
+    ///
+    /// It is deprecated in favour of [Store::rebuild_search_index], which does the same,
+    /// but also returns the number of keys in the rebuilt search index.
+    ///
+    /// It is much cheaper than [Store::compact] as the database file is not rewritten, for when many keys
+    /// have been deleted but the database file is not worth compacting.
+    /// There is no converse that compacts only the database file, since moving the key-value pairs around
+    /// changes their addresses, which the search index would have to be rebuilt for anyway.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled. It may also fail with
+    /// [ScdbError::Io] in case it cannot access the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, true)?;
+    /// # #[allow(deprecated)]
+    /// store.compact_search_index()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[deprecated(
+        note = "use `Store::rebuild_search_index` instead, which does the same and returns the number of keys indexed"
+    )]
+    pub fn compact_search_index(&mut self) -> Result<()> {
+        self.rebuild_search_index()?;
+        Ok(())
+    }
+
+    /// Clears the search index and adds to it every unexpired key in the database file afresh,
+    /// returning the number of keys added
+    ///
+    /// It populates the search index of a store that was created with search disabled, and then
+    /// opened with search enabled, whose search index would otherwise be empty. It also brings
+    /// a search index that has gone out of sync with the database file e.g. after a crash,
+    /// back in sync. The database file is left as is.
+    ///
+    /// As the search index is written afresh, the space taken up in the search index file by deleted,
+    /// expired or overwritten keys is reclaimed. It is much cheaper than [Store::compact] as the database file
+    /// is not rewritten, for when many keys have been deleted but the database file is not worth compacting.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled. It may also fail with
+    /// [ScdbError::Io] in case it cannot access the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, true)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.rebuild_search_index()?, 1);
+    /// assert_eq!(store.search(&b"f"[..], 0, 0)?, vec![(b"foo".to_vec(), b"bar".to_vec())]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rebuild_search_index(&mut self) -> Result<u64> {
+        let idx = self
+            .search_index
+            .as_ref()
+            .ok_or(ScdbError::SearchDisabled)?;
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+        let count = buffer_pool.rebuild_search_index(&self.header, &mut search_index)?;
+        Ok(count)
+    }
+
+    /// Preallocates `estimated_bytes` of space for key-value pairs in the database file
+    ///
+    /// This extends the file to hold that many bytes of key-value entries in one go, instead of
+    /// growing it a little with every `set`. It is a no-op if the file is already that big.
+    /// New key-value pairs are still written right after the last one in the file. The preallocated space
+    /// that is still unused is given back on [Store::clear] and on compaction.
+    ///
+    /// Each key-value pair takes up 25 bytes on disk besides its key and value.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// // about 500k key-value pairs of about 100 bytes each
+    /// store.preallocate(500_000 * 100)?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// # store.clear()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn preallocate(&mut self, estimated_bytes: u64) -> Result<()> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        buffer_pool.preallocate(estimated_bytes)?;
+        Ok(())
+    }
+
+    /// Compacts the store, but only if at least `min_reclaimable_bytes` bytes would be reclaimed
+    ///
+    /// It returns true if compaction was done, or false if it was not worth it, or if a compaction
+    /// is already in progress e.g. in the background. This makes it cheap enough to be called often, unlike [Store::compact].
+    ///
+    /// The reclaimable bytes are an estimate of the space taken up by deleted, overwritten and expired
+    /// key-value pairs. The first call after the store is opened walks the whole index to compute it,
+    /// after which it is tracked on every `set` and `delete`. Key-value pairs that expire after
+    /// that first call are however not counted until compaction.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// // compact only if at least 1MB would be reclaimed
+    /// let is_compacted = store.compact_if_needed(1024 * 1024)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compact_if_needed(&mut self, min_reclaimable_bytes: u64) -> Result<bool> {
+        let Some(_compaction_guard) = CompactionGuard::try_start(&self.is_compacting) else {
+            return Ok(false);
+        };
+
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let reclaimable_bytes = buffer_pool.get_dangling_bytes(&self.header)?;
+        if reclaimable_bytes < min_reclaimable_bytes {
+            return Ok(false);
+        }
+
+        let mut search_index = match &self.search_index {
+            None => None,
+            Some(idx) => {
+                let idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+                Some(idx)
+            }
+        };
+
+        buffer_pool.compact_file(&mut (search_index.as_deref_mut()))?;
+        Ok(true)
+    }
+
+    /// Returns true if a compaction of the store is in progress, be it in the background or
+    /// by [Store::compact] or [Store::compact_if_needed]
+    ///
+    /// It never waits for the lock on the database file, so it can be polled e.g. to wait for
+    /// the background compaction to finish before shutting down. Compacting on open, with
+    /// [crate::StoreBuilder::compact_on_open], is done before the store is returned and so is never seen.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// store.compact()?;
+    /// assert!(!store.is_compacting());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_compacting(&self) -> bool {
+        self.is_compacting.load(Ordering::Acquire)
+    }
+
+    /// Returns an estimate of how much compacting the store would reclaim, without compacting it
+    ///
+    /// It walks the index just like compaction does, classifying each key-value entry as live, expired
+    /// or deleted, but it neither writes a new file nor replaces the database file. This makes it a safe
+    /// way of deciding whether a compaction is worth scheduling. It is however as expensive as
+    /// [Store::fragmentation], and the store is locked while it runs.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.set(&b"fee"[..], &b"baz"[..], None)?;
+    /// store.delete(&b"foo"[..])?;
+    ///
+    /// let preview = store.compaction_preview()?;
+    /// assert_eq!(preview.deleted_entries, 1);
+    /// assert_eq!(preview.live_entries, 1);
+    /// assert_eq!(preview.reclaimable_bytes(), preview.deleted_bytes);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compaction_preview(&self) -> Result<CompactionPreview> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let preview = buffer_pool.get_compaction_preview(&self.header)?;
+        Ok(preview)
+    }
+
+    /// Returns the number and the total size in bytes of the expired or deleted key-value entries
+    /// still in the database file, waiting to be removed by compaction
+    ///
+    /// It walks every entry in the index, so it is as expensive as a full scan of the keys. It is meant for
+    /// monitoring, e.g. to decide when to call [Store::compact_if_needed]. Note that the older versions
+    /// of updated keys are also reclaimed by compaction but are not counted here, as the index no longer
+    /// points to them.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.delete(&b"foo"[..])?;
+    /// let (count, total_bytes) = store.expired_entries()?;
+    /// assert_eq!((count, total_bytes), (1, 31));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn expired_entries(&mut self) -> Result<(u64, u64)> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let expired_entries = buffer_pool.count_expired_entries(&self.header)?;
+        Ok(expired_entries)
+    }
+
+    /// Makes all expired key-value pairs unreachable, returning the number of them
+    ///
+    /// It walks the whole index, clearing the index entries (and removing from the search index, if any)
+    /// of the expired key-value pairs. This is much cheaper than [Store::compact] as the database file
+    /// is not rewritten. The space taken up by the expired key-value pairs in the database file is thus
+    /// only reclaimed on the next compaction.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], Some(1))?;
+    /// store.set(&b"fee"[..], &b"baz"[..], None)?;
+    /// thread::sleep(Duration::from_secs(2));
+    ///
+    /// assert_eq!(store.drain_expired()?, 1);
+    /// assert_eq!(store.expired_entries()?, (0, 0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn drain_expired(&mut self) -> Result<u64> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let keys = buffer_pool.remove_expired_from_index(&self.header)?;
+
+        // Update the search index while still holding the lock on the buffer pool
+        // so that its state never lags behind that of the scdb file
+        if let Some(idx) = &self.search_index {
+            let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+            for key in &keys {
+                idx.remove(key)?;
+            }
+        }
+
+        Ok(keys.len() as u64)
+    }
+
+    /// Checks the integrity of the database file, returning the problems found with its key-value entries
+    ///
+    /// It walks every entry in the index, checking that the key-value entry it points to is within
+    /// the bounds of the file, that its stored size is consistent with its contents, and that its key
+    /// belongs at that index entry. Unlike the other operations, it does not fail on the first problem
+    /// but rather collects all of them. It does not modify the file.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// let corruptions = store.verify()?;
+    /// assert!(corruptions.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify(&mut self) -> Result<Vec<Corruption>> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let corruptions = buffer_pool.verify(&self.header)?;
+        Ok(corruptions)
+    }
+
+    /// Makes all unexpired keys that start with the given prefix expire after `in_secs` seconds,
+    /// returning the number of keys updated
+    ///
+    /// The expiry of each key-value pair is overwritten in place, which is cheaper than deleting and
+    /// setting them again. The values remain readable until the new expiry passes.
+    /// It requires the search capability of the store to be enabled.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled, or with [ScdbError::InvalidInput]
+    /// if the prefix is empty. It may also fail with [ScdbError::Io] in case it cannot access
+    /// the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, true)?;
+    /// # store.clear()?;
+    /// store.set(&b"session:1"[..], &b"alice"[..], None)?;
+    /// store.set(&b"session:2"[..], &b"bob"[..], None)?;
+    /// // expire all sessions in an hour
+    /// let count = store.expire_all_matching(&b"session:"[..], 3_600)?;
+    /// assert_eq!(count, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn expire_all_matching(&mut self, prefix: &[u8], in_secs: u64) -> Result<u64> {
+        if prefix.is_empty() {
+            return Err(ScdbError::InvalidInput(
+                "prefix must not be empty".to_string(),
+            ));
+        }
+
+        let idx = self
+            .search_index
+            .as_ref()
+            .ok_or(ScdbError::SearchDisabled)?;
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+        let expiry = self.clock.now_secs() + in_secs;
+        let mut count = 0;
+
+        for kv_address in search_index.search(prefix, 0, 0, SearchMode::Prefix)? {
+            if let Some(key) = buffer_pool.read_key(kv_address)? {
+                buffer_pool.update_expiry(kv_address, &key, expiry)?;
+                search_index.update_expiry(&key, expiry)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Resets the time-to-live of each of the given keys to `ttl` seconds from now, returning for each key
+    /// whether it was found (unexpired) in the store
+    ///
+    /// Just like [Store::expire_all_matching], the expiry of each key-value pair is overwritten in place.
+    /// The lock on the database file is taken once for the whole batch, making this cheaper than
+    /// setting each of the keys again e.g. when refreshing many active sessions at once.
+    /// If `ttl` is None, the keys never expire.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if any of the keys is empty or bigger than the configured
+    /// `max_key_size`, in which case none of the keys is touched.
+    /// It may fail with [ScdbError::Io] in case it cannot access the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"session:1"[..], &b"alice"[..], Some(60))?;
+    /// store.set(&b"session:2"[..], &b"bob"[..], Some(60))?;
+    /// // keep the sessions alive for another hour
+    /// let found = store.touch_many(&[&b"session:1"[..], b"session:2", b"session:3"], Some(3_600))?;
+    /// assert_eq!(found, vec![true, true, false]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn touch_many(&mut self, keys: &[&[u8]], ttl: Option<u64>) -> Result<Vec<bool>> {
+        for &k in keys {
+            validate_key(k)?;
+            validate_entry_size(k.len(), 0, self.max_key_size, None)?;
+        }
+
+        let expiry = match ttl {
+            None => 0u64,
+            Some(expiry) => self.clock.now_secs() + expiry,
+        };
+
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index: Option<MutexGuard<'_, InvertedIndex>> = match &self.search_index {
+            None => None,
+            Some(idx) => Some(acquire_lock!(idx)?),
+        };
+        let mut found = Vec::with_capacity(keys.len());
+
+        for &k in keys {
+            match self.get_kv_address_from_pool(&mut buffer_pool, k)? {
+                Some(kv_address) => {
+                    buffer_pool.update_expiry(kv_address, k, expiry)?;
+                    if let Some(idx) = search_index.as_deref_mut() {
+                        idx.update_expiry(k, expiry)?;
+                    }
+                    found.push(true);
+                }
+                None => found.push(false),
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Deletes all unexpired key-value pairs whose keys start with the given prefix,
+    /// returning the number of keys deleted
+    ///
+    /// It requires the search capability of the store to be enabled.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled, or with [ScdbError::InvalidInput]
+    /// if the prefix is empty. It may also fail with [ScdbError::Io] in case it cannot access
+    /// the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, true)?;
+    /// # store.clear()?;
+    /// store.set(&b"session:1"[..], &b"alice"[..], None)?;
+    /// store.set(&b"session:2"[..], &b"bob"[..], None)?;
+    /// store.set(&b"user:1"[..], &b"alice"[..], None)?;
+    ///
+    /// assert_eq!(store.delete_prefix(&b"session:"[..])?, 2);
+    /// assert_eq!(store.get(&b"session:1"[..])?, None);
+    /// assert_eq!(store.get(&b"user:1"[..])?, Some(b"alice".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_prefix(&mut self, prefix: &[u8]) -> Result<u64> {
+        if prefix.is_empty() {
+            return Err(ScdbError::InvalidInput(
+                "prefix must not be empty".to_string(),
+            ));
+        }
+
+        let keys = {
+            let idx = self
+                .search_index
+                .as_ref()
+                .ok_or(ScdbError::SearchDisabled)?;
+            let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+            let mut search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+            let mut keys = vec![];
+            for kv_address in search_index.search(prefix, 0, 0, SearchMode::Prefix)? {
+                if let Some(key) = buffer_pool.read_key(kv_address)? {
+                    keys.push(key);
+                }
+            }
+            keys
+        };
+
+        let mut count = 0;
+        for key in keys {
+            if self.delete(&key)? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Deletes all unexpired key-value pairs for which `f` returns false, returning the number of keys deleted
+    ///
+    /// The whole index is walked to find the key-value pairs to delete, after which each of them is
+    /// marked as deleted in place, just like [Store::delete] does. The space they take up is only reclaimed
+    /// on the next compaction; call [Store::compact] or [Store::compact_if_needed] to reclaim it right away.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"small"[..], &b"bar"[..], None)?;
+    /// store.set(&b"big"[..], &[0u8; 1024][..], None)?;
+    ///
+    /// // drop all values bigger than 512 bytes
+    /// assert_eq!(store.retain(|_, v| v.len() <= 512)?, 1);
+    /// assert_eq!(store.get(&b"big"[..])?, None);
+    /// assert_eq!(store.get(&b"small"[..])?, Some(b"bar".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F) -> Result<u64>
+    where
+        F: FnMut(&[u8], &[u8]) -> bool,
+    {
+        let mut keys = vec![];
+        for pair in self.iter()? {
+            let (k, v) = pair?;
+            if !f(&k, &v) {
+                keys.push(k);
+            }
+        }
+
+        let mut count = 0;
+        for key in keys {
+            if self.delete(&key)? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Sets every unexpired key-value pair of the `other` store in this store, returning
+    /// the number of key-value pairs set
+    ///
+    /// The key-value pairs keep their expiry. Keys that exist in both stores are resolved as dictated
+    /// by `on_conflict`. Both stores are locked for the whole merge, the other store first; so do not merge
+    /// two stores into each other from different threads at the same time as that would deadlock.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with the same errors as [Store::set] e.g. [ScdbError::KeysMaxedOut] if this store
+    /// fills up, in which case the key-value pairs set before the failure remain set.
+    /// It may also fail with [ScdbError::Io] in case it cannot access the database file of the other store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scdb::{ConflictPolicy, Store};
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut store = Store::new("db", None, None, None, None, false)?;
+    /// let mut other = Store::new("db_other", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// # other.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// other.set(&b"foo"[..], &b"baz"[..], None)?;
+    /// other.set(&b"fee"[..], &b"fum"[..], None)?;
+    ///
+    /// assert_eq!(store.merge_from(&mut other, ConflictPolicy::Skip)?, 1);
+    /// assert_eq!(store.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// assert_eq!(store.get(&b"fee"[..])?, Some(b"fum".to_vec()));
+    /// # drop(other);
+    /// # std::fs::remove_dir_all("db_other")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn merge_from(&mut self, other: &mut Store, on_conflict: ConflictPolicy) -> Result<u64> {
+        let other_pool: MutexGuard<'_, BufferPool> = acquire_lock!(other.buffer_pool)?;
+        let entries = PoolIterator::new(
+            other_pool,
+            &other.header,
+            BufferPool::read_key_value_and_expiry,
+        );
+        let buffer_pool = self.buffer_pool.clone();
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+
+        let mut written = 0u64;
+        for entry in entries {
+            let (k, v, expiry) = entry?;
+            let should_set = match self.get_entry_from_pool(&mut buffer_pool, &k)? {
+                None => true,
+                Some((_, existing_expiry)) => on_conflict.should_replace(existing_expiry, expiry),
+            };
+
+            if should_set {
+                validate_entry_size(k.len(), v.len(), self.max_key_size, self.max_value_size)?;
+                self.set_in_pool(&mut buffer_pool, &k, &v, expiry)?;
+                written += 1;
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Creates a new store at `dest_dir` with the given configuration, and sets in it every unexpired
+    /// key-value pair of this store, returning the new store
+    ///
+    /// Unlike copying the database file, this lays the data out afresh in the geometry of `config`
+    /// i.e. its `max_keys`, `redundant_blocks` and `max_index_key_len`. It is thus a way of growing a store
+    /// that is running out of slots for its keys, compacting it at the same time. The key-value pairs keep
+    /// their expiry, and are added to the search index of the new store if search is enabled in `config`.
+    /// This store is left intact.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if `config` is invalid, or if the store at `dest_dir`
+    /// (given the file names in `config`) already has keys, so as not to mix two stores' data.
+    /// It may also fail with the same errors as [Store::merge_from] e.g. [ScdbError::KeysMaxedOut]
+    /// if the new store can't hold all the keys, in which case the new store is left with only some of them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scdb::{Store, StoreConfig};
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut store = Store::new("db", Some(10), None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], Some(3600))?;
+    ///
+    /// let config = StoreConfig {
+    ///     max_keys: Some(1000),
+    ///     redundant_blocks: Some(2),
+    ///     is_search_enabled: true,
+    ///     ..Default::default()
+    /// };
+    /// # std::fs::remove_dir_all("db_bigger").ok();
+    /// let mut bigger = store.clone_to("db_bigger", config)?;
+    /// assert_eq!(bigger.max_keys(), 1000);
+    /// assert_eq!(bigger.get(&b"foo"[..])?, Some(b"bar".to_vec()));
+    /// assert_eq!(bigger.search(&b"f"[..], 0, 0)?, vec![(b"foo".to_vec(), b"bar".to_vec())]);
+    /// # drop(bigger);
+    /// # std::fs::remove_dir_all("db_bigger")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clone_to(&mut self, dest_dir: &str, config: StoreConfig) -> Result<Store> {
+        let mut store = Store::from_config(dest_dir, config)?;
+        if store.keys()?.next().transpose()?.is_some() {
+            return Err(ScdbError::InvalidInput(format!(
+                "the store at {:?} already has keys",
+                dest_dir
+            )));
+        }
+
+        store.merge_from(self, ConflictPolicy::Overwrite)?;
+        Ok(store)
+    }
+
+    /// Returns a handle to the namespace of the store whose keys all start with the given prefix
+    ///
+    /// This allows multiple logical stores to share one database file (and one background
+    /// compaction task). See [Namespace] for more.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if the prefix is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, true)?;
+    /// # store.clear()?;
+    /// let mut users = store.namespaced(&b"users:"[..])?;
+    /// users.set(&b"1"[..], &b"alice"[..], None)?;
+    /// assert_eq!(users.get(&b"1"[..])?, Some(b"alice".to_vec()));
+    ///
+    /// // the key is actually saved with the prefix of the namespace
+    /// assert_eq!(store.get(&b"users:1"[..])?, Some(b"alice".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn namespaced(&mut self, prefix: &[u8]) -> Result<Namespace<'_>> {
+        Namespace::new(self, prefix)
+    }
+
+    /// Returns a loader for adding many key-value pairs to the store much faster than calling
+    /// [Store::set] for each of them e.g. when migrating a large dataset into the store.
+    /// See [BulkLoader] for more.
+    ///
+    /// The loader takes exclusive ownership of the store for as long as it is held. Compaction is paused
+    /// until it is dropped, and if a compaction is in progress e.g. in the background, this waits for it to finish.
+    ///
+    /// # Errors
+    ///
+    /// It is not expected to fail, but returns a [Result] to allow for failures in preparing the load in future.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// let mut loader = store.bulk_loader()?;
+    /// for (k, v) in [("foo", "bar"), ("hey", "English")] {
+    ///     loader.add(k.as_bytes(), v.as_bytes(), 0)?;
+    /// }
+    /// loader.finish()?;
+    ///
+    /// assert_eq!(store.get(&b"hey"[..])?, Some(b"English".to_vec()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bulk_loader(&mut self) -> Result<BulkLoader<'_>> {
+        Ok(BulkLoader::new(self))
+    }
+
+    /// Changes the interval in seconds at which the store is compacted in the background
+    ///
+    /// The current background compaction task is stopped and, if `interval` is greater than 0,
+    /// a new one is started with the new interval. An `interval` of 0 stops background compaction.
+    /// Unlike recreating the store, this keeps the in-memory cache intact.
+    ///
+    /// # Errors
+    ///
+    /// It is not expected to fail, but returns a [Result] to allow for failures
+    /// in starting the background task in future.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// // compact every 30 minutes instead
+    /// store.set_compaction_interval(1_800)?;
+    /// // stop compacting in the background
+    /// store.set_compaction_interval(0)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_compaction_interval(&mut self, interval: u32) -> Result<()> {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.stop();
+        }
+
+        self.scheduler = initialize_scheduler(
+            Some(interval),
+            &self.buffer_pool,
+            &self.search_index,
+            &self.is_compacting,
+            &self.is_bulk_loading,
+        );
+        Ok(())
+    }
+
+    /// Re-reads the files of the store, to pick up the changes made to them by another process
+    ///
+    /// The store keeps a copy of the header of the database file and caches parts of the file
+    /// in memory. Thus a long-lived reader process would not notice if a writer process compacted
+    /// or cleared the store with a different `max_keys`. Calling `refresh` re-opens the files,
+    /// re-reads their headers and file sizes, and discards the in-memory cache.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the files say if they were deleted
+    /// or due to permissions errors, or with [ScdbError::Corruption] if the header is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// // ...after another process has written to the store
+    /// store.refresh()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn refresh(&mut self) -> Result<()> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        self.header = buffer_pool.reload_file()?;
+
+        if let Some(idx) = &self.search_index {
+            let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+            idx.reload_file()?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes the store, returning any error that occurs while doing so
+    ///
+    /// It stops the background compaction, waiting for any compaction that is in progress
+    /// to finish, and then flushes the database and search index files to disk.
+    /// Dropping the store does the same except flushing, but it has to swallow any errors,
+    /// so `close` is preferred when one cares about errors.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot flush the files, or if the background
+    /// compaction failed, leaving the store in an unknown state.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.close()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close(mut self) -> Result<()> {
+        if let Some(scheduler) = self.scheduler.take() {
+            // this blocks till any compaction in progress is done
+            scheduler.stop();
+        }
+
+        // the lock is poisoned if the background compaction panicked midway
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        buffer_pool.sync_file()?;
+
+        if let Some(idx) = &self.search_index {
+            let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+            idx.sync_file()?;
+        }
+
+        Ok(())
+    }
+
+    /// Searches for unexpired keys containing the given search term, in [SearchMode::Contains]
+    ///
+    /// Only keys that start with the first `max_index_key_len` bytes of the term are looked at, and of those,
+    /// it returns the ones that contain the whole term anywhere. For a term no longer than `max_index_key_len`,
+    /// that amounts to the keys starting with it. To get only the keys that start with the whole term,
+    /// use [Store::search_with_mode] with [SearchMode::Prefix], or [Store::iter_prefix].
+    ///
+    /// It skips the first `skip` (default: 0) number of results and returns not more than
+    /// `limit` (default: 0) number of items. This is to avoid using up more memory than can be handled by the
+    /// host machine.
+    ///
+    /// If `limit` is 0, all items are returned since it would make no sense for someone to search
+    /// for zero items.
+    ///
+    /// returns a list of tuples of key-value
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, true)?; // enable search
+    /// # store.clear()?;   
+    /// // imagine the store has the following key value pairs
+    /// let data = vec![
+    ///     (&b"hi"[..], &b"ooliyo"[..]),
+    ///     (&b"high"[..], &b"haiguru"[..]),
+    ///     (&b"hind"[..], &b"enyuma"[..]),
+    ///     (&b"hill"[..], &b"akasozi"[..]),
+    ///     (&b"him"[..], &b"ogwo"[..]),
+    /// ];
+    /// # let mut expected: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+    /// # for (k, v) in data {
+    /// #    store.set(k, v, None)?;
+    /// #    expected.push((k.to_vec(), v.to_vec()))
+    /// # }
+    /// // search for key-values where the keys contain 'hi', which is shorter than `max_index_key_len`,
+    /// // so they all start with it
+    /// let key_values = store.search(&b"hi"[..], 0, 0)?;
+    /// assert_eq!(key_values, expected);
+    ///
+    /// // Or just return a few of them, say last three
+    /// let key_values = store.search(&b"hi"[..], 2, 3)?;
+    /// assert_eq!(key_values, expected[2..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search(
+        &mut self,
+        term: &[u8],
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.search_with_mode(term, skip, limit, SearchMode::Contains)
+    }
+
+    /// Searches for unexpired keys matching the given search term, as dictated by the search `mode`
+    ///
+    /// [SearchMode::Prefix] returns only the keys that start with `term`, while
+    /// [SearchMode::Contains] (used by [Store::search]) returns the keys that start with
+    /// the first `max_index_key_len` bytes of `term` and contain the whole `term` anywhere.
+    /// `skip` and `limit` work just like they do in [Store::search].
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled. It may also fail with
+    /// [ScdbError::Io] in case it cannot access the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::{SearchMode, Store};
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, true)?; // enable search
+    /// # store.clear()?;
+    /// store.set(&b"hiking"[..], &b"okulambula"[..], None)?;
+    /// store.set(&b"hikhiking"[..], &b"ebirala"[..], None)?;
+    ///
+    /// let key_values = store.search_with_mode(&b"hiki"[..], 0, 0, SearchMode::Prefix)?;
+    /// assert_eq!(key_values, vec![(b"hiking".to_vec(), b"okulambula".to_vec())]);
+    ///
+    /// let key_values = store.search_with_mode(&b"hiki"[..], 0, 0, SearchMode::Contains)?;
+    /// assert_eq!(key_values.len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(term_len = term.len(), skip, limit, ?mode))
+    )]
+    pub fn search_with_mode(
+        &mut self,
+        term: &[u8],
+        skip: u64,
+        limit: u64,
+        mode: SearchMode,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        if let Some(idx) = &self.search_index {
+            // Both locks are held for the whole search, in the same order as compaction takes them,
+            // so that compaction cannot move the key-values between reading their addresses and their data
+            let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+            let mut search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+            let offsets = search_index.search(term, skip, limit, mode)?;
+            let key_values = buffer_pool.get_many_key_values(&offsets)?;
+            Ok(key_values)
+        } else {
+            Err(ScdbError::SearchDisabled)
+        }
+    }
+
+    /// Searches for keys containing the given search term, just like [Store::search], but only returns
+    /// those with at least `min_ttl` seconds left before they expire
+    ///
+    /// Keys that never expire are always returned. Keys with too little time left are skipped as the search
+    /// index is walked, before `skip` and `limit` are applied, so they never count towards either.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled. It may also fail with
+    /// [ScdbError::Io] in case it cannot access the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, true)?; // enable search
+    /// # store.clear()?;
+    /// store.set(&b"session:1"[..], &b"alice"[..], Some(30))?;
+    /// store.set(&b"session:2"[..], &b"bob"[..], Some(3600))?;
+    ///
+    /// // only the sessions with at least 10 minutes left
+    /// let sessions = store.search_with_min_ttl(&b"session:"[..], 600, 0, 0)?;
+    /// assert_eq!(sessions, vec![(b"session:2".to_vec(), b"bob".to_vec())]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_with_min_ttl(
+        &mut self,
+        term: &[u8],
+        min_ttl: u64,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let idx = self
+            .search_index
+            .as_ref()
+            .ok_or(ScdbError::SearchDisabled)?;
+        let min_expiry = self.clock.now_secs().saturating_add(min_ttl);
+        let has_min_ttl = |expiry: u64| expiry == 0 || expiry >= min_expiry;
+
+        // the locks are taken in the same order as in search_with_mode, for the same reason
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let mut search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+        let offsets = search_index.search_with_filter(
+            term,
+            skip,
+            limit,
+            SearchMode::Contains,
+            has_min_ttl,
+        )?;
+        let key_values = buffer_pool.get_many_key_values(&offsets)?;
+        Ok(key_values)
+    }
+
+    /// Calls `f` with every unexpired key-value pair whose key contains the given search term,
+    /// just like [Store::search] would return them, stopping as soon as `f` returns false
+    ///
+    /// Unlike [Store::search], the key-value pairs are not collected up front. They are read one at a time,
+    /// as the list of keys that share the term's prefix in the search index is walked, so memory use does not
+    /// grow with the number of matches. The store is locked until it returns, so `f` must not access
+    /// the same store as that would deadlock.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled. It may also fail with
+    /// [ScdbError::Io] in case it cannot access the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, true)?; // enable search
+    /// # store.clear()?;
+    /// store.set(&b"hi"[..], &b"ooliyo"[..], None)?;
+    /// store.set(&b"high"[..], &b"haiguru"[..], None)?;
+    /// store.set(&b"hind"[..], &b"enyuma"[..], None)?;
+    ///
+    /// // count the keys starting with 'hi', stopping after the first two
+    /// let mut count = 0;
+    /// store.search_for_each(&b"hi"[..], |_key, _value| {
+    ///     count += 1;
+    ///     count < 2
+    /// })?;
+    /// assert_eq!(count, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_for_each<F>(&mut self, term: &[u8], mut f: F) -> Result<()>
+    where
+        F: FnMut(Vec<u8>, Vec<u8>) -> bool,
+    {
+        let idx = self
+            .search_index
+            .as_ref()
+            .ok_or(ScdbError::SearchDisabled)?;
+        let buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+
+        for pair in PrefixIterator::new(buffer_pool, search_index, term, SearchMode::Contains)? {
+            let (k, v) = pair?;
+            if !f(k, v) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the given key, in full, is in the search index and has not expired
+    ///
+    /// Unlike [Store::search], which matches keys that merely contain the term, it only matches
+    /// the exact key, however much longer than `max_index_key_len` it is. It is thus a reliable way
+    /// of checking that the search index is in sync with the database file for a given key.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled, or with [ScdbError::InvalidInput]
+    /// if the key is empty. It may also fail with [ScdbError::Io] in case it cannot access
+    /// the search index file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, true)?; // enable search
+    /// # store.clear()?;
+    /// store.set(&b"barricade"[..], &b"fence"[..], None)?;
+    ///
+    /// assert!(store.contains_key_indexed(&b"barricade"[..])?);
+    /// // unlike search, keys that merely start with the given key do not count
+    /// assert!(!store.contains_key_indexed(&b"barr"[..])?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn contains_key_indexed(&mut self, k: &[u8]) -> Result<bool> {
+        validate_key(k)?;
+        let idx = self
+            .search_index
+            .as_ref()
+            .ok_or(ScdbError::SearchDisabled)?;
+        // only the search index is read, and it is only ever changed while locked, so the buffer pool
+        // need not be locked too
+        let mut search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+        let is_indexed = search_index.contains_key_indexed(k)?;
+        Ok(is_indexed)
+    }
+
+    /// Returns the unexpired key-value pairs at the given addresses of key-value entries in the database
+    /// file, in the same order as the addresses
+    ///
+    /// It is meant for custom query layers that walk the index or the search index file themselves
+    /// and thus come by the addresses of the entries. An address that is out of the bounds of
+    /// the key-value entries, or at which no entry fits in the file, gives `None`, just as one
+    /// of an expired or deleted entry does. Beware though that an address within the bounds
+    /// that is not the start of an entry may give garbage.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"hey"[..], &b"English"[..], None)?;
+    ///
+    /// assert_eq!(store.get_by_addresses(&[0, u64::MAX])?, vec![None, None]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_by_addresses(&mut self, addrs: &[u64]) -> Result<Vec<Option<KeyValuePair>>> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let key_values = buffer_pool.get_key_values_at(addrs)?;
+        Ok(key_values)
+    }
+
+    /// Returns an iterator over all unexpired key-value pairs whose keys start with the given prefix
+    ///
+    /// Unlike [Store::search], the key-value pairs are not collected up front. They are read lazily,
+    /// one at a time, by walking the list of keys that share the prefix in the search index.
+    /// The store is locked for as long as the iterator lives.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::SearchDisabled] if search is not enabled, or with [ScdbError::InvalidInput]
+    /// if the prefix is empty. It may also fail with [ScdbError::Io] in case it cannot access
+    /// the database or search index files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, true)?; // enable search
+    /// # store.clear()?;
+    /// store.set(&b"hi"[..], &b"ooliyo"[..], None)?;
+    /// store.set(&b"high"[..], &b"haiguru"[..], None)?;
+    /// store.set(&b"low"[..], &b"wansi"[..], None)?;
+    ///
+    /// // only consume the first key-value pair starting with 'hi'
+    /// let (key, _) = store.iter_prefix(&b"hi"[..])?.next().unwrap()?;
+    /// assert!(key.starts_with(b"hi"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_prefix(
+        &mut self,
+        prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + '_> {
+        if prefix.is_empty() {
+            return Err(ScdbError::InvalidInput(
+                "prefix must not be empty".to_string(),
+            ));
+        }
+
+        let idx = self
+            .search_index
+            .as_ref()
+            .ok_or(ScdbError::SearchDisabled)?;
+        let buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let search_index: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+        let iter = PrefixIterator::new(buffer_pool, search_index, prefix, SearchMode::Prefix)?;
+        Ok(iter.map(|pair| pair.map_err(ScdbError::from)))
+    }
+
+    /// Returns an iterator over all unexpired key-value pairs in the store
+    ///
+    /// The key-value pairs are read lazily from the database file, in the order of the index
+    /// i.e. in no particular order. The store is locked for as long as the iterator lives
+    /// so any other threads trying to write to it will have to wait till it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// for pair in store.iter()? {
+    ///     let (key, value) = pair?;
+    ///     assert_eq!((key, value), (b"foo".to_vec(), b"bar".to_vec()));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter(&mut self) -> Result<impl Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + '_> {
+        let buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let iter = PoolIterator::new(buffer_pool, &self.header, BufferPool::read_key_value);
+        Ok(iter.map(|pair| pair.map_err(ScdbError::from)))
+    }
+
+    /// Returns an iterator over the keys of all unexpired key-value pairs in the store
+    ///
+    /// Only the keys are read from the database file; the values are never loaded.
+    /// Just like [Store::iter], the store is locked for as long as the iterator lives.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// let keys = store.keys()?.collect::<scdb::Result<Vec<_>>>()?;
+    /// assert_eq!(keys, vec![b"foo".to_vec()]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keys(&mut self) -> Result<impl Iterator<Item = Result<Vec<u8>>> + '_> {
+        let buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let iter = PoolIterator::new(buffer_pool, &self.header, BufferPool::read_key);
+        Ok(iter.map(|key| key.map_err(ScdbError::from)))
+    }
+
+    /// Returns the keys of all unexpired key-value pairs in the store, each with the number of seconds
+    /// it has left to live, or None if it never expires, e.g. for an admin view of a session cache
+    ///
+    /// Only the keys and expiries are read from the database file; the values are never loaded,
+    /// so it stays cheap however big the values are.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.set(&b"session"[..], &b"alice"[..], Some(3600))?;
+    /// let mut entries = store.entries_with_ttl()?;
+    /// entries.sort();
+    /// assert_eq!(entries[0], (b"foo".to_vec(), None));
+    /// assert_eq!(entries[1].0, b"session".to_vec());
+    /// assert!(entries[1].1 <= Some(3600));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn entries_with_ttl(&mut self) -> Result<Vec<(Vec<u8>, Option<u64>)>> {
+        let buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let iter = PoolIterator::new(buffer_pool, &self.header, BufferPool::read_key_and_ttl);
+        Ok(iter.collect::<io::Result<Vec<_>>>()?)
+    }
+
+    /// Returns an iterator over the values of all unexpired key-value pairs in the store
+    ///
+    /// Just like [Store::iter], the store is locked for as long as the iterator lives.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// let values = store.values()?.collect::<scdb::Result<Vec<_>>>()?;
+    /// assert_eq!(values, vec![b"bar".to_vec()]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn values(&mut self) -> Result<impl Iterator<Item = Result<Vec<u8>>> + '_> {
+        Ok(self.iter()?.map(|pair| pair.map(|(_, v)| v)))
+    }
+
+    /// Returns up to `count` unexpired key-value pairs, starting from where the given `cursor` points,
+    /// together with the cursor to pass to the next call, or None if all key-value pairs have been returned
+    ///
+    /// Unlike [Store::iter], the store is only locked for the duration of each call, so other threads can
+    /// write to the store between the calls. Much like the Redis `SCAN` command, any key-value pair that is
+    /// in the store throughout the whole enumeration is returned exactly once, starting with the default
+    /// [ScanCursor]. Those that are set or deleted midway may or may not be returned, and a key that is
+    /// deleted and then set again midway may be returned twice. Just like [Store::iter], the key-value pairs
+    /// are in no particular order.
+    ///
+    /// # Errors
+    ///
+    /// It fails with [ScdbError::InvalidInput] if `count` is 0. It may also fail with [ScdbError::Io]
+    /// in case it cannot access the database file say if it deleted or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::{ScanCursor, Store};
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.set(&b"fee"[..], &b"baz"[..], None)?;
+    ///
+    /// let mut pairs = vec![];
+    /// let mut cursor = Some(ScanCursor::default());
+    /// while let Some(current) = cursor {
+    ///     let (page, next) = store.scan(current, 1)?;
+    ///     assert!(page.len() <= 1);
+    ///     pairs.extend(page);
+    ///     cursor = next;
+    /// }
+    /// assert_eq!(pairs.len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scan(
+        &mut self,
+        cursor: ScanCursor,
+        count: usize,
+    ) -> Result<(Vec<KeyValuePair>, Option<ScanCursor>)> {
+        if count == 0 {
+            return Err(ScdbError::InvalidInput(
+                "count must be greater than 0".to_string(),
+            ));
+        }
+
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let (pairs, next) = buffer_pool.scan_key_values(&self.header, cursor.0, count)?;
+        Ok((pairs, next.map(ScanCursor)))
+    }
+
+    /// Writes all unexpired key-value pairs in the store to `w`, one per line, as `key<TAB>value<TAB>expiry`,
+    /// returning the number of key-value pairs written
+    ///
+    /// It is meant for eyeballing the contents of a store e.g. when debugging. The keys and values are
+    /// written as text as chosen by `value_as`, and the expiry as a Unix timestamp in seconds, or 0 if
+    /// the key-value pair never expires. Just like [Store::iter], the key-value pairs are in no particular
+    /// order and the store is locked for as long as the dump takes.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file, or write to `w`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::{Store, ValueEncoding};
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar\tbaz"[..], None)?;
+    ///
+    /// let mut out = vec![];
+    /// assert_eq!(store.dump(&mut out, ValueEncoding::Utf8Lossy)?, 1);
+    /// assert_eq!(out, b"foo\tbar\\tbaz\t0\n".to_vec());
+    ///
+    /// let mut out = vec![];
+    /// store.dump(&mut out, ValueEncoding::Base64)?;
+    /// assert_eq!(out, b"Zm9v\tYmFyCWJheg==\t0\n".to_vec());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dump<W: Write>(&mut self, w: &mut W, value_as: ValueEncoding) -> Result<u64> {
+        let buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let entries = PoolIterator::new(
+            buffer_pool,
+            &self.header,
+            BufferPool::read_key_value_and_expiry,
+        );
+
+        let mut written = 0u64;
+        for entry in entries {
+            let (k, v, expiry) = entry?;
+            writeln!(
+                w,
+                "{}\t{}\t{}",
+                value_as.encode(&k),
+                value_as.encode(&v),
+                expiry
+            )?;
+            written += 1;
+        }
+
+        Ok(written)
+    }
+}
+
+impl Store {
+    /// Returns the number of hits and misses of the in-memory cache of the store
+    /// since it was opened or last cleared.
+    ///
+    /// This is useful for tuning the `pool_capacity`: a low hit rate means the cache is
+    /// too small for the set of keys frequently accessed. It does not lock the store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.get(&b"foo"[..])?;
+    /// let stats = store.cache_stats();
+    /// // set read the index from file, get then found it in memory
+    /// assert_eq!(stats.index_misses, 1);
+    /// assert_eq!(stats.index_hits, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache_counters.snapshot()
+    }
+
+    /// Returns the number of bytes read from and written to the database file since the store was opened
+    /// or last cleared
+    ///
+    /// Together with [Store::cache_stats], this shows how much of the workload actually reaches the disk.
+    /// The I/O done by compaction is included. It does not lock the store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut  store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// let stats = store.io_stats();
+    /// // the key-value entry of 31 bytes and its address in the index of 8 bytes
+    /// assert_eq!(stats.bytes_written, 39);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn io_stats(&self) -> IoStats {
+        self.io_counters.snapshot()
+    }
+
+    /// Returns the load factor of the store i.e. the number of unexpired keys over its `max_keys`
+    ///
+    /// As it approaches 1, setting new keys is more likely to fail with [ScdbError::CollisionSaturated].
+    /// The first call after the store is opened walks the whole index to count the keys. Thereafter,
+    /// the count is kept up to date by `set` and `delete`, though keys that expire in the meantime
+    /// are only discounted after the next compaction.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut store = Store::new("db_load_factor", Some(1000), None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.load_factor()?, 0.001);
+    /// # drop(store);
+    /// # std::fs::remove_dir_all("db_load_factor")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_factor(&self) -> Result<f64> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        self.get_load_factor(&mut buffer_pool)
+    }
+
+    /// Returns the number of new keys that can still be set before the store holds `max_keys` unexpired keys,
+    /// after which setting new keys fails with [ScdbError::KeysMaxedOut]
+    ///
+    /// Just like [Store::load_factor], it is based on the count of unexpired keys kept up to date by `set`
+    /// and `delete`, so keys that expire in the meantime only free up room after the next compaction.
+    /// Together, they allow a service to shed load, or grow the store, before writes start failing.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut store = Store::new("db_remaining_capacity", Some(1000), None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// assert_eq!(store.remaining_capacity()?, 999);
+    /// # drop(store);
+    /// # std::fs::remove_dir_all("db_remaining_capacity")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remaining_capacity(&self) -> Result<u64> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let live_keys = buffer_pool.get_live_keys(&self.header)?;
+        Ok(self.header.max_keys.saturating_sub(live_keys))
+    }
+
+    /// Returns how much of the database file is taken up by live key-value pairs, and how much by
+    /// deleted, expired or overwritten ones, waiting to be removed by compaction
+    ///
+    /// The [FragmentationReport::ratio] of dead bytes is a single number to alert on, or to decide when
+    /// to compact, while [FragmentationReport::average_entry_size] helps in capacity planning.
+    /// It walks the whole index, just like [Store::verify] does, so it is as expensive.
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.set(&b"fee"[..], &b"baz"[..], None)?;
+    /// store.delete(&b"foo"[..])?;
+    ///
+    /// let report = store.fragmentation()?;
+    /// assert_eq!(report.ratio(), 0.5);
+    /// assert_eq!(report.average_entry_size(), 31.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fragmentation(&self) -> Result<FragmentationReport> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let report = buffer_pool.get_fragmentation(&self.header)?;
+        Ok(report)
+    }
+
+    /// Returns how many of the unexpired key-value pairs in the store have keys, and values, of sizes
+    /// in each power-of-two range e.g. values of 64 to 127 bytes
+    ///
+    /// It shows whether the values mostly fit in a buffer of the pool, or whether lookups for them
+    /// have to keep going to the database file. Only the sizes of the entries are read from the file,
+    /// never their keys or values, but all entries are visited, so it is as expensive as [Store::keys].
+    ///
+    /// # Errors
+    ///
+    /// It may fail with [ScdbError::Io] in case it cannot access the database file say if it deleted
+    /// or due to permissions errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::{SizeHistogram, Store};
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db", None, None, None, None, false)?;
+    /// # store.clear()?;
+    /// store.set(&b"foo"[..], &b"bar"[..], None)?;
+    /// store.set(&b"fee"[..], &[0u8; 100][..], None)?;
+    ///
+    /// let histogram = store.size_histogram()?;
+    /// // both keys are 3 bytes long i.e. between 2 and 3 bytes
+    /// assert_eq!(histogram.key_sizes, vec![0, 2]);
+    /// assert_eq!(SizeHistogram::bucket_bounds(1), (2, 4));
+    /// // one value of 3 bytes, and one of 100 bytes i.e. between 64 and 127 bytes
+    /// assert_eq!(histogram.value_sizes, vec![0, 1, 0, 0, 0, 0, 1]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn size_histogram(&self) -> Result<SizeHistogram> {
+        let buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let sizes = PoolIterator::new(
+            buffer_pool,
+            &self.header,
+            BufferPool::read_live_key_and_value_sizes,
+        );
+
+        let mut histogram = SizeHistogram::default();
+        for entry in sizes {
+            let (key_size, value_size) = entry?;
+            histogram.record(key_size as u64, value_size as u64);
+        }
+        Ok(histogram)
+    }
+
+    /// Returns the path to the database file of the store, or None if the store is not on the file system
+    /// e.g. if it was created via [Store::in_memory] or [Store::from_files]
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// # use std::path::Path;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// let store = Store::new("db", None, None, None, None, true)?;
+    /// assert_eq!(store.db_file_path(), Some(Path::new("db/dump.scdb")));
+    /// assert_eq!(store.index_file_path(), Some(Path::new("db/index.iscdb")));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn db_file_path(&self) -> Option<&Path> {
+        self.db_file_path.as_deref()
+    }
+
+    /// Returns the path to the search index file of the store, or None if search is not enabled
+    /// or if the store is not on the file system
+    pub fn index_file_path(&self) -> Option<&Path> {
+        self.index_file_path.as_deref()
+    }
+
+    /// Returns the maximum number of key-value pairs the store was created to hold
+    ///
+    /// This, like the other geometry getters, reflects the database file as it was when the store
+    /// was opened (or last cleared, grown or refreshed), which may differ from the configuration
+    /// passed when opening an existing store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scdb::Store;
+    /// #
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut store = Store::new("db_geometry", Some(1000), Some(2), None, None, true)?;
+    /// # drop(store);
+    /// // the geometry is that of the existing db file, not the one passed when opening it
+    /// let store = Store::new("db_geometry", None, None, None, None, true)?;
+    /// assert_eq!(store.max_keys(), 1000);
+    /// assert_eq!(store.redundant_blocks(), 2);
+    /// assert_eq!(store.max_index_key_len(), Some(3));
+    /// # drop(store);
+    /// # std::fs::remove_dir_all("db_geometry")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn max_keys(&self) -> u64 {
+        self.header.max_keys
+    }
+
+    /// Returns the number of redundant index blocks of the store, used to mitigate hash collisions
+    pub fn redundant_blocks(&self) -> u16 {
+        self.header.redundant_blocks
+    }
+
+    /// Returns the size in bytes of each index block of the database file,
+    /// usually the virtual memory page size of the machine on which the file was created
+    pub fn block_size(&self) -> u32 {
+        self.header.block_size
+    }
+
+    /// Returns the maximum number of bytes of each key that are indexed for searching,
+    /// or None if search is not enabled
+    pub fn max_index_key_len(&self) -> Option<u32> {
+        self.max_index_key_len
+    }
+}
+
+impl Debug for Store {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Store {{ buffer_pool: {:?}, header: {}}}",
+            self.buffer_pool, self.header
+        )
+    }
+}
+
+impl Display for Store {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.stop();
+        }
+    }
+}
+
+// The store is documented as safe to share across threads, so a field that is not thread-safe
+// should fail the build rather than quietly making the store unusable from other threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Store>();
+    assert_send_sync::<StoreBuilder>();
+};
+
+impl Store {
+    /// Checks that the given key value can be saved in the store, just like [Store::set] does
+    pub(crate) fn validate_key_value(&self, k: &[u8], v: &[u8]) -> Result<()> {
+        validate_key(k)?;
+        validate_entry_size(k.len(), v.len(), self.max_key_size, self.max_value_size)
+    }
+
+    /// Pauses compaction for a bulk load, waiting for any compaction in progress to finish first,
+    /// as the entries of the load are to stay where they are appended until they are indexed
+    pub(crate) fn start_bulk_load(&self) {
+        // the background compaction checks this flag after marking itself as in progress, so one of the two
+        // always sees the other, as long as both flags are written and read in sequentially consistent order
+        self.is_bulk_loading.store(true, Ordering::SeqCst);
+        while self.is_compacting.load(Ordering::SeqCst) {
+            thread::sleep(LOCK_POLL_INTERVAL);
+        }
+    }
+
+    /// Appends the given key-value entries of a bulk load to the end of the file, without indexing them.
+    /// It returns the address where they were appended.
+    pub(crate) fn append_bulk_entries(&self, data: &[u8]) -> Result<u64> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        Ok(buffer_pool.append_unbuffered(data)?)
+    }
+
+    /// Indexes the entries of a bulk load, appended to the file from the `start` address onwards,
+    /// returning the number of keys loaded. If they can't be indexed, they are discarded.
+    pub(crate) fn finish_bulk_load(&self, entries: &[AppendedEntry], start: u64) -> Result<u64> {
+        let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(self.buffer_pool)?;
+        let loaded = match buffer_pool.index_appended_entries(&self.header, entries) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                buffer_pool.discard_appended(start)?;
+                return Err(e.into());
+            }
+        };
+        telemetry::record_file_size(buffer_pool.file_size);
+
+        if let Some(idx) = &self.search_index {
+            let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+            // the last entry of a key replaces any earlier ones in the search index
+            for entry in entries {
+                idx.add(&entry.key, entry.address, entry.expiry)?;
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// Ends a bulk load, resuming compaction. The entries appended from `discard_from` onwards, if any,
+    /// are discarded.
+    pub(crate) fn end_bulk_load(&self, discard_from: Option<u64>) -> Result<()> {
+        let res = match discard_from {
+            None => Ok(()),
+            Some(address) => acquire_lock!(self.buffer_pool)
+                .map_err(ScdbError::from)
+                .and_then(|mut buffer_pool: MutexGuard<'_, BufferPool>| {
+                    Ok(buffer_pool.discard_appended(address)?)
+                }),
+        };
+
+        self.is_bulk_loading.store(false, Ordering::SeqCst);
+        res
+    }
+
+    /// Sets the given key value in the store, given a lock on the buffer pool.
+    ///
+    /// New keys are rejected with [ScdbError::KeysMaxedOut] once the store holds `max_keys` unexpired keys.
+    fn set_in_pool(
+        &mut self,
+        buffer_pool: &mut BufferPool,
+        k: &[u8],
+        v: &[u8],
+        expiry: u64,
+    ) -> Result<()> {
+        let live_keys = buffer_pool.get_live_keys(&self.header)?;
+        if live_keys >= self.header.max_keys && self.get_from_pool(buffer_pool, k, true)?.is_none()
+        {
+            return Err(ScdbError::KeysMaxedOut);
+        }
+
+        self.set_in_pool_over_max_keys(buffer_pool, k, v, expiry)
+    }
+
+    /// Sets the given key value in the store just like [Store::set_in_pool], but even if it is a new key
+    /// and the store already holds `max_keys` unexpired keys. It is for moving a value from a key that is
+    /// deleted right after, leaving the number of keys unchanged.
+    ///
+    /// If a `high_water_mark` is set, a warning is logged when the load factor crosses it,
+    /// or, if it is strict, new keys are rejected once the load factor has reached it.
+    fn set_in_pool_over_max_keys(
+        &mut self,
+        buffer_pool: &mut BufferPool,
+        k: &[u8],
+        v: &[u8],
+        expiry: u64,
+    ) -> Result<()> {
+        let prev_load_factor = match self.high_water_mark {
+            Some(mark) => {
+                let load_factor = self.get_load_factor(buffer_pool)?;
+                if self.is_high_water_mark_strict
+                    && load_factor >= mark
+                    && self.get_from_pool(buffer_pool, k, true)?.is_none()
+                {
+                    return Err(ScdbError::HighWaterMarkReached(load_factor));
+                }
+                Some(load_factor)
+            }
+            None => None,
+        };
+
+        self.set_in_pool_with_auto_grow(buffer_pool, k, v, expiry)?;
+        telemetry::record_file_size(buffer_pool.file_size);
+
+        if let (Some(mark), Some(prev_load_factor)) = (self.high_water_mark, prev_load_factor) {
+            let load_factor = self.get_load_factor(buffer_pool)?;
+            if prev_load_factor < mark && load_factor >= mark {
+                log::warn!(
+                    "the load factor of the store has reached {:.2}, at or above its high-water mark of {}; \
+                    consider growing the store before keys fail with collision saturation",
+                    load_factor,
+                    mark
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the given key value in the store, given a lock on the buffer pool.
+    ///
+    /// If all the slots of the key in the index are taken and `auto_grow` is set,
+    /// the store is grown by one redundant block and the key value is set in the new block.
+    fn set_in_pool_with_auto_grow(
+        &mut self,
+        buffer_pool: &mut BufferPool,
+        k: &[u8],
+        v: &[u8],
+        expiry: u64,
+    ) -> Result<()> {
+        match self.try_set_in_pool(buffer_pool, k, v, expiry) {
+            Err(ScdbError::CollisionSaturated(_)) if self.auto_grow => {
+                let mut search_index = match &self.search_index {
+                    None => None,
+                    Some(idx) => {
+                        let idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+                        Some(idx)
+                    }
+                };
+                self.header = buffer_pool.grow_file(&mut (search_index.as_deref_mut()))?;
+                drop(search_index);
+
+                self.try_set_in_pool(buffer_pool, k, v, expiry)
+            }
+            res => res,
+        }
+    }
+
+    /// Sets the given key value in the store, given a lock on the buffer pool,
+    /// failing if all the slots of the key in the index are taken
+    fn try_set_in_pool(
+        &self,
+        buffer_pool: &mut BufferPool,
+        k: &[u8],
+        v: &[u8],
+        expiry: u64,
+    ) -> Result<()> {
+        for index_offset in self.header.get_index_offsets(k) {
+            let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
+
+            let is_new_key = kv_offset_in_bytes == ZERO_U64_BYTES;
+            if is_new_key || buffer_pool.addr_belongs_to_key(&kv_offset_in_bytes, k)? {
+                let mut prev_version = 0;
+                if !is_new_key {
+                    // the previous entry for this key is about to be left unindexed
+                    let prev_kv_address = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
+                    prev_version = buffer_pool.read_version(prev_kv_address)?;
+                    buffer_pool.mark_dangling(prev_kv_address)?;
+                }
+
+                let kv =
+                    KeyValueEntry::new(k, v, expiry)?.with_version(prev_version.saturating_add(1));
+                let mut kv_bytes = kv.as_bytes();
+                let prev_last_offset = buffer_pool.append_kv_entry(index_offset, &mut kv_bytes)?;
+
+                // Update the search index
+                if let Some(idx) = &self.search_index {
+                    let mut idx: MutexGuard<'_, InvertedIndex> = acquire_lock!(idx)?;
+                    idx.add(k, prev_last_offset, expiry)?;
+                }
+
+                return Ok(());
+            }
+        }
+
+        Err(ScdbError::CollisionSaturated(k.to_vec()))
+    }
+
+    /// Returns the value and expiry of the given unexpired key, given a lock on the buffer pool
+    fn get_entry_from_pool(
+        &self,
+        buffer_pool: &mut BufferPool,
+        k: &[u8],
+    ) -> Result<Option<(Vec<u8>, u64)>> {
+        for index_offset in self.header.get_index_offsets(k) {
+            let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
+
+            if kv_offset_in_bytes != ZERO_U64_BYTES {
+                let entry_offset = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
+                if let Some(entry) = buffer_pool.read_value_and_expiry(entry_offset, k)? {
+                    return Ok(Some(entry));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the address of the entry of the given unexpired key, given a lock on the buffer pool
+    fn get_kv_address_from_pool(
+        &self,
+        buffer_pool: &mut BufferPool,
+        k: &[u8],
+    ) -> Result<Option<u64>> {
+        for index_offset in self.header.get_index_offsets(k) {
+            let kv_offset_in_bytes = buffer_pool.read_index(index_offset)?;
+
+            if kv_offset_in_bytes != ZERO_U64_BYTES {
+                let entry_offset = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
+                if buffer_pool.read_key(entry_offset)?.as_deref() == Some(k) {
+                    return Ok(Some(entry_offset));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the load factor of the store, given a lock on the buffer pool
+    fn get_load_factor(&self, buffer_pool: &mut BufferPool) -> Result<f64> {
+        let live_keys = buffer_pool.get_live_keys(&self.header)?;
+        Ok(live_keys as f64 / self.header.max_keys as f64)
+    }
+
+    /// Returns the value corresponding to the given key, given a lock on the buffer pool
+    fn get_from_pool(
+        &self,
+        buffer_pool: &mut BufferPool,
+        k: &[u8],
+        is_peek: bool,
+    ) -> Result<Option<Vec<u8>>> {
+        for index_offset in self.header.get_index_offsets(k) {
+            let kv_offset_in_bytes = if is_peek {
+                buffer_pool.peek_index(index_offset)?
+            } else {
+                buffer_pool.read_index(index_offset)?
+            };
+
+            if kv_offset_in_bytes != ZERO_U64_BYTES {
+                let entry_offset = u64::from_be_bytes(slice_to_array(&kv_offset_in_bytes)?);
+                let value = if is_peek {
+                    buffer_pool.peek_value(entry_offset, k)?
+                } else {
+                    buffer_pool.get_value(entry_offset, k)?
+                };
+
+                if let Some(v) = value {
+                    return if v.is_stale {
+                        Ok(None)
+                    } else {
+                        Ok(Some(v.data))
+                    };
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Initializes the scheduler that is to run the background task of compacting the store
+/// If interval (in seconds) passed is 0, No scheduler is created. The default interval is 1 hour
+fn initialize_scheduler(
+    interval: Option<u32>,
+    buffer_pool: &Arc<Mutex<BufferPool>>,
+    search_index: &Option<Arc<Mutex<InvertedIndex>>>,
+    is_compacting: &Arc<AtomicBool>,
+    is_bulk_loading: &Arc<AtomicBool>,
+) -> Option<ScheduleHandle> {
+    let interval = interval.unwrap_or(Store::DEFAULT_COMPACTION_INTERVAL);
+
+    if interval > 0 {
+        let mut scheduler = Scheduler::new();
+        let buffer_pool = buffer_pool.clone();
+        let search_index = search_index.as_ref().cloned();
+        let is_compacting = is_compacting.clone();
+        let is_bulk_loading = is_bulk_loading.clone();
+
+        scheduler.every(interval.seconds()).run(move || {
+            compact_in_steps(
+                &buffer_pool,
+                &search_index,
+                &is_compacting,
+                &is_bulk_loading,
+            )
+            .expect("compact db file in thread");
+        });
+
+        let handle = scheduler.watch_thread(Duration::from_millis(200));
+        Some(handle)
+    } else {
+        None
+    }
+}
+
+/// Compacts the db file, holding the lock on the buffer pool for only [COMPACTION_STEP_INDEX_BLOCKS]
+/// index blocks at a time, so that the other operations on the store are not held up for the whole compaction.
+///
+/// The writes made to the db file between the steps are caught up on when the compaction is finished, copying
+/// only the entries they touched in the index blocks already copied. If the layout of the db file changes between
+/// the steps e.g. if it is cleared, the compaction is started afresh instead. After [MAX_COMPACTION_RESTARTS]
+/// such restarts, the compaction is done in one go, holding the lock all through.
+///
+/// It does nothing if another compaction is already in progress, as shown by `is_compacting`.
+/// Thus a background compaction that takes longer than the compaction interval makes the next ones
+/// be skipped, rather than queued up behind it. It waits, however, for any bulk load in progress,
+/// as shown by `is_bulk_loading`, to end first.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(bytes_before = tracing::field::Empty, bytes_after = tracing::field::Empty)
+    )
+)]
+fn compact_in_steps(
+    buffer_pool: &Arc<Mutex<BufferPool>>,
+    search_index: &Option<Arc<Mutex<InvertedIndex>>>,
+    is_compacting: &AtomicBool,
+    is_bulk_loading: &AtomicBool,
+) -> Result<()> {
+    let _compaction_guard = loop {
+        while is_bulk_loading.load(Ordering::SeqCst) {
+            thread::sleep(LOCK_POLL_INTERVAL);
+        }
+
+        let Some(guard) = CompactionGuard::try_start(is_compacting) else {
+            log::debug!("skipping compaction as another one is still in progress");
+            return Ok(());
+        };
+
+        // a bulk load may have started just before the compaction was marked as in progress
+        if !is_bulk_loading.load(Ordering::SeqCst) {
+            break guard;
+        }
+    };
+
+    'restart: for _ in 0..MAX_COMPACTION_RESTARTS {
+        let mut compaction = {
+            let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+            telemetry::record_span_field("bytes_before", buffer_pool.file_size);
+            buffer_pool.start_compaction(search_index.is_some())?
+        };
+
+        loop {
+            let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+            if !buffer_pool.continue_compaction(&mut compaction, COMPACTION_STEP_INDEX_BLOCKS)? {
+                continue 'restart;
+            }
+
+            if compaction.is_copied() {
+                // Since compacting the db file disorganizes the addresses, we will rebuild
+                // the index every time compaction of db is done
+                let mut search_index: Option<MutexGuard<'_, InvertedIndex>> = search_index
+                    .as_ref()
+                    .map(|v| acquire_lock!(v))
+                    .transpose()?;
+                buffer_pool.finish_compaction(compaction, &mut (search_index.as_deref_mut()))?;
+                telemetry::record_span_field("bytes_after", buffer_pool.file_size);
+                telemetry::record_compaction();
+                telemetry::record_file_size(buffer_pool.file_size);
+                return Ok(());
+            }
+
+            // give the threads waiting for the lock a chance to take it
+            drop(buffer_pool);
+            thread::yield_now();
+        }
+    }
+
+    let mut buffer_pool: MutexGuard<'_, BufferPool> = acquire_lock!(buffer_pool)?;
+    let mut search_index: Option<MutexGuard<'_, InvertedIndex>> = search_index
+        .as_ref()
+        .map(|v| acquire_lock!(v))
+        .transpose()?;
+    buffer_pool.compact_file(&mut (search_index.as_deref_mut()))?;
+    Ok(())
+}
+
+/// Marks a compaction as in progress for as long as it is alive, even if the compaction fails midway
+struct CompactionGuard<'a>(&'a AtomicBool);
+
+impl<'a> CompactionGuard<'a> {
+    /// Marks a compaction as in progress, returning None if one already is
+    fn try_start(is_compacting: &'a AtomicBool) -> Option<Self> {
+        is_compacting
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| Self(is_compacting))
+    }
+}
+
+impl Drop for CompactionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Checks that the given key can be saved in the store i.e. it is not empty
+pub(crate) fn validate_key(k: &[u8]) -> Result<()> {
+    if k.is_empty() {
+        return Err(ScdbError::InvalidInput("key must not be empty".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Checks that a key-value pair of the given sizes is within the `max_key_size` and the `max_value_size`
+/// if any, and that its total size fits in the size field of a [KeyValueEntry] on disk
+fn validate_entry_size(
+    key_size: usize,
+    value_size: usize,
+    max_key_size: usize,
+    max_value_size: Option<usize>,
+) -> Result<()> {
+    if key_size > max_key_size {
+        return Err(ScdbError::InvalidInput(format!(
+            "key size {} exceeds max_key_size {}",
+            key_size, max_key_size
+        )));
+    }
+
+    if let Some(max_value_size) = max_value_size {
+        if value_size > max_value_size {
+            return Err(ScdbError::InvalidInput(format!(
+                "value size {} exceeds max_value_size {}",
+                value_size, max_value_size
+            )));
+        }
+    }
+
+    let entry_size = key_size as u64
+        + value_size as u64
+        + KEY_VALUE_MIN_SIZE_IN_BYTES as u64
+        + VERSION_SIZE_IN_BYTES as u64;
+    if entry_size > u32::MAX as u64 {
+        return Err(ScdbError::InvalidInput(format!(
+            "key-value entry size {} exceeds the maximum of {} bytes",
+            entry_size,
+            u32::MAX
+        )));
+    }
+
+    Ok(())
+}
+
+/// Initializes the header given the buffer bool
+fn extract_header_from_buffer_pool(buffer_pool: &mut BufferPool) -> io::Result<DbFileHeader> {
+    buffer_pool.read_header()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use nix::sys::wait::wait;
+    #[cfg(unix)]
+    use nix::unistd::fork;
+    #[cfg(unix)]
+    use nix::unistd::ForkResult::{Child, Parent};
+    use std::fs::OpenOptions;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::thread::JoinHandle;
+    use std::{fs, io, thread};
+
+    use serial_test::serial;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::internal::{get_current_timestamp, Header};
+    use crate::{KeyHasher, Probing, Xxh3Hasher};
+
+    const STORE_PATH: &str = "db";
+
+    /// Asserts that two lists of Result<Option<T>> are equal
+    macro_rules! assert_list_eq {
+        ($expected:expr, $got:expr) => {
+            assert_eq!($expected.len(), $got.len());
+            for (got, expected) in $got.into_iter().zip($expected) {
+                assert_eq!(got.as_ref().unwrap(), expected.as_ref().unwrap());
+            }
+        };
+    }
+
+    /// Converts a string slice into bytes
+    macro_rules! str_to_bytes {
+        ($v:expr) => {
+            $v.to_string().into_bytes()
+        };
+    }
+
+    /// Converts an array of strings into a vector of byte arrays
+    macro_rules! to_byte_arrays_vector {
+        ($data:expr) => {
+            $data.map(|v| str_to_bytes!(v)).to_vec()
+        };
+    }
+
+    #[test]
+    fn defaults_are_used_when_not_configured() {
+        let store =
+            Store::in_memory(StoreBuilder::new("").is_search_enabled(true)).expect("create store");
+        assert_eq!(store.header.max_keys, Store::DEFAULT_MAX_KEYS);
+        assert_eq!(
+            store.header.redundant_blocks,
+            Store::DEFAULT_REDUNDANT_BLOCKS
+        );
+        assert_eq!(
+            store.max_index_key_len(),
+            Some(Store::DEFAULT_MAX_INDEX_KEY_LEN)
+        );
+        assert!(store.scheduler.is_some());
+    }
+
+    #[test]
+    #[serial]
+    fn set_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        insert_test_data(&mut store, &keys, &values, None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+
+        let expected_values = wrap_values_in_result(&values);
+        assert_list_eq!(&expected_values, &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_rejects_values_bigger_than_max_value_size() {
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .max_value_size(4)
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let initial_file_size = get_file_size(db_file_path.to_str().unwrap());
+
+        let got = store.set(&b"foo"[..], &b"bigger"[..], None);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+        // nothing is appended to the file
+        assert_eq!(
+            get_file_size(db_file_path.to_str().unwrap()),
+            initial_file_size
+        );
+        assert_eq!(store.get(&b"foo"[..]).expect("get foo"), None);
+
+        store
+            .set(&b"foo"[..], &b"bar!"[..], None)
+            .expect("set value of max_value_size");
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(b"bar!".to_vec())
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_rejects_keys_bigger_than_max_key_size() {
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .max_key_size(4)
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let initial_file_size = get_file_size(db_file_path.to_str().unwrap());
+
+        let got = store.set(&b"fooba"[..], &b"bar"[..], None);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+        // nothing is appended to the file
+        assert_eq!(
+            get_file_size(db_file_path.to_str().unwrap()),
+            initial_file_size
+        );
+
+        store
+            .set(&b"foob"[..], &b"bar"[..], None)
+            .expect("set key of max_key_size");
+        assert_eq!(
+            store.get(&b"foob"[..]).expect("get foob"),
+            Some(b"bar".to_vec())
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn keys_longer_than_a_buffer_work() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let long_key = vec![b'k'; 10_000];
+        let mut other_long_key = long_key.clone();
+        other_long_key[9_999] = b'x';
+
+        store
+            .set(&long_key, &b"foo"[..], None)
+            .expect("set long key");
+        store
+            .set(&other_long_key, &b"bar"[..], None)
+            .expect("set other long key");
+        // updating it compares the whole key with the one in the file
+        store
+            .set(&long_key, &b"baz"[..], None)
+            .expect("update long key");
+
+        // reopening the store to read the entries from file rather than from the buffers
+        drop(store);
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("reopen store");
+        for _ in 0..2 {
+            assert_eq!(
+                store.get(&long_key).expect("get long key"),
+                Some(b"baz".to_vec())
+            );
+            assert_eq!(
+                store.get(&other_long_key).expect("get other long key"),
+                Some(b"bar".to_vec())
+            );
+        }
+
+        assert!(store.delete(&long_key).expect("delete long key"));
+        assert_eq!(store.get(&long_key).expect("get long key"), None);
+        assert_eq!(
+            store.search(&b"kk"[..], 0, 0).expect("search kk"),
+            vec![(other_long_key.clone(), b"bar".to_vec())]
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    fn validate_entry_size_works() {
+        let max_entry_size = u32::MAX as usize;
+        let min_size = (KEY_VALUE_MIN_SIZE_IN_BYTES + VERSION_SIZE_IN_BYTES) as usize;
+        let test_data = [
+            ((3, 3, 3, None), true),
+            ((3, 3, 3, Some(3)), true),
+            ((3, 4, 3, Some(3)), false),
+            ((3, 0, 3, Some(0)), true),
+            ((4, 3, 3, None), false),
+            ((3, max_entry_size - min_size - 3, 3, None), true),
+            ((3, max_entry_size - min_size - 2, 3, None), false),
+            ((0, max_entry_size, 3, None), false),
+            ((max_entry_size, 0, usize::MAX, None), false),
+        ];
+
+        for ((key_size, value_size, max_key_size, max_value_size), is_valid) in test_data {
+            let got = validate_entry_size(key_size, value_size, max_key_size, max_value_size);
+            let sizes = (key_size, value_size, max_key_size, max_value_size);
+            if is_valid {
+                assert!(got.is_ok(), "{:?}", sizes);
+            } else {
+                assert!(
+                    matches!(got, Err(ScdbError::InvalidInput(_))),
+                    "{:?}",
+                    sizes
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn empty_values_work() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "food", "fore"]);
+        let values = to_byte_arrays_vector!(["", "", "bar"]);
+        insert_test_data(&mut store, &keys, &values, None);
+
+        // repeated gets are served first from the file, then from memory
+        for _ in 0..2 {
+            let received_values = get_values_for_keys(&mut store, &keys);
+            assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+        }
+        assert_eq!(store.peek(&keys[0]).expect("peek foo"), Some(vec![]));
+        assert_eq!(
+            store.search(&b"foo"[..], 0, 0).expect("search foo"),
+            vec![(keys[0].clone(), vec![]), (keys[1].clone(), vec![])]
+        );
+
+        assert!(store.delete(&keys[0]).expect("delete foo"));
+        assert_eq!(store.get(&keys[0]).expect("get foo"), None);
+
+        store.compact().expect("compact store");
+        let expected_values: Vec<Result<Option<Vec<u8>>>> =
+            vec![Ok(None), Ok(Some(vec![])), Ok(Some(b"bar".to_vec()))];
+        for _ in 0..2 {
+            let received_values = get_values_for_keys(&mut store, &keys);
+            assert_list_eq!(&expected_values, &received_values);
+        }
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![
+                (keys[1].clone(), vec![]),
+                (keys[2].clone(), values[2].clone())
+            ]
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn empty_keys_are_rejected() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+
+        let got = store.set(&b""[..], &b"bar"[..], None);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+        let got = store.get(&b""[..]);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+        let got = store.delete(&b""[..]);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+
+        // the store is still usable
+        store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(b"bar".to_vec())
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn append_value_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .max_value_size(8)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"eng"[..], None).expect("set foo");
+        store
+            .set(&b"bar"[..], &b"port"[..], Some(1))
+            .expect("set bar");
+
+        // advance time beyond the expiry of 'bar' without sleeping
+        clock.0.fetch_add(2, Ordering::SeqCst);
+
+        let test_data = [
+            ("foo", ";lat", 7, "eng;lat"), // existing
+            ("bar", "span", 4, "span"),    // expired
+            ("fore", "fr", 2, "fr"),       // non-existent
+            ("fore", ";sw", 5, "fr;sw"),   // just appended to
+            ("fore", "", 5, "fr;sw"),      // nothing to append
+        ];
+        for (k, extra, expected_len, expected) in test_data {
+            let got = store
+                .append_value(&str_to_bytes!(k), &str_to_bytes!(extra), None)
+                .unwrap_or_else(|_| panic!("append {} to {}", extra, k));
+            assert_eq!(got, expected_len, "key: {}", k);
+            assert_eq!(
+                store.get(&str_to_bytes!(k)).expect("get"),
+                Some(str_to_bytes!(expected)),
+                "key: {}",
+                k
+            );
+        }
+
+        // the new value is searchable, and values bigger than the max_value_size are rejected
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![
+                (str_to_bytes!("foo"), str_to_bytes!("eng;lat")),
+                (str_to_bytes!("fore"), str_to_bytes!("fr;sw"))
+            ]
+        );
+        assert!(matches!(
+            store.append_value(&b"foo"[..], &b";ge"[..], None),
+            Err(ScdbError::InvalidInput(_))
+        ));
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(str_to_bytes!("eng;lat"))
+        );
+
+        // the ttl applies to the new value
+        store
+            .append_value(&b"bar"[..], &b";"[..], Some(1))
+            .expect("append with ttl");
+        clock.0.fetch_add(2, Ordering::SeqCst);
+        assert_eq!(store.get(&b"bar"[..]).expect("get bar"), None);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn get_or_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"eng"[..], None).expect("set foo");
+        store
+            .set(&b"bar"[..], &b"port"[..], Some(1))
+            .expect("set bar");
+
+        // advance time beyond the expiry of 'bar' without sleeping
+        clock.0.fetch_add(2, Ordering::SeqCst);
+
+        let mut calls = 0;
+        let test_data = [
+            ("foo", "eng", 0),  // existing
+            ("bar", "new", 1),  // expired
+            ("fore", "new", 2), // non-existent
+            ("fore", "new", 3), // still non-existent as defaults are not saved
+        ];
+        for (k, expected, expected_calls) in test_data {
+            let got = store
+                .get_or(&str_to_bytes!(k), str_to_bytes!("new"))
+                .unwrap_or_else(|_| panic!("get or {}", k));
+            assert_eq!(got, str_to_bytes!(expected), "key: {}", k);
+
+            let got = store
+                .get_or_else(&str_to_bytes!(k), || {
+                    calls += 1;
+                    str_to_bytes!("new")
+                })
+                .unwrap_or_else(|_| panic!("get or else {}", k));
+            assert_eq!(got, str_to_bytes!(expected), "key: {}", k);
+            assert_eq!(calls, expected_calls, "key: {}", k);
+        }
+
+        assert_eq!(store.get(&b"fore"[..]).expect("get fore"), None);
+        assert!(matches!(
+            store.get_or(&b""[..], vec![]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn get_or_insert_with_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"eng"[..], None).expect("set foo");
+        store
+            .set(&b"bar"[..], &b"port"[..], Some(1))
+            .expect("set bar");
+
+        // wait for expiry and some more just to be safe
+        thread::sleep(Duration::from_secs(2));
+
+        let mut calls = 0;
+        let test_data = [
+            ("foo", "eng", 0),  // existing
+            ("bar", "new", 1),  // expired
+            ("fore", "new", 2), // non-existent
+            ("fore", "new", 2), // just inserted
+        ];
+        for (k, expected, expected_calls) in test_data {
+            let got = store
+                .get_or_insert_with(&str_to_bytes!(k), None, || {
+                    calls += 1;
+                    str_to_bytes!("new")
+                })
+                .unwrap_or_else(|_| panic!("get or insert {}", k));
+            assert_eq!(got, str_to_bytes!(expected), "key: {}", k);
+            assert_eq!(calls, expected_calls, "key: {}", k);
+        }
+
+        // inserted values are saved, and are searchable
+        assert_eq!(
+            store.get(&b"bar"[..]).expect("get bar"),
+            Some(str_to_bytes!("new"))
+        );
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![
+                (str_to_bytes!("foo"), str_to_bytes!("eng")),
+                (str_to_bytes!("fore"), str_to_bytes!("new"))
+            ]
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_or_get_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"eng"[..], None).expect("set foo");
+        store
+            .set(&b"bar"[..], &b"port"[..], Some(1))
+            .expect("set bar");
+
+        // advance time beyond the expiry of 'bar' without sleeping
+        clock.0.fetch_add(2, Ordering::SeqCst);
+
+        let test_data = [
+            ("foo", Some("eng")),  // existing
+            ("bar", None),         // expired
+            ("fore", None),        // non-existent
+            ("fore", Some("new")), // just set
+        ];
+        for (k, expected) in test_data {
+            let got = store
+                .set_or_get(&str_to_bytes!(k), &b"new"[..], None)
+                .unwrap_or_else(|_| panic!("set or get {}", k));
+            assert_eq!(got, expected.map(|v| str_to_bytes!(v)), "key: {}", k);
+        }
+
+        // existing values are left as they were, and set values are searchable
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(str_to_bytes!("eng"))
+        );
+        assert_eq!(
+            store.get(&b"bar"[..]).expect("get bar"),
+            Some(str_to_bytes!("new"))
+        );
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![
+                (str_to_bytes!("foo"), str_to_bytes!("eng")),
+                (str_to_bytes!("fore"), str_to_bytes!("new"))
+            ]
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn bulk_loader_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"old"[..], None).expect("set foo");
+        store
+            .set(&b"foo"[..], &b"older"[..], None)
+            .expect("set foo");
+
+        let mut loader = store.bulk_loader().expect("bulk loader");
+        loader
+            .extend([
+                (&b"foo"[..], &b"eng"[..], 0),
+                (&b"fore"[..], &b"span"[..], 0),
+                (&b"bar"[..], &b"port"[..], 0),
+                // 1666023836u64 is some past timestamp in October 2022 so this is expired
+                (&b"hey"[..], &b"man"[..], 1666023836u64),
+                (&b"fore"[..], &b"span2"[..], 0),
+            ])
+            .expect("extend loader");
+        assert_eq!(loader.len(), 5);
+        assert_eq!(loader.finish().expect("finish load"), 4);
+
+        let test_data = [
+            ("foo", Some(("eng", 3))),
+            ("fore", Some(("span2", 1))),
+            ("bar", Some(("port", 1))),
+            ("hey", None),
+        ];
+        for (k, expected) in test_data {
+            let got = store
+                .get_versioned(&str_to_bytes!(k))
+                .unwrap_or_else(|_| panic!("get {}", k));
+            assert_eq!(
+                got,
+                expected.map(|(v, version)| (str_to_bytes!(v), version)),
+                "key: {}",
+                k
+            );
+        }
+
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![
+                (str_to_bytes!("foo"), str_to_bytes!("eng")),
+                (str_to_bytes!("fore"), str_to_bytes!("span2"))
+            ]
+        );
+        assert!(!store.is_compacting());
+
+        // the index is on disk
+        drop(store);
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("reopen store");
+        assert_eq!(
+            store.get(&b"bar"[..]).expect("get bar"),
+            Some(str_to_bytes!("port"))
+        );
+
+        // the overwritten and superseded entries are reclaimed on compaction
+        store.compact().expect("compact");
+        assert_eq!(
+            store.get(&b"fore"[..]).expect("get fore"),
+            Some(str_to_bytes!("span2"))
+        );
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(str_to_bytes!("eng"))
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn bulk_loader_discards_unfinished_loads() {
+        let mut store =
+            Store::new(STORE_PATH, Some(3), Some(0), None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"eng"[..], None).expect("set foo");
+        let db_file_path = store.db_file_path().expect("db file path").to_owned();
+        let db_file_path = db_file_path.to_str().expect("db file path as str");
+        let initial_file_size = get_file_size(db_file_path);
+
+        // dropped without finishing
+        let mut loader = store.bulk_loader().expect("bulk loader");
+        loader.add(&b"bar"[..], &b"port"[..], 0).expect("add bar");
+        drop(loader);
+        assert_eq!(store.get(&b"bar"[..]).expect("get bar"), None);
+        assert_eq!(get_file_size(db_file_path), initial_file_size);
+
+        // failing to finish as the keys would be maxed out
+        let mut loader = store.bulk_loader().expect("bulk loader");
+        for k in ["foo", "bar", "fore", "hey"] {
+            loader
+                .add(&str_to_bytes!(k), &b"val"[..], 0)
+                .unwrap_or_else(|_| panic!("add {}", k));
+        }
+        assert!(matches!(loader.finish(), Err(ScdbError::KeysMaxedOut)));
+        assert_eq!(get_file_size(db_file_path), initial_file_size);
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(str_to_bytes!("eng"))
+        );
+        assert_eq!(store.get(&b"bar"[..]).expect("get bar"), None);
+
+        // empty or invalid key-value pairs are rejected
+        let mut loader = store.bulk_loader().expect("bulk loader");
+        assert!(loader.add(&b""[..], &b"val"[..], 0).is_err());
+        assert!(loader.is_empty());
+        assert_eq!(loader.finish().expect("finish empty load"), 0);
+
+        // compaction can go on after the loads
+        assert!(!store.is_compacting());
+        store.compact().expect("compact");
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn background_compaction_waits_for_bulk_loads() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"eng"[..], None).expect("set foo");
+        store.delete(&b"foo"[..]).expect("delete foo");
+
+        // a bulk load is not a compaction
+        store.start_bulk_load();
+        assert!(!store.is_compacting());
+
+        let buffer_pool = store.buffer_pool.clone();
+        let search_index = store.search_index.clone();
+        let is_compacting = store.is_compacting.clone();
+        let is_bulk_loading = store.is_bulk_loading.clone();
+        let handle = thread::spawn(move || {
+            compact_in_steps(
+                &buffer_pool,
+                &search_index,
+                &is_compacting,
+                &is_bulk_loading,
+            )
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        assert!(!store.is_compacting());
+
+        store.end_bulk_load(None).expect("end bulk load");
+        handle.join().expect("join").expect("compact in steps");
+        assert!(!store.is_compacting());
+        assert!(!store.compact_if_needed(1).expect("compact if needed"));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_with_ttl_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        insert_test_data(&mut store, &keys[0..2], &values, None);
+        insert_test_data(&mut store, &keys[2..], &values, Some(1)); // 1 second ttl
+
+        // wait for expiry and some more just to be safe
+        thread::sleep(Duration::from_secs(2));
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let mut expected_values = wrap_values_in_result(&values[..2]);
+        for _ in 2..keys.len() {
+            expected_values.push(Ok(None));
+        }
+
+        assert_list_eq!(&expected_values, &received_values);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_with_expiry_at_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        let now = get_current_timestamp();
+
+        for (k, v) in keys[..3].iter().zip(&values[..3]) {
+            store
+                .set_with_expiry_at(k, v, now + 1)
+                .unwrap_or_else(|_| panic!("set key {:?}", k));
+        }
+        for (k, v) in keys[3..].iter().zip(&values[3..]) {
+            store
+                .set_with_expiry_at(k, v, now + 3600)
+                .unwrap_or_else(|_| panic!("set key {:?}", k));
+        }
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+
+        // wait for expiry and some more just to be safe
+        thread::sleep(Duration::from_secs(3));
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let mut expected_values: Vec<Result<Option<Vec<u8>>>> =
+            keys[..3].iter().map(|_| Ok(None)).collect();
+        expected_values.extend(wrap_values_in_result(&values[3..]));
+        assert_list_eq!(&expected_values, &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_with_expiry_at_rejects_past_expiry() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let now = get_current_timestamp();
+
+        for expiry in [0, now - 1] {
+            let got = store.set_with_expiry_at(&b"foo"[..], &b"bar"[..], expiry);
+            assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+        }
+        assert_eq!(store.get(&b"foo"[..]).expect("get foo"), None);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn expiry_uses_the_configured_clock() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port"]);
+
+        insert_test_data(&mut store, &keys[..2], &values[..2], Some(10));
+        insert_test_data(&mut store, &keys[2..], &values[2..], Some(100));
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+
+        // advance time beyond the expiry of 'foo' and 'fore' without sleeping
+        clock.0.fetch_add(11, Ordering::SeqCst);
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> =
+            vec![Ok(None), Ok(None), Ok(Some(str_to_bytes!("port")))];
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(store.search(&b"f"[..], 0, 0).expect("search f"), vec![]);
+        let got_keys = store
+            .keys()
+            .expect("keys")
+            .collect::<Result<Vec<Vec<u8>>>>()
+            .expect("collect keys");
+        assert_eq!(got_keys, to_byte_arrays_vector!(["bar"]));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn rename_key_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store
+            .set(&b"foo"[..], &b"eng"[..], Some(10))
+            .expect("set foo");
+        store.set(&b"bar"[..], &b"port"[..], None).expect("set bar");
+
+        assert!(store
+            .rename_key(&b"foo"[..], &b"fore"[..], false)
+            .expect("rename foo"));
+        assert_eq!(store.get(&b"foo"[..]).expect("get foo"), None);
+        assert_eq!(
+            store.get(&b"fore"[..]).expect("get fore"),
+            Some(b"eng".to_vec())
+        );
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![(b"fore".to_vec(), b"eng".to_vec())]
+        );
+
+        // an existing key is only overwritten if asked to
+        assert!(matches!(
+            store.rename_key(&b"bar"[..], &b"fore"[..], false),
+            Err(ScdbError::InvalidInput(_))
+        ));
+        assert_eq!(
+            store.get(&b"bar"[..]).expect("get bar"),
+            Some(b"port".to_vec())
+        );
+        assert!(!store
+            .rename_key(&b"foo"[..], &b"bar"[..], true)
+            .expect("rename non-existent foo"));
+        assert!(store
+            .rename_key(&b"bar"[..], &b"foo"[..], true)
+            .expect("rename bar"));
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(b"port".to_vec())
+        );
+
+        // the expiry is carried over
+        clock.0.fetch_add(11, Ordering::SeqCst);
+        assert_eq!(store.get(&b"fore"[..]).expect("get fore"), None);
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(b"port".to_vec())
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn swap_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store
+            .set(&b"foo"[..], &b"eng"[..], Some(10))
+            .expect("set foo");
+        store.set(&b"bar"[..], &b"port"[..], None).expect("set bar");
+
+        // both present
+        store.swap(&b"foo"[..], &b"bar"[..]).expect("swap foo, bar");
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(b"port".to_vec())
+        );
+        assert_eq!(
+            store.get(&b"bar"[..]).expect("get bar"),
+            Some(b"eng".to_vec())
+        );
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![(b"foo".to_vec(), b"port".to_vec())]
+        );
+
+        // one absent: the value is moved to the absent key, whichever side it is on
+        store.swap(&b"baz"[..], &b"foo"[..]).expect("swap baz, foo");
+        assert_eq!(store.get(&b"foo"[..]).expect("get foo"), None);
+        assert_eq!(
+            store.get(&b"baz"[..]).expect("get baz"),
+            Some(b"port".to_vec())
+        );
+        assert_eq!(store.search(&b"fo"[..], 0, 0).expect("search fo"), vec![]);
+
+        // both absent, or the same key, is a no-op
+        store.swap(&b"foo"[..], &b"fee"[..]).expect("swap foo, fee");
+        store.swap(&b"bar"[..], &b"bar"[..]).expect("swap bar, bar");
+        assert_eq!(store.get(&b"fee"[..]).expect("get fee"), None);
+        assert_eq!(
+            store.get(&b"bar"[..]).expect("get bar"),
+            Some(b"eng".to_vec())
+        );
+        assert!(matches!(
+            store.swap(&b""[..], &b"bar"[..]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        // the expiries are carried over
+        clock.0.fetch_add(11, Ordering::SeqCst);
+        assert_eq!(store.get(&b"bar"[..]).expect("get bar"), None);
+        assert_eq!(
+            store.get(&b"baz"[..]).expect("get baz"),
+            Some(b"port".to_vec())
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn contains_key_indexed_works() {
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+
+        for k in &keys[1..] {
+            assert!(store.contains_key_indexed(k).expect("contains key"));
+        }
+        // keys longer than max_index_key_len are only matched in full
+        assert!(!store
+            .contains_key_indexed(&b"yoo"[..])
+            .expect("contains yoo"));
+        assert!(!store
+            .contains_key_indexed(&b"yoo-hoo-hoo"[..])
+            .expect("contains yoo-hoo-hoo"));
+        assert!(!store.contains_key_indexed(&keys[0]).expect("contains hey"));
+        assert!(matches!(
+            store.contains_key_indexed(&b""[..]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        let mut store = Store::new(STORE_PATH, None, None, None, Some(0), false)
+            .expect("create store without search");
+        assert!(matches!(
+            store.contains_key_indexed(&keys[1]),
+            Err(ScdbError::SearchDisabled)
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn get_versioned_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        insert_test_data(&mut store, &keys[..2], &values[2..4], None);
+        delete_keys(&mut store, &keys[1..3].to_vec());
+        insert_test_data(&mut store, &keys[1..2], &values[..1], None);
+
+        let expected: Vec<Option<(Vec<u8>, u64)>> = vec![
+            Some((values[2].clone(), 2)),
+            Some((values[0].clone(), 3)),
+            None,
+            Some((values[3].clone(), 1)),
+            Some((values[4].clone(), 1)),
+            Some((values[5].clone(), 1)),
+        ];
+        let got: Vec<Option<(Vec<u8>, u64)>> = keys
+            .iter()
+            .map(|k| store.get_versioned(k).expect("get versioned"))
+            .collect();
+        assert_eq!(got, expected);
+
+        // versions are kept across compaction, though deleted keys start afresh
+        store.compact().expect("compact");
+        insert_test_data(&mut store, &keys[2..3], &values[2..3], None);
+        assert_eq!(
+            store.get_versioned(&keys[0]).expect("get versioned"),
+            Some((values[2].clone(), 2))
+        );
+        assert_eq!(
+            store.get_versioned(&keys[2]).expect("get versioned"),
+            Some((values[2].clone(), 1))
+        );
+
+        // entries written before versions were kept are at version 0, up to their next set
+        let (k, v) = (b"old".to_vec(), b"entry".to_vec());
+        {
+            let mut buffer_pool = store.buffer_pool.lock().expect("lock buffer pool");
+            let mut kv_bytes = KeyValueEntry::new(&k, &v, 0)
+                .expect("new key value entry")
+                .as_bytes();
+            buffer_pool
+                .append_kv_entry(store.header.get_index_offset(&k), &mut kv_bytes)
+                .expect("append unversioned entry");
+        }
+        assert_eq!(
+            store.get_versioned(&k).expect("get versioned"),
+            Some((v.clone(), 0))
+        );
+        store.set(&k, &v, None).expect("set old entry");
+        assert_eq!(
+            store.get_versioned(&k).expect("get versioned"),
+            Some((v.clone(), 1))
+        );
+
+        assert!(matches!(
+            store.get_versioned(&b""[..]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    fn get_range_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store =
+            Store::in_memory(StoreBuilder::new("").clock(clock.clone())).expect("create store");
+        // spanning many buffers
+        let large_value: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        store
+            .set(&b"large"[..], &large_value, None)
+            .expect("set large");
+        store
+            .set(&b"small"[..], &b"barracuda"[..], Some(10))
+            .expect("set small");
+
+        let test_data = vec![
+            (&b"large"[..], 0, 10, Some(large_value[..10].to_vec())),
+            (
+                &b"large"[..],
+                50_000,
+                8_000,
+                Some(large_value[50_000..58_000].to_vec()),
+            ),
+            (
+                &b"large"[..],
+                99_999,
+                1,
+                Some(large_value[99_999..].to_vec()),
+            ),
+            (&b"large"[..], 100_000, 0, Some(vec![])),
+            (&b"small"[..], 3, 4, Some(b"racu".to_vec())),
+            (&b"small"[..], 0, 9, Some(b"barracuda".to_vec())),
+            (&b"smal"[..], 0, 1, None),
+            (&b"missing"[..], 0, 1, None),
+        ];
+        for (k, start, len, expected) in test_data {
+            assert_eq!(
+                store.get_range(k, start, len).expect("get range"),
+                expected,
+                "{:?}",
+                (k, start, len)
+            );
+        }
+
+        for (start, len) in [(0, 10), (9, 1), (10, 0), (usize::MAX, 2)] {
+            assert!(
+                matches!(
+                    store.get_range(&b"small"[..], start, len),
+                    Err(ScdbError::InvalidInput(_))
+                ),
+                "{:?}",
+                (start, len)
+            );
+        }
+        assert!(matches!(
+            store.get_range(&b""[..], 0, 1),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        // entries written before versions were kept are read too
+        let (k, v) = (b"old".to_vec(), b"entry".to_vec());
+        {
+            let mut buffer_pool = store.buffer_pool.lock().expect("lock buffer pool");
+            let mut kv_bytes = KeyValueEntry::new(&k, &v, 0)
+                .expect("new key value entry")
+                .as_bytes();
+            buffer_pool
+                .append_kv_entry(store.header.get_index_offset(&k), &mut kv_bytes)
+                .expect("append unversioned entry");
+        }
+        assert_eq!(
+            store.get_range(&k, 1, 3).expect("get range"),
+            Some(b"ntr".to_vec())
+        );
+
+        // deleted or expired keys are not found, whatever the range
+        store.delete(&b"large"[..]).expect("delete large");
+        clock.0.fetch_add(11, Ordering::SeqCst);
+        assert_eq!(
+            store.get_range(&b"large"[..], 0, 10).expect("get range"),
+            None
+        );
+        assert_eq!(
+            store.get_range(&b"small"[..], 0, 100).expect("get range"),
+            None
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn get_by_addresses_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+
+        let mut addresses: Vec<u64> = {
+            let mut buffer_pool = store.buffer_pool.lock().expect("lock buffer pool");
+            keys.iter()
+                .map(|k| {
+                    store
+                        .get_kv_address_from_pool(&mut buffer_pool, k)
+                        .expect("get address")
+                        .expect("address exists")
+                })
+                .collect()
+        };
+        delete_keys(&mut store, &keys[..1].to_vec());
+        addresses.extend([0, addresses[1] + 1, u64::MAX]);
+
+        let expected: Vec<Option<(Vec<u8>, Vec<u8>)>> = keys
+            .into_iter()
+            .zip(values)
+            .enumerate()
+            .map(|(i, pair)| if i == 0 { None } else { Some(pair) })
+            .chain([None, None, None])
+            .collect();
+        assert_eq!(
+            store
+                .get_by_addresses(&addresses)
+                .expect("get by addresses"),
+            expected
+        );
+        assert_eq!(
+            store.get_by_addresses(&[]).expect("get by no addresses"),
+            vec![]
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_with_min_ttl_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let test_data = [
+            (&b"sess:a"[..], &b"1"[..], Some(10)),
+            (b"sess:b", b"2", None),
+            (b"sess:c", b"3", Some(100)),
+            (b"sess:d", b"4", Some(1000)),
+            (b"user:e", b"5", Some(1000)),
+        ];
+        for (k, v, ttl) in test_data {
+            store.set(k, v, ttl).expect("set");
+        }
+        let pair = |k: &[u8], v: &[u8]| (k.to_vec(), v.to_vec());
+
+        let test_table = vec![
+            (
+                (0u64, 0u64, 0u64),
+                vec![
+                    pair(b"sess:a", b"1"),
+                    pair(b"sess:b", b"2"),
+                    pair(b"sess:c", b"3"),
+                    pair(b"sess:d", b"4"),
+                ],
+            ),
+            (
+                (50, 0, 0),
+                vec![
+                    pair(b"sess:b", b"2"),
+                    pair(b"sess:c", b"3"),
+                    pair(b"sess:d", b"4"),
+                ],
+            ),
+            (
+                (50, 1, 0),
+                vec![pair(b"sess:c", b"3"), pair(b"sess:d", b"4")],
+            ),
+            ((50, 1, 1), vec![pair(b"sess:c", b"3")]),
+            (
+                (500, 0, 0),
+                vec![pair(b"sess:b", b"2"), pair(b"sess:d", b"4")],
+            ),
+            ((5000, 0, 2), vec![pair(b"sess:b", b"2")]),
+        ];
+        for ((min_ttl, skip, limit), expected) in test_table {
+            let got = store
+                .search_with_min_ttl(&b"sess:"[..], min_ttl, skip, limit)
+                .expect("search with min ttl");
+            assert_eq!(
+                got, expected,
+                "min_ttl: {}, skip: {}, limit: {}",
+                min_ttl, skip, limit
+            );
+        }
+
+        // the time left shrinks as time goes by
+        clock.0.fetch_add(60, Ordering::SeqCst);
+        assert_eq!(
+            store
+                .search_with_min_ttl(&b"sess:"[..], 50, 0, 0)
+                .expect("search with min ttl"),
+            vec![pair(b"sess:b", b"2"), pair(b"sess:d", b"4")]
+        );
+
+        let mut store = Store::new(STORE_PATH, None, None, None, Some(0), false)
+            .expect("create store without search");
+        assert!(matches!(
+            store.search_with_min_ttl(&b"sess:"[..], 50, 0, 0),
+            Err(ScdbError::SearchDisabled)
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn expire_all_matching_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "ofo", "food"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "sw", "lat"]);
+        insert_test_data(&mut store, &keys, &values, None);
+        // already expired keys are not updated
+        insert_test_data(&mut store, &keys[4..], &values[4..], Some(1));
+        clock.0.fetch_add(2, Ordering::SeqCst);
+
+        let count = store
+            .expire_all_matching(&b"fo"[..], 10)
+            .expect("expire all matching");
+        assert_eq!(count, 2);
+
+        // values remain readable until the new expiry
+        clock.0.fetch_add(10, Ordering::SeqCst);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = vec![
+            Ok(Some(str_to_bytes!("eng"))),
+            Ok(Some(str_to_bytes!("span"))),
+            Ok(Some(str_to_bytes!("port"))),
+            Ok(Some(str_to_bytes!("sw"))),
+            Ok(None),
+        ];
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(store.search(&b"f"[..], 0, 0).expect("search f").len(), 2);
+
+        clock.0.fetch_add(1, Ordering::SeqCst);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = vec![
+            Ok(None),
+            Ok(None),
+            Ok(Some(str_to_bytes!("port"))),
+            Ok(Some(str_to_bytes!("sw"))),
+            Ok(None),
+        ];
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(store.search(&b"f"[..], 0, 0).expect("search f"), vec![]);
+
+        assert!(matches!(
+            store.expire_all_matching(&b""[..], 10),
+            Err(ScdbError::InvalidInput(_))
+        ));
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(matches!(
+            store.expire_all_matching(&b"fo"[..], 10),
+            Err(ScdbError::SearchDisabled)
+        ));
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_can_update() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        let unchanged_values = values[2..].to_vec();
+        let updated_keys = keys[0..2].to_vec();
+        let updated_values: Vec<Vec<u8>> = values[0..2]
+            .iter()
+            .map(|v| v.iter().chain(b"bear").map(|v| v.to_owned()).collect())
+            .collect();
+
+        insert_test_data(&mut store, &keys, &values, None);
+        insert_test_data(&mut store, &updated_keys, &updated_values, None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let received_unchanged_values = &received_values[2..];
+        let received_updated_values = &received_values[0..2];
+
+        // unchanged
+        let expected_unchanged_values = wrap_values_in_result(&unchanged_values);
+        let expected_updated_values = wrap_values_in_result(&updated_values);
+
+        assert_list_eq!(&expected_unchanged_values, &received_unchanged_values);
+        assert_list_eq!(&expected_updated_values, &received_updated_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn delete_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        let keys_to_delete = keys[2..].to_vec();
+
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys_to_delete);
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let mut expected_values = wrap_values_in_result(&values[..2]);
+        for _ in 0..keys_to_delete.len() {
+            expected_values.push(Ok(None));
+        }
+        assert_list_eq!(&expected_values, &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn delete_returns_whether_key_existed() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "bar", "fore"]);
+        let values = to_byte_arrays_vector!(["eng", "port", "span"]);
+
+        insert_test_data(&mut store, &keys[..1], &values[..1], Some(1));
+        insert_test_data(&mut store, &keys[1..], &values[1..], None);
+
+        // wait for expiry and some more just to be safe
+        thread::sleep(Duration::from_secs(2));
+
+        let test_data = [
+            ("foo", false), // expired
+            ("bar", true),
+            ("bar", false), // already deleted
+            ("fore", true),
+            ("non-existent", false),
+        ];
+        for (k, expected) in test_data {
+            let got = store
+                .delete(&str_to_bytes!(k))
+                .unwrap_or_else(|_| panic!("delete {}", k));
+            assert_eq!(got, expected, "key: {}", k);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn delete_surfaces_search_index_errors() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+
+        let search_idx_file_path = Path::new(STORE_PATH).join(DEFAULT_SEARCH_INDEX_FILE);
+        let search_idx_file_path = search_idx_file_path.to_str().unwrap();
+        let empty_idx_file_size = get_file_size(search_idx_file_path);
+        store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+
+        // chop off the entries of the search index so that removing 'foo' from it fails
+        OpenOptions::new()
+            .write(true)
+            .open(search_idx_file_path)
+            .expect("open search index file")
+            .set_len(empty_idx_file_size)
+            .expect("truncate search index file");
+
+        let got = store.delete(&b"foo"[..]);
+        assert!(
+            matches!(&got, Err(ScdbError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof),
+            "got = {:?}",
+            got
+        );
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn clear_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        insert_test_data(&mut store, &keys, &values, None);
+        store.clear().expect("store cleared");
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = keys.iter().map(|_| Ok(None)).collect();
+        assert_list_eq!(&expected_values, &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn clear_with_max_keys_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        let mut store =
+            Store::new(STORE_PATH, Some(100_000), None, None, Some(0), true).expect("create store");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+        insert_test_data(&mut store, &keys, &values, None);
+
+        let db_file_path = store.db_file_path().expect("db file path").to_path_buf();
+        let original_file_size = get_file_size(db_file_path.to_str().unwrap());
+
+        store.clear_with_max_keys(100).expect("clear with max keys");
+
+        let expected_header = DbFileHeader::new(Some(100), None, None);
+        let final_file_size = get_file_size(db_file_path.to_str().unwrap());
+        assert_eq!(&store.header, &expected_header);
+        assert_eq!(final_file_size, expected_header.key_values_start_point);
+        assert!(final_file_size < original_file_size);
+
+        // everything is cleared
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = keys.iter().map(|_| Ok(None)).collect();
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(store.search(b"f", 0, 0).expect("search f"), vec![]);
+
+        // and the store still works
+        insert_test_data(&mut store, &keys, &values, None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+        assert_eq!(
+            store.search(b"ba", 0, 0).expect("search ba"),
+            vec![
+                (str_to_bytes!("bar"), str_to_bytes!("port")),
+                (str_to_bytes!("band"), str_to_bytes!("nyoro"))
+            ]
+        );
+
+        // and the new geometry persists
+        drop(store);
+        let store = Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        assert_eq!(&store.header, &expected_header);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn clear_with_max_keys_errs_for_zero() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        let got = store.clear_with_max_keys(0);
+        assert!(matches!(got, Err(ScdbError::InvalidInput(_))));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn path_methods_work() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+
+        store
+            .set_path(Path::new("images/cat.png"), &b"meow"[..], None)
+            .expect("set cat");
+        store
+            .set_path(PathBuf::from("images").join("dog.png"), &b"woof"[..], None)
+            .expect("set dog");
+        assert_eq!(
+            store.get_path("images/cat.png").expect("get cat"),
+            Some(b"meow".to_vec())
+        );
+        assert_eq!(
+            store.get(&b"images/cat.png"[..]).expect("get cat bytes"),
+            Some(b"meow".to_vec())
+        );
+        assert_eq!(
+            store
+                .get_path(Path::new("images").join("dog.png"))
+                .expect("get dog"),
+            Some(b"woof".to_vec())
+        );
+        // paths are not normalized
+        assert_eq!(store.get_path("images/./cat.png").expect("get cat"), None);
+
+        assert!(store.delete_path("images/cat.png").expect("delete cat"));
+        assert!(!store
+            .delete_path("images/cat.png")
+            .expect("delete cat again"));
+        assert_eq!(store.get_path("images/cat.png").expect("get cat"), None);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn str_methods_work() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+
+        store.set_str("foo", "bar", None).expect("set foo");
+        store.set_str("🦀", "ferris", None).expect("set crab");
+        assert_eq!(
+            store.get_str("foo").expect("get foo"),
+            Some("bar".to_string())
+        );
+        assert_eq!(
+            store.get_str("🦀").expect("get crab"),
+            Some("ferris".to_string())
+        );
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo bytes"),
+            Some(b"bar".to_vec())
+        );
+        assert_eq!(store.get_str("bar").expect("get bar"), None);
+
+        store
+            .set(&b"bytes"[..], &[255u8, 0][..], None)
+            .expect("set bytes");
+        assert!(matches!(
+            store.get_str("bytes"),
+            Err(ScdbError::InvalidUtf8(_))
+        ));
+
+        assert!(store.delete_str("foo").expect("delete foo"));
+        assert!(!store.delete_str("foo").expect("delete foo again"));
+        assert_eq!(store.get_str("foo").expect("get foo"), None);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn delete_prefix_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+
+        assert!(matches!(
+            store.delete_prefix(&b""[..]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+        assert_eq!(store.delete_prefix(&b"o"[..]).expect("delete o"), 2);
+        assert_eq!(store.delete_prefix(&b"o"[..]).expect("delete o again"), 0);
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[4] = Ok(None);
+        expected_values[5] = Ok(None);
+        assert_list_eq!(&expected_values, &received_values);
+
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(matches!(
+            store.delete_prefix(&b"h"[..]),
+            Err(ScdbError::SearchDisabled)
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn entries_with_ttl_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "cow"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "moo"]);
+
+        insert_test_data(&mut store, &keys[..1], &values[..1], None);
+        insert_test_data(&mut store, &keys[1..2], &values[1..2], Some(10));
+        insert_test_data(&mut store, &keys[2..], &values[2..], Some(100));
+        delete_keys(&mut store, &keys[3..].to_vec());
+
+        clock.0.fetch_add(4, Ordering::SeqCst);
+        let mut got = store.entries_with_ttl().expect("entries with ttl");
+        got.sort();
+        let expected = vec![
+            (str_to_bytes!("bar"), Some(96)),
+            (str_to_bytes!("foo"), None),
+            (str_to_bytes!("fore"), Some(6)),
+        ];
+        assert_eq!(got, expected);
+
+        // expired entries are left out
+        clock.0.fetch_add(7, Ordering::SeqCst);
+        let mut got = store.entries_with_ttl().expect("entries with ttl");
+        got.sort();
+        let expected = vec![
+            (str_to_bytes!("bar"), Some(89)),
+            (str_to_bytes!("foo"), None),
+        ];
+        assert_eq!(got, expected);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn iter_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+
+        let mut got = store
+            .iter()
+            .expect("iter")
+            .collect::<Result<Vec<(Vec<u8>, Vec<u8>)>>>()
+            .expect("collect key-values");
+        got.sort();
+        let mut expected: Vec<(Vec<u8>, Vec<u8>)> = keys[1..]
+            .iter()
+            .cloned()
+            .zip(values[1..].iter().cloned())
+            .collect();
+        expected.sort();
+        assert_eq!(&expected, &got);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    fn scan_works() {
+        let mut store =
+            Store::in_memory(StoreBuilder::new("").max_keys(1000)).expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+
+        let mut got: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+        let mut cursor = Some(ScanCursor::default());
+        let mut pages = 0;
+        while let Some(current) = cursor {
+            let (page, next) = store.scan(current, 2).expect("scan");
+            assert!(page.len() <= 2);
+            got.extend(page);
+            cursor = next;
+            pages += 1;
+
+            // writes in between calls do not upset the enumeration of the other keys
+            if pages == 1 {
+                store.set(&b"new"[..], &b"key"[..], None).expect("set new");
+                store.set(&keys[5], &values[0], None).expect("update");
+            }
+        }
+        // the key set midway may or may not be returned, but every other key is, exactly once
+        let mut got_keys: Vec<Vec<u8>> = got
+            .into_iter()
+            .map(|(k, _)| k)
+            .filter(|k| k != b"new")
+            .collect();
+        got_keys.sort();
+        let mut expected_keys = keys[1..].to_vec();
+        expected_keys.sort();
+        assert_eq!(&expected_keys, &got_keys);
+
+        // the end of the index
+        let position = store.header.items_per_index_block * store.header.number_of_index_blocks;
+        assert_eq!(
+            store
+                .scan(ScanCursor::from(position - 1), 10)
+                .expect("scan end"),
+            (vec![], None)
+        );
+        assert_eq!(
+            store
+                .scan(ScanCursor::from(u64::MAX), 10)
+                .expect("scan beyond end"),
+            (vec![], None)
+        );
+        assert!(matches!(
+            store.scan(ScanCursor::default(), 0),
+            Err(ScdbError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn dump_works() {
+        let now = get_current_timestamp();
+        let clock = Arc::new(MockClock(AtomicU64::new(now)));
+        let mut store = Store::in_memory(StoreBuilder::new("").clock(clock)).expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+        store
+            .set(&b"multi\nline"[..], &b"\xffbytes"[..], Some(60))
+            .expect("set multi-line key");
+
+        let dump = |store: &mut Store, value_as: ValueEncoding| {
+            let mut out = vec![];
+            let written = store.dump(&mut out, value_as).expect("dump");
+            let mut lines: Vec<String> = String::from_utf8(out)
+                .expect("utf8 dump")
+                .lines()
+                .map(str::to_string)
+                .collect();
+            lines.sort();
+            assert_eq!(written, lines.len() as u64);
+            lines
+        };
+
+        let mut expected: Vec<String> = keys[1..]
+            .iter()
+            .zip(&values[1..])
+            .map(|(k, v)| {
+                let k = String::from_utf8(k.clone()).unwrap();
+                let v = String::from_utf8(v.clone()).unwrap();
+                format!("{}\t{}\t0", k, v)
+            })
+            .chain([format!("multi\\nline\t\u{fffd}bytes\t{}", now + 60)])
+            .collect();
+        expected.sort();
+        assert_eq!(dump(&mut store, ValueEncoding::Utf8Lossy), expected);
+
+        let lines = dump(&mut store, ValueEncoding::Base64);
+        assert_eq!(lines.len(), keys.len());
+        assert!(lines.contains(&format!("bXVsdGkKbGluZQ==\t/2J5dGVz\t{}", now + 60)));
+    }
+
+    #[test]
+    #[serial]
+    fn retain_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+
+        // deleted keys are not passed to the predicate, nor counted
+        let count = store.retain(|_, v| v.len() < 7).expect("retain");
+        assert_eq!(count, 3);
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = vec![
+            Ok(None),
+            Ok(None),
+            Ok(Some(values[2].clone())),
+            Ok(Some(values[3].clone())),
+            Ok(None),
+            Ok(None),
+        ];
+        assert_list_eq!(&expected_values, &received_values);
+
+        // the search index is also updated
+        assert_eq!(store.search(&b"o"[..], 0, 0).expect("search o"), vec![]);
+        assert_eq!(store.retain(|_, _| true).expect("retain all"), 0);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn iter_prefix_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "food", "fore", "bar", "foodie", "afoo", "fo"]);
+        let values = to_byte_arrays_vector!(["eng", "lat", "span", "port", "fr", "sw", "ger"]);
+
+        insert_test_data(&mut store, &keys[..5], &values[..5], None);
+        insert_test_data(&mut store, &keys[5..], &values[5..], Some(1));
+        delete_keys(&mut store, &keys[1..2].to_vec());
+
+        // advance time beyond the expiry of the last keys without sleeping
+        clock.0.fetch_add(2, Ordering::SeqCst);
+
+        let test_data = vec![
+            (&b"f"[..], vec![0usize, 2, 4]),
+            (&b"foo"[..], vec![0, 4]),
+            (&b"food"[..], vec![4]),
+            (&b"fore"[..], vec![2]),
+            (&b"fox"[..], vec![]),
+            (&b"pig"[..], vec![]),
+        ];
+
+        for (prefix, indices) in test_data {
+            let mut got = store
+                .iter_prefix(prefix)
+                .expect("iter prefix")
+                .collect::<Result<Vec<(Vec<u8>, Vec<u8>)>>>()
+                .expect("collect key-values");
+            got.sort();
+            let mut expected: Vec<(Vec<u8>, Vec<u8>)> = indices
+                .into_iter()
+                .map(|i| (keys[i].clone(), values[i].clone()))
+                .collect();
+            expected.sort();
+            assert_eq!(&expected, &got, "prefix: {:?}", prefix);
+        }
+
+        // it is lazy
+        let first = store
+            .iter_prefix(&b"f"[..])
+            .expect("iter prefix")
+            .next()
+            .expect("first item")
+            .expect("first key-value");
+        assert!(first.0.starts_with(b"f"));
+
+        assert!(matches!(
+            store.iter_prefix(&b""[..]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(matches!(
+            store.iter_prefix(&b"f"[..]),
+            Err(ScdbError::SearchDisabled)
+        ));
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn keys_and_values_work() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "bar", "fore", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "port", "span", "nyoro", "dan"]);
+
+        insert_test_data(&mut store, &keys[..2], &values[..2], Some(1));
+        insert_test_data(&mut store, &keys[2..], &values[2..], None);
+        // updates do not lead to duplicates
+        insert_test_data(&mut store, &keys[2..3], &values[2..3], None);
+        delete_keys(&mut store, &keys[4..].to_vec());
+
+        // wait for expiry and some more just to be safe
+        thread::sleep(Duration::from_secs(2));
+
+        let mut got_keys = store
+            .keys()
+            .expect("keys")
+            .collect::<Result<Vec<Vec<u8>>>>()
+            .expect("collect keys");
+        got_keys.sort();
+        let mut got_values = store
+            .values()
+            .expect("values")
+            .collect::<Result<Vec<Vec<u8>>>>()
+            .expect("collect values");
+        got_values.sort();
+
+        assert_eq!(got_keys, to_byte_arrays_vector!(["band", "fore"]));
+        assert_eq!(got_values, to_byte_arrays_vector!(["nyoro", "span"]));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn geometry_getters_work() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+        let block_size = {
+            let store = Store::new(STORE_PATH, Some(100), Some(3), None, Some(0), true)
+                .expect("create store");
+            assert_eq!(store.max_keys(), 100);
+            assert_eq!(store.redundant_blocks(), 3);
+            assert_eq!(
+                store.max_index_key_len(),
+                Some(Store::DEFAULT_MAX_INDEX_KEY_LEN)
+            );
+            assert!(store.block_size() > 0);
+            store.block_size()
+        };
+
+        // the geometry of the existing file is returned
+        let mut store =
+            Store::new(STORE_PATH, Some(600), None, None, Some(0), false).expect("reopen store");
+        assert_eq!(store.max_keys(), 100);
+        assert_eq!(store.redundant_blocks(), 3);
+        assert_eq!(store.block_size(), block_size);
+        assert_eq!(store.max_index_key_len(), None);
+
+        store.clear_with_max_keys(50).expect("clear with max keys");
+        assert_eq!(store.max_keys(), 50);
+        assert_eq!(store.redundant_blocks(), 3);
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn verify_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+        assert_eq!(store.verify().expect("verify"), vec![]);
+
+        // corrupt the size of the last entry, making it spill beyond the file
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let file_size = get_file_size(db_file_path.to_str().unwrap());
+        let last_entry_address = file_size
+            - (KEY_VALUE_MIN_SIZE_IN_BYTES as u64)
+            - (VERSION_SIZE_IN_BYTES as u64)
+            - (keys[5].len() + values[5].len()) as u64;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&db_file_path)
+            .expect("open db file");
+        file.seek(SeekFrom::Start(last_entry_address))
+            .expect("seek to last entry");
+        file.write_all(&u32::MAX.to_be_bytes())
+            .expect("corrupt last entry");
+        drop(file);
+
+        let got = store.verify().expect("verify");
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].kv_address, last_entry_address);
+        assert!(got[0].message.contains("spills beyond"), "{}", got[0]);
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn get_fails_for_index_pointing_past_end_of_file() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+
+        // corrupt the index slot of the first key to point past the end of the file
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let file_size = get_file_size(db_file_path.to_str().unwrap());
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&db_file_path)
+            .expect("open db file");
+        let index_offset = store
+            .header
+            .get_index_offsets(&keys[0])
+            .find(|offset| {
+                let mut kv_address = [0u8; 8];
+                file.seek(SeekFrom::Start(*offset)).expect("seek to slot");
+                file.read_exact(&mut kv_address).expect("read slot");
+                kv_address != ZERO_U64_BYTES
+            })
+            .expect("index slot of first key");
+        file.seek(SeekFrom::Start(index_offset))
+            .expect("seek to slot");
+        file.write_all(&(file_size + 100).to_be_bytes())
+            .expect("corrupt slot");
+        drop(file);
+        drop(store);
+
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("reopen store");
+        assert!(matches!(store.get(&keys[0]), Err(ScdbError::Corruption(_))));
+        assert!(matches!(
+            store.peek(&keys[0]),
+            Err(ScdbError::Corruption(_))
+        ));
+        // the other keys are unaffected
+        let received_values = get_values_for_keys(&mut store, &keys[1..].to_vec());
+        assert_list_eq!(&wrap_values_in_result(&values[1..]), &received_values);
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn try_set_and_try_get_work() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+
+        // as if a compaction were going on
+        let buffer_pool = store.buffer_pool.clone();
+        let guard = buffer_pool.lock().expect("lock buffer pool");
+        assert!(!store
+            .try_set(&b"fore"[..], &b"front"[..], None)
+            .expect("try set fore"));
+        assert_eq!(store.try_get(&b"foo"[..]).expect("try get foo"), None);
+        drop(guard);
+
+        assert_eq!(store.get(&b"fore"[..]).expect("get fore"), None);
+        assert!(store
+            .try_set(&b"fore"[..], &b"front"[..], None)
+            .expect("try set fore"));
+        assert_eq!(
+            store.try_get(&b"fore"[..]).expect("try get fore"),
+            Some(b"front".to_vec())
+        );
+        // lookups need only a shared reference to the store, so they can be made from other threads
+        let got = thread::scope(|s| s.spawn(|| store.try_get(&b"foo"[..])).join().expect("join"));
+        assert_eq!(got.expect("try get foo"), Some(b"bar".to_vec()));
+        assert_eq!(
+            store.search(&b"fo"[..], 0, 0).expect("search fo"),
+            vec![
+                (b"foo".to_vec(), b"bar".to_vec()),
+                (b"fore".to_vec(), b"front".to_vec())
+            ]
+        );
+        assert!(matches!(
+            store.try_set(&b""[..], &b"front"[..], None),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn get_timeout_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+        let timeout = Duration::from_millis(100);
+
+        // as if a compaction were going on
+        let buffer_pool = store.buffer_pool.clone();
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            let _guard = buffer_pool.lock().expect("lock buffer pool");
+            locked_tx.send(()).expect("send locked");
+            release_rx.recv().expect("recv release");
+            thread::sleep(Duration::from_millis(20));
+        });
+        locked_rx.recv().expect("recv locked");
+
+        let start = Instant::now();
+        match store.get_timeout(&b"foo"[..], timeout) {
+            Err(ScdbError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::WouldBlock),
+            v => panic!("expected a would-block error, got {:?}", v),
+        }
+        assert!(start.elapsed() >= timeout);
+
+        // the lock is released before the timeout elapses
+        release_tx.send(()).expect("send release");
+        assert_eq!(
+            store
+                .get_timeout(&b"foo"[..], Duration::from_secs(5))
+                .expect("get foo"),
+            Some(b"bar".to_vec())
+        );
+        handle.join().expect("join");
+        assert_eq!(
+            store.get_timeout(&b"baz"[..], timeout).expect("get baz"),
+            None
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn peek_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        store.delete(&keys[0]).expect("delete");
+        drop(store);
+
+        // reopen the store so that its cache is empty
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        let received_values: Vec<Result<Option<Vec<u8>>>> =
+            keys.iter().map(|k| store.peek(k)).collect();
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(store.cache_stats(), CacheStats::default());
+
+        // peeking still reads what is already in the cache
+        store.get(&keys[1]).expect("get");
+        store.set(&keys[1], &b"new"[..], None).expect("set");
+        assert_eq!(store.peek(&keys[1]).expect("peek"), Some(b"new".to_vec()));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn clone_to_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .max_keys(6)
+            .build()
+            .expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys[..3], &values[..3], None);
+        insert_test_data(&mut store, &keys[3..], &values[3..], Some(3600));
+        delete_keys(&mut store, &keys[..1].to_vec());
+
+        let config = StoreConfig {
+            max_keys: Some(100),
+            redundant_blocks: Some(2),
+            compaction_interval: Some(0),
+            is_search_enabled: true,
+            max_index_key_len: Some(5),
+            db_file_name: Some("clone.scdb".to_string()),
+            index_file_name: Some("clone.iscdb".to_string()),
+            ..Default::default()
+        };
+        let mut clone = store.clone_to(STORE_PATH, config.clone()).expect("clone");
+        assert_eq!(clone.max_keys(), 100);
+        assert_eq!(clone.redundant_blocks(), 2);
+        assert_eq!(clone.max_index_key_len(), Some(5));
+
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        let received_values = get_values_for_keys(&mut clone, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        for k in &keys[1..] {
+            let original = store.get_meta(k).expect("get original meta");
+            let cloned = clone.get_meta(k).expect("get cloned meta");
+            assert_eq!(cloned, original);
+        }
+        assert_eq!(
+            clone.search(&b"yoo-h"[..], 0, 0).expect("search yoo-h"),
+            vec![(keys[2].clone(), values[2].clone())]
+        );
+
+        // the original store is left intact
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+
+        // a store that already has keys is not cloned into
+        assert!(matches!(
+            store.clone_to(STORE_PATH, config.clone()),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        // a store too small for the keys fails
+        let small_config = StoreConfig {
+            max_keys: Some(2),
+            db_file_name: Some("small.scdb".to_string()),
+            ..config
+        };
+        assert!(matches!(
+            store.clone_to(STORE_PATH, small_config),
+            Err(ScdbError::KeysMaxedOut)
+        ));
+
+        drop(clone);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn merge_from_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let now = clock.now_secs();
+        let builder = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone());
+        let mut other = builder
+            .clone()
+            .db_file_name("other.scdb")
+            .index_file_name("other.iscdb")
+            .build()
+            .expect("create other store");
+        let mut store = builder.build().expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        let other_values: Vec<Vec<u8>> = values
+            .iter()
+            .map(|v| [b"other:", &v[..]].concat())
+            .collect();
+
+        other.clear().expect("clear other store");
+        for (i, ttl) in [
+            (0, Some(50)),
+            (1, Some(200)),
+            (2, Some(50)),
+            (4, None),
+            (5, Some(10)),
+        ] {
+            other
+                .set(&keys[i], &other_values[i], ttl)
+                .expect("set in other store");
+        }
+
+        // (policy, number of key-value pairs set, indices of the keys whose values come from the other store)
+        let test_data = [
+            (ConflictPolicy::Skip, 1, vec![4]),
+            (ConflictPolicy::Overwrite, 4, vec![0, 1, 2, 4]),
+            (ConflictPolicy::KeepNewerExpiry, 2, vec![1, 4]),
+        ];
+        for (policy, expected_written, from_other) in test_data {
+            clock.0.store(now, Ordering::SeqCst);
+            store.clear().expect("store failed to clear");
+            insert_test_data(&mut store, &keys[..1], &values[..1], None);
+            insert_test_data(&mut store, &keys[1..4], &values[1..4], Some(100));
+            // the last key of the other store expires
+            clock.0.store(now + 20, Ordering::SeqCst);
+
+            let written = store.merge_from(&mut other, policy).expect("merge");
+            assert_eq!(written, expected_written, "{:?}", policy);
+
+            let expected_values: Vec<Result<Option<Vec<u8>>>> = (0..keys.len())
+                .map(|i| {
+                    if from_other.contains(&i) {
+                        Ok(Some(other_values[i].clone()))
+                    } else if i < 4 {
+                        Ok(Some(values[i].clone()))
+                    } else {
+                        Ok(None)
+                    }
+                })
+                .collect();
+            let received_values = get_values_for_keys(&mut store, &keys);
+            assert_list_eq!(&expected_values, &received_values);
+            assert_eq!(
+                store.search(&keys[4], 0, 0).expect("search"),
+                vec![(keys[4].clone(), other_values[4].clone())]
+            );
+        }
+
+        // the expiry of merged key-value pairs is kept
+        let meta = store.get_meta(&keys[1]).expect("get meta").expect("meta");
+        assert_eq!(meta.expiry, now + 200);
+        clock.0.store(now + 150, Ordering::SeqCst);
+        assert_eq!(
+            store.get(&keys[1]).expect("get"),
+            Some(other_values[1].clone())
+        );
+        assert_eq!(store.get(&keys[2]).expect("get"), None);
+
+        drop(other);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn remaining_capacity_works() {
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .max_keys(4)
+            .compaction_interval(0)
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        assert_eq!(store.remaining_capacity().expect("remaining capacity"), 4);
+
+        insert_test_data(&mut store, &keys[..3], &values[..3], None);
+        assert_eq!(store.remaining_capacity().expect("remaining capacity"), 1);
+
+        // updates take up no room
+        store.set(&keys[0], &values[1], None).expect("update key");
+        assert_eq!(store.remaining_capacity().expect("remaining capacity"), 1);
+
+        store.set(&keys[3], &values[3], None).expect("set last key");
+        assert_eq!(store.remaining_capacity().expect("remaining capacity"), 0);
+        assert!(matches!(
+            store.set(&keys[4], &values[4], None),
+            Err(ScdbError::KeysMaxedOut)
+        ));
+        assert_eq!(store.remaining_capacity().expect("remaining capacity"), 0);
+
+        delete_keys(&mut store, &keys[..2].to_vec());
+        assert_eq!(store.remaining_capacity().expect("remaining capacity"), 2);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn set_fails_when_keys_are_maxed_out() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let max_keys = 4;
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .max_keys(max_keys as u64)
+            .compaction_interval(0)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(
+            &mut store,
+            &keys[..max_keys - 1],
+            &values[..max_keys - 1],
+            None,
+        );
+        store
+            .set(&keys[max_keys - 1], &values[max_keys - 1], Some(10))
+            .expect("set last key");
+
+        // max_keys + 1 distinct keys can't be set
+        assert!(matches!(
+            store.set(&keys[max_keys], &values[max_keys], None),
+            Err(ScdbError::KeysMaxedOut)
+        ));
+        assert_eq!(store.get(&keys[max_keys]).expect("get"), None);
+
+        // existing keys can still be updated
+        store.set(&keys[0], &values[1], None).expect("update key");
+        assert_eq!(store.get(&keys[0]).expect("get"), Some(values[1].clone()));
+
+        // deleted keys free up room, and expired ones do so once compacted away
+        store.delete(&keys[0]).expect("delete key");
+        store
+            .set(&keys[max_keys], &values[max_keys], None)
+            .expect("set new key after delete");
+        assert!(matches!(
+            store.set(&keys[0], &values[0], None),
+            Err(ScdbError::KeysMaxedOut)
+        ));
+        clock.0.fetch_add(20, Ordering::SeqCst);
+        assert!(matches!(
+            store.set(&keys[0], &values[0], None),
+            Err(ScdbError::KeysMaxedOut)
+        ));
+        store.compact().expect("compact");
+        store
+            .set(&keys[0], &values[0], None)
+            .expect("set new key after expiry");
+        assert!(matches!(
+            store.set(&keys[max_keys - 1], &values[max_keys - 1], None),
+            Err(ScdbError::KeysMaxedOut)
+        ));
+
+        // moving values to new keys does not add to the keys
+        assert!(store
+            .rename_key(&keys[0], &keys[max_keys - 1], false)
+            .expect("rename key"));
+        assert_eq!(store.get(&keys[0]).expect("get"), None);
+        assert_eq!(
+            store.get(&keys[max_keys - 1]).expect("get"),
+            Some(values[0].clone())
+        );
+        store
+            .swap(&keys[max_keys - 1], &keys[0])
+            .expect("swap with missing key");
+        assert_eq!(store.get(&keys[max_keys - 1]).expect("get"), None);
+        assert_eq!(store.get(&keys[0]).expect("get"), Some(values[0].clone()));
+        assert!(matches!(
+            store.set(&keys[max_keys - 1], &values[max_keys - 1], None),
+            Err(ScdbError::KeysMaxedOut)
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn load_factor_and_high_water_mark_work() {
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .max_keys(10)
+            .compaction_interval(0)
+            .high_water_mark(0.5)
+            .is_high_water_mark_strict(true)
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        assert_eq!(store.load_factor().expect("load factor"), 0.0);
+
+        insert_test_data(&mut store, &keys[..5], &values[..5], None);
+        assert_eq!(store.load_factor().expect("load factor"), 0.5);
+
+        // new keys are rejected, but existing ones can still be updated
+        assert!(matches!(
+            store.set(&keys[5], &values[5], None),
+            Err(ScdbError::HighWaterMarkReached(v)) if v == 0.5
+        ));
+        store.set(&keys[0], &values[5], None).expect("update key");
+        assert_eq!(store.load_factor().expect("load factor"), 0.5);
+
+        store.delete(&keys[0]).expect("delete key");
+        assert_eq!(store.load_factor().expect("load factor"), 0.4);
+        store.set(&keys[5], &values[5], None).expect("set new key");
+        assert_eq!(store.load_factor().expect("load factor"), 0.5);
+
+        // the count is recomputed from the file when the store is reopened
+        drop(store);
+        let store = Store::new(STORE_PATH, None, None, None, Some(0), false).expect("open store");
+        assert_eq!(store.load_factor().expect("load factor"), 0.5);
+        drop(store);
+
+        for mark in [0.0, 1.5, f64::NAN] {
+            let res = StoreBuilder::new(STORE_PATH).high_water_mark(mark).build();
+            assert!(matches!(res, Err(ScdbError::InvalidInput(_))));
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn from_config_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        let config = StoreConfig {
+            max_keys: Some(1000),
+            redundant_blocks: Some(2),
+            compaction_interval: Some(0),
+            is_search_enabled: true,
+            max_index_key_len: Some(5),
+            ..Default::default()
+        };
+        let mut store = Store::from_config(STORE_PATH, config.clone()).expect("create store");
+        assert_eq!(&store.header, &DbFileHeader::new(Some(1000), Some(2), None));
+        assert_eq!(store.max_index_key_len(), Some(5));
+
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        assert_eq!(
+            store.search(&b"orir"[..], 0, 0).expect("search orir"),
+            vec![(keys[5].clone(), values[5].clone())]
+        );
+        drop(store);
+
+        let invalid_configs = [
+            (
+                "max_keys",
+                StoreConfig {
+                    max_keys: Some(0),
+                    ..config.clone()
+                },
+            ),
+            (
+                "pool_capacity",
+                StoreConfig {
+                    pool_capacity: Some(0),
+                    ..config.clone()
+                },
+            ),
+            (
+                "max_key_size",
+                StoreConfig {
+                    max_key_size: Some(0),
+                    ..config.clone()
+                },
+            ),
+            (
+                "high_water_mark",
+                StoreConfig {
+                    high_water_mark: Some(2.0),
+                    ..config.clone()
+                },
+            ),
+        ];
+        for (name, config) in invalid_configs {
+            match Store::from_config(STORE_PATH, config) {
+                Err(ScdbError::InvalidInput(msg)) => assert!(msg.starts_with(name), "{}", msg),
+                Err(e) => panic!("expected an invalid input error, got {:?}", e),
+                Ok(_) => panic!("expected {} to be invalid", name),
+            }
+        }
+
+        // a max_index_key_len of 0 keeps no search index
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+        let config = StoreConfig {
+            max_index_key_len: Some(0),
+            ..config
+        };
+        let mut store = Store::from_config(STORE_PATH, config).expect("create store");
+        assert_eq!(store.max_index_key_len(), None);
+        assert_eq!(store.index_file_path(), None);
+        assert!(!Path::new(STORE_PATH)
+            .join(DEFAULT_SEARCH_INDEX_FILE)
+            .exists());
+        store.set(&keys[0], &values[0], None).expect("set");
+        assert!(matches!(
+            store.search(&keys[0], 0, 0),
+            Err(ScdbError::SearchDisabled)
+        ));
+        drop(store);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn fragmentation_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        assert_eq!(
+            store.fragmentation().expect("fragmentation"),
+            FragmentationReport::default()
+        );
+
+        let keys = get_keys();
+        let values = get_values();
+        let entry_sizes: Vec<u64> = keys
+            .iter()
+            .zip(&values)
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .size as u64
+            })
+            .collect();
+        let total_bytes: u64 = entry_sizes.iter().sum();
+        insert_test_data(&mut store, &keys, &values, None);
+        let report = store.fragmentation().expect("fragmentation");
+        assert_eq!(
+            report,
+            FragmentationReport {
+                total_bytes,
+                live_bytes: total_bytes,
+                dead_bytes: 0,
+                live_entries: keys.len() as u64,
+            }
+        );
+        assert_eq!(report.ratio(), 0.0);
+        assert_eq!(
+            report.average_entry_size(),
+            total_bytes as f64 / keys.len() as f64
+        );
+
+        // deleted and overwritten entries are dead
+        delete_keys(&mut store, &keys[..1].to_vec());
+        store.set(&keys[1], &values[0], None).expect("update key");
+        let updated_size = KeyValueEntry::new(&keys[1], &values[0], 0)
+            .expect("new key value entry")
+            .with_version(1)
+            .size as u64;
+        let dead_bytes = entry_sizes[0] + entry_sizes[1];
+        let report = store.fragmentation().expect("fragmentation");
+        assert_eq!(
+            report,
+            FragmentationReport {
+                total_bytes: total_bytes + updated_size,
+                live_bytes: total_bytes + updated_size - dead_bytes,
+                dead_bytes,
+                live_entries: keys.len() as u64 - 1,
+            }
+        );
+        assert_eq!(
+            report.ratio(),
+            dead_bytes as f64 / (total_bytes + updated_size) as f64
+        );
+
+        store.compact().expect("compact");
+        let report = store.fragmentation().expect("fragmentation");
+        assert_eq!(report.dead_bytes, 0);
+        assert_eq!(report.live_bytes, total_bytes + updated_size - dead_bytes);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn size_histogram_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        assert_eq!(
+            store.size_histogram().expect("size histogram"),
+            SizeHistogram::default()
+        );
+
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        store
+            .set(&b"big"[..], &vec![7u8; 5000][..], None)
+            .expect("set big");
+        store.set(&b"empty"[..], &b""[..], None).expect("set empty");
+        // deleted keys are not counted
+        delete_keys(&mut store, &keys[..1].to_vec());
+
+        let mut expected = SizeHistogram::default();
+        for (k, v) in keys[1..].iter().zip(&values[1..]) {
+            expected.record(k.len() as u64, v.len() as u64);
+        }
+        expected.record(3, 5000);
+        expected.record(5, 0);
+        let histogram = store.size_histogram().expect("size histogram");
+        assert_eq!(histogram, expected);
+        assert_eq!(histogram.value_sizes.len(), 13);
+        assert_eq!(histogram.value_sizes[12], 1);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn io_stats_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, Some(5), Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        assert_eq!(store.io_stats(), IoStats::default());
+
+        store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+        let entry_size = KeyValueEntry::new(&b"foo"[..], &b"bar"[..], 0)
+            .expect("new key value entry")
+            .with_version(1)
+            .size as u64;
+        let stats = store.io_stats();
+        assert_eq!(stats.bytes_written, entry_size + 8);
+        assert!(stats.bytes_read > 0);
+
+        // only the first get reads the key-value from the file, the rest are served from memory
+        store.get(&b"foo"[..]).expect("get foo");
+        let bytes_read = store.io_stats().bytes_read;
+        assert!(bytes_read > stats.bytes_read);
+        for _ in 0..2 {
+            store.get(&b"foo"[..]).expect("get foo");
+        }
+        assert_eq!(store.io_stats().bytes_read, bytes_read);
+
+        // compaction rewrites the whole file
+        store.compact().expect("compact");
+        let stats = store.io_stats();
+        assert!(stats.bytes_written > store.header.key_values_start_point + entry_size);
+        assert!(stats.bytes_read > bytes_read);
+
+        store.clear().expect("store failed to clear");
+        assert_eq!(store.io_stats(), IoStats::default());
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn cache_stats_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, Some(5), Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        assert_eq!(store.cache_stats(), CacheStats::default());
+
+        store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+        for _ in 0..3 {
+            store.get(&b"foo"[..]).expect("get foo");
+        }
+
+        let stats = store.cache_stats();
+        assert_eq!(stats.index_misses, 1);
+        assert_eq!(stats.index_hits, 3);
+        assert_eq!(stats.kv_misses, 1);
+        assert_eq!(stats.kv_hits, 2);
+
+        store.clear().expect("store failed to clear");
+        assert_eq!(store.cache_stats(), CacheStats::default());
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_errs_when_disabled() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+
+        insert_test_data(&mut store, &keys, &values, None);
+        assert!(matches!(
+            store.search(b"f".as_ref(), 0, 0),
+            Err(ScdbError::SearchDisabled)
+        ));
+        // no search index file is created, nor kept up to date
+        assert_eq!(store.index_file_path(), None);
+        assert!(!Path::new(STORE_PATH)
+            .join(DEFAULT_SEARCH_INDEX_FILE)
+            .exists());
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+
+        insert_test_data(&mut store, &keys, &values, None);
+        let test_data = [
+            ("f", vec![("foo", "eng"), ("fore", "span")]),
+            ("fo", vec![("foo", "eng"), ("fore", "span")]),
+            ("foo", vec![("foo", "eng")]),
+            ("for", vec![("fore", "span")]),
+            ("b", vec![("bar", "port"), ("band", "nyoro")]),
+            ("ba", vec![("bar", "port"), ("band", "nyoro")]),
+            ("bar", vec![("bar", "port")]),
+            ("ban", vec![("band", "nyoro")]),
+            ("band", vec![("band", "nyoro")]),
+            ("p", vec![("pig", "dan")]),
+            ("pi", vec![("pig", "dan")]),
+            ("pig", vec![("pig", "dan")]),
+            ("pigg", vec![]),
+            ("food", vec![]),
+            ("bandana", vec![]),
+            ("bare", vec![]),
+        ];
+
+        for (term, expected) in test_data {
+            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
+                .into_iter()
+                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
+                .collect();
+            let got = store
+                .search(&str_to_bytes!(term), 0, 0)
+                .unwrap_or_else(|_| panic!("search for {}", term));
+            assert_eq!(&expected, &got);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_works_during_background_compaction() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(1), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        let expected: Vec<(Vec<u8>, Vec<u8>)> = vec![
+            (keys[4].clone(), values[4].clone()),
+            (keys[5].clone(), values[5].clone()),
+        ];
+
+        // keep creating dangling entries for the background compaction to move the others around
+        let start = std::time::Instant::now();
+        while start.elapsed() < Duration::from_millis(2500) {
+            insert_test_data(&mut store, &keys, &values, None);
+            delete_keys(&mut store, &keys[..4].to_vec());
+            let got = store.search(&b"o"[..], 0, 0).expect("search o");
+            assert_eq!(&got, &expected);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn operations_do_not_deadlock_with_background_compaction() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let keys = get_keys();
+        let values = get_values();
+
+        // run the operations in a separate thread so that a deadlock fails the test instead of hanging it
+        thread::spawn(move || {
+            let mut store =
+                Store::new(STORE_PATH, None, None, None, Some(1), true).expect("create store");
+            let start = std::time::Instant::now();
+            while start.elapsed() < Duration::from_millis(2500) {
+                store.clear().expect("clear store");
+                insert_test_data(&mut store, &keys, &values, None);
+                delete_keys(&mut store, &keys[..2].to_vec());
+                let got = store.search(&b"b"[..], 0, 0).expect("search b");
+                assert_eq!(&got, &vec![(keys[3].clone(), values[3].clone())]);
+                store.compact().expect("compact store");
+            }
+            tx.send(()).expect("send done");
+        });
+
+        rx.recv_timeout(Duration::from_secs(30))
+            .expect("operations deadlocked with the background compaction");
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_after_update_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let key = &b"barricade"[..];
+        let keys = to_byte_arrays_vector!(["bar", "barricade", "bare"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port"]);
+        insert_test_data(&mut store, &keys, &values, None);
+
+        for value in [&b"ebizibiti"[..], b"obugoye"] {
+            store.set(key, value, None).expect("update key");
+
+            // every prefix list of the key, and terms longer than them, return only the new value
+            for i in 4..=key.len() {
+                let got = store.search(&key[..i], 0, 0).expect("search");
+                assert_eq!(got, vec![(key.to_vec(), value.to_vec())]);
+            }
+            for i in 1..=3 {
+                let got = store.search(&key[..i], 0, 0).expect("search");
+                assert!(got.contains(&(key.to_vec(), value.to_vec())));
+                assert_eq!(got.iter().filter(|(k, _)| k == key).count(), 1);
+            }
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_with_mode_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foobar", "foofoob", "barfoob"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port"]);
+
+        insert_test_data(&mut store, &keys, &values, None);
+        let test_data = [
+            (
+                "foo",
+                SearchMode::Prefix,
+                vec![("foobar", "eng"), ("foofoob", "span")],
+            ),
+            (
+                "foo",
+                SearchMode::Contains,
+                vec![("foobar", "eng"), ("foofoob", "span")],
+            ),
+            ("foob", SearchMode::Prefix, vec![("foobar", "eng")]),
+            (
+                "foob",
+                SearchMode::Contains,
+                vec![("foobar", "eng"), ("foofoob", "span")],
+            ),
+            ("foobar", SearchMode::Prefix, vec![("foobar", "eng")]),
+            ("foobarx", SearchMode::Prefix, vec![]),
+            // keys that contain the term but do not start with its prefix are missed
+            ("oob", SearchMode::Contains, vec![]),
+        ];
+
+        for (term, mode, expected) in test_data {
+            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
+                .into_iter()
+                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
+                .collect();
+            let got = store
+                .search_with_mode(&str_to_bytes!(term), 0, 0, mode)
+                .unwrap_or_else(|_| panic!("search for {} {:?}", term, mode));
+            assert_eq!(&expected, &got);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_for_each_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foobar", "foofoob", "barfoob", "foo", "fox"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "lat", "fr"]);
+        insert_test_data(&mut store, &keys[..4], &values[..4], None);
+        insert_test_data(&mut store, &keys[4..], &values[4..], Some(1));
+        delete_keys(&mut store, &keys[3..4].to_vec());
+
+        // advance time beyond the expiry of 'fox' without sleeping
+        clock.0.fetch_add(2, Ordering::SeqCst);
+
+        for term in ["f", "foo", "foob", "oob", "x", ""] {
+            let term = str_to_bytes!(term);
+            let mut got: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+            store
+                .search_for_each(&term, |k, v| {
+                    got.push((k, v));
+                    true
+                })
+                .expect("search for each");
+            let expected = store.search(&term, 0, 0).expect("search");
+            assert_eq!(&expected, &got, "term: {:?}", term);
+        }
+
+        // it stops as soon as the callback returns false
+        let mut got: Vec<Vec<u8>> = vec![];
+        store
+            .search_for_each(&b"foo"[..], |k, _| {
+                got.push(k);
+                false
+            })
+            .expect("search for each");
+        assert_eq!(got.len(), 1);
+        assert!(got[0].starts_with(b"foo"));
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(matches!(
+            store.search_for_each(&b"foo"[..], |_, _| true),
+            Err(ScdbError::SearchDisabled)
+        ));
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_works_after_expire() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "bar", "fore", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "port", "span", "nyoro", "dan"]);
+
+        insert_test_data(&mut store, &keys.to_vec(), &values.to_vec(), Some(1));
+        insert_test_data(&mut store, &keys[2..], &values[2..], None);
+
+        // wait for expiry and some more just to be safe
+        thread::sleep(Duration::from_secs(2));
+
+        // expired items are ignored
+        let test_data = [
+            ("f", vec![("fore", "span")]),
+            ("fo", vec![("fore", "span")]),
+            ("foo", vec![]),
+            ("for", vec![("fore", "span")]),
+            ("b", vec![("band", "nyoro")]),
+            ("ba", vec![("band", "nyoro")]),
+            ("bar", vec![]),
+            ("ban", vec![("band", "nyoro")]),
+            ("band", vec![("band", "nyoro")]),
+            ("p", vec![("pig", "dan")]),
+            ("pi", vec![("pig", "dan")]),
+            ("pig", vec![("pig", "dan")]),
+            ("pigg", vec![]),
+            ("food", vec![]),
+            ("bandana", vec![]),
+            ("bare", vec![]),
+        ];
+
+        for (term, expected) in test_data {
+            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
+                .into_iter()
+                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
+                .collect();
+            let got = store
+                .search(&str_to_bytes!(term), 0, 0)
+                .unwrap_or_else(|_| panic!("search for {}", term));
+            assert_eq!(&expected, &got);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_works_after_delete() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &to_byte_arrays_vector!(["foo", "bar", "band"]));
+        let test_data = [
+            ("f", vec![("fore", "span")]),
+            ("fo", vec![("fore", "span")]),
+            ("foo", vec![]),
+            ("for", vec![("fore", "span")]),
+            ("b", vec![]),
+            ("ba", vec![]),
+            ("bar", vec![]),
+            ("ban", vec![]),
+            ("band", vec![]),
+            ("p", vec![("pig", "dan")]),
+            ("pi", vec![("pig", "dan")]),
+            ("pig", vec![("pig", "dan")]),
+            ("pigg", vec![]),
+            ("food", vec![]),
+            ("bandana", vec![]),
+            ("bare", vec![]),
+        ];
+
+        for (term, expected) in test_data {
+            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
+                .into_iter()
+                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
+                .collect();
+            let got = store
+                .search(&str_to_bytes!(term), 0, 0)
+                .unwrap_or_else(|_| panic!("search for {}", term));
+            assert_eq!(&expected, &got);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn search_works_after_clear() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+
+        insert_test_data(&mut store, &keys, &values, None);
+        let test_data = [
+            "f", "fo", "foo", "for", "b", "ba", "bar", "ban", "band", "p", "pi", "pig", "pigg",
+            "food", "bandana", "bare",
+        ];
+        store.clear().expect("store cleared");
+        let expected: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+
+        for term in test_data {
+            let got = store
+                .search(&str_to_bytes!(term), 0, 0)
+                .unwrap_or_else(|_| panic!("search for {}", term));
+            assert_eq!(&expected, &got);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn paginated_search_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = to_byte_arrays_vector!(["foo", "fore", "food", "bar", "band", "pig"]);
+        let values = to_byte_arrays_vector!(["eng", "span", "lug", "port", "nyoro", "dan"]);
+
+        insert_test_data(&mut store, &keys, &values, None);
+        let test_data = [
+            (
+                "fo",
+                0,
+                0,
+                vec![("foo", "eng"), ("fore", "span"), ("food", "lug")],
+            ),
+            (
+                "fo",
+                0,
+                8,
+                vec![("foo", "eng"), ("fore", "span"), ("food", "lug")],
+            ),
+            ("fo", 1, 8, vec![("fore", "span"), ("food", "lug")]),
+            ("fo", 1, 0, vec![("fore", "span"), ("food", "lug")]),
+            ("fo", 0, 2, vec![("foo", "eng"), ("fore", "span")]),
+            ("fo", 1, 2, vec![("fore", "span"), ("food", "lug")]),
+            ("fo", 0, 1, vec![("foo", "eng")]),
+            ("fo", 2, 1, vec![("food", "lug")]),
+            ("fo", 1, 1, vec![("fore", "span")]),
+        ];
+
+        for (term, skip, limit, expected) in test_data {
+            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
+                .into_iter()
+                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
+                .collect();
+            let got = store
+                .search(&str_to_bytes!(term), skip, limit)
+                .unwrap_or_else(|_| {
+                    panic!("search for {}, skip: {}, limit: {}", term, skip, limit)
+                });
+            assert_eq!(&expected, &got);
+        }
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn persists_to_file() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store
+            .clear()
+            .expect("store failed to get cleared for some reason");
+        let keys = get_keys();
+        let values = get_values();
+
+        insert_test_data(&mut store, &keys, &values, None);
+
+        // Open new store instance
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let expected_values = wrap_values_in_result(&values);
+        assert_list_eq!(&expected_values, &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn persists_to_file_after_delete() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        let keys_to_delete = keys[2..].to_vec();
+
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys_to_delete);
+
+        // Open new store instance
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let mut expected_values = wrap_values_in_result(&values[..2]);
+        for _ in 0..keys_to_delete.len() {
+            expected_values.push(Ok(None));
+        }
+        assert_list_eq!(&expected_values, &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn persists_to_file_after_clear() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        insert_test_data(&mut store, &keys, &values, None);
+        store.clear().expect("store failed to clear");
+
+        // Open new store instance
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = keys.iter().map(|_| Ok(None)).collect();
+
+        assert_list_eq!(&expected_values, &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn compact_in_steps_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        // many keys so as to have many index blocks
+        let mut store =
+            Store::new(STORE_PATH, Some(100_000), None, None, Some(0), true).expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[2..3].to_vec());
+
+        let db_file_path = store.db_file_path().expect("db file path").to_path_buf();
+        let original_file_size = get_file_size(db_file_path.to_str().unwrap());
+
+        // other operations go on in between the steps
+        let buffer_pool = store.buffer_pool.clone();
+        let search_index = store.search_index.clone();
+        let is_compacting = store.is_compacting.clone();
+        let is_bulk_loading = store.is_bulk_loading.clone();
+        let handle = thread::spawn(move || {
+            compact_in_steps(
+                &buffer_pool,
+                &search_index,
+                &is_compacting,
+                &is_bulk_loading,
+            )
+        });
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[2] = Ok(None);
+        while !handle.is_finished() {
+            let received_values = get_values_for_keys(&mut store, &keys);
+            assert_list_eq!(&expected_values, &received_values);
+        }
+        handle.join().expect("join").expect("compact in steps");
+        assert!(!store.is_compacting());
+
+        let final_file_size = get_file_size(db_file_path.to_str().unwrap());
+        assert_eq!(
+            original_file_size - final_file_size,
+            KeyValueEntry::new(&keys[2], &values[2], 0)
+                .expect("new key value entry")
+                .with_version(1)
+                .size as u64
+        );
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.search(&b"bo"[..], 0, 0).expect("search bo"),
+            vec![(keys[3].clone(), values[3].clone())]
+        );
+        assert_eq!(store.search(&b"yo"[..], 0, 0).expect("search yo"), vec![]);
+        assert_eq!(store.verify().expect("verify"), vec![]);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn compact_in_steps_keeps_writes_made_between_steps() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        // many keys so as to have many index blocks
+        let mut store =
+            Store::new(STORE_PATH, Some(100_000), None, None, Some(0), true).expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+
+        let buffer_pool = store.buffer_pool.clone();
+        let search_index = store.search_index.clone();
+        let is_compacting = store.is_compacting.clone();
+        let is_bulk_loading = store.is_bulk_loading.clone();
+        let handle = thread::spawn(move || {
+            compact_in_steps(
+                &buffer_pool,
+                &search_index,
+                &is_compacting,
+                &is_bulk_loading,
+            )
+        });
+        // new keys are set, and existing ones updated and deleted, in between the steps
+        let mut new_keys = vec![];
+        while !handle.is_finished() {
+            let k = format!("new{}", new_keys.len()).into_bytes();
+            store.set(&k, &b"val"[..], None).expect("set new key");
+            new_keys.push(k);
+            store.set(&keys[0], &values[1], None).expect("update key");
+            store.delete(&keys[1]).expect("delete key");
+            store
+                .set(&keys[1], &values[1], None)
+                .expect("set key again");
+            store.delete(&keys[2]).expect("delete key");
+        }
+        handle.join().expect("join").expect("compact in steps");
+
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(Some(values[1].clone()));
+        expected_values[2] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        for k in &new_keys {
+            assert_eq!(store.get(k).expect("get new key"), Some(b"val".to_vec()));
+        }
+        assert_eq!(
+            store.search(&b"new"[..], 0, 0).expect("search new").len(),
+            new_keys.len()
+        );
+        assert_eq!(
+            store.search(&b"h"[..], 0, 0).expect("search h"),
+            vec![
+                (keys[0].clone(), values[1].clone()),
+                (keys[1].clone(), values[1].clone())
+            ]
+        );
+        assert_eq!(store.search(&b"yo"[..], 0, 0).expect("search yo"), vec![]);
+        assert_eq!(store.verify().expect("verify"), vec![]);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn compact_removes_deleted_and_expired_from_db_file() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+
+        insert_test_data(&mut store, &keys[0..2], &values[0..2], Some(1));
+        insert_test_data(&mut store, &keys[2..], &values[2..], None);
+        delete_keys(&mut store, &keys[2..3].to_vec());
+
+        let db_file_path = store
+            .db_file_path()
+            .expect("db file path")
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        // wait for some keys to expire
+        thread::sleep(Duration::from_secs(2));
+
+        let original_file_size = get_file_size(&db_file_path);
+
+        store.compact().expect("compact store");
+
+        let final_file_size = get_file_size(&db_file_path);
+        let expected_file_size_reduction = keys[0..3]
+            .iter()
+            .zip(&values[0..3])
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .as_bytes()
+                    .len() as u64
+            })
+            .reduce(|accum, v| accum + v)
+            .unwrap();
+
+        assert_eq!(
+            original_file_size - final_file_size,
+            expected_file_size_reduction
+        );
+
+        // And the store is still acting as before
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let received_unchanged_values = &received_values[3..];
+        let received_removed_values = &received_values[0..3];
+
+        // unchanged
+        let expected_unchanged_values = wrap_values_in_result(&values[3..]);
+        let expected_expired_values: Vec<Result<Option<Vec<u8>>>> =
+            keys[0..3].iter().map(|_| Ok(None)).collect();
+
+        assert_list_eq!(&expected_unchanged_values, &received_unchanged_values);
+        assert_list_eq!(&expected_expired_values, &received_removed_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn compact_cancellable_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[2..3].to_vec());
+
+        let db_file_path = store
+            .db_file_path()
+            .expect("db file path")
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let original_file_size = get_file_size(&db_file_path);
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[2] = Ok(None);
+
+        // a cancelled compaction leaves the store as it was, and only its files in the store_path
+        let cancel = AtomicBool::new(true);
+        assert!(!store
+            .compact_cancellable(&cancel)
+            .expect("cancelled compact"));
+        assert!(!store.is_compacting());
+        assert_eq!(get_file_size(&db_file_path), original_file_size);
+        let mut file_names: Vec<_> = fs::read_dir(STORE_PATH)
+            .expect("read store folder")
+            .map(|entry| entry.expect("dir entry").file_name())
+            .collect();
+        file_names.sort();
+        assert_eq!(file_names, vec![DEFAULT_DB_FILE, DEFAULT_SEARCH_INDEX_FILE]);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.search(&b"bo"[..], 0, 0).expect("search bo"),
+            vec![(keys[3].clone(), values[3].clone())]
+        );
+
+        // one that is not cancelled compacts the store just like compact
+        cancel.store(false, Ordering::Release);
+        assert!(store.compact_cancellable(&cancel).expect("compact"));
+        assert_eq!(
+            original_file_size - get_file_size(&db_file_path),
+            KeyValueEntry::new(&keys[2], &values[2], 0)
+                .expect("new key value entry")
+                .with_version(1)
+                .size as u64
+        );
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.search(&b"bo"[..], 0, 0).expect("search bo"),
+            vec![(keys[3].clone(), values[3].clone())]
+        );
+        assert_eq!(store.verify().expect("verify"), vec![]);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn file_path_getters_work() {
+        let store = Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        let store_path = Path::new(STORE_PATH);
+        assert_eq!(
+            store.db_file_path(),
+            Some(store_path.join(DEFAULT_DB_FILE).as_path())
+        );
+        assert_eq!(
+            store.index_file_path(),
+            Some(store_path.join(DEFAULT_SEARCH_INDEX_FILE).as_path())
+        );
+        drop(store);
+
+        let store = Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(store.db_file_path().is_some());
+        assert_eq!(store.index_file_path(), None);
+
+        let store = Store::in_memory(StoreBuilder::new(STORE_PATH).is_search_enabled(true))
+            .expect("create store");
+        assert_eq!(store.db_file_path(), None);
+        assert_eq!(store.index_file_path(), None);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn preallocate_works() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let db_file_path = store.db_file_path().expect("db file path").to_path_buf();
+        let db_file_path = db_file_path.to_str().expect("db file path");
+        let initial_file_size = get_file_size(db_file_path);
+        let keys = get_keys();
+        let values = get_values();
+
+        store.preallocate(4096).expect("preallocate");
+        assert_eq!(get_file_size(db_file_path), initial_file_size + 4096);
+        insert_test_data(&mut store, &keys[..3], &values[..3], None);
+        assert_eq!(get_file_size(db_file_path), initial_file_size + 4096);
+
+        // the end of the last entry is found when the store is reopened
+        drop(store);
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        insert_test_data(&mut store, &keys[3..], &values[3..], None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+        assert_eq!(
+            store.search(&b"o"[..], 0, 0).expect("search o"),
+            vec![
+                (keys[4].clone(), values[4].clone()),
+                (keys[5].clone(), values[5].clone())
+            ]
+        );
+        assert_eq!(store.verify().expect("verify"), vec![]);
+
+        // compaction gives back the unused space
+        store.compact().expect("compact");
+        let entries_size: u64 = keys
+            .iter()
+            .zip(&values)
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .size as u64
+            })
+            .sum();
+        assert_eq!(
+            get_file_size(db_file_path),
+            initial_file_size + entries_size
+        );
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn stale_grace_secs_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .clock(clock.clone())
+            .stale_grace_secs(30)
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store
+            .set(&b"foo"[..], &b"eng"[..], Some(10))
+            .expect("set foo");
+        store.set(&b"bar"[..], &b"port"[..], None).expect("set bar");
+
+        // expired, but within the grace period
+        clock.0.fetch_add(20, Ordering::SeqCst);
+        for _ in 0..2 {
+            assert_eq!(
+                store.get(&b"foo"[..]).expect("get foo"),
+                Some(str_to_bytes!("eng"))
+            );
+            assert_eq!(
+                store.peek(&b"foo"[..]).expect("peek foo"),
+                Some(str_to_bytes!("eng"))
+            );
+            let meta = store
+                .get_meta(&b"foo"[..])
+                .expect("get meta foo")
+                .expect("meta of foo");
+            assert!(meta.is_expired);
+
+            // compaction keeps it
+            store.compact().expect("compact");
+        }
+
+        // past the grace period
+        clock.0.fetch_add(21, Ordering::SeqCst);
+        assert_eq!(store.get(&b"foo"[..]).expect("get foo"), None);
+        assert_eq!(store.peek(&b"foo"[..]).expect("peek foo"), None);
+
+        // compaction reclaims it
+        store.compact().expect("compact");
+        assert_eq!(store.get_meta(&b"foo"[..]).expect("get meta foo"), None);
+        assert_eq!(
+            store.get(&b"bar"[..]).expect("get bar"),
+            Some(str_to_bytes!("port"))
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn stale_grace_secs_applies_to_every_read() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .stale_grace_secs(30)
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store
+            .set(&b"foo"[..], &b"english"[..], Some(10))
+            .expect("set foo");
+
+        // expired, but within the grace period
+        clock.0.fetch_add(20, Ordering::SeqCst);
+        for _ in 0..2 {
+            assert_eq!(
+                store.get_versioned(&b"foo"[..]).expect("get versioned foo"),
+                Some((str_to_bytes!("english"), 1))
+            );
+            assert_eq!(
+                store.get_range(&b"foo"[..], 1, 3).expect("get range foo"),
+                Some(str_to_bytes!("ngl"))
+            );
+            assert_eq!(
+                store.search(&b"fo"[..], 0, 0).expect("search fo"),
+                vec![(str_to_bytes!("foo"), str_to_bytes!("english"))]
+            );
+
+            // rebuilding the search index keeps it
+            assert_eq!(
+                store.rebuild_search_index().expect("rebuild search index"),
+                1
+            );
+        }
+
+        // past the grace period
+        clock.0.fetch_add(21, Ordering::SeqCst);
+        assert_eq!(
+            store.get_versioned(&b"foo"[..]).expect("get versioned foo"),
+            None
+        );
+        assert_eq!(
+            store.get_range(&b"foo"[..], 1, 3).expect("get range foo"),
+            None
+        );
+        assert_eq!(store.search(&b"fo"[..], 0, 0).expect("search fo"), vec![]);
+        assert_eq!(
+            store.rebuild_search_index().expect("rebuild search index"),
+            0
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn get_meta_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        let now = clock.now_secs();
+        insert_test_data(&mut store, &keys[..2], &values[..2], Some(10));
+        insert_test_data(&mut store, &keys[2..4], &values[2..4], None);
+        delete_keys(&mut store, &keys[3..4].to_vec());
+        clock.0.fetch_add(20, Ordering::SeqCst);
+        store
+            .set(&keys[1], &values[1], Some(100))
+            .expect("set again");
+
+        let expected = vec![
+            Some(ValueMeta {
+                data: values[0].clone(),
+                expiry: now + 10,
+                is_deleted: false,
+                is_expired: true,
+            }),
+            Some(ValueMeta {
+                data: values[1].clone(),
+                expiry: now + 120,
+                is_deleted: false,
+                is_expired: false,
+            }),
+            Some(ValueMeta {
+                data: values[2].clone(),
+                expiry: 0,
+                is_deleted: false,
+                is_expired: false,
+            }),
+            Some(ValueMeta {
+                data: values[3].clone(),
+                expiry: 0,
+                is_deleted: true,
+                is_expired: false,
+            }),
+            None,
+            None,
+        ];
+        for (k, expected) in keys.iter().zip(&expected) {
+            assert_eq!(&store.get_meta(k).expect("get meta"), expected);
+        }
+        assert!(matches!(
+            store.get_meta(&b""[..]),
+            Err(ScdbError::InvalidInput(_))
+        ));
+
+        // compaction removes the stale entries from the file
+        store.compact().expect("compact");
+        assert_eq!(store.get_meta(&keys[0]).expect("get meta"), None);
+        assert_eq!(store.get_meta(&keys[3]).expect("get meta"), None);
+        assert_eq!(store.get_meta(&keys[2]).expect("get meta"), expected[2]);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn touch_many_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, Some(10));
+        delete_keys(&mut store, &keys[1..2].to_vec());
+
+        let found = store
+            .touch_many(&[&keys[0][..], &keys[1], &keys[2], b"unknown"], Some(100))
+            .expect("touch many");
+        assert_eq!(found, vec![true, false, true, false]);
+        let found = store.touch_many(&[&keys[3][..]], None).expect("touch many");
+        assert_eq!(found, vec![true]);
+
+        clock.0.fetch_add(50, Ordering::SeqCst);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = vec![
+            Ok(Some(values[0].clone())),
+            Ok(None),
+            Ok(Some(values[2].clone())),
+            Ok(Some(values[3].clone())),
+            Ok(None),
+            Ok(None),
+        ];
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.search(&b"yo"[..], 0, 0).expect("search yo"),
+            vec![(keys[2].clone(), values[2].clone())]
+        );
+
+        clock.0.fetch_add(100, Ordering::SeqCst);
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = vec![
+            Ok(None),
+            Ok(None),
+            Ok(None),
+            Ok(Some(values[3].clone())),
+            Ok(None),
+            Ok(None),
+        ];
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.touch_many(&[&keys[0][..]], None).expect("touch many"),
+            vec![false]
+        );
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn touch_many_rejects_invalid_keys() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .max_key_size(4)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        store
+            .set(&b"foo"[..], &b"eng"[..], Some(10))
+            .expect("set foo");
+
+        for invalid_key in [&b""[..], &b"fooba"[..]] {
+            assert!(matches!(
+                store.touch_many(&[&b"foo"[..], invalid_key], None),
+                Err(ScdbError::InvalidInput(_))
+            ));
+        }
+
+        // none of the keys is touched if any of them is invalid
+        clock.0.fetch_add(11, Ordering::SeqCst);
+        assert_eq!(store.get(&b"foo"[..]).expect("get foo"), None);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn set_works() {
+    #[allow(deprecated)]
+    fn compact_search_index_works() {
         let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
         store.clear().expect("store failed to clear");
         let keys = get_keys();
         let values = get_values();
-
         insert_test_data(&mut store, &keys, &values, None);
-        let received_values = get_values_for_keys(&mut store, &keys);
+        delete_keys(&mut store, &keys[..4].to_vec());
 
-        let expected_values = wrap_values_in_result(&values);
-        assert_list_eq!(&expected_values, &received_values);
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let db_file_path = db_file_path.to_str().unwrap();
+        let idx_file_path = Path::new(STORE_PATH).join(DEFAULT_SEARCH_INDEX_FILE);
+        let idx_file_path = idx_file_path.to_str().unwrap();
+        let initial_db_file_size = get_file_size(db_file_path);
+        let initial_idx_file_size = get_file_size(idx_file_path);
+
+        store.compact_search_index().expect("compact search index");
+
+        assert_eq!(get_file_size(db_file_path), initial_db_file_size);
+        assert!(get_file_size(idx_file_path) < initial_idx_file_size);
+        assert_eq!(
+            store.search(&b"o"[..], 0, 0).expect("search o"),
+            vec![
+                (keys[4].clone(), values[4].clone()),
+                (keys[5].clone(), values[5].clone())
+            ]
+        );
+        assert_eq!(store.search(&b"h"[..], 0, 0).expect("search h"), vec![]);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
 
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(matches!(
+            store.compact_search_index(),
+            Err(ScdbError::SearchDisabled)
+        ));
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn set_with_ttl_works() {
+    fn rebuild_search_index_works() {
         let mut store =
             Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
         store.clear().expect("store failed to clear");
         let keys = get_keys();
         let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+        assert!(matches!(
+            store.rebuild_search_index(),
+            Err(ScdbError::SearchDisabled)
+        ));
+        drop(store);
 
-        insert_test_data(&mut store, &keys[0..2].to_vec(), &values, None);
-        insert_test_data(&mut store, &keys[2..].to_vec(), &values, Some(1)); // 1 second ttl
+        // turning on search for a store created without it gives an empty search index
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        assert_eq!(store.search(&b"o"[..], 0, 0).expect("search o"), vec![]);
 
-        // wait for expiry and some more just to be safe
-        thread::sleep(Duration::from_secs(2));
+        assert_eq!(store.rebuild_search_index().expect("rebuild"), 5);
+        assert_eq!(
+            store.search(&b"h"[..], 0, 0).expect("search h"),
+            vec![(keys[1].clone(), values[1].clone())]
+        );
+        assert_eq!(
+            store.search(&b"o"[..], 0, 0).expect("search o"),
+            vec![
+                (keys[4].clone(), values[4].clone()),
+                (keys[5].clone(), values[5].clone())
+            ]
+        );
 
-        let received_values = get_values_for_keys(&mut store, &keys);
-        let mut expected_values = wrap_values_in_result(&values[..2]);
-        for _ in 2..keys.len() {
-            expected_values.push(Ok(None));
-        }
+        // rebuilding again adds the same keys, without duplicating them
+        assert_eq!(store.rebuild_search_index().expect("rebuild"), 5);
+        assert_eq!(
+            store.search(&b"h"[..], 0, 0).expect("search h"),
+            vec![(keys[1].clone(), values[1].clone())]
+        );
 
-        assert_list_eq!(&expected_values, &received_values);
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn set_can_update() {
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+    fn expired_entries_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
         store.clear().expect("store failed to clear");
         let keys = get_keys();
         let values = get_values();
-        let unchanged_values = values[2..].to_vec();
-        let updated_keys = keys[0..2].to_vec();
-        let updated_values: Vec<Vec<u8>> = values[0..2]
+        let entry_sizes: Vec<u64> = keys
             .iter()
-            .map(|v| v.iter().chain(b"bear").map(|v| v.to_owned()).collect())
+            .zip(&values)
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .size as u64
+            })
             .collect();
+        insert_test_data(&mut store, &keys[..3], &values[..3], None);
+        insert_test_data(&mut store, &keys[3..], &values[3..], Some(10));
+        assert_eq!(store.expired_entries().expect("expired entries"), (0, 0));
 
-        insert_test_data(&mut store, &keys, &values, None);
-        insert_test_data(&mut store, &updated_keys, &updated_values, None);
-        let received_values = get_values_for_keys(&mut store, &keys);
-        let received_unchanged_values = &received_values[2..];
-        let received_updated_values = &received_values[0..2];
+        delete_keys(&mut store, &keys[..1].to_vec());
+        assert_eq!(
+            store.expired_entries().expect("expired entries"),
+            (1, entry_sizes[0])
+        );
 
-        // unchanged
-        let expected_unchanged_values = wrap_values_in_result(&unchanged_values);
-        let expected_updated_values = wrap_values_in_result(&updated_values);
+        clock.0.fetch_add(11, Ordering::SeqCst);
+        assert_eq!(
+            store.expired_entries().expect("expired entries"),
+            (4, entry_sizes[0] + entry_sizes[3..].iter().sum::<u64>())
+        );
 
-        assert_list_eq!(&expected_unchanged_values, &received_unchanged_values);
-        assert_list_eq!(&expected_updated_values, &received_updated_values);
+        store.compact().expect("compact store");
+        assert_eq!(store.expired_entries().expect("expired entries"), (0, 0));
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn delete_works() {
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+    fn compaction_preview_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
         store.clear().expect("store failed to clear");
+        assert_eq!(
+            store.compaction_preview().expect("compaction preview"),
+            CompactionPreview::default()
+        );
+
         let keys = get_keys();
         let values = get_values();
+        let entry_sizes: Vec<u64> = keys
+            .iter()
+            .zip(&values)
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .size as u64
+            })
+            .collect();
+        let total_bytes: u64 = entry_sizes.iter().sum();
+        insert_test_data(&mut store, &keys[..3], &values[..3], None);
+        insert_test_data(&mut store, &keys[3..], &values[3..], Some(10));
+        delete_keys(&mut store, &keys[..1].to_vec());
+        store.set(&keys[1], &values[1], None).expect("update key");
+        clock.0.fetch_add(11, Ordering::SeqCst);
+
+        let db_file_path = store.db_file_path().expect("db file path").to_path_buf();
+        let file_size = get_file_size(db_file_path.to_str().unwrap());
+        let preview = store.compaction_preview().expect("compaction preview");
+        assert_eq!(
+            preview,
+            CompactionPreview {
+                total_bytes: total_bytes + entry_sizes[1],
+                live_entries: 2,
+                live_bytes: entry_sizes[1] + entry_sizes[2],
+                expired_entries: 3,
+                expired_bytes: entry_sizes[3..].iter().sum(),
+                deleted_entries: 1,
+                deleted_bytes: entry_sizes[0],
+            }
+        );
+        assert_eq!(preview.overwritten_bytes(), entry_sizes[1]);
+        // nothing is changed by the preview
+        assert_eq!(get_file_size(db_file_path.to_str().unwrap()), file_size);
+        assert_eq!(
+            store.get(&keys[1]).expect("get key"),
+            Some(values[1].clone())
+        );
 
-        let keys_to_delete = keys[2..].to_vec();
+        // compaction reclaims exactly what was previewed
+        store.compact().expect("compact store");
+        assert_eq!(
+            get_file_size(db_file_path.to_str().unwrap()),
+            file_size - preview.reclaimable_bytes()
+        );
+        let preview = store.compaction_preview().expect("compaction preview");
+        assert_eq!(preview.reclaimable_bytes(), 0);
+        assert_eq!(preview.live_entries, 2);
 
-        insert_test_data(&mut store, &keys, &values, None);
-        delete_keys(&mut store, &keys_to_delete);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
 
+    #[test]
+    #[serial]
+    fn drain_expired_works() {
+        let clock = Arc::new(MockClock(AtomicU64::new(get_current_timestamp())));
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .clock(clock.clone())
+            .build()
+            .expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys[..3], &values[..3], None);
+        insert_test_data(&mut store, &keys[3..], &values[3..], Some(10));
+        delete_keys(&mut store, &keys[..1].to_vec());
+        assert_eq!(store.drain_expired().expect("drain expired"), 0);
+
+        clock.0.fetch_add(11, Ordering::SeqCst);
+        let db_file_path = store.db_file_path().expect("db file path").to_path_buf();
+        let original_file_size = get_file_size(db_file_path.to_str().unwrap());
+        assert_eq!(store.drain_expired().expect("drain expired"), 3);
+
+        // only the deleted key is still pointed to by the index
+        let deleted_size = KeyValueEntry::new(&keys[0], &values[0], 0)
+            .expect("new key value entry")
+            .with_version(1)
+            .size as u64;
+        assert_eq!(
+            store.expired_entries().expect("expired entries"),
+            (1, deleted_size)
+        );
+        assert_eq!(
+            get_file_size(db_file_path.to_str().unwrap()),
+            original_file_size
+        );
         let received_values = get_values_for_keys(&mut store, &keys);
-        let mut expected_values = wrap_values_in_result(&values[..2]);
-        for _ in 0..keys_to_delete.len() {
-            expected_values.push(Ok(None));
-        }
+        let mut expected_values: Vec<Result<Option<Vec<u8>>>> =
+            keys.iter().map(|_| Ok(None)).collect();
+        expected_values[1] = Ok(Some(values[1].clone()));
+        expected_values[2] = Ok(Some(values[2].clone()));
         assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(store.search(&b"o"[..], 0, 0).expect("search o"), vec![]);
+        assert_eq!(store.verify().expect("verify"), vec![]);
+        assert_eq!(store.drain_expired().expect("drain expired"), 0);
+
+        // the drained keys can be set again
+        insert_test_data(&mut store, &keys[3..], &values[3..], None);
+        let received_values = get_values_for_keys(&mut store, &keys[3..].to_vec());
+        assert_list_eq!(&wrap_values_in_result(&values[3..]), &received_values);
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn clear_works() {
+    fn is_compacting_works() {
         let mut store =
             Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
         store.clear().expect("store failed to clear");
         let keys = get_keys();
         let values = get_values();
-
         insert_test_data(&mut store, &keys, &values, None);
-        store.clear().expect("store cleared");
+        delete_keys(&mut store, &keys[..1].to_vec());
+        assert!(!store.is_compacting());
+
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let db_file_path = db_file_path.to_str().unwrap();
+        let original_file_size = get_file_size(db_file_path);
+
+        // as though the background task were midway through a compaction
+        let is_compacting = store.is_compacting.clone();
+        let compaction_guard =
+            CompactionGuard::try_start(&is_compacting).expect("start compaction");
+        assert!(store.is_compacting());
+        assert!(CompactionGuard::try_start(&store.is_compacting).is_none());
+        store.compact().expect("compact");
+        assert!(!store.compact_if_needed(1).expect("compact if needed"));
+        compact_in_steps(
+            &store.buffer_pool,
+            &store.search_index,
+            &store.is_compacting,
+            &store.is_bulk_loading,
+        )
+        .expect("compact in steps");
+        assert_eq!(get_file_size(db_file_path), original_file_size);
+        assert!(store.is_compacting());
 
-        let received_values = get_values_for_keys(&mut store, &keys);
-        let expected_values: Vec<io::Result<Option<Vec<u8>>>> =
-            keys.iter().map(|_| Ok(None)).collect();
-        assert_list_eq!(&expected_values, &received_values);
+        drop(compaction_guard);
+        assert!(!store.is_compacting());
+        store.compact().expect("compact");
+        assert!(!store.is_compacting());
+        assert!(get_file_size(db_file_path) < original_file_size);
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn search_errs_when_disabled() {
+    fn compact_if_needed_works() {
         let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
         store.clear().expect("store failed to clear");
-        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
-        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
-
+        let keys = get_keys();
+        let values = get_values();
+        let entry_sizes: Vec<u64> = keys
+            .iter()
+            .zip(&values)
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .size as u64
+            })
+            .collect();
         insert_test_data(&mut store, &keys, &values, None);
-        assert!(store.search(&b"f".to_vec(), 0, 0).is_err());
+
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let db_file_path = db_file_path.to_str().unwrap();
+        let initial_file_size = get_file_size(db_file_path);
+
+        // nothing to reclaim
+        assert!(!store.compact_if_needed(1).expect("compact if needed"));
+
+        // update the first key, delete the second
+        insert_test_data(&mut store, &keys[..1], &values[..1], None);
+        delete_keys(&mut store, &keys[1..2].to_vec());
+        let reclaimable = entry_sizes[0] + entry_sizes[1];
+
+        assert!(!store
+            .compact_if_needed(reclaimable + 1)
+            .expect("compact if needed"));
+        assert_eq!(
+            get_file_size(db_file_path),
+            initial_file_size + entry_sizes[0]
+        );
+
+        assert!(store
+            .compact_if_needed(reclaimable)
+            .expect("compact if needed"));
+        assert_eq!(
+            get_file_size(db_file_path),
+            initial_file_size - entry_sizes[1]
+        );
+
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[1] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+
+        // everything was reclaimed
+        assert!(!store.compact_if_needed(1).expect("compact if needed"));
+
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn search_works() {
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
-        store.clear().expect("store failed to clear");
-        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
-        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+    fn compact_on_open_works() {
+        let keys = get_keys();
+        let values = get_values();
+        let deleted_size: u64 = keys[..2]
+            .iter()
+            .zip(&values)
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .size as u64
+            })
+            .sum();
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let db_file_path = db_file_path.to_str().unwrap();
+        {
+            let mut store =
+                Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+            store.clear().expect("store failed to clear");
+            insert_test_data(&mut store, &keys, &values, None);
+            delete_keys(&mut store, &keys[..2].to_vec());
+        }
+        let initial_file_size = get_file_size(db_file_path);
+
+        // without the flag, nothing is reclaimed
+        let store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .build()
+            .expect("reopen store");
+        drop(store);
+        assert_eq!(get_file_size(db_file_path), initial_file_size);
+
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .compact_on_open(true)
+            .build()
+            .expect("reopen store");
+        assert_eq!(
+            get_file_size(db_file_path),
+            initial_file_size - deleted_size
+        );
 
-        insert_test_data(&mut store, &keys, &values, None);
-        let test_data = [
-            ("f", vec![("foo", "eng"), ("fore", "span")]),
-            ("fo", vec![("foo", "eng"), ("fore", "span")]),
-            ("foo", vec![("foo", "eng")]),
-            ("for", vec![("fore", "span")]),
-            ("b", vec![("bar", "port"), ("band", "nyoro")]),
-            ("ba", vec![("bar", "port"), ("band", "nyoro")]),
-            ("bar", vec![("bar", "port")]),
-            ("ban", vec![("band", "nyoro")]),
-            ("band", vec![("band", "nyoro")]),
-            ("p", vec![("pig", "dan")]),
-            ("pi", vec![("pig", "dan")]),
-            ("pig", vec![("pig", "dan")]),
-            ("pigg", vec![]),
-            ("food", vec![]),
-            ("bandana", vec![]),
-            ("bare", vec![]),
-        ];
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        expected_values[1] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.search(&b"yo"[..], 0, 0).expect("search yo"),
+            vec![(keys[2].clone(), values[2].clone())]
+        );
 
-        for (term, expected) in test_data {
-            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
-                .into_iter()
-                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
-                .collect();
-            let got = store
-                .search(&str_to_bytes!(term), 0, 0)
-                .expect(&format!("search for {}", term));
-            assert_eq!(&expected, &got);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn compact_if_needed_walks_index_for_reopened_store() {
+        let keys = get_keys();
+        let values = get_values();
+        let deleted_size = KeyValueEntry::new(&keys[0], &values[0], 0)
+            .expect("new key value entry")
+            .with_version(1)
+            .size as u64;
+        {
+            let mut store =
+                Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+            store.clear().expect("store failed to clear");
+            insert_test_data(&mut store, &keys, &values, None);
+            delete_keys(&mut store, &keys[..1].to_vec());
         }
 
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(!store
+            .compact_if_needed(deleted_size + 1)
+            .expect("compact if needed"));
+        assert!(store
+            .compact_if_needed(deleted_size)
+            .expect("compact if needed"));
+
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn search_works_after_expire() {
+    fn compact_removes_expired_from_search_index_file() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
         let mut store =
             Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
         store.clear().expect("store failed to clear");
         let keys = to_byte_arrays_vector!(["foo", "bar", "fore", "band", "pig"]);
         let values = to_byte_arrays_vector!(["eng", "port", "span", "nyoro", "dan"]);
 
-        insert_test_data(&mut store, &keys.to_vec(), &values.to_vec(), Some(1));
-        insert_test_data(&mut store, &keys[2..].to_vec(), &values[2..].to_vec(), None);
+        insert_test_data(&mut store, &keys[0..2], &values[0..2], Some(1));
+        insert_test_data(&mut store, &keys[2..], &values[2..], None);
 
-        // wait for expiry and some more just to be safe
+        let search_index_file_path = store
+            .index_file_path()
+            .expect("index file path")
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        // wait for some keys to expire
         thread::sleep(Duration::from_secs(2));
 
-        // expired items are ignored
-        let test_data = [
-            ("f", vec![("fore", "span")]),
-            ("fo", vec![("fore", "span")]),
-            ("foo", vec![]),
-            ("for", vec![("fore", "span")]),
-            ("b", vec![("band", "nyoro")]),
-            ("ba", vec![("band", "nyoro")]),
-            ("bar", vec![]),
-            ("ban", vec![("band", "nyoro")]),
-            ("band", vec![("band", "nyoro")]),
-            ("p", vec![("pig", "dan")]),
-            ("pi", vec![("pig", "dan")]),
-            ("pig", vec![("pig", "dan")]),
-            ("pigg", vec![]),
-            ("food", vec![]),
-            ("bandana", vec![]),
-            ("bare", vec![]),
-        ];
+        let original_file_size = get_file_size(&search_index_file_path);
 
-        for (term, expected) in test_data {
-            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
-                .into_iter()
-                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
-                .collect();
-            let got = store
-                .search(&str_to_bytes!(term), 0, 0)
-                .expect(&format!("search for {}", term));
-            assert_eq!(&expected, &got);
-        }
+        store.compact().expect("compact store");
 
-        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
-    }
+        let final_file_size = get_file_size(&search_index_file_path);
+        let expected_file_size_reduction = 282u64;
 
-    #[test]
-    #[serial]
-    fn search_works_after_delete() {
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
-        store.clear().expect("store failed to clear");
-        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
-        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+        assert_eq!(
+            original_file_size - final_file_size,
+            expected_file_size_reduction
+        );
 
-        insert_test_data(&mut store, &keys, &values, None);
-        delete_keys(&mut store, &to_byte_arrays_vector!(["foo", "bar", "band"]));
+        // And the search is still acting as before, with the expired not showing up
+        // expired items are ignored
         let test_data = [
             ("f", vec![("fore", "span")]),
             ("fo", vec![("fore", "span")]),
             ("foo", vec![]),
             ("for", vec![("fore", "span")]),
-            ("b", vec![]),
-            ("ba", vec![]),
+            ("b", vec![("band", "nyoro")]),
+            ("ba", vec![("band", "nyoro")]),
             ("bar", vec![]),
-            ("ban", vec![]),
-            ("band", vec![]),
+            ("ban", vec![("band", "nyoro")]),
+            ("band", vec![("band", "nyoro")]),
             ("p", vec![("pig", "dan")]),
             ("pi", vec![("pig", "dan")]),
             ("pig", vec![("pig", "dan")]),
@@ -891,9 +8365,10 @@ mod tests {
                 .into_iter()
                 .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
                 .collect();
+            // Compaction of db file moves the addresses around, therefore it must also update the inverted db!
             let got = store
                 .search(&str_to_bytes!(term), 0, 0)
-                .expect(&format!("search for {}", term));
+                .unwrap_or_else(|_| panic!("search for {}", term));
             assert_eq!(&expected, &got);
         }
 
@@ -902,152 +8377,570 @@ mod tests {
 
     #[test]
     #[serial]
-    fn search_works_after_clear() {
+    fn background_task_compacts_db_file() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        // set the compaction interval to 1 second
         let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+            Store::new(STORE_PATH, None, None, None, Some(1), false).expect("create store");
         store.clear().expect("store failed to clear");
-        let keys = to_byte_arrays_vector!(["foo", "fore", "bar", "band", "pig"]);
-        let values = to_byte_arrays_vector!(["eng", "span", "port", "nyoro", "dan"]);
+        let keys = get_keys();
+        let values = get_values();
 
-        insert_test_data(&mut store, &keys, &values, None);
-        let test_data = [
-            "f", "fo", "foo", "for", "b", "ba", "bar", "ban", "band", "p", "pi", "pig", "pigg",
-            "food", "bandana", "bare",
-        ];
-        store.clear().expect("store cleared");
-        let expected: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+        insert_test_data(&mut store, &keys[0..2], &values[0..2], Some(1));
+        insert_test_data(&mut store, &keys[2..], &values[2..], None);
+        delete_keys(&mut store, &keys[2..3].to_vec());
 
-        for term in test_data {
-            let got = store
-                .search(&str_to_bytes!(term), 0, 0)
-                .expect(&format!("search for {}", term));
-            assert_eq!(&expected, &got);
-        }
+        let db_file_path = store
+            .db_file_path()
+            .expect("db file path")
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        let original_file_size = get_file_size(&db_file_path);
+
+        // wait for some keys to expire
+        thread::sleep(Duration::from_secs(4));
+
+        let final_file_size = get_file_size(&db_file_path);
+        let expected_file_size_reduction = keys[0..3]
+            .iter()
+            .zip(&values[0..3])
+            .map(|(k, v)| {
+                KeyValueEntry::new(k, v, 0)
+                    .expect("new key value entry")
+                    .with_version(1)
+                    .as_bytes()
+                    .len() as u64
+            })
+            .reduce(|accum, v| accum + v)
+            .unwrap();
+
+        assert_eq!(
+            original_file_size - final_file_size,
+            expected_file_size_reduction
+        );
+
+        // And the store is still acting as before
+        let received_values = get_values_for_keys(&mut store, &keys);
+        let received_unchanged_values = &received_values[3..];
+        let received_removed_values = &received_values[0..3];
+
+        // unchanged
+        let expected_unchanged_values = wrap_values_in_result(&values[3..]);
+        let expected_expired_values: Vec<Result<Option<Vec<u8>>>> =
+            keys[0..3].iter().map(|_| Ok(None)).collect();
+
+        assert_list_eq!(&expected_unchanged_values, &received_unchanged_values);
+        assert_list_eq!(&expected_expired_values, &received_removed_values);
+
+        // ensure background tasks stop running
+        drop(store);
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn paginated_search_works() {
+    fn background_task_skips_ticks_while_compacting() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        // set the compaction interval to 1 second
         let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+            Store::new(STORE_PATH, None, None, None, Some(1), false).expect("create store");
         store.clear().expect("store failed to clear");
-        let keys = to_byte_arrays_vector!(["foo", "fore", "food", "bar", "band", "pig"]);
-        let values = to_byte_arrays_vector!(["eng", "span", "lug", "port", "nyoro", "dan"]);
-
+        let keys = get_keys();
+        let values = get_values();
         insert_test_data(&mut store, &keys, &values, None);
-        let test_data = [
-            (
-                "fo",
-                0,
-                0,
-                vec![("foo", "eng"), ("fore", "span"), ("food", "lug")],
-            ),
-            (
-                "fo",
-                0,
-                8,
-                vec![("foo", "eng"), ("fore", "span"), ("food", "lug")],
-            ),
-            ("fo", 1, 8, vec![("fore", "span"), ("food", "lug")]),
-            ("fo", 1, 0, vec![("fore", "span"), ("food", "lug")]),
-            ("fo", 0, 2, vec![("foo", "eng"), ("fore", "span")]),
-            ("fo", 1, 2, vec![("fore", "span"), ("food", "lug")]),
-            ("fo", 0, 1, vec![("foo", "eng")]),
-            ("fo", 2, 1, vec![("food", "lug")]),
-            ("fo", 1, 1, vec![("fore", "span")]),
-        ];
+        delete_keys(&mut store, &keys[..1].to_vec());
+
+        let db_file_path = store
+            .db_file_path()
+            .expect("db file path")
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let original_file_size = get_file_size(&db_file_path);
 
-        for (term, skip, limit, expected) in test_data {
-            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
-                .into_iter()
-                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
-                .collect();
-            let got = store
-                .search(&str_to_bytes!(term), skip, limit)
-                .expect(&format!(
-                    "search for {}, skip: {}, limit: {}",
-                    term, skip, limit
-                ));
-            assert_eq!(&expected, &got);
-        }
+        // a compaction taking far longer than the compaction interval
+        let is_compacting = store.is_compacting.clone();
+        let slow_compaction = thread::spawn(move || {
+            let _compaction_guard =
+                CompactionGuard::try_start(&is_compacting).expect("start compaction");
+            thread::sleep(Duration::from_secs(3));
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert!(store.is_compacting());
+
+        // the ticks in the meantime are skipped, rather than queued up
+        thread::sleep(Duration::from_millis(2500));
+        assert_eq!(get_file_size(&db_file_path), original_file_size);
+        slow_compaction.join().expect("join slow compaction");
+        assert!(!store.is_compacting());
+        assert_eq!(get_file_size(&db_file_path), original_file_size);
+
+        // the ticks after it compact the file as usual
+        thread::sleep(Duration::from_secs(2));
+        let deleted_size = KeyValueEntry::new(&keys[0], &values[0], 0)
+            .expect("new key value entry")
+            .with_version(1)
+            .size as u64;
+        assert_eq!(
+            original_file_size - get_file_size(&db_file_path),
+            deleted_size
+        );
+        let received_values = get_values_for_keys(&mut store, &keys[1..].to_vec());
+        assert_list_eq!(&wrap_values_in_result(&values[1..]), &received_values);
+
+        // ensure background tasks stop running
+        drop(store);
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn persists_to_file() {
+    fn set_compaction_interval_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
         let mut store =
             Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        assert!(store.scheduler.is_none());
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let db_file_path = db_file_path.to_str().unwrap();
+        let entry_size = |i: usize| {
+            KeyValueEntry::new(&keys[i], &values[i], 0)
+                .expect("new key value entry")
+                .with_version(1)
+                .size as u64
+        };
+
+        // turning it on compacts the db file in the background
+        delete_keys(&mut store, &keys[0..1].to_vec());
+        let original_file_size = get_file_size(db_file_path);
         store
-            .clear()
-            .expect("store failed to get cleared for some reason");
+            .set_compaction_interval(1)
+            .expect("set compaction interval");
+        assert!(store.scheduler.is_some());
+        thread::sleep(Duration::from_secs(3));
+        assert_eq!(
+            get_file_size(db_file_path),
+            original_file_size - entry_size(0)
+        );
+
+        // turning it off stops the background compaction
+        store
+            .set_compaction_interval(0)
+            .expect("set compaction interval");
+        assert!(store.scheduler.is_none());
+        delete_keys(&mut store, &keys[1..2].to_vec());
+        let original_file_size = get_file_size(db_file_path);
+        thread::sleep(Duration::from_secs(3));
+        assert_eq!(get_file_size(db_file_path), original_file_size);
+
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        expected_values[1] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn new_rejects_non_scdb_files() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+        fs::create_dir_all(STORE_PATH).expect("create store folder");
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        fs::write(&db_file_path, "not an scdb file. ".repeat(10)).expect("write file");
+
+        let got = Store::new(STORE_PATH, None, None, None, Some(0), false);
+        assert!(matches!(
+            got,
+            Err(ScdbError::Corruption(msg)) if msg.contains("expected \"Scdb versn 0.002\"")
+        ));
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn custom_hasher_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
         let keys = get_keys();
         let values = get_values();
+        let open_store = |hasher: Arc<dyn KeyHasher>| {
+            StoreBuilder::new(STORE_PATH)
+                .compaction_interval(0)
+                .hasher(hasher)
+                .build()
+        };
 
+        let mut store = open_store(Arc::new(ReverseHasher)).expect("create store");
         insert_test_data(&mut store, &keys, &values, None);
+        assert_eq!(store.header.hasher_id, 5);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+        drop(store);
 
-        // Open new store instance
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        // a mismatched hasher would lose the keys so it is rejected
+        let err = open_store(Arc::new(Xxh3Hasher)).expect_err("hasher mismatch");
+        assert!(matches!(err, ScdbError::InvalidInput(_)));
 
+        let mut store = open_store(Arc::new(ReverseHasher)).expect("reopen store");
         let received_values = get_values_for_keys(&mut store, &keys);
-        let expected_values = wrap_values_in_result(&values);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+
+        // a store with no keys can be opened with any hasher
+        store.clear().expect("clear store");
+        drop(store);
+        let store = open_store(Arc::new(Xxh3Hasher)).expect("open cleared store");
+        assert_eq!(store.header.hasher_id, 0);
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn auto_grow_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+        let keys = get_keys();
+        let values = get_values();
+        // all keys collide as they are placed in the same slot
+        let builder = StoreBuilder::new(STORE_PATH)
+            .max_keys(keys.len() as u64)
+            .redundant_blocks(0)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .hasher(Arc::new(ConstantHasher));
+
+        let mut store = builder.clone().build().expect("create store");
+        store
+            .set(&keys[0], &values[0], None)
+            .expect("set first key");
+        let err = store
+            .set(&keys[1], &values[1], None)
+            .expect_err("collision saturated");
+        assert!(matches!(err, ScdbError::CollisionSaturated(k) if k == keys[1]));
+        drop(store);
+
+        let mut store = builder
+            .clone()
+            .auto_grow(true)
+            .build()
+            .expect("reopen store");
+        store.delete(&keys[0]).expect("delete first key");
+        insert_test_data(&mut store, &keys, &values, None);
+        assert_eq!(store.header.redundant_blocks, keys.len() as u16 - 1);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+        assert_eq!(
+            store.search(&keys[1], 0, 0).expect("search"),
+            vec![(keys[1].clone(), values[1].clone())]
+        );
+        drop(store);
+
+        // the grown geometry is persisted
+        let mut store = builder.build().expect("reopen store");
+        assert_eq!(store.header.redundant_blocks, keys.len() as u16 - 1);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn probing_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+        let keys = get_keys();
+        let values = get_values();
+        // all keys collide as they are placed in the same slot
+        let builder = StoreBuilder::new(STORE_PATH)
+            .max_keys(keys.len() as u64)
+            .redundant_blocks(0)
+            .compaction_interval(0)
+            .hasher(Arc::new(ConstantHasher));
+        let count_inserted = |store: &mut Store| {
+            keys.iter()
+                .zip(&values)
+                .filter(|(k, v)| store.set(k, v, None).is_ok())
+                .count()
+        };
+
+        let mut store = builder.clone().build().expect("create store");
+        assert_eq!(count_inserted(&mut store), 1);
+        let key_values_start_point = store.header.key_values_start_point;
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+
+        let probes = keys.len() as u16 - 1;
+        for probing in [Probing::Linear(probes), Probing::Quadratic(probes)] {
+            let mut store = builder
+                .clone()
+                .probing(probing)
+                .build()
+                .expect("create store");
+            assert_eq!(count_inserted(&mut store), keys.len());
+            // the index is no bigger than without probing
+            assert_eq!(store.header.key_values_start_point, key_values_start_point);
+            store.delete(&keys[0]).expect("delete first key");
+            store.compact().expect("compact");
+            assert_eq!(store.verify().expect("verify"), vec![]);
+            drop(store);
+
+            // the probing is persisted, whatever probing the store is reopened with
+            let mut store = builder.clone().build().expect("reopen store");
+            assert_eq!(store.header.probing, probing);
+            assert_eq!(store.get(&keys[0]).expect("get first key"), None);
+            let received_values = get_values_for_keys(&mut store, &keys[1..].to_vec());
+            assert_list_eq!(&wrap_values_in_result(&values[1..]), &received_values);
+
+            // a cleared store keeps its probing too
+            store.clear().expect("clear");
+            assert_eq!(count_inserted(&mut store), keys.len());
+
+            drop(store);
+            fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn custom_file_names_work() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+        let keys = get_keys();
+        let values = get_values();
+        let builder = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_search_enabled(true)
+            .is_durable(true);
+
+        // stores with different file names can share a folder
+        let mut users = builder
+            .clone()
+            .db_file_name("users.scdb")
+            .index_file_name("users.iscdb")
+            .build()
+            .expect("create users store");
+        let mut sessions = builder
+            .clone()
+            .db_file_name("sessions.scdb")
+            .index_file_name("sessions.iscdb")
+            .build()
+            .expect("create sessions store");
+        let store_path = Path::new(STORE_PATH);
+        assert_eq!(
+            users.db_file_path(),
+            Some(store_path.join("users.scdb").as_path())
+        );
+        assert_eq!(
+            users.index_file_path(),
+            Some(store_path.join("users.iscdb").as_path())
+        );
+        for file_name in [
+            "users.wal",
+            "sessions.scdb",
+            "sessions.iscdb",
+            "sessions.wal",
+        ] {
+            assert!(store_path.join(file_name).exists(), "{}", file_name);
+        }
+
+        insert_test_data(&mut users, &keys, &values, None);
+        insert_test_data(&mut sessions, &keys[..3], &values[3..], None);
+        delete_keys(&mut sessions, &keys[..1].to_vec());
+        users.compact().expect("compact users");
+        sessions.compact().expect("compact sessions");
+
+        let received_values = get_values_for_keys(&mut users, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+        let received_values = get_values_for_keys(&mut sessions, &keys);
+        let mut expected_values: Vec<Result<Option<Vec<u8>>>> =
+            keys.iter().map(|_| Ok(None)).collect();
+        expected_values[1] = Ok(Some(values[4].clone()));
+        expected_values[2] = Ok(Some(values[5].clone()));
         assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            sessions.search(&b"h"[..], 0, 0).expect("search h"),
+            vec![(keys[1].clone(), values[4].clone())]
+        );
+        assert!(!store_path.join(DEFAULT_DB_FILE).exists());
+        drop(users);
+        drop(sessions);
+
+        let invalid_builders = [
+            builder.clone().db_file_name(""),
+            builder.clone().index_file_name(""),
+            builder.clone().db_file_name("x").index_file_name("x"),
+            builder.clone().db_file_name("dump.wal"),
+        ];
+        for builder in invalid_builders {
+            assert!(matches!(builder.build(), Err(ScdbError::InvalidInput(_))));
+        }
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn persists_to_file_after_delete() {
+    fn store_path_that_is_a_file_fails() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+        fs::write(STORE_PATH, b"not a folder").expect("create file at store path");
+
+        let got = Store::new(STORE_PATH, None, None, None, Some(0), false);
+        match got {
+            Err(ScdbError::InvalidInput(msg)) => {
+                assert!(msg.contains("exists and is not a directory"), "{}", msg)
+            }
+            other => panic!("expected InvalidInput, got {:?}", other.map(|_| ())),
+        }
+        // the file is left untouched
+        assert_eq!(
+            fs::read(STORE_PATH).expect("read file"),
+            b"not a folder".to_vec()
+        );
+
+        fs::remove_file(STORE_PATH).expect("delete file at store path");
+    }
+
+    #[test]
+    #[serial]
+    fn open_existing_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
+
+        // a fresh path is not turned into a new store
+        let got = Store::open_existing(STORE_PATH, None, None, None, Some(0), true);
+        match got {
+            Err(ScdbError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            other => panic!("expected a NotFound error, got {:?}", other.map(|_| ())),
+        }
+        assert!(!Path::new(STORE_PATH).exists());
+        let got = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .must_exist(true)
+            .build();
+        assert!(matches!(got, Err(e) if e.kind() == io::ErrorKind::NotFound));
+        assert!(!Path::new(STORE_PATH).exists());
+
         let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
-        store.clear().expect("store failed to clear");
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+        drop(store);
+
+        let mut store = Store::open_existing(STORE_PATH, None, None, None, Some(0), true)
+            .expect("open existing store");
+        assert_eq!(
+            store.get(&b"foo"[..]).expect("get foo"),
+            Some(b"bar".to_vec())
+        );
+        drop(store);
+
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn durable_store_works() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
         let keys = get_keys();
         let values = get_values();
+        let wal_file_path = Path::new(STORE_PATH)
+            .join(DEFAULT_DB_FILE)
+            .with_extension(WAL_FILE_EXTENSION);
+
+        let mut store = StoreBuilder::new(STORE_PATH)
+            .compaction_interval(0)
+            .is_durable(true)
+            .build()
+            .expect("create store");
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+        assert_eq!(get_file_size(wal_file_path.to_str().unwrap()), 0);
 
-        let keys_to_delete = keys[2..].to_vec();
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        let header = store.header.clone();
+        drop(store);
 
-        insert_test_data(&mut store, &keys, &values, None);
-        delete_keys(&mut store, &keys_to_delete);
+        // log the writes for setting a key, as though the store crashed before making them
+        let (k, v) = (b"crashed".to_vec(), b"before writing".to_vec());
+        let kv_bytes = KeyValueEntry::new(&k, &v, 0)
+            .expect("new key value entry")
+            .as_bytes();
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let kv_address = get_file_size(db_file_path.to_str().unwrap());
+        let mut wal = WriteAheadLog::new(&wal_file_path).expect("open wal");
+        wal.log(&[
+            (kv_address, &kv_bytes),
+            (header.get_index_offset(&k), &kv_address.to_be_bytes()),
+        ])
+        .expect("log writes");
+        drop(wal);
 
-        // Open new store instance
+        // the writes are made on opening the store, even if it is no longer durable
         let mut store =
             Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
-
+        assert_eq!(store.get(&k).expect("get"), Some(v));
         let received_values = get_values_for_keys(&mut store, &keys);
-        let mut expected_values = wrap_values_in_result(&values[..2]);
-        for _ in 0..keys_to_delete.len() {
-            expected_values.push(Ok(None));
-        }
         assert_list_eq!(&expected_values, &received_values);
+        assert!(!wal_file_path.exists());
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn persists_to_file_after_clear() {
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
-        store.clear().expect("store failed to clear");
+    fn durable_store_keeps_bulk_loaded_keys() {
+        // pre-clean up for the right results
+        fs::remove_dir_all(STORE_PATH).ok();
         let keys = get_keys();
         let values = get_values();
+        let wal_file_path = Path::new(STORE_PATH)
+            .join(DEFAULT_DB_FILE)
+            .with_extension(WAL_FILE_EXTENSION);
+        let build_store = || {
+            StoreBuilder::new(STORE_PATH)
+                .compaction_interval(0)
+                .is_durable(true)
+                .build()
+                .expect("create store")
+        };
 
-        insert_test_data(&mut store, &keys, &values, None);
-        store.clear().expect("store failed to clear");
-
-        // Open new store instance
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        let mut store = build_store();
+        let mut loader = store.bulk_loader().expect("bulk loader");
+        for (k, v) in keys.iter().zip(&values) {
+            loader.add(k, v, 0).expect("add to loader");
+        }
+        assert_eq!(loader.finish().expect("finish load"), keys.len() as u64);
+        assert_eq!(get_file_size(wal_file_path.to_str().unwrap()), 0);
+        drop(store);
 
+        let mut store = build_store();
+        let expected_values = wrap_values_in_result(&values);
         let received_values = get_values_for_keys(&mut store, &keys);
-        let expected_values: Vec<io::Result<Option<Vec<u8>>>> =
-            keys.iter().map(|_| Ok(None)).collect();
-
         assert_list_eq!(&expected_values, &received_values);
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
@@ -1055,200 +8948,190 @@ mod tests {
 
     #[test]
     #[serial]
-    fn compact_removes_deleted_and_expired_from_db_file() {
+    fn from_files_works() {
         // pre-clean up for the right results
         fs::remove_dir_all(STORE_PATH).ok();
-
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
-        store.clear().expect("store failed to clear");
+        fs::create_dir_all(STORE_PATH).expect("create store folder");
+        let db_file_path = Path::new(STORE_PATH).join(DEFAULT_DB_FILE);
+        let idx_file_path = Path::new(STORE_PATH).join(DEFAULT_SEARCH_INDEX_FILE);
+        let open_file = |path: &Path| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .expect("open file")
+        };
+        let config = StoreBuilder::new("")
+            .compaction_interval(0)
+            .is_search_enabled(true);
         let keys = get_keys();
         let values = get_values();
 
-        insert_test_data(
-            &mut store,
-            &keys[0..2].to_vec(),
-            &values[0..2].to_vec(),
-            Some(1),
-        );
-        insert_test_data(&mut store, &keys[2..].to_vec(), &values[2..].to_vec(), None);
-        delete_keys(&mut store, &keys[2..3].to_vec());
+        let mut store = Store::from_files(
+            open_file(&db_file_path),
+            open_file(&idx_file_path),
+            config.clone(),
+        )
+        .expect("create store");
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
+        store.compact().expect("compact");
+        drop(store);
 
-        let buffer_pool = acquire_lock!(store.buffer_pool).expect("acquire lock on buffer pool");
-        let db_file_path = buffer_pool.file_path.to_str().unwrap().to_owned();
-        drop(buffer_pool);
+        // the data persists in the files, and is readable by a path-based store
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.search(&keys[1], 0, 0).expect("search"),
+            vec![(keys[1].clone(), values[1].clone())]
+        );
 
-        // wait for some keys to expire
-        thread::sleep(Duration::from_secs(2));
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
 
-        let original_file_size = get_file_size(&db_file_path);
+    #[test]
+    fn from_files_compacts_files_not_on_file_system() {
+        let config = StoreBuilder::new("")
+            .compaction_interval(0)
+            .is_search_enabled(true);
+        let mut store = Store::from_files(Cursor::new(vec![]), Cursor::new(vec![]), config)
+            .expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
 
-        store.compact().expect("compact store");
+        let get_file_size = |store: &Store| -> (u64, u64) {
+            let mut pool = store.buffer_pool.lock().unwrap();
+            (pool.file_size, pool.file.seek(SeekFrom::End(0)).unwrap())
+        };
+        let (original_file_size, _) = get_file_size(&store);
+        store.compact().expect("compact");
+
+        let deleted_size = KeyValueEntry::new(&keys[0], &values[0], 0)
+            .expect("new key value entry")
+            .with_version(1)
+            .size as u64;
+        let (file_size, actual_file_size) = get_file_size(&store);
+        assert_eq!(file_size, original_file_size - deleted_size);
+        assert_eq!(actual_file_size, file_size);
+
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
+        assert_eq!(
+            store.search(&keys[1], 0, 0).expect("search"),
+            vec![(keys[1].clone(), values[1].clone())]
+        );
+    }
 
-        let final_file_size = get_file_size(&db_file_path);
-        let expected_file_size_reduction = keys[0..3]
-            .iter()
-            .zip(&values[0..3])
-            .map(|(k, v)| KeyValueEntry::new(k, v, 0).as_bytes().len() as u64)
-            .reduce(|accum, v| accum + v)
-            .unwrap();
+    #[test]
+    fn in_memory_works() {
+        let store_path = "db_in_memory";
+        let config = StoreBuilder::new(store_path).is_search_enabled(true);
+        let mut store = Store::in_memory(config.clone()).expect("create store");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        delete_keys(&mut store, &keys[..1].to_vec());
 
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        let received_values = get_values_for_keys(&mut store, &keys);
+        assert_list_eq!(&expected_values, &received_values);
         assert_eq!(
-            original_file_size - final_file_size,
-            expected_file_size_reduction
+            store.search(&keys[1], 0, 0).expect("search"),
+            vec![(keys[1].clone(), values[1].clone())]
         );
+        assert!(!Path::new(store_path).exists());
 
-        // And the store is still acting as before
+        // nothing persists after drop
+        drop(store);
+        let mut store = Store::in_memory(config).expect("create store");
         let received_values = get_values_for_keys(&mut store, &keys);
-        let received_unchanged_values = &received_values[3..];
-        let received_removed_values = &received_values[0..3];
-
-        // unchanged
-        let expected_unchanged_values = wrap_values_in_result(&values[3..]);
-        let expected_expired_values: Vec<io::Result<Option<Vec<u8>>>> =
-            keys[0..3].iter().map(|_| Ok(None)).collect();
-
-        assert_list_eq!(&expected_unchanged_values, &received_unchanged_values);
-        assert_list_eq!(&expected_expired_values, &received_removed_values);
-
-        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+        let expected_values: Vec<Result<Option<Vec<u8>>>> = keys.iter().map(|_| Ok(None)).collect();
+        assert_list_eq!(&expected_values, &received_values);
     }
 
     #[test]
     #[serial]
-    fn compact_removes_expired_from_search_index_file() {
+    fn refresh_picks_up_changes_by_other_stores() {
         // pre-clean up for the right results
         fs::remove_dir_all(STORE_PATH).ok();
+        let keys = get_keys();
+        let values = get_values();
 
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create store");
-        store.clear().expect("store failed to clear");
-        let keys = to_byte_arrays_vector!(["foo", "bar", "fore", "band", "pig"]);
-        let values = to_byte_arrays_vector!(["eng", "port", "span", "nyoro", "dan"]);
-
-        insert_test_data(
-            &mut store,
-            &keys[0..2].to_vec(),
-            &values[0..2].to_vec(),
-            Some(1),
-        );
-        insert_test_data(&mut store, &keys[2..].to_vec(), &values[2..].to_vec(), None);
-
-        let search_index = store.search_index.as_ref().expect("has search index");
-        let search_index = acquire_lock!(search_index).expect("acquire lock on search index");
-        let search_index_file_path = search_index.file_path.to_str().unwrap().to_owned();
-        drop(search_index);
-
-        // wait for some keys to expire
-        thread::sleep(Duration::from_secs(2));
-
-        let original_file_size = get_file_size(&search_index_file_path);
-
-        store.compact().expect("compact store");
-
-        let final_file_size = get_file_size(&search_index_file_path);
-        let expected_file_size_reduction = 282u64;
-
+        let mut writer =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create writer");
+        insert_test_data(&mut writer, &keys[..2], &values[..2], None);
+        let mut reader =
+            Store::new(STORE_PATH, None, None, None, Some(0), true).expect("create reader");
+
+        // compaction replaces the db file
+        delete_keys(&mut writer, &keys[..1].to_vec());
+        writer.compact().expect("compact");
+        insert_test_data(&mut writer, &keys[2..], &values[2..], None);
+        assert_eq!(reader.get(&keys[2]).expect("get"), None);
+
+        reader.refresh().expect("refresh");
+        let mut expected_values = wrap_values_in_result(&values);
+        expected_values[0] = Ok(None);
+        let received_values = get_values_for_keys(&mut reader, &keys);
+        assert_list_eq!(&expected_values, &received_values);
         assert_eq!(
-            original_file_size - final_file_size,
-            expected_file_size_reduction
+            reader.search(&keys[2], 0, 0).expect("search"),
+            vec![(keys[2].clone(), values[2].clone())]
         );
 
-        // And the search is still acting as before, with the expired not showing up
-        // expired items are ignored
-        let test_data = [
-            ("f", vec![("fore", "span")]),
-            ("fo", vec![("fore", "span")]),
-            ("foo", vec![]),
-            ("for", vec![("fore", "span")]),
-            ("b", vec![("band", "nyoro")]),
-            ("ba", vec![("band", "nyoro")]),
-            ("bar", vec![]),
-            ("ban", vec![("band", "nyoro")]),
-            ("band", vec![("band", "nyoro")]),
-            ("p", vec![("pig", "dan")]),
-            ("pi", vec![("pig", "dan")]),
-            ("pig", vec![("pig", "dan")]),
-            ("pigg", vec![]),
-            ("food", vec![]),
-            ("bandana", vec![]),
-            ("bare", vec![]),
-        ];
-
-        for (term, expected) in test_data {
-            let expected: Vec<(Vec<u8>, Vec<u8>)> = expected
-                .into_iter()
-                .map(|(k, v)| (str_to_bytes!(k), str_to_bytes!(v)))
-                .collect();
-            // Compaction of db file moves the addresses around, therefore it must also update the inverted db!
-            let got = store
-                .search(&str_to_bytes!(term), 0, 0)
-                .expect(&format!("search for {}", term));
-            assert_eq!(&expected, &got);
-        }
-
+        // clearing with a different max_keys changes the header
+        writer
+            .clear_with_max_keys(100)
+            .expect("clear with max keys");
+        insert_test_data(&mut writer, &keys, &values, None);
+        reader.refresh().expect("refresh");
+        assert_eq!(reader.header, writer.header);
+        let received_values = get_values_for_keys(&mut reader, &keys);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+
+        drop(reader);
+        drop(writer);
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
     #[test]
     #[serial]
-    fn background_task_compacts_db_file() {
+    fn close_works() {
         // pre-clean up for the right results
         fs::remove_dir_all(STORE_PATH).ok();
-
-        // set the compaction interval to 1 second
-        let mut store =
-            Store::new(STORE_PATH, None, None, None, Some(1), false).expect("create store");
-        store.clear().expect("store failed to clear");
         let keys = get_keys();
         let values = get_values();
 
-        insert_test_data(
-            &mut store,
-            &keys[0..2].to_vec(),
-            &values[0..2].to_vec(),
-            Some(1),
-        );
-        insert_test_data(&mut store, &keys[2..].to_vec(), &values[2..].to_vec(), None);
-        delete_keys(&mut store, &keys[2..3].to_vec());
-
-        let buffer_pool = acquire_lock!(store.buffer_pool).expect("acquire lock on buffer pool");
-        let db_file_path = buffer_pool.file_path.to_str().unwrap().to_owned();
-        drop(buffer_pool);
-
-        let original_file_size = get_file_size(&db_file_path);
-
-        // wait for some keys to expire
-        thread::sleep(Duration::from_secs(4));
-
-        let final_file_size = get_file_size(&db_file_path);
-        let expected_file_size_reduction = keys[0..3]
-            .iter()
-            .zip(&values[0..3])
-            .map(|(k, v)| KeyValueEntry::new(k, v, 0).as_bytes().len() as u64)
-            .reduce(|accum, v| accum + v)
-            .unwrap();
-
-        assert_eq!(
-            original_file_size - final_file_size,
-            expected_file_size_reduction
-        );
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(1), true).expect("create store");
+        insert_test_data(&mut store, &keys, &values, None);
+        store.close().expect("close store");
 
-        // And the store is still acting as before
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(1), true).expect("create store");
         let received_values = get_values_for_keys(&mut store, &keys);
-        let received_unchanged_values = &received_values[3..];
-        let received_removed_values = &received_values[0..3];
-
-        // unchanged
-        let expected_unchanged_values = wrap_values_in_result(&values[3..]);
-        let expected_expired_values: Vec<io::Result<Option<Vec<u8>>>> =
-            keys[0..3].iter().map(|_| Ok(None)).collect();
-
-        assert_list_eq!(&expected_unchanged_values, &received_unchanged_values);
-        assert_list_eq!(&expected_expired_values, &received_removed_values);
-
-        // ensure background tasks stop running
-        drop(store);
+        assert_list_eq!(&wrap_values_in_result(&values), &received_values);
+
+        // errors are returned, not swallowed
+        let buffer_pool = store.buffer_pool.clone();
+        thread::spawn(move || {
+            let _lock = buffer_pool.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join()
+        .ok();
+        assert!(matches!(store.close(), Err(ScdbError::Io(_))));
 
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
@@ -1266,18 +9149,15 @@ mod tests {
         let keys = to_byte_arrays_vector!(["foo", "bar", "fore", "band", "pig"]);
         let values = to_byte_arrays_vector!(["eng", "port", "span", "nyoro", "dan"]);
 
-        insert_test_data(
-            &mut store,
-            &keys[0..2].to_vec(),
-            &values[0..2].to_vec(),
-            Some(1),
-        );
-        insert_test_data(&mut store, &keys[2..].to_vec(), &values[2..].to_vec(), None);
+        insert_test_data(&mut store, &keys[0..2], &values[0..2], Some(1));
+        insert_test_data(&mut store, &keys[2..], &values[2..], None);
 
-        let search_index = store.search_index.as_ref().expect("has search index");
-        let search_index = acquire_lock!(search_index).expect("acquire lock on search index");
-        let search_index_file_path = search_index.file_path.to_str().unwrap().to_owned();
-        drop(search_index);
+        let search_index_file_path = store
+            .index_file_path()
+            .expect("index file path")
+            .to_str()
+            .unwrap()
+            .to_owned();
 
         let original_file_size = get_file_size(&search_index_file_path);
 
@@ -1320,7 +9200,7 @@ mod tests {
                 .collect();
             let got = store
                 .search(&str_to_bytes!(term), 0, 0)
-                .expect(&format!("search for {}", term));
+                .unwrap_or_else(|_| panic!("search for {}", term));
             assert_eq!(&expected, &got);
         }
 
@@ -1420,10 +9300,147 @@ mod tests {
         fs::remove_dir_all(STORE_PATH).expect("delete store folder");
     }
 
+    #[test]
+    #[serial]
+    fn clear_is_atomic_to_concurrent_gets() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, Some(2), Some(0), true).expect("create store");
+        store.clear().expect("store failed to clear");
+        let store = Arc::new(Mutex::new(store));
+        let keys = Arc::new(get_keys());
+        let values = Arc::new(get_values());
+        let is_done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<JoinHandle<()>> = (0..4)
+            .map(|_| {
+                let (store, keys, values) = (store.clone(), keys.clone(), values.clone());
+                let is_done = is_done.clone();
+                thread::spawn(move || {
+                    while !is_done.load(Ordering::Acquire) {
+                        let mut store = store.lock().unwrap();
+                        let got: Vec<Option<Vec<u8>>> = keys
+                            .iter()
+                            .map(|k| store.get(k).expect("get key"))
+                            .collect();
+                        drop(store);
+
+                        // the keys are set in order after every clear, so the ones found are always
+                        // the first few, each with its own value
+                        let found = got.iter().take_while(|v| v.is_some()).count();
+                        assert!(got[found..].iter().all(Option::is_none), "got: {:?}", got);
+                        for (v, expected) in got.iter().zip(values.iter()).take(found) {
+                            assert_eq!(v.as_ref(), Some(expected));
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..50u64 {
+            {
+                let mut store = store.lock().unwrap();
+                if i % 2 == 0 {
+                    store.clear().expect("clear");
+                } else {
+                    store
+                        .clear_with_max_keys(1_000 + i)
+                        .expect("clear with max keys");
+                }
+            }
+
+            for (k, v) in keys.iter().zip(values.iter()) {
+                store.lock().unwrap().set(k, v, None).expect("set key");
+            }
+        }
+
+        is_done.store(true, Ordering::Release);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    #[test]
+    #[serial]
+    fn shared_store_can_be_read_from_many_threads() {
+        let mut store =
+            Store::new(STORE_PATH, None, None, None, Some(0), false).expect("create store");
+        store.clear().expect("store failed to clear");
+        let keys = get_keys();
+        let values = get_values();
+        insert_test_data(&mut store, &keys, &values, None);
+        let expected_report = store.fragmentation().expect("fragmentation");
+
+        // no Mutex is needed for the methods that take &self
+        let store = Arc::new(store);
+        let handles: Vec<JoinHandle<()>> = (0..10)
+            .map(|_| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || {
+                    assert_eq!(
+                        store.fragmentation().expect("fragmentation"),
+                        expected_report
+                    );
+                    assert!(store.load_factor().expect("load factor") > 0.0);
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        drop(store);
+        fs::remove_dir_all(STORE_PATH).expect("delete store folder");
+    }
+
+    /// A clock whose time is set manually
+    /// A hasher that hashes the key with its bytes reversed
+    #[derive(Debug)]
+    struct ReverseHasher;
+
+    impl KeyHasher for ReverseHasher {
+        fn id(&self) -> u32 {
+            5
+        }
+
+        fn hash(&self, key: &[u8]) -> u64 {
+            let reversed: Vec<u8> = key.iter().rev().copied().collect();
+            Xxh3Hasher.hash(&reversed)
+        }
+    }
+
+    /// A hasher that places all keys in the same slot
+    #[derive(Debug)]
+    struct ConstantHasher;
+
+    impl KeyHasher for ConstantHasher {
+        fn id(&self) -> u32 {
+            6
+        }
+
+        fn hash(&self, _key: &[u8]) -> u64 {
+            0
+        }
+    }
+
+    #[derive(Debug)]
+    struct MockClock(AtomicU64);
+
+    impl Clock for MockClock {
+        fn now_secs(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
     /// Deletes the given keys in the store
     fn delete_keys(store: &mut Store, keys_to_delete: &Vec<Vec<u8>>) {
         for k in keys_to_delete {
-            store.delete(k).expect(&format!("delete key {:?}", k));
+            store
+                .delete(k)
+                .unwrap_or_else(|_| panic!("delete key {:?}", k));
         }
     }
 
@@ -1432,16 +9449,13 @@ mod tests {
         let mut file = OpenOptions::new()
             .read(true)
             .open(file_path)
-            .expect(&format!("open file {}", file_path));
+            .unwrap_or_else(|_| panic!("open file {}", file_path));
         file.seek(SeekFrom::End(0)).expect("get file size")
     }
 
     /// Gets a vector of responses from the store when store.get is called
     /// for each key passed in keys
-    fn get_values_for_keys(
-        store: &mut Store,
-        keys: &Vec<Vec<u8>>,
-    ) -> Vec<io::Result<Option<Vec<u8>>>> {
+    fn get_values_for_keys(store: &mut Store, keys: &Vec<Vec<u8>>) -> Vec<Result<Option<Vec<u8>>>> {
         let mut received_values = Vec::with_capacity(keys.len());
 
         for k in keys {
@@ -1452,16 +9466,11 @@ mod tests {
     }
 
     /// Inserts test data into the store
-    fn insert_test_data(
-        store: &mut Store,
-        keys: &Vec<Vec<u8>>,
-        values: &Vec<Vec<u8>>,
-        ttl: Option<u64>,
-    ) {
+    fn insert_test_data(store: &mut Store, keys: &[Vec<u8>], values: &[Vec<u8>], ttl: Option<u64>) {
         for (k, v) in keys.iter().zip(values) {
             store
                 .set(k, v, ttl)
-                .expect(&format!("set key {:?}, value {:?}", k, v));
+                .unwrap_or_else(|_| panic!("set key {:?}, value {:?}", k, v));
         }
     }
 
@@ -1483,7 +9492,7 @@ mod tests {
     }
 
     /// Wraps values in Result<Option<T>>
-    fn wrap_values_in_result(values: &[Vec<u8>]) -> Vec<io::Result<Option<Vec<u8>>>> {
+    fn wrap_values_in_result(values: &[Vec<u8>]) -> Vec<Result<Option<Vec<u8>>>> {
         values.iter().map(|v| Ok(Some(v.clone()))).collect()
     }
 }
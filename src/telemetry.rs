@@ -0,0 +1,253 @@
+//! The hooks through which the [crate::Store] emits its metrics via the [metrics](https://docs.rs/metrics)
+//! facade, and its spans via [tracing](https://docs.rs/tracing), when the `metrics` and `tracing` features
+//! respectively are enabled. Without them, they are empty and compile away.
+//!
+//! The metrics emitted are:
+//!
+//! - `scdb_operations_total` (counter, labelled by `op`: "get", "set" or "delete"). Each call to an operation
+//!   on a single key is counted once: as a "delete" for [crate::Store::delete], as a "set" if it writes
+//!   the key e.g. [crate::Store::append_value], [crate::Store::rename_key] or a [crate::Store::set_or_get]
+//!   of a missing key, and as a "get" otherwise e.g. [crate::Store::get_range] or [crate::Store::try_get].
+//!   A [crate::Store::try_set] or [crate::Store::try_get] that gives up on the lock is not counted.
+//! - `scdb_cache_hits_total` and `scdb_cache_misses_total` (counters, labelled by `kind`: "kv" or "index")
+//! - `scdb_compactions_total` (counter)
+//! - `scdb_db_file_size_bytes` (gauge, updated on every set and compaction)
+//!
+//! The spans are those of `set` (and `set_with_expiry_at`), `get`, `delete` and searches, of compactions
+//! (of the database file and of the search index) and of each read from or write to the database file,
+//! whose durations subscribers can report. The spans of file reads and writes are at the "trace" level
+//! and record the `bytes` read or written, while the rest are at the "debug" level.
+
+/// The name of the counter of the operations on the store
+#[cfg(feature = "metrics")]
+const OPERATIONS_TOTAL: &str = "scdb_operations_total";
+/// The name of the counter of lookups served from the in-memory buffers
+#[cfg(feature = "metrics")]
+const CACHE_HITS_TOTAL: &str = "scdb_cache_hits_total";
+/// The name of the counter of lookups that had to read from the file
+#[cfg(feature = "metrics")]
+const CACHE_MISSES_TOTAL: &str = "scdb_cache_misses_total";
+/// The name of the counter of compactions of the database file
+#[cfg(feature = "metrics")]
+const COMPACTIONS_TOTAL: &str = "scdb_compactions_total";
+/// The name of the gauge of the size of the database file
+#[cfg(feature = "metrics")]
+const DB_FILE_SIZE_BYTES: &str = "scdb_db_file_size_bytes";
+
+/// Records an operation, named `op`, on the store
+#[cfg(feature = "metrics")]
+#[inline]
+pub(crate) fn record_op(op: &'static str) {
+    metrics::counter!(OPERATIONS_TOTAL, "op" => op).increment(1);
+}
+
+/// Records an operation, named `op`, on the store
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn record_op(_op: &'static str) {}
+
+/// Records a lookup of the given `kind` ("kv" or "index") in the buffer pool, as a hit if `is_hit` is true
+/// or else as a miss
+#[cfg(feature = "metrics")]
+#[inline]
+pub(crate) fn record_lookup(kind: &'static str, is_hit: bool) {
+    let name = if is_hit {
+        CACHE_HITS_TOTAL
+    } else {
+        CACHE_MISSES_TOTAL
+    };
+    metrics::counter!(name, "kind" => kind).increment(1);
+}
+
+/// Records a lookup of the given `kind` ("kv" or "index") in the buffer pool, as a hit if `is_hit` is true
+/// or else as a miss
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn record_lookup(_kind: &'static str, _is_hit: bool) {}
+
+/// Records a compaction of the database file
+#[cfg(feature = "metrics")]
+#[inline]
+pub(crate) fn record_compaction() {
+    metrics::counter!(COMPACTIONS_TOTAL).increment(1);
+}
+
+/// Records a compaction of the database file
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn record_compaction() {}
+
+/// Records the current size in bytes of the database file
+#[cfg(feature = "metrics")]
+#[inline]
+pub(crate) fn record_file_size(file_size: u64) {
+    metrics::gauge!(DB_FILE_SIZE_BYTES).set(file_size as f64);
+}
+
+/// Records the current size in bytes of the database file
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn record_file_size(_file_size: u64) {}
+
+/// Records the given value of the field named `field` of the current span
+#[cfg(feature = "tracing")]
+#[inline]
+pub(crate) fn record_span_field(field: &'static str, value: u64) {
+    tracing::Span::current().record(field, value);
+}
+
+/// Records the given value of the field named `field` of the current span
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn record_span_field(_field: &'static str, _value: u64) {}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn store_emits_metrics() {
+        use super::*;
+        use crate::{Store, StoreBuilder};
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+        use std::collections::HashMap;
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            let mut store = Store::in_memory(StoreBuilder::new("").compaction_interval(0))
+                .expect("create store");
+            store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+            store.set(&b"fee"[..], &b"baz"[..], None).expect("set fee");
+            store
+                .set_with_expiry_at(&b"fum"[..], &b"qux"[..], u64::MAX)
+                .expect("set fum");
+            store.get(&b"foo"[..]).expect("get foo");
+            store
+                .get_range(&b"foo"[..], 0, 1)
+                .expect("get range of foo");
+            store
+                .set_or_get(&b"foo"[..], &b"qux"[..], None)
+                .expect("set or get foo");
+            store
+                .append_value(&b"fum"[..], &b"x"[..], None)
+                .expect("append to fum");
+            store.delete(&b"fee"[..]).expect("delete fee");
+            store.compact().expect("compact");
+        });
+
+        let metrics: HashMap<(String, Vec<String>), DebugValue> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels = key.labels().map(|l| l.value().to_string()).collect();
+                ((key.name().to_string(), labels), value)
+            })
+            .collect();
+        let counter = |name: &str, label: Option<&str>| {
+            let labels = label.into_iter().map(str::to_string).collect();
+            metrics.get(&(name.to_string(), labels))
+        };
+
+        assert_eq!(
+            counter(OPERATIONS_TOTAL, Some("set")),
+            Some(&DebugValue::Counter(4))
+        );
+        assert_eq!(
+            counter(OPERATIONS_TOTAL, Some("get")),
+            Some(&DebugValue::Counter(3))
+        );
+        assert_eq!(
+            counter(OPERATIONS_TOTAL, Some("delete")),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            counter(COMPACTIONS_TOTAL, None),
+            Some(&DebugValue::Counter(1))
+        );
+        let kv_lookups: u64 = [CACHE_HITS_TOTAL, CACHE_MISSES_TOTAL]
+            .into_iter()
+            .map(|name| match counter(name, Some("kv")) {
+                Some(&DebugValue::Counter(n)) => n,
+                _ => 0,
+            })
+            .sum();
+        assert!(kv_lookups > 0);
+        assert!(matches!(
+            counter(DB_FILE_SIZE_BYTES, None),
+            Some(DebugValue::Gauge(size)) if size.0 > 0.0
+        ));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn store_emits_spans() {
+        use crate::{Store, StoreBuilder};
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Mutex;
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// A subscriber that only keeps the names of the spans created
+        #[derive(Default)]
+        struct SpanNames {
+            names: Mutex<Vec<&'static str>>,
+            next_id: AtomicU64,
+        }
+
+        impl Subscriber for SpanNames {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                self.names.lock().unwrap().push(span.metadata().name());
+                Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+            }
+
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+            fn event(&self, _event: &Event<'_>) {}
+
+            fn enter(&self, _span: &Id) {}
+
+            fn exit(&self, _span: &Id) {}
+        }
+
+        let subscriber = std::sync::Arc::new(SpanNames::default());
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            let mut store = Store::in_memory(
+                StoreBuilder::new("")
+                    .compaction_interval(0)
+                    .is_search_enabled(true),
+            )
+            .expect("create store");
+            store.set(&b"foo"[..], &b"bar"[..], None).expect("set foo");
+            store
+                .set_with_expiry_at(&b"fum"[..], &b"qux"[..], u64::MAX)
+                .expect("set fum");
+            store.get(&b"foo"[..]).expect("get foo");
+            store.search(&b"f"[..], 0, 0).expect("search f");
+            store.delete(&b"foo"[..]).expect("delete foo");
+            store.compact().expect("compact");
+        });
+
+        let names = subscriber.names.lock().unwrap();
+        for name in [
+            "set",
+            "set_with_expiry_at",
+            "get",
+            "search_with_mode",
+            "delete",
+            "compact_file",
+            "read",
+            "write",
+        ] {
+            assert!(names.contains(&name), "no {} span in {:?}", name, names);
+        }
+    }
+}